@@ -0,0 +1,318 @@
+use goblin::pe::PE;
+use goblin::pe::certificate_table::AttributeCertificateType;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use crate::core::msi;
+use crate::wine::steam::SteamLauncher;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SignatureStatus {
+    /// No certificate table
+    Unsigned,
+    /// The signed digest matches the file. The signer is not verified, so this only
+    /// means the file is unchanged since someone signed it
+    DigestMatches,
+    /// The file was modified after signing
+    Tampered,
+    /// Signature present but couldn't be read (unknown digest, BER encoding, ...)
+    Unreadable,
+    /// Signed MSI package; its digest covers the database streams and isn't checked
+    SignedUnverified,
+}
+
+/// Authenticode signature of a PE. Only the file digest is checked; the certificate
+/// chain and the RSA/ECDSA signature over it are not verified.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SignatureInfo {
+    pub status: SignatureStatus,
+    /// Signer and issuer names as the signature claims them. Nothing checks that the
+    /// named signer produced the signature, so they are unverified.
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    /// Countersignature or RFC 3161 timestamp, as written in the signature (UTCTime/GeneralizedTime)
+    pub timestamp: Option<String>,
+    pub digest_algorithm: Option<String>,
+    pub signed_digest: Option<String>,
+    pub file_digest: Option<String>,
+}
+
+impl SignatureInfo {
+    pub fn with_status(status: SignatureStatus) -> Self {
+        SignatureInfo {
+            status,
+            subject: None,
+            issuer: None,
+            timestamp: None,
+            digest_algorithm: None,
+            signed_digest: None,
+            file_digest: None,
+        }
+    }
+}
+
+// DER tags
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const SET: u8 = 0x31;
+const CONTEXT_0: u8 = 0xA0;
+const CONTEXT_1: u8 = 0xA1;
+
+const OID_SIGNED_DATA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
+const OID_SIGNING_TIME: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x05];
+const OID_COUNTER_SIGNATURE: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x06];
+const OID_RFC3161_TIMESTAMP: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x03, 0x03, 0x01];
+
+const DIGESTS: [(&[u8], &str); 4] = [
+    (&[0x2B, 0x0E, 0x03, 0x02, 0x1A], "SHA-1"),
+    (&[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01], "SHA-256"),
+    (&[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02], "SHA-384"),
+    (&[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03], "SHA-512"),
+];
+
+// X.520 attribute types (2.5.4.x) shown in names
+const NAME_ATTRIBUTES: [(u8, &str); 6] = [(3, "CN"), (11, "OU"), (10, "O"), (7, "L"), (8, "ST"), (6, "C")];
+
+#[derive(Clone, Copy)]
+struct Der<'a> {
+    tag: u8,
+    content: &'a [u8],
+    /// Tag, length and content
+    raw: &'a [u8],
+}
+
+/// Reads one DER element, returning it and the bytes after it
+fn read_der(data: &[u8]) -> Option<(Der<'_>, &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        // 0x80 is BER indefinite length, which DER forbids
+        let count = first & 0x7F;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = data.get(2..2 + count)?.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + count)
+    };
+    let end = header.checked_add(len)?;
+    let content = data.get(header..end)?;
+    Some((Der { tag, content, raw: &data[..end] }, &data[end..]))
+}
+
+fn children(content: &[u8]) -> Vec<Der<'_>> {
+    let mut items = Vec::new();
+    let mut rest = content;
+    while let Some((item, next)) = read_der(rest) {
+        items.push(item);
+        rest = next;
+    }
+    items
+}
+
+/// Renders an X.501 Name as `CN=..., O=..., C=...`
+fn format_name(name: &Der) -> String {
+    let mut parts: Vec<(usize, String)> = Vec::new();
+    for rdn in children(name.content) {
+        for attribute in children(rdn.content) {
+            let fields = children(attribute.content);
+            let (Some(oid), Some(value)) = (fields.first(), fields.get(1)) else { continue };
+            if oid.content.len() != 3 || oid.content[..2] != [0x55, 0x04] {
+                continue;
+            }
+            if let Some(order) = NAME_ATTRIBUTES.iter().position(|(id, _)| *id == oid.content[2]) {
+                parts.push((order, format!("{}={}", NAME_ATTRIBUTES[order].1, String::from_utf8_lossy(value.content))));
+            }
+        }
+    }
+    parts.sort_by_key(|(order, _)| *order);
+    parts.into_iter().map(|(_, part)| part).collect::<Vec<_>>().join(", ")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hash_file(pe: &PE, algorithm: &str) -> Option<Vec<u8>> {
+    fn run<D: Digest>(pe: &PE) -> Vec<u8> {
+        let mut hasher = D::new();
+        for range in pe.authenticode_ranges() {
+            hasher.update(range);
+        }
+        hasher.finalize().to_vec()
+    }
+    Some(match algorithm {
+        "SHA-1" => run::<Sha1>(pe),
+        "SHA-256" => run::<Sha256>(pe),
+        "SHA-384" => run::<Sha384>(pe),
+        "SHA-512" => run::<Sha512>(pe),
+        _ => return None,
+    })
+}
+
+/// Reads the first PKCS#7 signature in the certificate table and checks its digest
+pub fn verify(pe: &PE) -> SignatureInfo {
+    let Some(certificate) = pe.certificates.iter().find(|c| c.certificate_type == AttributeCertificateType::PkcsSignedData) else {
+        return SignatureInfo::with_status(SignatureStatus::Unsigned);
+    };
+    let Some(mut info) = parse_signed_data(certificate.certificate) else {
+        return SignatureInfo::with_status(SignatureStatus::Unreadable);
+    };
+
+    let algorithm = info.digest_algorithm.clone().unwrap_or_default();
+    match hash_file(pe, &algorithm) {
+        Some(digest) => {
+            let file_digest = to_hex(&digest);
+            info.status = if info.signed_digest.as_deref() == Some(file_digest.as_str()) {
+                SignatureStatus::DigestMatches
+            } else {
+                SignatureStatus::Tampered
+            };
+            info.file_digest = Some(file_digest);
+        }
+        None => info.status = SignatureStatus::Unreadable,
+    }
+    info
+}
+
+/// Accepts PE files and MSI packages. MSI signatures cover the database streams with a
+/// different digest, so for those only the presence of a signature is reported.
+pub fn verify_file(data: &[u8]) -> Result<SignatureInfo, String> {
+    if msi::is_msi(data) {
+        let signed = msi::parse_msi(data)?.signed;
+        return Ok(SignatureInfo::with_status(if signed { SignatureStatus::SignedUnverified } else { SignatureStatus::Unsigned }));
+    }
+    let pe = PE::parse(data).map_err(|e| e.to_string())?;
+    Ok(verify(&pe))
+}
+
+/// ContentInfo { signedData, [0] SignedData { version, digestAlgorithms, contentInfo, [0] certificates, [1] crls, signerInfos } }
+fn parse_signed_data(data: &[u8]) -> Option<SignatureInfo> {
+    let (content_info, _) = read_der(data)?;
+    let fields = children(content_info.content);
+    if fields.first()?.content != OID_SIGNED_DATA {
+        return None;
+    }
+    let (signed_data, _) = read_der(fields.get(1)?.content)?;
+    let signed_fields = children(signed_data.content);
+
+    let mut info = SignatureInfo::with_status(SignatureStatus::Unreadable);
+
+    // SpcIndirectDataContent { data, messageDigest DigestInfo { algorithm, digest } }
+    let spc = children(signed_fields.get(2)?.content);
+    let (indirect, _) = read_der(spc.get(1)?.content)?;
+    let digest_info = children(indirect.content).get(1).copied()?;
+    let digest_fields = children(digest_info.content);
+    let algorithm = children(digest_fields.first()?.content).first().copied()?;
+    info.digest_algorithm = DIGESTS.iter().find(|(oid, _)| *oid == algorithm.content).map(|(_, name)| name.to_string());
+    info.signed_digest = digest_fields.get(1).filter(|d| d.tag == OCTET_STRING).map(|d| to_hex(d.content));
+
+    let certificates = signed_fields.iter()
+        .find(|f| f.tag == CONTEXT_0)
+        .map(|f| children(f.content))
+        .unwrap_or_default();
+
+    let signer = signed_fields.iter()
+        .rev()
+        .find(|f| f.tag == SET)
+        .and_then(|set| children(set.content).first().copied());
+
+    if let Some(signer) = signer {
+        let signer_fields = children(signer.content);
+        // issuerAndSerialNumber picks the signing certificate out of the bundle
+        if let Some(issuer_and_serial) = signer_fields.get(1) {
+            let id = children(issuer_and_serial.content);
+            if let (Some(issuer), Some(serial)) = (id.first(), id.get(1)) {
+                if let Some((offset, tbs_fields)) = certificates.iter().find_map(|cert| {
+                    let tbs = children(cert.content).first().copied()?;
+                    let tbs_fields = children(tbs.content);
+                    let offset = usize::from(tbs_fields.first()?.tag == CONTEXT_0);
+                    let cert_serial = tbs_fields.get(offset)?;
+                    let cert_issuer = tbs_fields.get(offset + 2)?;
+                    (cert_serial.tag == INTEGER && cert_serial.raw == serial.raw && cert_issuer.raw == issuer.raw)
+                        .then_some((offset, tbs_fields))
+                }) {
+                    info.issuer = tbs_fields.get(offset + 2).map(format_name);
+                    info.subject = tbs_fields.get(offset + 4).map(format_name);
+                }
+            }
+        }
+
+        info.timestamp = signer_fields.iter()
+            .find(|f| f.tag == CONTEXT_1)
+            .and_then(|attributes| find_timestamp(attributes.content))
+            .or_else(|| signer_fields.iter().find(|f| f.tag == CONTEXT_0).and_then(|a| signing_time(a.content)));
+    }
+
+    Some(info)
+}
+
+/// signingTime inside a set of authenticated attributes
+fn signing_time(attributes: &[u8]) -> Option<String> {
+    children(attributes).iter().find_map(|attribute| {
+        let fields = children(attribute.content);
+        if fields.first()?.content != OID_SIGNING_TIME {
+            return None;
+        }
+        let time = children(fields.get(1)?.content).first().copied()?;
+        matches!(time.tag, UTC_TIME | GENERALIZED_TIME).then(|| String::from_utf8_lossy(time.content).to_string())
+    })
+}
+
+/// Legacy countersignature (a SignerInfo with signingTime) or an RFC 3161 token (TSTInfo.genTime)
+fn find_timestamp(unauthenticated: &[u8]) -> Option<String> {
+    for attribute in children(unauthenticated) {
+        let fields = children(attribute.content);
+        let (Some(oid), Some(values)) = (fields.first(), fields.get(1)) else { continue };
+        let Some(value) = children(values.content).first().copied() else { continue };
+
+        if oid.content == OID_COUNTER_SIGNATURE {
+            let signer = children(value.content);
+            if let Some(time) = signer.iter().find(|f| f.tag == CONTEXT_0).and_then(|a| signing_time(a.content)) {
+                return Some(time);
+            }
+        } else if oid.content == OID_RFC3161_TIMESTAMP {
+            // ContentInfo -> SignedData -> encapContentInfo -> [0] OCTET STRING -> TSTInfo
+            let content_info = children(value.content);
+            let Some((signed_data, _)) = content_info.get(1).and_then(|c| read_der(c.content)) else { continue };
+            let Some(encapsulated) = children(signed_data.content).get(2).copied() else { continue };
+            let Some(explicit) = children(encapsulated.content).get(1).copied() else { continue };
+            let Some((octets, _)) = read_der(explicit.content) else { continue };
+            let Some((tst_info, _)) = read_der(octets.content) else { continue };
+            if let Some(time) = children(tst_info.content).iter().find(|f| f.tag == GENERALIZED_TIME) {
+                return Some(String::from_utf8_lossy(time.content).to_string());
+            }
+        }
+    }
+    None
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstallerTrust {
+    pub signature: SignatureInfo,
+    /// The bottle remembers a Steam login an untrusted installer could steal
+    pub steam_account: bool,
+    /// Set for every installer when there's a login to steal. A matching digest doesn't
+    /// clear it: anyone can sign a file, and the signer isn't verified.
+    pub warn: bool,
+}
+
+#[tauri::command]
+pub fn check_installer_signature(path: String, bottle_id: String, handle: tauri::AppHandle) -> Result<InstallerTrust, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id)
+        .ok_or("Bottle not found")?;
+
+    let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let signature = verify_file(&data)?;
+    let steam_account = SteamLauncher::has_logged_in_account(&bottle.path);
+
+    Ok(InstallerTrust {
+        warn: steam_account,
+        signature,
+        steam_account,
+    })
+}
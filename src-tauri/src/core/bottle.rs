@@ -1,9 +1,10 @@
 use serde::{Serialize, Deserialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use tauri::Manager;
 use rand::Rng;
 
+use crate::core::msi::MsiProduct;
 use crate::core::scanner::DetectedApp;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -20,6 +21,8 @@ pub struct Bottle {
     pub engine_path: Option<PathBuf>,
     #[serde(default)]
     pub environment_type: String, // "classic" or "pro"
+    #[serde(default)]
+    pub msi_products: Vec<MsiProduct>,
 }
 
 pub fn get_bottles_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
@@ -93,10 +96,16 @@ pub fn remove_pinned_app(app_handle: &tauri::AppHandle, bottle_id: &str, exe_pat
     } else {
         // If it wasn't in registry, add it as unpinned to "blacklist" it from Library
         bottle.app_registry.push(DetectedApp {
-            name: exe_path.split('/').last().unwrap_or("App").replace(".exe", ""),
+            name: exe_path.split('/').next_back().unwrap_or("App").replace(".exe", ""),
             exe_path: exe_path.to_string(),
             is_priority: false,
             pinned: false,
+            steam_app_id: None,
+            cover: None,
+            class: Default::default(),
+            windows_path: None,
+            launch_args: Vec::new(),
+            working_dir: None,
         });
     }
 
@@ -105,6 +114,44 @@ pub fn remove_pinned_app(app_handle: &tauri::AppHandle, bottle_id: &str, exe_pat
     Ok(())
 }
 
+/// Reads a bottle's `pancho.json`, applies `update` and writes it back
+pub fn update_bottle_config(bottle_dir: &Path, update: impl FnOnce(&mut Bottle)) -> Result<(), String> {
+    let config_path = bottle_dir.join("pancho.json");
+
+    if !config_path.exists() { return Err("Bottle config not found".to_string()); }
+
+    let config_str = fs::read_to_string(&config_path).map_err(|e| e.to_string())?;
+    let mut bottle: Bottle = serde_json::from_str(&config_str).map_err(|e| e.to_string())?;
+
+    update(&mut bottle);
+
+    let new_config_str = serde_json::to_string(&bottle).map_err(|e| e.to_string())?;
+    fs::write(config_path, new_config_str).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+impl Bottle {
+    /// Remembers an installed MSI, replacing an older record with the same ProductCode
+    pub fn record_msi_product(&mut self, product: MsiProduct) {
+        self.forget_msi_product(&product.product_code);
+        self.msi_products.push(product);
+    }
+
+    pub fn forget_msi_product(&mut self, product_code: &str) {
+        self.msi_products.retain(|p| !p.product_code.eq_ignore_ascii_case(product_code));
+    }
+}
+
+pub fn record_msi_product(app_handle: &tauri::AppHandle, bottle_id: &str, product: MsiProduct) -> Result<(), String> {
+    let bottle_dir = get_bottles_dir(app_handle)?.join(bottle_id);
+    update_bottle_config(&bottle_dir, |bottle| bottle.record_msi_product(product))
+}
+
+pub fn forget_msi_product(app_handle: &tauri::AppHandle, bottle_id: &str, product_code: &str) -> Result<(), String> {
+    let bottle_dir = get_bottles_dir(app_handle)?.join(bottle_id);
+    update_bottle_config(&bottle_dir, |bottle| bottle.forget_msi_product(product_code))
+}
+
 pub fn create_bottle(app_handle: &tauri::AppHandle, name: &str, env_type: &str) -> Result<Bottle, String> {
     let id = name.to_lowercase().replace(" ", "_");
     let bottles_dir = get_bottles_dir(app_handle)?;
@@ -132,6 +179,7 @@ pub fn create_bottle(app_handle: &tauri::AppHandle, name: &str, env_type: &str)
         cover,
         engine_path: None,
         environment_type: env_type.to_string(),
+        msi_products: Vec::new(),
     };
 
     let config_path = bottle_path.join("pancho.json");
@@ -0,0 +1,239 @@
+use std::path::Path;
+use goblin::pe::PE;
+use serde::{Deserialize, Serialize};
+use crate::core::installer::InstallerKind;
+use crate::core::mapped_file::MappedFile;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ExecutableClass {
+    Installer,
+    /// Removes an installed program (`unins000.exe`, `Uninstall.exe`); never offered a silent install
+    Uninstaller,
+    Game,
+    Launcher,
+    Tool,
+    Redistributable,
+    #[default]
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Classification {
+    pub class: ExecutableClass,
+    pub product_name: Option<String>,
+    /// Human readable reasons, e.g. "NSIS overlay" or "imports d3d11.dll"
+    pub evidence: Vec<String>,
+}
+
+// GUI binaries this big are almost always games, even without a telltale import
+const LARGE_BINARY: usize = 64 * 1024 * 1024;
+
+// Signature scans only look at this much of the overlay and resources, so a library scan
+// touches a few pages of each file however big it is
+const SIGNATURE_WINDOW: usize = 1024 * 1024;
+
+const REDIST_NAMES: [&str; 12] = [
+    "vcredist", "vc_redist", "dxsetup", "dxwebsetup", "directx", "dotnetfx",
+    "ndp4", "physx", "oalinst", "ue4prereqsetup", "ueprereqsetup", "unitycrashhandler",
+];
+
+const LAUNCHER_NAMES: [&str; 7] = [
+    "steam.exe", "epicgameslauncher.exe", "galaxyclient.exe", "eadesktop.exe",
+    "origin.exe", "upc.exe", "battle.net.exe",
+];
+
+const TOOL_MARKERS: [&str; 8] = [
+    "crash", "report", "config", "settings", "benchmark", "editor", "dedicated", "updater",
+];
+
+const GRAPHICS_DLLS: [&str; 9] = [
+    "d3d8.dll", "d3d9.dll", "d3d10.dll", "d3d10_1.dll", "d3d11.dll", "d3d12.dll",
+    "dxgi.dll", "opengl32.dll", "vulkan-1.dll",
+];
+
+impl Classification {
+    /// Whether this is the Steam client installer: its version info says so, or the
+    /// file name does when there is no version info
+    pub fn is_steam_setup(&self, path: &Path) -> bool {
+        match self.product_name.as_deref() {
+            Some(product) => self.class == ExecutableClass::Installer && product.to_lowercase().contains("steam"),
+            None => path.file_name().unwrap_or_default().to_string_lossy().to_lowercase().contains("steam"),
+        }
+    }
+}
+
+/// Classifies a file from a read-only mapping. Only the pages the PE parser and the
+/// signature checks look at are ever read.
+pub fn classify_file(path: &Path) -> Classification {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    let Ok(file) = MappedFile::open(path) else { return classify_by_name(&file_name) };
+
+    match PE::parse(&file) {
+        Ok(pe) => classify(&file, &pe, &file_name),
+        Err(_) => classify_by_name(&file_name),
+    }
+}
+
+fn classify_by_name(file_name: &str) -> Classification {
+    let mut result = Classification::default();
+
+    if let Some(name) = REDIST_NAMES.iter().find(|n| file_name.starts_with(*n)) {
+        result.class = ExecutableClass::Redistributable;
+        result.evidence.push(format!("known redistributable '{}'", name));
+    } else if LAUNCHER_NAMES.contains(&file_name) {
+        result.class = ExecutableClass::Launcher;
+        result.evidence.push("known store client".to_string());
+    }
+
+    result
+}
+
+/// Classifies an already parsed PE. `data` must be the whole file (or a mapping of it)
+/// so the overlay can be inspected.
+pub fn classify(data: &[u8], pe: &PE, file_name: &str) -> Classification {
+    let mut result = classify_by_name(file_name);
+
+    let version = pe.resource_data.as_ref().and_then(|r| r.version_info.as_ref());
+    let description = version.and_then(|v| v.string_info.file_description()).unwrap_or_default();
+    let comments = version.and_then(|v| v.string_info.comments()).unwrap_or_default();
+    result.product_name = version.and_then(|v| v.string_info.product_name());
+
+    if result.class != ExecutableClass::Unknown {
+        return result;
+    }
+
+    let text = format!(
+        "{} {} {} {}",
+        file_name,
+        description,
+        comments,
+        result.product_name.clone().unwrap_or_default()
+    ).to_lowercase();
+
+    // Inno and NSIS uninstallers carry the same overlay as their installers, so they are
+    // told apart by name before the signature check
+    if file_name.starts_with("unins") || text.contains("uninstall") {
+        result.class = ExecutableClass::Uninstaller;
+        result.evidence.push("uninstaller naming".to_string());
+        return result;
+    }
+
+    // 1. Installer evidence: packaging signatures beat any name heuristic
+    if let Some(framework) = detect_installer_signature(data, pe, &comments) {
+        result.class = ExecutableClass::Installer;
+        result.evidence.push(format!("{} installer", framework.label()));
+        return result;
+    }
+
+    let requires_admin = pe.resource_data.as_ref()
+        .and_then(|r| r.manifest_data.as_ref())
+        .map(|m| String::from_utf8_lossy(m.data).contains("requireAdministrator"))
+        .unwrap_or(false);
+
+    let installer_words = ["setup", "installer", "install "];
+    if installer_words.iter().any(|w| text.contains(w)) {
+        result.class = ExecutableClass::Installer;
+        result.evidence.push("setup/installer naming".to_string());
+        if requires_admin {
+            result.evidence.push("manifest requires administrator".to_string());
+        }
+        return result;
+    }
+
+    if text.contains("redistributable") || text.contains("runtime installer") {
+        result.class = ExecutableClass::Redistributable;
+        result.evidence.push("redistributable version info".to_string());
+        return result;
+    }
+
+    // 2. Launchers and tools
+    if text.contains("launcher") {
+        result.class = ExecutableClass::Launcher;
+        result.evidence.push("launcher naming".to_string());
+        return result;
+    }
+
+    let subsystem = pe.header.optional_header
+        .map(|o| o.windows_fields.subsystem)
+        .unwrap_or(0);
+
+    if subsystem == goblin::pe::subsystem::IMAGE_SUBSYSTEM_WINDOWS_CUI {
+        result.class = ExecutableClass::Tool;
+        result.evidence.push("console subsystem".to_string());
+        return result;
+    }
+
+    if let Some(marker) = TOOL_MARKERS.iter().find(|m| file_name.contains(*m)) {
+        result.class = ExecutableClass::Tool;
+        result.evidence.push(format!("tool naming '{}'", marker));
+        return result;
+    }
+
+    // 3. Games: GUI binaries that render with a 3D API or ship with a known engine
+    let libraries: Vec<String> = pe.libraries.iter().map(|l| l.to_lowercase()).collect();
+    if let Some(dll) = libraries.iter().find(|l| GRAPHICS_DLLS.contains(&l.as_str())) {
+        result.class = ExecutableClass::Game;
+        result.evidence.push(format!("imports {}", dll));
+    } else if libraries.iter().any(|l| l == "unityplayer.dll") {
+        result.class = ExecutableClass::Game;
+        result.evidence.push("Unity player".to_string());
+    } else if requires_admin {
+        result.class = ExecutableClass::Installer;
+        result.evidence.push("manifest requires administrator".to_string());
+    } else if data.len() > LARGE_BINARY && subsystem == goblin::pe::subsystem::IMAGE_SUBSYSTEM_WINDOWS_GUI {
+        result.class = ExecutableClass::Game;
+        result.evidence.push("large GUI binary".to_string());
+    }
+
+    result
+}
+
+/// Looks for the stub/overlay signatures of common installer builders
+pub fn detect_installer_signature(data: &[u8], pe: &PE, comments: &str) -> Option<InstallerKind> {
+    if pe.sections.iter().any(|s| s.name().unwrap_or_default() == ".wixburn") {
+        return Some(InstallerKind::WixBurn);
+    }
+
+    let overlay_start = pe.sections.iter()
+        .map(|s| s.pointer_to_raw_data as usize + s.size_of_raw_data as usize)
+        .max()
+        .unwrap_or(data.len())
+        .min(data.len());
+    let overlay = &data[overlay_start..];
+    let overlay_head = &overlay[..overlay.len().min(SIGNATURE_WINDOW)];
+
+    if find_bytes(overlay_head, b"\xEF\xBE\xAD\xDENullsoftInst") {
+        return Some(InstallerKind::Nsis);
+    }
+    if comments.contains("Inno Setup") || find_bytes(overlay_head, b"Inno Setup Setup Data") {
+        return Some(InstallerKind::InnoSetup);
+    }
+    if find_bytes(overlay_head, b"InstallShield") || find_bytes(overlay_head, b"ISSetupStream") {
+        return Some(InstallerKind::InstallShield);
+    }
+    if overlay_head.starts_with(b"7z\xBC\xAF\x27\x1C") {
+        return Some(InstallerKind::SevenZipSfx);
+    }
+    // Squirrel's Setup.exe embeds a zip with Update.exe and the app's .nupkg as a resource.
+    // The zip's local headers sit at the start of the resource, its directory at the end.
+    let resources = pe.sections.iter()
+        .find(|s| s.name().unwrap_or_default() == ".rsrc")
+        .and_then(|s| data.get(s.pointer_to_raw_data as usize..)?.get(..s.size_of_raw_data as usize));
+    if let Some(resources) = resources {
+        let head = &resources[..resources.len().min(SIGNATURE_WINDOW)];
+        let tail = &resources[resources.len().saturating_sub(SIGNATURE_WINDOW)..];
+        let contains = |needle: &[u8]| find_bytes(head, needle) || find_bytes(tail, needle);
+        if contains(b"Update.exe") && contains(b".nupkg") {
+            return Some(InstallerKind::Squirrel);
+        }
+    }
+    if find_bytes(overlay_head, b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1") {
+        return Some(InstallerKind::MsiBootstrapper);
+    }
+
+    None
+}
+
+pub fn find_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty() && haystack.windows(needle.len()).any(|w| w == needle)
+}
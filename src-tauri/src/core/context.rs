@@ -1,30 +1,34 @@
 use std::arch::asm;
+use crate::core::teb::{ProcessEnvironment, ThreadEnvironment};
+use crate::core::vmm::{self, RegionType, PAGE_READWRITE, PAGE_SIZE};
 
 pub struct ExecutionContext {
     pub entry_point: usize,
     pub stack_base: *mut libc::c_void,
     pub stack_size: usize,
+    /// TEB of the thread that launches, describing the stack above
+    pub thread: ThreadEnvironment,
 }
 
 impl ExecutionContext {
-    pub fn new(entry_point: usize) -> Self {
+    pub fn new(entry_point: usize, process: &ProcessEnvironment) -> Result<Self, String> {
         let stack_size = 2 * 1024 * 1024; // 2MB stack
-        let stack_base = unsafe {
-            libc::mmap(
-                std::ptr::null_mut(),
-                stack_size,
-                libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
-                -1,
-                0
-            )
+        // The lowest page stays reserved so an overflow faults instead of running into other memory
+        let stack_base = {
+            let mut memory = vmm::process_memory();
+            memory.reserve(0, stack_size, PAGE_READWRITE, RegionType::Private, "Stack")
+                .and_then(|base| memory.commit(base + PAGE_SIZE as u64, stack_size - PAGE_SIZE, PAGE_READWRITE).map(|_| base))
+                .map_err(|e| format!("Failed to allocate the stack: {}", e))?
         };
+        let stack_top = stack_base as usize + stack_size;
+        let thread = ThreadEnvironment::new(process, stack_top, stack_base as usize + PAGE_SIZE)?;
 
-        ExecutionContext {
+        Ok(ExecutionContext {
             entry_point,
-            stack_base,
+            stack_base: stack_base as *mut libc::c_void,
             stack_size,
-        }
+            thread,
+        })
     }
 
     /// THE JUMP: This is the moment of truth where we hand control to the Windows binary.
@@ -32,16 +36,21 @@ impl ExecutionContext {
     /// 
     /// SAFETY: This is extremely dangerous. We are manually manipulating the 
     /// instruction pointer and stack.
+    /// `thread` must already be installed as the GS base; the helper does that before DllMain runs.
     pub unsafe fn launch(&self) -> ! {
-        let stack_ptr = (self.stack_base as usize + self.stack_size - 16) as *mut usize;
+        // 16-byte aligned, with room above for the entry's 32-byte shadow space
+        let stack_ptr = (self.stack_base as usize + self.stack_size - 48) as *mut usize;
         let entry = self.entry_point;
 
-        println!("Pancho-Context: JUMPING to entry point 0x{:x}", entry);
-
         // Windows x64 Calling Convention Setup
         // We clear registers and align the stack to 16 bytes
+        // The entry goes on the new stack first, since it may sit in any of the registers cleared
+        // below. Under it a null return address, so the entry starts with rsp 8 off alignment
+        // as after a call, which is what Windows code expects
         asm!(
             "mov rsp, {stack}",   // Set the new stack pointer
+            "push 0",
+            "push {entry}",
             "xor rax, rax",       // Clear registers for a clean state
             "xor rbx, rbx",
             "xor rcx, rcx",
@@ -56,7 +65,7 @@ impl ExecutionContext {
             "xor r13, r13",
             "xor r14, r14",
             "xor r15, r15",
-            "jmp {entry}",        // THE JUMP
+            "ret",                // THE JUMP
             stack = in(reg) stack_ptr,
             entry = in(reg) entry,
             options(noreturn)
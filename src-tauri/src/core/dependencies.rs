@@ -0,0 +1,380 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use goblin::pe::PE;
+use serde::{Deserialize, Serialize};
+use crate::core::loader::{self, ImportKind};
+use crate::core::mapped_file::MappedFile;
+use crate::core::runner;
+use crate::core::shim::NativeRegistry;
+use crate::recovery::auto_fix;
+use crate::wine::log_parser::WineError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum DllSource {
+    AppDirectory,
+    SystemDirectory,
+    WineBuiltin,
+    ApiSet,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResolvedDll {
+    pub name: String,
+    pub source: DllSource,
+    pub path: Option<PathBuf>,
+    pub required_by: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum DependencyIssue {
+    MissingDll { dll_name: String, required_by: String, delay_load: bool },
+    DisabledByOverride { dll_name: String, required_by: String },
+    ArchitectureMismatch { dll_name: String, path: PathBuf, expected: String, found: String },
+    MissingExport { dll_name: String, function: String, required_by: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DependencyReport {
+    pub executable: String,
+    pub is_64_bit: bool,
+    pub resolved: Vec<ResolvedDll>,
+    pub issues: Vec<DependencyIssue>,
+    /// Missing DLLs as `WineError`s, ready for `attempt_auto_fix`
+    pub fixes: Vec<WineError>,
+    /// Winetricks verbs that would provide the missing DLLs
+    pub winetricks: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+struct LoadOrder {
+    native: bool,
+    builtin: bool,
+}
+
+struct Module {
+    path: PathBuf,
+    source: DllSource,
+    machine: u16,
+    exports: Option<ExportSet>,
+}
+
+struct ExportSet {
+    names: HashSet<String>,
+    ordinal_base: u32,
+    ordinal_count: u32,
+}
+
+/// Walks an executable's import tables the way Wine's loader would, but before launch
+pub struct DependencyResolver {
+    machine: u16,
+    system_dir: HashMap<String, PathBuf>,
+    app_files: HashMap<String, PathBuf>,
+    builtins: HashMap<String, PathBuf>,
+    overrides: HashMap<String, LoadOrder>,
+    modules: HashMap<String, Option<Module>>,
+}
+
+impl DependencyResolver {
+    pub fn new(bottle_path: &Path, exe_path: &Path, engine: Option<&Path>, machine: u16) -> Self {
+        let is_64 = machine == 0x8664 || machine == 0xAA64;
+        let drive_c = bottle_path.join("drive_c/windows");
+        // 32-bit programs on a win64 prefix load from syswow64
+        let system_dir = if !is_64 && drive_c.join("syswow64").is_dir() {
+            drive_c.join("syswow64")
+        } else {
+            drive_c.join("system32")
+        };
+
+        let app_dir = exe_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let arch_dir = if is_64 { "x86_64-windows" } else { "i386-windows" };
+        let mut builtins = HashMap::new();
+        if let Some(root) = engine.and_then(|e| e.parent()).and_then(|bin| bin.parent()) {
+            for dir in [
+                root.join("lib/wine").join(arch_dir),
+                root.join("lib64/wine").join(arch_dir),
+                root.join("lib/wine"),
+            ] {
+                for (name, path) in list_dir(&dir) {
+                    builtins.entry(name.trim_end_matches(".so").to_string()).or_insert(path);
+                }
+            }
+        }
+
+        let exe_name = exe_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+        DependencyResolver {
+            app_files: list_dir(&app_dir),
+            machine,
+            system_dir: list_dir(&system_dir),
+            builtins,
+            overrides: load_overrides(bottle_path, &exe_name),
+            modules: HashMap::new(),
+        }
+    }
+
+    pub fn check(bottle_path: &Path, exe_path: &Path, engine: Option<&Path>) -> Result<DependencyReport, String> {
+        let file = MappedFile::open(exe_path)?;
+        let pe = PE::parse(&file).map_err(|e| e.to_string())?;
+
+        let mut resolver = Self::new(bottle_path, exe_path, engine, pe.header.coff_header.machine);
+        let exe_name = exe_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+        let mut report = DependencyReport {
+            executable: exe_path.to_string_lossy().to_string(),
+            is_64_bit: pe.is_64,
+            resolved: Vec::new(),
+            issues: Vec::new(),
+            fixes: Vec::new(),
+            winetricks: Vec::new(),
+        };
+
+        let mut queue: VecDeque<(String, Vec<loader::DllImports>)> = VecDeque::new();
+        queue.push_back((exe_name, loader::parse_imports(&file, &pe)?));
+        let mut seen: HashSet<String> = HashSet::new();
+
+        while let Some((importer, imports)) = queue.pop_front() {
+            for dll in imports {
+                let name = normalize(&dll.dll);
+                let delay_load = dll.kind == ImportKind::DelayLoad;
+
+                let order = resolver.overrides.get(name.trim_end_matches(".dll")).copied();
+                if order.is_some_and(|o| !o.native && !o.builtin) {
+                    if !delay_load {
+                        report.issues.push(DependencyIssue::DisabledByOverride { dll_name: name.clone(), required_by: importer.clone() });
+                    }
+                    continue;
+                }
+
+                if !resolver.modules.contains_key(&name) {
+                    let module = resolver.resolve(&name, order.unwrap_or(LoadOrder { native: true, builtin: true }), &mut report);
+                    resolver.modules.insert(name.clone(), module);
+                }
+
+                let Some(module) = resolver.modules.get(&name).and_then(|m| m.as_ref()) else {
+                    report.issues.push(DependencyIssue::MissingDll { dll_name: name.clone(), required_by: importer.clone(), delay_load });
+                    if !delay_load && !report.fixes.iter().any(|f| matches!(f, WineError::MissingDLL { dll_name } if *dll_name == name)) {
+                        report.fixes.push(WineError::MissingDLL { dll_name: name.clone() });
+                        if let Some(package) = auto_fix::winetricks_package(&name) {
+                            if !report.winetricks.iter().any(|p| p == package) {
+                                report.winetricks.push(package.to_string());
+                            }
+                        }
+                    }
+                    continue;
+                };
+
+                if seen.insert(name.clone()) {
+                    report.resolved.push(ResolvedDll {
+                        name: name.clone(),
+                        source: module.source.clone(),
+                        path: Some(module.path.clone()).filter(|p| !p.as_os_str().is_empty()),
+                        required_by: importer.clone(),
+                    });
+
+                    // Wine's own DLLs are consistent with each other, only native ones are walked
+                    if matches!(module.source, DllSource::AppDirectory | DllSource::SystemDirectory) {
+                        if let Some(nested) = read_imports(&module.path) {
+                            queue.push_back((name.clone(), nested));
+                        }
+                    }
+                }
+
+                if let Some(exports) = &module.exports {
+                    for function in &dll.functions {
+                        let present = match function.ordinal {
+                            Some(ordinal) => {
+                                (ordinal as u32) >= exports.ordinal_base
+                                    && (ordinal as u32) < exports.ordinal_base + exports.ordinal_count
+                            }
+                            None => exports.names.contains(&function.function),
+                        };
+                        if !present {
+                            report.issues.push(DependencyIssue::MissingExport {
+                                dll_name: name.clone(),
+                                function: function.function.clone(),
+                                required_by: importer.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Finds the DLL Wine would load. Copies of the wrong architecture are skipped like Wine
+    /// does, and only reported when no other candidate can be loaded instead.
+    fn resolve(&self, name: &str, order: LoadOrder, report: &mut DependencyReport) -> Option<Module> {
+        let mut mismatches = Vec::new();
+        let module = self.find(name, order, &mut mismatches);
+        if module.is_none() {
+            report.issues.extend(mismatches);
+        }
+        module
+    }
+
+    fn find(&self, name: &str, order: LoadOrder, mismatches: &mut Vec<DependencyIssue>) -> Option<Module> {
+        if name.starts_with("api-ms-win-") || name.starts_with("ext-ms-") {
+            // API sets are virtual and redirected by the loader; Wine ships them as builtins
+            let path = self.builtins.get(name).cloned().unwrap_or_default();
+            return Some(Module { path, source: DllSource::ApiSet, machine: self.machine, exports: None });
+        }
+
+        let mut placeholder = None;
+        if order.native {
+            for (files, source) in [(&self.app_files, DllSource::AppDirectory), (&self.system_dir, DllSource::SystemDirectory)] {
+                let Some(path) = files.get(name) else { continue };
+                if is_wine_placeholder(path) {
+                    placeholder = Some(path.clone());
+                    continue;
+                }
+                let Some(module) = load_module(path, source) else { continue };
+
+                // Wine skips DLLs of the wrong architecture and keeps searching
+                if module.machine != self.machine {
+                    mismatches.push(DependencyIssue::ArchitectureMismatch {
+                        dll_name: name.to_string(),
+                        path: path.clone(),
+                        expected: loader::machine_name(self.machine).to_string(),
+                        found: loader::machine_name(module.machine).to_string(),
+                    });
+                    continue;
+                }
+                return Some(module);
+            }
+        }
+
+        if order.builtin {
+            if let Some(path) = self.builtins.get(name) {
+                return load_module(path, DllSource::WineBuiltin).or(Some(Module {
+                    path: path.clone(),
+                    source: DllSource::WineBuiltin,
+                    machine: self.machine,
+                    exports: None,
+                }));
+            }
+            // A fresh prefix has a placeholder in system32 for every builtin
+            let placeholder = placeholder.or_else(|| self.system_dir.get(name).filter(|p| is_wine_placeholder(p)).cloned());
+            if let Some(path) = placeholder {
+                return Some(Module { path, source: DllSource::WineBuiltin, machine: self.machine, exports: None });
+            }
+        }
+
+        None
+    }
+}
+
+fn normalize(dll: &str) -> String {
+    let lower = dll.to_lowercase();
+    if Path::new(&lower).extension().is_some() { lower } else { format!("{}.dll", lower) }
+}
+
+/// Lowercased file name -> path, for case-insensitive lookups
+fn list_dir(dir: &Path) -> HashMap<String, PathBuf> {
+    let mut files = HashMap::new();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.path().is_file() {
+                files.insert(entry.file_name().to_string_lossy().to_lowercase(), entry.path());
+            }
+        }
+    }
+    files
+}
+
+/// Wine fills system32 with stub PEs tagged right after the DOS header
+pub(crate) fn is_wine_placeholder(path: &Path) -> bool {
+    let mut header = [0u8; 0x60];
+    let Ok(mut file) = File::open(path) else { return false };
+    if file.read_exact(&mut header).is_err() {
+        return false;
+    }
+    header[0x40..].starts_with(b"Wine placeholder DLL") || header[0x40..].starts_with(b"Wine builtin DLL")
+}
+
+fn load_module(path: &Path, source: DllSource) -> Option<Module> {
+    let data = MappedFile::open(path).ok()?;
+    let pe = PE::parse(&data).ok()?;
+
+    let exports = pe.export_data.as_ref().map(|export_data| ExportSet {
+        names: pe.exports.iter().filter_map(|e| e.name.map(String::from)).collect(),
+        ordinal_base: export_data.export_directory_table.ordinal_base,
+        ordinal_count: export_data.export_directory_table.address_table_entries,
+    });
+
+    Some(Module {
+        path: path.to_path_buf(),
+        source,
+        machine: pe.header.coff_header.machine,
+        exports: Some(exports.unwrap_or(ExportSet { names: HashSet::new(), ordinal_base: 0, ordinal_count: 0 })),
+    })
+}
+
+fn read_imports(path: &Path) -> Option<Vec<loader::DllImports>> {
+    let data = MappedFile::open(path).ok()?;
+    let pe = PE::parse(&data).ok()?;
+    loader::parse_imports(&data, &pe).ok()
+}
+
+/// Merges overrides the way Wine applies them: WINEDLLOVERRIDES, then the
+/// per-app registry key, then the global `Software\Wine\DllOverrides`
+fn load_overrides(bottle_path: &Path, exe_name: &str) -> HashMap<String, LoadOrder> {
+    let mut overrides = HashMap::new();
+    let registry = NativeRegistry::new(bottle_path);
+
+    let keys = [
+        r"Software\Wine\DllOverrides".to_string(),
+        format!(r"Software\Wine\AppDefaults\{}\DllOverrides", exe_name),
+    ];
+    for key in keys {
+        if let Some(reg_key) = registry.key(&key) {
+            for (name, value) in &reg_key.values {
+                if let Some(mode) = value.as_string() {
+                    let name = name.trim_start_matches('*').to_lowercase();
+                    overrides.insert(name.trim_end_matches(".dll").to_string(), parse_load_order(mode));
+                }
+            }
+        }
+    }
+
+    // "d3d11,dxgi=n;dwrite=d"
+    for group in runner::DLL_OVERRIDES.split(';') {
+        let Some((names, mode)) = group.split_once('=') else { continue };
+        let order = parse_load_order(mode);
+        for name in names.split(',') {
+            overrides.insert(name.to_lowercase().trim_end_matches(".dll").to_string(), order);
+        }
+    }
+
+    overrides
+}
+
+fn parse_load_order(mode: &str) -> LoadOrder {
+    let mut order = LoadOrder { native: false, builtin: false };
+    for part in mode.split(',').map(|p| p.trim().to_lowercase()) {
+        match part.as_str() {
+            "n" | "native" => order.native = true,
+            "b" | "builtin" => order.builtin = true,
+            _ => {}
+        }
+    }
+    order
+}
+
+#[tauri::command]
+pub async fn check_dependencies(bottle_id: String, exe_path: String, handle: tauri::AppHandle) -> Result<DependencyReport, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+
+    let engine = crate::core::engine::engine_for_bottle(&handle, bottle).or_else(runner::find_runner);
+    let bottle_path = bottle.path.clone();
+    // Runs before every launch and reads every DLL in the chain, so keep it off the main thread
+    tokio::task::spawn_blocking(move || {
+        DependencyResolver::check(&bottle_path, Path::new(&exe_path), engine.as_deref().map(Path::new))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -12,6 +12,15 @@ pub fn get_engines_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String>
     Ok(path)
 }
 
+/// The bottle's own engine, falling back to the downloaded Pro engine
+pub fn engine_for_bottle(app_handle: &tauri::AppHandle, bottle: &crate::core::bottle::Bottle) -> Option<String> {
+    if let Some(path) = &bottle.engine_path {
+        Some(path.to_str().unwrap().to_string())
+    } else {
+        get_pro_engine_path(app_handle).map(|p| p.to_str().unwrap().to_string())
+    }
+}
+
 pub fn get_pro_engine_path(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
     let engines_dir = get_engines_dir(app_handle).ok()?;
     let engine_folder = engines_dir.join("pancho-pro-v1");
@@ -26,12 +35,7 @@ pub fn get_pro_engine_path(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
         engine_folder.join("Contents").join("Resources").join("wine").join("bin").join("wine64"),
     ];
 
-    for path in possible_paths {
-        if path.exists() {
-            return Some(path);
-        }
-    }
-    None
+    possible_paths.into_iter().find(|path| path.exists())
 }
 
 pub async fn setup_gaming_engine(app_handle: tauri::AppHandle) -> Result<(), String> {
@@ -0,0 +1,713 @@
+use std::fs::File;
+use std::cell::UnsafeCell;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::collections::VecDeque;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use crate::core::context::ExecutionContext;
+use crate::core::modules::ModuleLoader;
+use crate::core::signals;
+use crate::core::teb::{ProcessEnvironment, ProcessParameters};
+use crate::core::trace::{self, Record, TraceBatch, TraceEvent};
+
+/// First argument that turns the app binary into the HLE helper
+pub const HELPER_FLAG: &str = "--pancho-hle-helper";
+/// The helper writes its reports here; stdout and stderr belong to the guest
+pub const REPORT_FD: i32 = 3;
+/// Traced calls sent along with an exit or crash
+pub const LAST_CALLS: usize = 64;
+
+/// How often the helper sends the calls traced since its last batch
+const TRACE_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the app checks whether the helper exited or the run was cancelled
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+/// A helper still running after this long is killed, unless the run asks for another limit
+pub const RUN_TIMEOUT: Duration = Duration::from_secs(300);
+/// Guest stdout and stderr kept per run; anything before the last this many bytes is dropped
+pub const OUTPUT_LIMIT: usize = 1024 * 1024;
+
+/// Cancel flags of the runs in progress, by the id the frontend gave each run
+static RUNS: Mutex<Vec<(String, Arc<AtomicBool>)>> = Mutex::new(Vec::new());
+
+/// Set in the helper once REPORT_FD is connected to the app
+static REPORTING: AtomicBool = AtomicBool::new(false);
+/// Next trace position the helper sends. Held while a batch or the final report is written,
+/// so lines from the streaming thread and the guest's thread never interleave on the pipe.
+static TRACE_CURSOR: Mutex<u64> = Mutex::new(0);
+
+/// What the app asks the helper to run
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecutionRequest {
+    pub exe_path: String,
+    pub bottle_path: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Route imports through the trace thunks so the report can include the last calls
+    #[serde(default)]
+    pub trace: bool,
+}
+
+/// One line of JSON on the report pipe
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HelperMessage {
+    /// Calls traced since the previous batch, sent while the guest runs and once more at the end
+    Trace { batch: TraceBatch },
+    /// Guest code called ExitProcess
+    Exited { exit_code: u32, last_calls: Vec<TraceEvent> },
+    /// A fatal signal; `fault_address` is the address that was accessed
+    Crashed { signal: i32, fault_address: u64, instruction_pointer: Option<u64>, last_calls: Vec<TraceEvent> },
+    /// Guest code called an import nothing implements; the helper exits with 127 next
+    Unimplemented { function: String, last_calls: Vec<TraceEvent> },
+    /// Loading failed before any guest code ran
+    Failed { error: String },
+}
+
+/// How a helper run ended, as seen from the app
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ExecutionReport {
+    pub exit_code: Option<i32>,
+    /// Signal that killed the helper, if any
+    pub signal: Option<i32>,
+    pub fault_address: Option<u64>,
+    pub instruction_pointer: Option<u64>,
+    pub last_calls: Vec<TraceEvent>,
+    /// Every call the helper streamed back; kept on the app side for export_api_trace
+    /// rather than sent to the frontend with the report
+    #[serde(skip)]
+    pub trace: Vec<TraceEvent>,
+    /// Traced calls the ring overwrote before the helper could send them
+    pub trace_dropped: u64,
+    /// The helper was killed through cancel_hle
+    pub cancelled: bool,
+    /// The helper was killed for running longer than the run's timeout
+    pub timed_out: bool,
+    /// "DLL!Function" of the unimplemented import that ended the run
+    pub unimplemented: Option<String>,
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// --- APP SIDE ---
+
+/// A run in progress. cancel_hle sets `cancel` while it's listed, i.e. until this is dropped.
+pub struct RunHandle {
+    id: String,
+    pub cancel: Arc<AtomicBool>,
+}
+
+impl RunHandle {
+    pub fn register(id: &str) -> Result<Self, String> {
+        let mut runs = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+        if runs.iter().any(|(run, _)| run == id) {
+            return Err(format!("HLE run {} is already in progress", id));
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        runs.push((id.to_string(), cancel.clone()));
+        Ok(RunHandle { id: id.to_string(), cancel })
+    }
+}
+
+impl Drop for RunHandle {
+    fn drop(&mut self) {
+        RUNS.lock().unwrap_or_else(|e| e.into_inner()).retain(|(run, _)| *run != self.id);
+    }
+}
+
+/// Asks the run `id` to stop; false if no such run is in progress
+pub fn cancel_run(id: &str) -> bool {
+    let runs = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some((_, cancel)) = runs.iter().find(|(run, _)| run == id) else { return false };
+    cancel.store(true, Ordering::SeqCst);
+    true
+}
+
+/// Guest code finds its TEB through GS, which only Linux x86_64 lets the helper point anywhere
+pub const HLE_SUPPORTED: bool = cfg!(all(target_os = "linux", target_arch = "x86_64"));
+
+/// Runs `request` in a fresh helper process and waits for it. A crash in guest code
+/// only takes the helper down. `on_trace` sees each trace batch as it arrives; setting
+/// `cancel` or running past `timeout` kills the helper.
+pub fn execute_isolated(
+    request: &ExecutionRequest,
+    cancel: &AtomicBool,
+    timeout: Duration,
+    mut on_trace: impl FnMut(&TraceBatch) + Send + 'static,
+) -> Result<ExecutionReport, String> {
+    if !HLE_SUPPORTED {
+        return Err("Running Windows code in the HLE helper needs Linux on x86_64".to_string());
+    }
+    let payload = serde_json::to_vec(request).map_err(|e| e.to_string())?;
+    let (read_fd, write_fd) = report_pipe()?;
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+
+    let mut command = Command::new(exe);
+    command.arg(HELPER_FLAG)
+           .stdin(Stdio::piped())
+           .stdout(Stdio::piped())
+           .stderr(Stdio::piped());
+    unsafe {
+        command.pre_exec(move || {
+            // dup2 clears close-on-exec on the copy, but not when the descriptor is already REPORT_FD
+            let result = if write_fd == REPORT_FD {
+                libc::fcntl(REPORT_FD, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(write_fd, REPORT_FD)
+            };
+            if result < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let spawned = command.spawn();
+    unsafe { libc::close(write_fd) };
+    let reports = unsafe { File::from_raw_fd(read_fd) };
+    let mut child = spawned.map_err(|e| format!("Failed to spawn the HLE helper: {}", e))?;
+
+    // A helper that dies before reading its request breaks the pipe; it still gets waited
+    // for, and its exit status says more than the write error
+    let sent = match child.stdin.take() {
+        Some(mut stdin) => stdin.write_all(&payload),
+        None => Ok(()),
+    };
+
+    // Drain the report pipe on its own thread so neither pipe can fill up and stall the helper
+    let reader = std::thread::spawn(move || {
+        let mut text = String::new();
+        for line in BufReader::new(reports).lines().map_while(Result::ok) {
+            if let Ok(HelperMessage::Trace { batch }) = serde_json::from_str(&line) {
+                on_trace(&batch);
+            }
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text
+    });
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let exit = wait_for_helper(&mut child, cancel, timeout)?;
+    let text = reader.join().unwrap_or_default();
+
+    let mut report = build_report(exit.status, &text);
+    report.cancelled = exit.cancelled;
+    report.timed_out = exit.timed_out;
+    if exit.timed_out {
+        report.error.get_or_insert(format!("Still running after {} seconds", timeout.as_secs()));
+    }
+    report.stdout = output_text(stdout.join().unwrap_or_default());
+    report.stderr = output_text(stderr.join().unwrap_or_default());
+    if let Err(e) = sent {
+        report.error.get_or_insert(format!("Failed to send the request to the HLE helper: {}", e));
+    }
+    Ok(report)
+}
+
+/// How the wait for a helper ended
+pub struct HelperExit {
+    pub status: ExitStatus,
+    pub cancelled: bool,
+    pub timed_out: bool,
+}
+
+/// Waits for the helper to exit, killing it once `cancel` is set or `timeout` has passed
+pub fn wait_for_helper(child: &mut Child, cancel: &AtomicBool, timeout: Duration) -> Result<HelperExit, String> {
+    let deadline = Instant::now() + timeout;
+    let mut exit = HelperExit { status: ExitStatus::default(), cancelled: false, timed_out: false };
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                exit.status = status;
+                return Ok(exit);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                // Still reap the helper so it doesn't outlive the run
+                let _ = child.kill();
+                child.wait().map_err(|e| e.to_string())?;
+                return Err(e.to_string());
+            }
+        }
+        let killed = exit.cancelled || exit.timed_out;
+        if !killed && cancel.load(Ordering::SeqCst) {
+            exit.cancelled = true;
+            let _ = child.kill();
+        } else if !killed && Instant::now() >= deadline {
+            exit.timed_out = true;
+            let _ = child.kill();
+        }
+        std::thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+/// Reads one of the helper's output pipes to the end on its own thread, keeping the last
+/// OUTPUT_LIMIT bytes. Returns them and how many bytes came before.
+pub fn drain(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<(Vec<u8>, usize)> {
+    std::thread::spawn(move || {
+        let mut output = VecDeque::new();
+        let mut dropped = 0;
+        let Some(mut pipe) = pipe else { return (Vec::new(), 0) };
+        let mut chunk = [0u8; 64 * 1024];
+        loop {
+            match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(count) => output.extend(&chunk[..count]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+            let excess = output.len().saturating_sub(OUTPUT_LIMIT);
+            output.drain(..excess);
+            dropped += excess;
+        }
+        (output.into(), dropped)
+    })
+}
+
+/// Guest output as text, saying so when its beginning was dropped
+fn output_text((output, dropped): (Vec<u8>, usize)) -> String {
+    let text = String::from_utf8_lossy(&output);
+    if dropped == 0 {
+        return text.to_string();
+    }
+    format!("[{} earlier bytes not kept]\n{}", dropped, text)
+}
+
+/// Combines the helper's exit status with what it reported before exiting
+pub fn build_report(status: ExitStatus, reports: &str) -> ExecutionReport {
+    let mut report = ExecutionReport {
+        exit_code: status.code(),
+        signal: status.signal(),
+        ..Default::default()
+    };
+
+    for line in reports.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str::<HelperMessage>(line) {
+            Ok(HelperMessage::Trace { batch }) => {
+                report.trace.extend(batch.events);
+                report.trace_dropped += batch.dropped;
+            }
+            Ok(HelperMessage::Exited { exit_code, last_calls }) => {
+                report.exit_code = Some(exit_code as i32);
+                report.last_calls = last_calls;
+            }
+            Ok(HelperMessage::Crashed { signal, fault_address, instruction_pointer, last_calls }) => {
+                report.exit_code = None;
+                report.signal = Some(signal);
+                report.fault_address = Some(fault_address);
+                report.instruction_pointer = instruction_pointer;
+                report.last_calls = last_calls;
+            }
+            Ok(HelperMessage::Unimplemented { function, last_calls }) => {
+                report.unimplemented = Some(function);
+                report.last_calls = last_calls;
+            }
+            Ok(HelperMessage::Failed { error }) => report.error = Some(error),
+            Err(e) => report.error = Some(format!("Unreadable helper report: {}", e)),
+        }
+    }
+    report
+}
+
+/// Pipe with both ends close-on-exec, so only REPORT_FD reaches the helper
+fn report_pipe() -> Result<(i32, i32), String> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(format!("Failed to create the report pipe: {}", std::io::Error::last_os_error()));
+    }
+    for fd in fds {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    Ok((fds[0], fds[1]))
+}
+
+/// Kills the helper of run `run_id`, if it's still going; its run_hle then returns the report
+#[tauri::command]
+pub fn cancel_hle(run_id: String) {
+    cancel_run(&run_id);
+}
+
+/// Whether this build can run executables through the HLE helper
+#[tauri::command]
+pub fn is_hle_supported() -> bool {
+    HLE_SUPPORTED
+}
+
+#[tauri::command]
+pub async fn run_hle(
+    run_id: String,
+    path: String,
+    bottle_id: Option<String>,
+    args: Option<Vec<String>>,
+    trace: Option<bool>,
+    timeout_secs: Option<u64>,
+    handle: tauri::AppHandle,
+) -> Result<ExecutionReport, String> {
+    let bottle_path = match bottle_id {
+        Some(id) => {
+            let bottles = crate::core::bottle::list_bottles(&handle)?;
+            let bottle = bottles.iter().find(|b| b.id == id).ok_or("Bottle not found")?;
+            Some(bottle.path.to_string_lossy().to_string())
+        }
+        None => None,
+    };
+    let request = ExecutionRequest {
+        exe_path: path,
+        bottle_path,
+        args: args.unwrap_or_default(),
+        trace: trace.unwrap_or(true),
+    };
+
+    // Calls show up in the frontend as "api-trace" events while the helper runs
+    let emitter = handle.clone();
+    let on_trace = move |batch: &TraceBatch| {
+        let _ = emitter.emit("api-trace", batch);
+    };
+    // Listed before the helper starts, so a cancel can't arrive too early to count
+    let run = RunHandle::register(&run_id)?;
+    let cancel = run.cancel.clone();
+    let timeout = timeout_secs.map_or(RUN_TIMEOUT, Duration::from_secs);
+    let mut report = tokio::task::spawn_blocking(move || execute_isolated(&request, &cancel, timeout, on_trace))
+        .await
+        .map_err(|e| e.to_string())??;
+    trace::record_run(std::mem::take(&mut report.trace));
+    Ok(report)
+}
+
+// --- HELPER SIDE ---
+
+/// Entry point of the helper process: reads the request from stdin, loads the image
+/// and jumps to it. Only returns if nothing could be launched.
+pub fn run_helper() -> i32 {
+    unsafe { start_reporting() };
+
+    let mut input = String::new();
+    let result = std::io::stdin().read_to_string(&mut input)
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::from_str::<ExecutionRequest>(&input).map_err(|e| e.to_string()))
+        .and_then(|request| launch(&request));
+
+    let error = match result {
+        Ok(never) => match never {},
+        Err(e) => e,
+    };
+    send_last(&HelperMessage::Failed { error });
+    1
+}
+
+fn launch(request: &ExecutionRequest) -> Result<std::convert::Infallible, String> {
+    let exe = PathBuf::from(&request.exe_path);
+    let app_dir = exe.parent().unwrap_or(Path::new("/")).to_path_buf();
+    let bottle = request.bottle_path.as_deref().map(Path::new);
+
+    let mut loader = ModuleLoader::new(&app_dir, bottle);
+    loader.linker.get_mut().set_tracing(request.trace);
+
+    let windows_path = |path: &Path| match &loader.drives {
+        Some(drives) => drives.to_windows(path).ok_or(format!("No drive in this bottle reaches {}", path.display())),
+        None => Ok(path.display().to_string()),
+    };
+    let image_path = windows_path(&exe)?;
+    let command_line = std::iter::once(&image_path)
+        .chain(&request.args)
+        .map(|arg| quote_argument(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let parameters = ProcessParameters {
+        current_directory: windows_path(&app_dir)?,
+        image_path,
+        command_line,
+        environment: default_environment(),
+    };
+    *loader.environment.get_mut() = Some(ProcessEnvironment::new(&parameters)?);
+
+    // The TEB goes in first so DllMain already runs with GS set up
+    let mut context = ExecutionContext::new(0, loader.environment.get_mut().as_ref().unwrap())?;
+    context.thread.install()?;
+    // `loader` doesn't move from here on, so LoadLibrary can reach it
+    loader.activate();
+    if request.trace {
+        stream_trace();
+    }
+    loader.load(&exe)?;
+    context.entry_point = loader.modules.borrow().first()
+        .and_then(|module| module.entry_point)
+        .ok_or(format!("{} has no entry point", request.exe_path))?;
+
+    // `loader` owns the mapped images and stays on this stack for as long as the guest runs
+    unsafe { context.launch() }
+}
+
+/// The environment a fresh Wine prefix gives its processes
+fn default_environment() -> Vec<(String, String)> {
+    [
+        ("ComSpec", "C:\\windows\\system32\\cmd.exe"),
+        ("PATH", "C:\\windows\\system32;C:\\windows;C:\\windows\\system32\\wbem"),
+        ("PATHEXT", ".COM;.EXE;.BAT;.CMD;.VBS;.JS;.WSF"),
+        ("SystemDrive", "C:"),
+        ("SystemRoot", "C:\\windows"),
+        ("TEMP", "C:\\windows\\temp"),
+        ("TMP", "C:\\windows\\temp"),
+        ("windir", "C:\\windows"),
+    ]
+    .iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+}
+
+/// Quotes an argument the way CommandLineToArgvW splits it back
+fn quote_argument(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+/// Sends exits and crashes to REPORT_FD from now on. Only for the helper process:
+/// the crash handlers it installs are process-wide.
+pub unsafe fn start_reporting() {
+    REPORTING.store(true, Ordering::SeqCst);
+    signals::install_crash_reporter();
+}
+
+/// Sends the calls traced since the last batch every TRACE_INTERVAL until the helper exits
+fn stream_trace() {
+    std::thread::spawn(|| loop {
+        std::thread::sleep(TRACE_INTERVAL);
+        let mut cursor = TRACE_CURSOR.lock().unwrap_or_else(|e| e.into_inner());
+        let batch = trace::ring().read_since(*cursor);
+        send_trace(&mut cursor, batch);
+    });
+}
+
+fn send_trace(cursor: &mut u64, batch: TraceBatch) {
+    *cursor = batch.next;
+    if !batch.events.is_empty() || batch.dropped > 0 {
+        send(&HelperMessage::Trace { batch });
+    }
+}
+
+/// Sends the calls not streamed yet, then `message`. The cursor stays locked since the
+/// helper exits right after: a batch cut off halfway would be an unreadable last line.
+fn send_last(message: &HelperMessage) {
+    if !REPORTING.load(Ordering::SeqCst) {
+        return;
+    }
+    let mut cursor = TRACE_CURSOR.lock().unwrap_or_else(|e| e.into_inner());
+    let batch = trace::ring().read_since(*cursor);
+    send_trace(&mut cursor, batch);
+    send(message);
+    std::mem::forget(cursor);
+}
+
+/// ExitProcess: tells the app the exit code and the calls leading up to it
+pub fn report_exit(exit_code: u32) {
+    send_last(&HelperMessage::Exited { exit_code, last_calls: trace::ring().last(LAST_CALLS) });
+}
+
+/// Called from an import stub just before the helper exits with 127
+pub fn report_unimplemented(function: &str) {
+    send_last(&HelperMessage::Unimplemented { function: function.to_string(), last_calls: trace::ring().last(LAST_CALLS) });
+}
+
+/// Called from the crash handler just before the helper dies. Nothing here allocates or
+/// blocks: the calls not streamed yet and the crash report are written as JSON lines into
+/// a static buffer and sent with write(2). The streaming thread may be halfway through a
+/// batch, so this waits a little for the cursor, but not forever in case the crash
+/// happened while this thread held it.
+pub fn report_crash(signal: i32, fault_address: u64, instruction_pointer: Option<u64>) {
+    if !REPORTING.load(Ordering::SeqCst) {
+        return;
+    }
+    // A second thread crashing meanwhile waits for the first one to take the process down
+    if CRASH_LINE.busy.swap(true, Ordering::SeqCst) {
+        loop {
+            unsafe { libc::pause() };
+        }
+    }
+    let line = unsafe { &mut *CRASH_LINE.line.get() };
+    let ring = trace::ring();
+
+    trace::with_names_without_blocking(|names| {
+        let cursor = (0..100).find_map(|_| match TRACE_CURSOR.try_lock() {
+            Ok(cursor) => Some(cursor),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => {
+                std::thread::sleep(Duration::from_millis(1));
+                None
+            }
+        });
+        if let Some(mut cursor) = cursor {
+            // As many trace lines as the unsent calls need
+            loop {
+                line.clear();
+                line.push(r#"{"kind":"trace","batch":{"events":["#);
+                let mut first = true;
+                let (next, dropped) = ring.scan(*cursor, |seq, record| {
+                    if line.len > CRASH_LINE_FLUSH {
+                        return false;
+                    }
+                    line.event(&mut first, seq, record, names);
+                    true
+                });
+                if next == *cursor && dropped == 0 {
+                    break;
+                }
+                let _ = write!(line, r#"],"next":{},"dropped":{}}}}}"#, next, dropped);
+                line.push("\n");
+                write_line(line.bytes());
+                *cursor = next;
+            }
+            std::mem::forget(cursor);
+        }
+
+        line.clear();
+        let _ = write!(line, r#"{{"kind":"crashed","signal":{},"fault_address":{},"instruction_pointer":"#, signal, fault_address);
+        match instruction_pointer {
+            Some(ip) => { let _ = write!(line, "{}", ip); }
+            None => line.push("null"),
+        }
+        line.push(r#","last_calls":["#);
+        let mut first = true;
+        ring.scan(ring.head().saturating_sub(LAST_CALLS as u64), |seq, record| {
+            line.event(&mut first, seq, record, names);
+            true
+        });
+        line.push("]}\n");
+        write_line(line.bytes());
+    });
+}
+
+/// Room for one report line built in the crash handler
+const CRASH_LINE_SIZE: usize = 128 * 1024;
+/// A trace line from the crash handler is sent once it's this full; one more call always fits
+const CRASH_LINE_FLUSH: usize = CRASH_LINE_SIZE - 8 * 1024;
+/// Function names in crash reports are cut short after this many bytes
+const CRASH_NAME_LIMIT: usize = 256;
+
+/// The crash handler's line buffer; `busy` goes to the first thread that crashes
+struct CrashLineCell {
+    busy: AtomicBool,
+    line: UnsafeCell<CrashLine>,
+}
+
+unsafe impl Sync for CrashLineCell {}
+
+static CRASH_LINE: CrashLineCell = CrashLineCell {
+    busy: AtomicBool::new(false),
+    line: UnsafeCell::new(CrashLine { buffer: [0; CRASH_LINE_SIZE], len: 0 }),
+};
+
+/// Fixed-size text buffer; whatever doesn't fit is dropped
+struct CrashLine {
+    buffer: [u8; CRASH_LINE_SIZE],
+    len: usize,
+}
+
+impl CrashLine {
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    fn push(&mut self, text: &str) {
+        let count = text.len().min(CRASH_LINE_SIZE - self.len);
+        self.buffer[self.len..self.len + count].copy_from_slice(&text.as_bytes()[..count]);
+        self.len += count;
+    }
+
+    /// A TraceEvent as serde writes it, after a comma unless it's the first
+    fn event(&mut self, first: &mut bool, seq: u64, record: &Record, names: &[String]) {
+        if !std::mem::take(first) {
+            self.push(",");
+        }
+        let _ = write!(self, r#"{{"seq":{},"function":""#, seq);
+        match names.get(record.function as usize) {
+            Some(name) => self.escaped(name),
+            None => { let _ = write!(self, "#{}", record.function); }
+        }
+        let [rcx, rdx, r8, r9] = record.args;
+        let _ = write!(
+            self,
+            r#"","args":[{},{},{},{}],"ret":{},"start_ns":{},"duration_ns":{},"thread_id":{}}}"#,
+            rcx, rdx, r8, r9, record.ret, record.start, record.duration, record.thread,
+        );
+    }
+
+    /// JSON string contents, up to CRASH_NAME_LIMIT bytes of `text`
+    fn escaped(&mut self, text: &str) {
+        for (offset, c) in text.char_indices() {
+            if offset + c.len_utf8() > CRASH_NAME_LIMIT {
+                break;
+            }
+            let _ = match c {
+                '"' => self.write_str("\\\""),
+                '\\' => self.write_str("\\\\"),
+                c if (c as u32) < 0x20 => write!(self, "\\u{:04x}", c as u32),
+                c => self.write_char(c),
+            };
+        }
+    }
+}
+
+impl std::fmt::Write for CrashLine {
+    fn write_str(&mut self, text: &str) -> std::fmt::Result {
+        self.push(text);
+        Ok(())
+    }
+}
+
+/// Writes one report line with a single write(2), so nothing buffered is lost if the helper dies next
+fn send(message: &HelperMessage) {
+    if !REPORTING.load(Ordering::SeqCst) {
+        return;
+    }
+    let Ok(mut line) = serde_json::to_vec(message) else { return };
+    line.push(b'\n');
+    write_line(&line);
+}
+
+/// Writes a whole line to REPORT_FD; async-signal-safe
+fn write_line(line: &[u8]) {
+    let mut written = 0;
+    while written < line.len() {
+        let result = unsafe { libc::write(REPORT_FD, line[written..].as_ptr() as *const libc::c_void, line.len() - written) };
+        if result <= 0 {
+            return;
+        }
+        written += result as usize;
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use goblin::pe::PE;
+use serde::{Deserialize, Serialize};
+use crate::core::{classifier, msi};
+use crate::core::mapped_file::MappedFile;
+use crate::wine::paths::{host_to_windows_path, is_windows_path};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum InstallerKind {
+    Nsis,
+    InnoSetup,
+    InstallShield,
+    WixBurn,
+    Squirrel,
+    SevenZipSfx,
+    /// Setup.exe wrapping an embedded MSI (Advanced Installer, InstallAware, ...)
+    MsiBootstrapper,
+    /// An `.msi` package, run through msiexec
+    Msi,
+}
+
+impl InstallerKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            InstallerKind::Nsis => "NSIS",
+            InstallerKind::InnoSetup => "Inno Setup",
+            InstallerKind::InstallShield => "InstallShield",
+            InstallerKind::WixBurn => "WiX Burn",
+            InstallerKind::Squirrel => "Squirrel",
+            InstallerKind::SevenZipSfx => "7-Zip SFX",
+            InstallerKind::MsiBootstrapper => "MSI bootstrapper",
+            InstallerKind::Msi => "MSI",
+        }
+    }
+
+    /// Whether `silent_args` can honour a target directory
+    pub fn supports_target_dir(&self) -> bool {
+        !matches!(self, InstallerKind::WixBurn | InstallerKind::Squirrel | InstallerKind::MsiBootstrapper)
+    }
+
+    /// Switches for an unattended install, written as they appear on the Windows command
+    /// line (see `command_line`). `target_dir` is a Windows path.
+    /// MSI switches are the ones passed to msiexec after the package path.
+    pub fn silent_args(&self, target_dir: Option<&str>) -> Vec<String> {
+        let mut args: Vec<String> = match self {
+            InstallerKind::Nsis => vec!["/S".into()],
+            InstallerKind::InnoSetup => vec!["/VERYSILENT".into(), "/SUPPRESSMSGBOXES".into(), "/NORESTART".into(), "/SP-".into()],
+            InstallerKind::InstallShield => vec!["/s".into()],
+            InstallerKind::WixBurn | InstallerKind::MsiBootstrapper => vec!["/quiet".into(), "/norestart".into()],
+            InstallerKind::Squirrel => vec!["--silent".into()],
+            InstallerKind::SevenZipSfx => vec!["-y".into()],
+            InstallerKind::Msi => vec!["/qn".into(), "/norestart".into()],
+        };
+
+        match (self, target_dir) {
+            // NSIS takes everything after /D= as the path, so it goes last and unquoted
+            (InstallerKind::Nsis, Some(dir)) => args.push(format!("/D={}", dir)),
+            (InstallerKind::InnoSetup, Some(dir)) => args.push(format!("/DIR=\"{}\"", dir)),
+            // /v hands the quoted rest to the embedded msiexec; inner quotes are escaped with \
+            (InstallerKind::InstallShield, Some(dir)) => args.push(format!("/v\"/qn INSTALLDIR=\\\"{}\\\"\"", dir)),
+            (InstallerKind::InstallShield, None) => args.push("/v/qn".into()),
+            (InstallerKind::SevenZipSfx, Some(dir)) => args.push(format!("-o\"{}\"", dir)),
+            (InstallerKind::Msi, Some(dir)) => args.push(format!("TARGETDIR=\"{}\"", dir)),
+            _ => {}
+        }
+        args
+    }
+}
+
+/// Windows command line for `program` and switches from `silent_args`. Wine quotes each
+/// argv item that contains a space, which breaks `/D=` and `/v"..."`, so installers are
+/// started from this line as written (see `runner::run_command_line`).
+pub fn command_line(program: &str, switches: &[String]) -> String {
+    std::iter::once(format!("\"{}\"", program))
+        .chain(switches.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Identifies the installer technology of a setup file
+pub fn detect(path: &Path) -> Option<InstallerKind> {
+    let data = MappedFile::open(path).ok()?;
+    if msi::is_msi(&data) {
+        return Some(InstallerKind::Msi);
+    }
+
+    let pe = PE::parse(&data).ok()?;
+    let comments = pe.resource_data.as_ref()
+        .and_then(|r| r.version_info.as_ref())
+        .and_then(|v| v.string_info.comments())
+        .unwrap_or_default();
+    classifier::detect_installer_signature(&data, &pe, &comments)
+}
+
+/// Windows command line for an unattended install. `target_dir` may be a Windows path or a host path.
+pub fn unattended_command(bottle_path: &Path, installer: &Path, target_dir: Option<&str>) -> Result<String, String> {
+    let kind = detect(installer).ok_or("Unknown installer type; no silent switches available")?;
+
+    let target_dir = target_dir.map(|dir| {
+        if is_windows_path(dir) {
+            Ok(dir.to_string())
+        } else {
+            host_to_windows_path(bottle_path, Path::new(dir))
+        }
+    }).transpose()?;
+    if target_dir.is_some() && !kind.supports_target_dir() {
+        return Err(format!("{} installers can't be given a target directory", kind.label()));
+    }
+
+    let switches = kind.silent_args(target_dir.as_deref());
+    if kind == InstallerKind::Msi {
+        return msiexec_command(bottle_path, installer, switches);
+    }
+
+    Ok(command_line(&host_to_windows_path(bottle_path, installer)?, &switches))
+}
+
+/// Verbose msiexec log for a package, kept in the bottle's `logs/msi` folder
+pub fn msi_log_path(bottle_path: &Path, package: &Path) -> PathBuf {
+    let stem = package.file_stem().unwrap_or_default().to_string_lossy();
+    bottle_path.join("logs").join("msi").join(format!("{}.log", stem))
+}
+
+/// `msiexec /i <package> /l*v <log> [switches]` inside the bottle, as a Windows command line
+pub fn msiexec_command(bottle_path: &Path, package: &Path, switches: Vec<String>) -> Result<String, String> {
+    let log = msi_log_path(bottle_path, package);
+    if let Some(dir) = log.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    let mut args = vec![
+        "/i".to_string(),
+        format!("\"{}\"", host_to_windows_path(bottle_path, package)?),
+        "/l*v".to_string(),
+        format!("\"{}\"", host_to_windows_path(bottle_path, &log)?),
+    ];
+    args.extend(switches);
+    Ok(command_line(r"C:\windows\system32\msiexec.exe", &args))
+}
+
+#[tauri::command]
+pub fn detect_installer(path: String) -> Option<InstallerKind> {
+    detect(Path::new(&path))
+}
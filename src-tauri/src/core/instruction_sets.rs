@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use goblin::pe::PE;
+use goblin::pe::section_table::IMAGE_SCN_MEM_EXECUTE;
+use iced_x86::{CpuidFeature, Decoder, DecoderOptions, Instruction, Mnemonic};
+use serde::{Deserialize, Serialize};
+use crate::core::mapped_file::MappedFile;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InstructionSet {
+    Sse3,
+    Ssse3,
+    Sse41,
+    Sse42,
+    Sse4a,
+    Popcnt,
+    Avx,
+    Avx2,
+    Fma,
+    F16c,
+    Bmi1,
+    Bmi2,
+    Lzcnt,
+    AvxVnni,
+    Avx512,
+    Fma4,
+    Xop,
+}
+
+impl InstructionSet {
+    fn from_cpuid(feature: CpuidFeature) -> Option<Self> {
+        use CpuidFeature::*;
+        Some(match feature {
+            SSE3 => Self::Sse3,
+            SSSE3 => Self::Ssse3,
+            SSE4_1 => Self::Sse41,
+            SSE4_2 => Self::Sse42,
+            SSE4A => Self::Sse4a,
+            POPCNT => Self::Popcnt,
+            AVX => Self::Avx,
+            AVX2 => Self::Avx2,
+            FMA => Self::Fma,
+            F16C => Self::F16c,
+            BMI1 => Self::Bmi1,
+            BMI2 => Self::Bmi2,
+            LZCNT => Self::Lzcnt,
+            AVX_VNNI | AVX_IFMA | AVX_NE_CONVERT | AVX_VNNI_INT8 => Self::AvxVnni,
+            AVX512F | AVX512CD | AVX512BW | AVX512DQ | AVX512VL | AVX512ER | AVX512PF
+            | AVX512_4FMAPS | AVX512_4VNNIW | AVX512_BF16 | AVX512_BITALG | AVX512_IFMA
+            | AVX512_VBMI | AVX512_VBMI2 | AVX512_VNNI | AVX512_VP2INTERSECT
+            | AVX512_VPOPCNTDQ | AVX512_FP16 => Self::Avx512,
+            FMA4 => Self::Fma4,
+            XOP => Self::Xop,
+            _ => return None,
+        })
+    }
+}
+
+/// The extensions a target CPU (or translator) can execute
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CpuProfile {
+    pub name: String,
+    pub supported: Vec<InstructionSet>,
+}
+
+const X86_64_V2: [InstructionSet; 5] = [
+    InstructionSet::Sse3,
+    InstructionSet::Ssse3,
+    InstructionSet::Sse41,
+    InstructionSet::Sse42,
+    InstructionSet::Popcnt,
+];
+
+const X86_64_V3: [InstructionSet; 7] = [
+    InstructionSet::Avx,
+    InstructionSet::Avx2,
+    InstructionSet::Fma,
+    InstructionSet::F16c,
+    InstructionSet::Bmi1,
+    InstructionSet::Bmi2,
+    InstructionSet::Lzcnt,
+];
+
+impl CpuProfile {
+    pub const PRESETS: [&'static str; 5] = ["rosetta2", "rosetta2-avx", "x86-64-v2", "x86-64-v3", "x86-64-v4"];
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let supported: Vec<InstructionSet> = match name {
+            // Rosetta 2 before macOS 15 stops at SSE4.2
+            "rosetta2" | "x86-64-v2" => X86_64_V2.to_vec(),
+            // macOS 15 added AVX and AVX2 translation
+            "rosetta2-avx" | "x86-64-v3" => X86_64_V2.iter().chain(X86_64_V3.iter()).copied().collect(),
+            "x86-64-v4" => X86_64_V2.iter().chain(X86_64_V3.iter()).copied().chain([InstructionSet::Avx512]).collect(),
+            _ => return None,
+        };
+        Some(CpuProfile { name: name.to_string(), supported })
+    }
+}
+
+impl Default for CpuProfile {
+    fn default() -> Self {
+        Self::preset("rosetta2").unwrap()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstructionSite {
+    pub module: String,
+    pub rva: u32,
+    pub mnemonic: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstructionUsage {
+    pub set: InstructionSet,
+    pub count: u64,
+    pub modules: Vec<String>,
+    /// First few places the extension shows up
+    pub sites: Vec<InstructionSite>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstructionScanReport {
+    pub profile: CpuProfile,
+    pub modules: Vec<String>,
+    pub usage: Vec<InstructionUsage>,
+    /// Extensions the profile can't execute that the code uses without checking the CPU first
+    pub unsupported: Vec<InstructionSet>,
+    /// Extensions the profile can't execute that only show up behind a CPUID check
+    pub dispatched: Vec<InstructionSet>,
+    /// Modules that execute CPUID; their AVX paths may only run when the CPU reports support
+    pub runtime_dispatch: Vec<String>,
+}
+
+/// What the disassembly found, independent of the CPU profile
+#[derive(Clone)]
+struct ModuleScan {
+    modules: Vec<String>,
+    usage: Vec<InstructionUsage>,
+    runtime_dispatch: Vec<String>,
+    /// Extensions used outside CPUID-dispatched code
+    required: Vec<InstructionSet>,
+}
+
+/// Scanned files and their modification times; a changed or added DLL invalidates the entry
+type Fingerprint = Vec<(PathBuf, Option<SystemTime>)>;
+
+static SCAN_CACHE: Mutex<BTreeMap<PathBuf, (Fingerprint, ModuleScan)>> = Mutex::new(BTreeMap::new());
+
+const SITES_PER_SET: usize = 10;
+// Bundled DLLs beyond this are not disassembled
+const MAX_BUNDLED_DLLS: usize = 64;
+const MAX_MODULE_SIZE: u64 = 512 * 1024 * 1024;
+// Code built for an extension (/arch:AVX2) uses it throughout, while CPUID-dispatched
+// paths such as the CRT's memcpy are a tiny share of the module
+const DISPATCHED_SHARE: f64 = 0.01;
+
+pub struct InstructionScanner;
+
+impl InstructionScanner {
+    /// Disassembles the executable sections of the exe and every DLL next to it
+    pub fn scan(exe_path: &Path, profile: &CpuProfile) -> Result<InstructionScanReport, String> {
+        let mut targets = vec![exe_path.to_path_buf()];
+        if let Some(app_dir) = exe_path.parent() {
+            let mut dlls: Vec<_> = std::fs::read_dir(app_dir)
+                .map(|entries| entries.flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dll")))
+                    .collect())
+                .unwrap_or_default();
+            dlls.sort();
+            dlls.truncate(MAX_BUNDLED_DLLS);
+            targets.extend(dlls);
+        }
+
+        let fingerprint: Fingerprint = targets.iter()
+            .map(|t| (t.clone(), std::fs::metadata(t).and_then(|m| m.modified()).ok()))
+            .collect();
+        let cached = SCAN_CACHE.lock().unwrap().get(exe_path)
+            .filter(|(seen, _)| *seen == fingerprint)
+            .map(|(_, scan)| scan.clone());
+        let scan = match cached {
+            Some(scan) => scan,
+            None => {
+                let scan = Self::scan_targets(&targets)?;
+                SCAN_CACHE.lock().unwrap().insert(exe_path.to_path_buf(), (fingerprint, scan.clone()));
+                scan
+            }
+        };
+
+        let (unsupported, dispatched) = scan.usage.iter()
+            .map(|u| u.set)
+            .filter(|set| !profile.supported.contains(set))
+            .partition(|set| scan.required.contains(set));
+
+        Ok(InstructionScanReport {
+            profile: profile.clone(),
+            modules: scan.modules,
+            usage: scan.usage,
+            unsupported,
+            dispatched,
+            runtime_dispatch: scan.runtime_dispatch,
+        })
+    }
+
+    fn scan_targets(targets: &[PathBuf]) -> Result<ModuleScan, String> {
+        let mut usage: BTreeMap<InstructionSet, InstructionUsage> = BTreeMap::new();
+        let mut modules = Vec::new();
+        let mut runtime_dispatch = Vec::new();
+        let mut required = Vec::new();
+
+        for (index, target) in targets.iter().enumerate() {
+            if std::fs::metadata(target).map(|m| m.len()).unwrap_or(0) > MAX_MODULE_SIZE {
+                continue;
+            }
+            let data = match MappedFile::open(target) {
+                Ok(data) => data,
+                Err(e) if index == 0 => return Err(e),
+                Err(_) => continue,
+            };
+            let pe = match PE::parse(&data) {
+                Ok(pe) => pe,
+                Err(e) if index == 0 => return Err(e.to_string()),
+                Err(_) => continue,
+            };
+            let module = target.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+            let mut counts: BTreeMap<InstructionSet, u64> = BTreeMap::new();
+            let (has_cpuid, total) = Self::scan_module(&module, &data, &pe, &mut usage, &mut counts);
+            for (set, count) in counts {
+                let dispatched = has_cpuid && (count as f64) < total as f64 * DISPATCHED_SHARE;
+                if !dispatched && !required.contains(&set) {
+                    required.push(set);
+                }
+            }
+            if has_cpuid {
+                runtime_dispatch.push(module.clone());
+            }
+            modules.push(module);
+        }
+
+        Ok(ModuleScan {
+            modules,
+            usage: usage.into_values().collect(),
+            runtime_dispatch,
+            required,
+        })
+    }
+
+    /// Linear sweep over code sections, counting each extension in `counts`.
+    /// Returns whether the module executes CPUID and how many instructions it decoded
+    fn scan_module(
+        module: &str,
+        data: &[u8],
+        pe: &PE,
+        usage: &mut BTreeMap<InstructionSet, InstructionUsage>,
+        counts: &mut BTreeMap<InstructionSet, u64>,
+    ) -> (bool, u64) {
+        let bitness = if pe.is_64 { 64 } else { 32 };
+        let image_base = pe.image_base;
+        let mut has_cpuid = false;
+        let mut total = 0u64;
+        let mut instruction = Instruction::default();
+
+        for section in pe.sections.iter().filter(|s| s.characteristics & IMAGE_SCN_MEM_EXECUTE != 0) {
+            let start = section.pointer_to_raw_data as usize;
+            // Raw data is padded to the file alignment; the padding isn't code
+            let len = match section.virtual_size {
+                0 => section.size_of_raw_data,
+                size => size.min(section.size_of_raw_data),
+            } as usize;
+            let Some(code) = data.get(start..start.saturating_add(len).min(data.len())) else {
+                continue;
+            };
+
+            let ip = image_base + section.virtual_address as u64;
+            let mut decoder = Decoder::with_ip(bitness, code, ip, DecoderOptions::NONE);
+            while decoder.can_decode() {
+                decoder.decode_out(&mut instruction);
+                if instruction.is_invalid() {
+                    continue;
+                }
+                total += 1;
+                if instruction.mnemonic() == Mnemonic::Cpuid {
+                    has_cpuid = true;
+                }
+
+                let mut seen: Option<InstructionSet> = None;
+                for feature in instruction.cpuid_features() {
+                    let Some(set) = InstructionSet::from_cpuid(*feature) else { continue };
+                    if seen == Some(set) {
+                        continue;
+                    }
+                    seen = Some(set);
+                    *counts.entry(set).or_default() += 1;
+
+                    let entry = usage.entry(set).or_insert_with(|| InstructionUsage {
+                        set,
+                        count: 0,
+                        modules: Vec::new(),
+                        sites: Vec::new(),
+                    });
+                    entry.count += 1;
+                    if !entry.modules.iter().any(|m| m == module) {
+                        entry.modules.push(module.to_string());
+                    }
+                    if entry.sites.len() < SITES_PER_SET {
+                        entry.sites.push(InstructionSite {
+                            module: module.to_string(),
+                            rva: (instruction.ip() - image_base) as u32,
+                            mnemonic: format!("{:?}", instruction.mnemonic()).to_lowercase(),
+                        });
+                    }
+                }
+            }
+        }
+        (has_cpuid, total)
+    }
+}
+
+#[tauri::command]
+pub async fn scan_instruction_sets(path: String, profile: Option<String>) -> Result<InstructionScanReport, String> {
+    let profile = match profile {
+        Some(name) => CpuProfile::preset(&name).ok_or(format!("Unknown CPU profile: {} (expected one of {})", name, CpuProfile::PRESETS.join(", ")))?,
+        None => CpuProfile::default(),
+    };
+    tokio::task::spawn_blocking(move || InstructionScanner::scan(Path::new(&path), &profile))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn list_cpu_profiles() -> Vec<&'static str> {
+    CpuProfile::PRESETS.to_vec()
+}
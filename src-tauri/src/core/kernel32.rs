@@ -0,0 +1,612 @@
+use std::collections::BTreeMap;
+use std::ffi::{c_char, CStr};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use libc::c_void;
+use crate::core::executor;
+use crate::core::linker::SymbolicLinker;
+use crate::core::modules;
+use crate::core::teb;
+use crate::core::vmm::{self, ALLOCATION_GRANULARITY, MAX_APPLICATION_ADDRESS, MIN_APPLICATION_ADDRESS, PAGE_SIZE};
+
+// Win32 error codes
+pub const ERROR_FILE_NOT_FOUND: u32 = 2;
+pub const ERROR_PATH_NOT_FOUND: u32 = 3;
+pub const ERROR_ACCESS_DENIED: u32 = 5;
+pub const ERROR_INVALID_HANDLE: u32 = 6;
+pub const ERROR_NOT_ENOUGH_MEMORY: u32 = 8;
+pub const ERROR_BAD_LENGTH: u32 = 24;
+pub const ERROR_GEN_FAILURE: u32 = 31;
+pub const ERROR_FILE_EXISTS: u32 = 80;
+pub const ERROR_INVALID_PARAMETER: u32 = 87;
+pub const ERROR_MOD_NOT_FOUND: u32 = 126;
+pub const ERROR_PROC_NOT_FOUND: u32 = 127;
+pub const ERROR_ALREADY_EXISTS: u32 = 183;
+pub const ERROR_INVALID_ADDRESS: u32 = 487;
+pub const ERROR_NOACCESS: u32 = 998;
+
+pub const HEAP_ZERO_MEMORY: u32 = 0x0000_0008;
+
+pub const INVALID_HANDLE_VALUE: usize = usize::MAX;
+pub const INVALID_FILE_ATTRIBUTES: u32 = u32::MAX;
+pub const GENERIC_READ: u32 = 0x8000_0000;
+pub const GENERIC_WRITE: u32 = 0x4000_0000;
+const GENERIC_ALL: u32 = 0x1000_0000;
+const FILE_READ_DATA: u32 = 0x0001;
+const FILE_WRITE_DATA: u32 = 0x0002;
+const FILE_APPEND_DATA: u32 = 0x0004;
+
+// CreateFile dispositions
+pub const CREATE_NEW: u32 = 1;
+pub const CREATE_ALWAYS: u32 = 2;
+pub const OPEN_EXISTING: u32 = 3;
+pub const OPEN_ALWAYS: u32 = 4;
+pub const TRUNCATE_EXISTING: u32 = 5;
+
+pub const FILE_ATTRIBUTE_READONLY: u32 = 0x01;
+pub const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+pub const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+
+pub const FILE_BEGIN: u32 = 0;
+pub const FILE_CURRENT: u32 = 1;
+pub const FILE_END: u32 = 2;
+
+const PROCESSOR_ARCHITECTURE_AMD64: u16 = 9;
+const PROCESSOR_AMD_X8664: u32 = 8664;
+/// QueryPerformanceFrequency on Windows 10 and later
+const PERFORMANCE_FREQUENCY: i64 = 10_000_000;
+
+/// SYSTEM_INFO as laid out for 64-bit Windows
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemInfo {
+    pub processor_architecture: u16,
+    pub reserved: u16,
+    pub page_size: u32,
+    pub minimum_application_address: usize,
+    pub maximum_application_address: usize,
+    pub active_processor_mask: usize,
+    pub number_of_processors: u32,
+    pub processor_type: u32,
+    pub allocation_granularity: u32,
+    pub processor_level: u16,
+    pub processor_revision: u16,
+}
+
+/// MEMORY_BASIC_INFORMATION as laid out for 64-bit Windows
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryBasicInformation {
+    pub base_address: usize,
+    pub allocation_base: usize,
+    pub allocation_protect: u32,
+    pub partition_id: u16,
+    pub region_size: usize,
+    pub state: u32,
+    pub protect: u32,
+    pub mem_type: u32,
+}
+
+thread_local! {
+    static THREAD_ID: u32 = NEXT_THREAD_ID.fetch_add(4, Ordering::Relaxed);
+}
+
+/// Windows thread ids are multiples of 4
+static NEXT_THREAD_ID: AtomicU32 = AtomicU32::new(0x104);
+
+/// Process heap pseudo-handle; the heap functions forward to malloc regardless of handle
+static PROCESS_HEAP: u8 = 0;
+
+/// Files opened through CreateFile, by handle
+static FILES: Mutex<BTreeMap<usize, File>> = Mutex::new(BTreeMap::new());
+/// Kernel handles are multiples of 4
+static NEXT_HANDLE: AtomicUsize = AtomicUsize::new(0x100);
+
+/// Registers the kernel32 functions implemented here
+pub fn register(linker: &mut SymbolicLinker) {
+    let functions: [(&str, usize); 33] = [
+        ("GetSystemInfo", pancho_hle_get_system_info as *const () as usize),
+        ("GetTickCount", pancho_hle_get_tick_count as *const () as usize),
+        ("GetTickCount64", pancho_hle_get_tick_count64 as *const () as usize),
+        ("QueryPerformanceCounter", pancho_hle_query_performance_counter as *const () as usize),
+        ("QueryPerformanceFrequency", pancho_hle_query_performance_frequency as *const () as usize),
+        ("GetCurrentProcessId", pancho_hle_get_current_process_id as *const () as usize),
+        ("GetCurrentThreadId", pancho_hle_get_current_thread_id as *const () as usize),
+        ("GetLastError", pancho_hle_get_last_error as *const () as usize),
+        ("SetLastError", pancho_hle_set_last_error as *const () as usize),
+        ("GetProcessHeap", pancho_hle_get_process_heap as *const () as usize),
+        ("HeapAlloc", pancho_hle_heap_alloc as *const () as usize),
+        ("HeapFree", pancho_hle_heap_free as *const () as usize),
+        ("VirtualAlloc", pancho_hle_virtual_alloc as *const () as usize),
+        ("VirtualFree", pancho_hle_virtual_free as *const () as usize),
+        ("VirtualProtect", pancho_hle_virtual_protect as *const () as usize),
+        ("VirtualQuery", pancho_hle_virtual_query as *const () as usize),
+        ("ExitProcess", pancho_hle_exit_process as *const () as usize),
+        ("OutputDebugStringA", pancho_hle_output_debug_string_a as *const () as usize),
+        ("OutputDebugStringW", pancho_hle_output_debug_string_w as *const () as usize),
+        ("LoadLibraryA", pancho_hle_load_library_a as *const () as usize),
+        ("LoadLibraryW", pancho_hle_load_library_w as *const () as usize),
+        ("GetModuleHandleA", pancho_hle_get_module_handle_a as *const () as usize),
+        ("GetModuleHandleW", pancho_hle_get_module_handle_w as *const () as usize),
+        ("GetProcAddress", pancho_hle_get_proc_address as *const () as usize),
+        ("CreateFileA", pancho_hle_create_file_a as *const () as usize),
+        ("CreateFileW", pancho_hle_create_file_w as *const () as usize),
+        ("ReadFile", pancho_hle_read_file as *const () as usize),
+        ("WriteFile", pancho_hle_write_file as *const () as usize),
+        ("CloseHandle", pancho_hle_close_handle as *const () as usize),
+        ("GetFileSizeEx", pancho_hle_get_file_size_ex as *const () as usize),
+        ("SetFilePointerEx", pancho_hle_set_file_pointer_ex as *const () as usize),
+        ("GetFileAttributesA", pancho_hle_get_file_attributes_a as *const () as usize),
+        ("GetFileAttributesW", pancho_hle_get_file_attributes_w as *const () as usize),
+    ];
+    for (name, address) in functions {
+        linker.register("kernel32.dll", name, address);
+    }
+}
+
+fn set_last_error(code: u32) {
+    teb::set_last_error(code);
+}
+
+fn read_ansi(string: *const c_char) -> Option<String> {
+    (!string.is_null()).then(|| unsafe { CStr::from_ptr(string) }.to_string_lossy().to_string())
+}
+
+fn read_wide(string: *const u16) -> Option<String> {
+    if string.is_null() {
+        return None;
+    }
+    let len = (0..).take_while(|i| unsafe { *string.add(*i) } != 0).count();
+    Some(String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(string, len) }))
+}
+
+/// Monotonic time since boot, the clock GetTickCount and QPC are based on
+fn monotonic_nanos() -> u64 {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+// --- SYSTEM INFORMATION ---
+
+pub extern "win64" fn pancho_hle_get_system_info(info: *mut SystemInfo) {
+    if info.is_null() {
+        return;
+    }
+    let processors = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(64);
+    let mask = if processors == 64 { usize::MAX } else { (1usize << processors) - 1 };
+
+    unsafe {
+        *info = SystemInfo {
+            processor_architecture: PROCESSOR_ARCHITECTURE_AMD64,
+            reserved: 0,
+            page_size: PAGE_SIZE as u32,
+            minimum_application_address: MIN_APPLICATION_ADDRESS as usize,
+            maximum_application_address: MAX_APPLICATION_ADDRESS as usize,
+            active_processor_mask: mask,
+            number_of_processors: processors as u32,
+            processor_type: PROCESSOR_AMD_X8664,
+            allocation_granularity: ALLOCATION_GRANULARITY as u32,
+            processor_level: 6,
+            processor_revision: 0,
+        };
+    }
+}
+
+pub extern "win64" fn pancho_hle_get_tick_count() -> u32 {
+    // Wraps after 49.7 days, like the real thing
+    (monotonic_nanos() / 1_000_000) as u32
+}
+
+pub extern "win64" fn pancho_hle_get_tick_count64() -> u64 {
+    monotonic_nanos() / 1_000_000
+}
+
+pub extern "win64" fn pancho_hle_query_performance_counter(counter: *mut i64) -> i32 {
+    if counter.is_null() {
+        set_last_error(ERROR_INVALID_PARAMETER);
+        return 0;
+    }
+    unsafe { *counter = (monotonic_nanos() / (1_000_000_000 / PERFORMANCE_FREQUENCY as u64)) as i64 };
+    1
+}
+
+pub extern "win64" fn pancho_hle_query_performance_frequency(frequency: *mut i64) -> i32 {
+    if frequency.is_null() {
+        set_last_error(ERROR_INVALID_PARAMETER);
+        return 0;
+    }
+    unsafe { *frequency = PERFORMANCE_FREQUENCY };
+    1
+}
+
+// --- PROCESS AND THREAD ---
+
+pub extern "win64" fn pancho_hle_get_current_process_id() -> u32 {
+    std::process::id()
+}
+
+pub extern "win64" fn pancho_hle_get_current_thread_id() -> u32 {
+    THREAD_ID.with(|id| *id)
+}
+
+pub extern "win64" fn pancho_hle_get_last_error() -> u32 {
+    teb::last_error()
+}
+
+pub extern "win64" fn pancho_hle_set_last_error(code: u32) {
+    set_last_error(code);
+}
+
+pub extern "win64" fn pancho_hle_exit_process(exit_code: u32) -> ! {
+    executor::report_exit(exit_code);
+    std::process::exit(exit_code as i32);
+}
+
+pub extern "win64" fn pancho_hle_output_debug_string_a(message: *const c_char) {
+    if let Some(message) = read_ansi(message) {
+        eprintln!("Pancho-HLE: OutputDebugString: {}", message.trim_end());
+    }
+}
+
+pub extern "win64" fn pancho_hle_output_debug_string_w(message: *const u16) {
+    if let Some(message) = read_wide(message) {
+        eprintln!("Pancho-HLE: OutputDebugString: {}", message.trim_end());
+    }
+}
+
+// --- MODULES ---
+
+pub extern "win64" fn pancho_hle_load_library_a(name: *const c_char) -> usize {
+    load_library(read_ansi(name))
+}
+
+pub extern "win64" fn pancho_hle_load_library_w(name: *const u16) -> usize {
+    load_library(read_wide(name))
+}
+
+fn load_library(name: Option<String>) -> usize {
+    let Some(name) = name else {
+        set_last_error(ERROR_INVALID_PARAMETER);
+        return 0;
+    };
+    match modules::with_active_loader(|loader| loader.load_library(&name)) {
+        Some(Ok(base)) => base,
+        Some(Err(e)) => {
+            eprintln!("Pancho-HLE: LoadLibrary({}) failed: {}", name, e);
+            set_last_error(ERROR_MOD_NOT_FOUND);
+            0
+        }
+        None => {
+            set_last_error(ERROR_MOD_NOT_FOUND);
+            0
+        }
+    }
+}
+
+/// A null name is the executable
+pub extern "win64" fn pancho_hle_get_module_handle_a(name: *const c_char) -> usize {
+    module_handle(read_ansi(name))
+}
+
+pub extern "win64" fn pancho_hle_get_module_handle_w(name: *const u16) -> usize {
+    module_handle(read_wide(name))
+}
+
+fn module_handle(name: Option<String>) -> usize {
+    match modules::with_active_loader(|loader| loader.module_handle(name.as_deref())).flatten() {
+        Some(base) => base,
+        None => {
+            set_last_error(ERROR_MOD_NOT_FOUND);
+            0
+        }
+    }
+}
+
+pub extern "win64" fn pancho_hle_get_proc_address(module: usize, name: *const c_char) -> usize {
+    // Values below 64K are ordinals (MAKEINTRESOURCE), not pointers
+    let function = if (name as usize) < 0x10000 {
+        format!("#{}", name as usize)
+    } else {
+        read_ansi(name).unwrap_or_default()
+    };
+    match modules::with_active_loader(|loader| loader.proc_address(module, &function)).flatten() {
+        Some(address) => address,
+        None => {
+            set_last_error(ERROR_PROC_NOT_FOUND);
+            0
+        }
+    }
+}
+
+// --- HEAP ---
+
+pub extern "win64" fn pancho_hle_get_process_heap() -> usize {
+    &PROCESS_HEAP as *const u8 as usize
+}
+
+/// malloc already returns 16-byte aligned blocks, matching the Windows x64 heap
+pub extern "win64" fn pancho_hle_heap_alloc(_heap: usize, flags: u32, size: usize) -> *mut c_void {
+    // HeapAlloc(0) returns a unique, freeable pointer
+    let size = size.max(1);
+    unsafe {
+        if flags & HEAP_ZERO_MEMORY != 0 {
+            libc::calloc(1, size)
+        } else {
+            libc::malloc(size)
+        }
+    }
+}
+
+pub extern "win64" fn pancho_hle_heap_free(_heap: usize, _flags: u32, block: *mut c_void) -> i32 {
+    unsafe { libc::free(block) };
+    1
+}
+
+// --- VIRTUAL MEMORY ---
+
+pub extern "win64" fn pancho_hle_virtual_alloc(address: *mut c_void, size: usize, allocation_type: u32, protect: u32) -> *mut c_void {
+    match vmm::process_memory().allocate(address as u64, size, allocation_type, protect) {
+        Ok(base) => base as *mut c_void,
+        Err(e) => {
+            set_last_error(e.win32_code());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+pub extern "win64" fn pancho_hle_virtual_free(address: *mut c_void, size: usize, free_type: u32) -> i32 {
+    match vmm::process_memory().free(address as u64, size, free_type) {
+        Ok(()) => 1,
+        Err(e) => {
+            set_last_error(e.win32_code());
+            0
+        }
+    }
+}
+
+pub extern "win64" fn pancho_hle_virtual_protect(address: *mut c_void, size: usize, protect: u32, old_protect: *mut u32) -> i32 {
+    if old_protect.is_null() {
+        set_last_error(ERROR_NOACCESS);
+        return 0;
+    }
+    match vmm::process_memory().protect(address as u64, size, protect) {
+        Ok(old) => {
+            unsafe { *old_protect = old };
+            1
+        }
+        Err(e) => {
+            set_last_error(e.win32_code());
+            0
+        }
+    }
+}
+
+/// Returns the number of bytes written to `buffer`, 0 on failure
+pub extern "win64" fn pancho_hle_virtual_query(address: *const c_void, buffer: *mut MemoryBasicInformation, length: usize) -> usize {
+    if buffer.is_null() || length < std::mem::size_of::<MemoryBasicInformation>() {
+        set_last_error(ERROR_BAD_LENGTH);
+        return 0;
+    }
+    let Some(info) = vmm::process_memory().query(address as u64) else {
+        set_last_error(ERROR_INVALID_PARAMETER);
+        return 0;
+    };
+
+    unsafe {
+        *buffer = MemoryBasicInformation {
+            base_address: info.base_address as usize,
+            allocation_base: info.allocation_base as usize,
+            allocation_protect: info.allocation_protect,
+            partition_id: 0,
+            region_size: info.region_size,
+            state: info.state.mem_state(),
+            protect: info.protect,
+            mem_type: info.kind.map(|k| k.mem_type()).unwrap_or(0),
+        };
+    }
+    std::mem::size_of::<MemoryBasicInformation>()
+}
+
+// --- FILES ---
+
+/// Host path of a file name guest code passed in, translated through the bottle's drives
+fn host_path(name: Option<String>) -> Option<PathBuf> {
+    let name = name?;
+    modules::with_active_loader(|loader| loader.host_path(&name)).flatten()
+}
+
+fn io_error_code(error: &std::io::Error) -> u32 {
+    match error.kind() {
+        ErrorKind::NotFound => ERROR_FILE_NOT_FOUND,
+        ErrorKind::PermissionDenied => ERROR_ACCESS_DENIED,
+        ErrorKind::AlreadyExists => ERROR_FILE_EXISTS,
+        _ => ERROR_GEN_FAILURE,
+    }
+}
+
+pub extern "win64" fn pancho_hle_create_file_a(name: *const c_char, access: u32, _share: u32, _security: usize, disposition: u32, _flags: u32, _template: usize) -> usize {
+    create_file(host_path(read_ansi(name)), access, disposition)
+}
+
+pub extern "win64" fn pancho_hle_create_file_w(name: *const u16, access: u32, _share: u32, _security: usize, disposition: u32, _flags: u32, _template: usize) -> usize {
+    create_file(host_path(read_wide(name)), access, disposition)
+}
+
+fn create_file(path: Option<PathBuf>, access: u32, disposition: u32) -> usize {
+    let Some(path) = path else {
+        set_last_error(ERROR_PATH_NOT_FOUND);
+        return INVALID_HANDLE_VALUE;
+    };
+    let read = access & (GENERIC_READ | GENERIC_ALL | FILE_READ_DATA) != 0;
+    let write = access & (GENERIC_WRITE | GENERIC_ALL | FILE_WRITE_DATA | FILE_APPEND_DATA) != 0;
+
+    let mut options = OpenOptions::new();
+    // Access 0 only queries the file, which still needs a descriptor
+    options.read(read || !write).write(write);
+    match disposition {
+        CREATE_NEW => options.write(true).create_new(true),
+        CREATE_ALWAYS => options.write(true).create(true).truncate(true),
+        OPEN_EXISTING => &mut options,
+        OPEN_ALWAYS => options.write(write).create(write),
+        TRUNCATE_EXISTING => options.write(true).truncate(true),
+        _ => {
+            set_last_error(ERROR_INVALID_PARAMETER);
+            return INVALID_HANDLE_VALUE;
+        }
+    };
+    let existed = path.exists();
+    if !existed && path.parent().is_some_and(|parent| !parent.is_dir()) {
+        set_last_error(ERROR_PATH_NOT_FOUND);
+        return INVALID_HANDLE_VALUE;
+    }
+    if disposition == OPEN_ALWAYS && !existed && !write {
+        // Read-only OPEN_ALWAYS still creates the file
+        if let Err(e) = File::create(&path) {
+            set_last_error(io_error_code(&e));
+            return INVALID_HANDLE_VALUE;
+        }
+    }
+
+    match options.open(&path) {
+        Ok(file) => {
+            let handle = NEXT_HANDLE.fetch_add(4, Ordering::Relaxed);
+            FILES.lock().unwrap().insert(handle, file);
+            // Both dispositions report whether they replaced or opened an existing file
+            let reopened = existed && (disposition == CREATE_ALWAYS || disposition == OPEN_ALWAYS);
+            set_last_error(if reopened { ERROR_ALREADY_EXISTS } else { 0 });
+            handle
+        }
+        Err(e) => {
+            set_last_error(io_error_code(&e));
+            INVALID_HANDLE_VALUE
+        }
+    }
+}
+
+/// Runs `f` on the file behind `handle`, turning a bad handle or an I/O error into FALSE
+fn with_file(handle: usize, f: impl FnOnce(&mut File) -> std::io::Result<()>) -> i32 {
+    let mut files = FILES.lock().unwrap();
+    let Some(file) = files.get_mut(&handle) else {
+        set_last_error(ERROR_INVALID_HANDLE);
+        return 0;
+    };
+    match f(file) {
+        Ok(()) => 1,
+        Err(e) => {
+            set_last_error(io_error_code(&e));
+            0
+        }
+    }
+}
+
+/// Synchronous reads only; OVERLAPPED is ignored
+pub extern "win64" fn pancho_hle_read_file(handle: usize, buffer: *mut u8, length: u32, read: *mut u32, _overlapped: usize) -> i32 {
+    if buffer.is_null() && length > 0 {
+        set_last_error(ERROR_NOACCESS);
+        return 0;
+    }
+    let buffer = if length == 0 { &mut [][..] } else { unsafe { std::slice::from_raw_parts_mut(buffer, length as usize) } };
+    with_file(handle, |file| {
+        // Only the end of the file makes a read come back short
+        let mut total = 0;
+        while total < buffer.len() {
+            match file.read(&mut buffer[total..]) {
+                Ok(0) => break,
+                Ok(n) => total += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        if !read.is_null() {
+            unsafe { *read = total as u32 };
+        }
+        Ok(())
+    })
+}
+
+pub extern "win64" fn pancho_hle_write_file(handle: usize, buffer: *const u8, length: u32, written: *mut u32, _overlapped: usize) -> i32 {
+    if buffer.is_null() && length > 0 {
+        set_last_error(ERROR_NOACCESS);
+        return 0;
+    }
+    let buffer = if length == 0 { &[][..] } else { unsafe { std::slice::from_raw_parts(buffer, length as usize) } };
+    with_file(handle, |file| {
+        file.write_all(buffer)?;
+        if !written.is_null() {
+            unsafe { *written = length };
+        }
+        Ok(())
+    })
+}
+
+pub extern "win64" fn pancho_hle_close_handle(handle: usize) -> i32 {
+    if FILES.lock().unwrap().remove(&handle).is_none() {
+        set_last_error(ERROR_INVALID_HANDLE);
+        return 0;
+    }
+    1
+}
+
+pub extern "win64" fn pancho_hle_get_file_size_ex(handle: usize, size: *mut i64) -> i32 {
+    if size.is_null() {
+        set_last_error(ERROR_NOACCESS);
+        return 0;
+    }
+    with_file(handle, |file| {
+        unsafe { *size = file.metadata()?.len() as i64 };
+        Ok(())
+    })
+}
+
+pub extern "win64" fn pancho_hle_set_file_pointer_ex(handle: usize, distance: i64, new_position: *mut i64, method: u32) -> i32 {
+    let position = match method {
+        FILE_BEGIN if distance >= 0 => SeekFrom::Start(distance as u64),
+        FILE_CURRENT => SeekFrom::Current(distance),
+        FILE_END => SeekFrom::End(distance),
+        _ => {
+            set_last_error(ERROR_INVALID_PARAMETER);
+            return 0;
+        }
+    };
+    with_file(handle, |file| {
+        let position = file.seek(position)?;
+        if !new_position.is_null() {
+            unsafe { *new_position = position as i64 };
+        }
+        Ok(())
+    })
+}
+
+pub extern "win64" fn pancho_hle_get_file_attributes_a(name: *const c_char) -> u32 {
+    file_attributes(host_path(read_ansi(name)))
+}
+
+pub extern "win64" fn pancho_hle_get_file_attributes_w(name: *const u16) -> u32 {
+    file_attributes(host_path(read_wide(name)))
+}
+
+fn file_attributes(path: Option<PathBuf>) -> u32 {
+    let metadata = match path.map(std::fs::metadata) {
+        Some(Ok(metadata)) => metadata,
+        Some(Err(e)) => {
+            set_last_error(io_error_code(&e));
+            return INVALID_FILE_ATTRIBUTES;
+        }
+        None => {
+            set_last_error(ERROR_PATH_NOT_FOUND);
+            return INVALID_FILE_ATTRIBUTES;
+        }
+    };
+    let mut attributes = 0;
+    if metadata.is_dir() {
+        attributes |= FILE_ATTRIBUTE_DIRECTORY;
+    }
+    if metadata.permissions().readonly() {
+        attributes |= FILE_ATTRIBUTE_READONLY;
+    }
+    if attributes == 0 { FILE_ATTRIBUTE_NORMAL } else { attributes }
+}
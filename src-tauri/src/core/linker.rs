@@ -1,14 +1,117 @@
 use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use goblin::pe::PE;
+use goblin::pe::export::ExportAddressTableEntry;
+use serde::Serialize;
+use crate::core::{executor, kernel32};
+use crate::core::loader::{read_cstr, DllImports};
+use crate::core::vmm::{self, RegionType, PAGE_EXECUTE_READ, PAGE_READWRITE};
+
+/// Exit status of the process when guest code calls an import nothing implements
+pub const UNIMPLEMENTED_IMPORT_EXIT_CODE: i32 = 127;
+
+/// Forwarder chains longer than this are treated as unresolved (they're usually cycles)
+const MAX_FORWARDER_DEPTH: usize = 8;
 
 pub struct SymbolicLinker {
     /// Maps DLL!Function names to native function pointers
     pub function_map: HashMap<String, usize>,
+    /// Export tables of DLL images mapped into the process, keyed by lowercase module name
+    pub modules: HashMap<String, ExportTable>,
+    /// Route resolved imports through recording thunks (see `core::trace`)
+    pub trace: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportTarget {
+    Address(usize),
+    /// "NTDLL.RtlAllocateHeap" or "NTDLL.#12"
+    Forwarder(String),
+}
+
+#[derive(Debug, Default)]
+pub struct ExportTable {
+    pub by_name: HashMap<String, ExportTarget>,
+    pub by_ordinal: HashMap<u16, ExportTarget>,
+}
+
+impl ExportTable {
+    /// Export directory of an image mapped at `base`. `image` is the mapped image, laid out by RVA.
+    pub fn from_image(pe: &PE, image: &[u8], base: usize) -> Self {
+        let mut table = ExportTable::default();
+        let Some(export_data) = &pe.export_data else {
+            return table;
+        };
+
+        let ordinal_base = export_data.export_directory_table.ordinal_base as usize;
+        for (index, entry) in export_data.export_address_table.iter().enumerate() {
+            let target = match entry {
+                ExportAddressTableEntry::ExportRVA(0) => continue,
+                ExportAddressTableEntry::ExportRVA(rva) => ExportTarget::Address(base + *rva as usize),
+                ExportAddressTableEntry::ForwarderRVA(rva) => match read_cstr(image, *rva as usize) {
+                    Some(forwarder) => ExportTarget::Forwarder(forwarder),
+                    None => continue,
+                },
+            };
+            table.by_ordinal.insert((ordinal_base + index) as u16, target);
+        }
+
+        // The ordinal table holds indexes into the address table, not biased ordinals
+        for (name_rva, index) in export_data.export_name_pointer_table.iter().zip(&export_data.export_ordinal_table) {
+            let Some(name) = read_cstr(image, *name_rva as usize) else { continue };
+            if let Some(target) = table.by_ordinal.get(&((ordinal_base + *index as usize) as u16)) {
+                table.by_name.insert(name, target.clone());
+            }
+        }
+        table
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum ImportSource {
+    /// Native implementation in this process
+    Hle,
+    /// Export of a DLL image loaded into the process
+    Export,
+    /// Nothing provides it; the IAT points at a stub that reports the call
+    Stub,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ResolvedImport {
+    pub dll: String,
+    /// Function name, or `#<ordinal>`
+    pub function: String,
+    pub source: ImportSource,
+    /// Where an export forwarder sent the lookup, e.g. "NTDLL.RtlAllocateHeap"
+    pub forwarded_to: Option<String>,
+    #[serde(skip)]
+    pub address: usize,
+    /// IAT slot the binder writes `address` to
+    #[serde(skip)]
+    pub iat_rva: u32,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ImportCoverage {
+    pub total: usize,
+    pub hle: usize,
+    pub exported: usize,
+    pub unresolved: usize,
+    /// Fraction of imports handled by the HLE layer, 0.0 to 1.0
+    pub hle_ratio: f64,
+    /// Imported DLLs the HLE layer stands in for. They're never loaded from disk, so
+    /// their functions it doesn't implement are stubbed even when a real copy exists.
+    pub emulated_modules: Vec<String>,
+    pub imports: Vec<ResolvedImport>,
 }
 
 impl SymbolicLinker {
     pub fn new() -> Self {
         let mut linker = SymbolicLinker {
             function_map: HashMap::new(),
+            modules: HashMap::new(),
+            trace: false,
         };
         linker.bootstrap_core_apis();
         linker
@@ -16,39 +119,221 @@ impl SymbolicLinker {
 
     /// Registers core Windows APIs and links them to native macOS/Rust implementations
     fn bootstrap_core_apis(&mut self) {
-        // KERNEL32 - System Info
-        self.register("kernel32.dll", "GetSystemInfo", pancho_hle_get_system_info as *const () as usize);
-        self.register("kernel32.dll", "GetTickCount", pancho_hle_get_tick_count as *const () as usize);
+        // KERNEL32 - System info, timing, process/thread, heap and virtual memory
+        kernel32::register(self);
         
         // USER32 - Windowing (Eventually maps to AppKit/Metal)
         self.register("user32.dll", "GetDesktopWindow", pancho_hle_get_desktop_window as *const () as usize);
     }
 
     pub fn register(&mut self, dll: &str, function: &str, address: usize) {
-        let key = format!("{}!{}", dll.to_lowercase(), function);
+        let key = format!("{}!{}", module_key(dll), function);
         self.function_map.insert(key, address);
     }
 
     pub fn resolve(&self, dll: &str, function: &str) -> Option<usize> {
-        let key = format!("{}!{}", dll.to_lowercase(), function);
+        let key = format!("{}!{}", module_key(dll), function);
         self.function_map.get(&key).copied()
     }
+
+    /// Makes a mapped DLL's exports available to images linked after it
+    pub fn register_module(&mut self, dll: &str, exports: ExportTable) {
+        self.modules.insert(module_key(dll), exports);
+    }
+
+    /// Images bound after this call record every call to a resolved import
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+
+    pub fn unregister_module(&mut self, dll: &str) {
+        self.modules.remove(&module_key(dll));
+    }
+
+    /// Whether the HLE layer stands in for a DLL, in which case it's never loaded from disk.
+    /// One registered function is enough: Wine's builtin DLLs can't run without its Unix side.
+    pub fn implements_module(&self, dll: &str) -> bool {
+        let prefix = format!("{}!", module_key(dll));
+        self.function_map.keys().any(|key| key.starts_with(&prefix))
+    }
+
+    /// Looks an import up in the HLE table first, then in the loaded DLLs' exports,
+    /// following forwarders. Returns the address, its source and the first forwarder taken.
+    pub fn resolve_import(&self, dll: &str, function: &str, ordinal: Option<u16>) -> Option<(usize, ImportSource, Option<String>)> {
+        self.follow_import(dll, function, ordinal).ok()
+    }
+
+    /// The module a forwarder leads into when that's what keeps an import from resolving:
+    /// one that isn't loaded and that the HLE layer doesn't stand in for
+    pub fn missing_forwarder_target(&self, dll: &str, function: &str, ordinal: Option<u16>) -> Option<String> {
+        self.follow_import(dll, function, ordinal).err().flatten()
+    }
+
+    /// Walks the forwarder chain; a failed lookup carries the unloaded module it ended in, if any
+    fn follow_import(&self, dll: &str, function: &str, ordinal: Option<u16>) -> Result<(usize, ImportSource, Option<String>), Option<String>> {
+        let mut dll = module_key(dll);
+        let mut function = function.to_string();
+        let mut ordinal = ordinal;
+        let mut forwarded_to = None;
+
+        for _ in 0..=MAX_FORWARDER_DEPTH {
+            if ordinal.is_none() {
+                if let Some(address) = self.resolve(&dll, &function) {
+                    return Ok((address, ImportSource::Hle, forwarded_to));
+                }
+            }
+
+            let Some(exports) = self.modules.get(&dll) else {
+                let loadable = forwarded_to.is_some() && !self.implements_module(&dll);
+                return Err(loadable.then_some(dll));
+            };
+            let target = match ordinal {
+                Some(ordinal) => exports.by_ordinal.get(&ordinal),
+                None => exports.by_name.get(&function),
+            }.ok_or(None)?;
+            match target {
+                ExportTarget::Address(address) => return Ok((*address, ImportSource::Export, forwarded_to)),
+                ExportTarget::Forwarder(forwarder) => {
+                    // Module names such as api-ms-win-core-synch-l1-2-0 contain no dots, so split at the last one
+                    let (module, name) = forwarder.rsplit_once('.').ok_or(None)?;
+                    forwarded_to.get_or_insert_with(|| forwarder.clone());
+                    dll = module_key(module);
+                    ordinal = name.strip_prefix('#').and_then(|n| n.parse().ok());
+                    function = name.to_string();
+                }
+            }
+        }
+        Err(None)
+    }
+
+    /// How every import of an image would be resolved right now
+    pub fn coverage(&self, imports: &[DllImports]) -> ImportCoverage {
+        let mut coverage = ImportCoverage::default();
+        for entry in imports.iter().flat_map(|dll| &dll.functions) {
+            let resolved = self.resolve_import(&entry.dll, &entry.function, entry.ordinal);
+            let (address, source, forwarded_to) = resolved.unwrap_or((0, ImportSource::Stub, None));
+            match source {
+                ImportSource::Hle => coverage.hle += 1,
+                ImportSource::Export => coverage.exported += 1,
+                ImportSource::Stub => coverage.unresolved += 1,
+            }
+            coverage.imports.push(ResolvedImport {
+                dll: entry.dll.clone(),
+                function: entry.function.clone(),
+                source,
+                forwarded_to,
+                address,
+                iat_rva: entry.iat_rva,
+            });
+        }
+        for dll in imports {
+            let key = module_key(&dll.dll);
+            if self.implements_module(&key) && !coverage.emulated_modules.contains(&key) {
+                coverage.emulated_modules.push(key);
+            }
+        }
+        coverage.total = coverage.imports.len();
+        if coverage.total > 0 {
+            coverage.hle_ratio = coverage.hle as f64 / coverage.total as f64;
+        }
+        coverage
+    }
 }
 
-// --- NATIVE SHIMS (HLE IMPLEMENTATIONS) ---
+/// "KERNEL32.dll" and forwarder-style "KERNEL32" both become "kernel32.dll"
+pub fn module_key(dll: &str) -> String {
+    let key = dll.to_lowercase();
+    if key.contains('.') { key } else { format!("{}.dll", key) }
+}
+
+/// Read+execute pages holding generated thunks, released on drop
+pub struct CodePage {
+    base: u64,
+}
+
+impl CodePage {
+    /// Lays out `count` thunks of `size` bytes each, padded with int3, in a reservation named `label`
+    pub fn generate(label: &str, count: usize, size: usize, mut write: impl FnMut(usize, &mut [u8])) -> Result<Self, String> {
+        let len = (count * size).max(1).div_ceil(4096) * 4096;
+        let base = {
+            let mut memory = vmm::process_memory();
+            let base = memory.reserve(0, len, PAGE_EXECUTE_READ, RegionType::Private, label)
+                .map_err(|e| format!("Failed to allocate thunk memory: {}", e))?;
+            if let Err(e) = memory.commit(base, len, PAGE_READWRITE) {
+                let _ = memory.release(base);
+                return Err(format!("Failed to allocate thunk memory: {}", e));
+            }
+            base
+        };
+        let code = CodePage { base };
+
+        let buffer = unsafe { std::slice::from_raw_parts_mut(base as *mut u8, len) };
+        buffer.fill(0xCC);
+        for i in 0..count {
+            write(i, &mut buffer[i * size..(i + 1) * size]);
+        }
+
+        vmm::process_memory().protect(base, len, PAGE_EXECUTE_READ)
+            .map_err(|e| format!("Failed to make thunks executable: {}", e))?;
+        Ok(code)
+    }
 
-extern "C" fn pancho_hle_get_system_info() {
-    println!("Pancho-HLE: Intercepted GetSystemInfo. Reporting 8-Core M-Series CPU.");
-    // In a real implementation, this writes to a SYSTEM_INFO struct in the game's memory
+    pub fn address(&self, offset: usize) -> usize {
+        self.base as usize + offset
+    }
+}
+
+impl Drop for CodePage {
+    fn drop(&mut self) {
+        let _ = vmm::process_memory().release(self.base);
+    }
 }
 
-extern "C" fn pancho_hle_get_tick_count() -> u32 {
-    let now = std::time::Instant::now();
-    // Simplified tick count
-    now.elapsed().as_millis() as u32
+const STUB_SIZE: usize = 32;
+
+/// Executable trampolines for imports nothing provides. Each one passes its
+/// "DLL!Function" name to `pancho_hle_unimplemented` instead of jumping to garbage.
+pub struct ImportStubs {
+    code: CodePage,
+    /// Referenced by address from the generated code
+    _names: Vec<CString>,
 }
 
-extern "C" fn pancho_hle_get_desktop_window() -> usize {
-    println!("Pancho-HLE: Providing virtual desktop handle.");
-    0xDEADBEEF // Mock handle
+impl ImportStubs {
+    pub fn generate(names: &[String]) -> Result<Self, String> {
+        let names: Vec<CString> = names.iter()
+            .map(|name| CString::new(name.replace('\0', "")).unwrap_or_default())
+            .collect();
+        let handler = pancho_hle_unimplemented as *const () as u64;
+        let code = CodePage::generate("Import stubs", names.len(), STUB_SIZE, |i, stub| {
+            // mov rcx, name; mov rax, handler; jmp rax
+            stub[0..2].copy_from_slice(&[0x48, 0xB9]);
+            stub[2..10].copy_from_slice(&(names[i].as_ptr() as u64).to_le_bytes());
+            stub[10..12].copy_from_slice(&[0x48, 0xB8]);
+            stub[12..20].copy_from_slice(&handler.to_le_bytes());
+            stub[20..22].copy_from_slice(&[0xFF, 0xE0]);
+        })?;
+        Ok(ImportStubs { code, _names: names })
+    }
+
+    pub fn address(&self, index: usize) -> usize {
+        self.code.address(index * STUB_SIZE)
+    }
+}
+
+// --- NATIVE SHIMS (HLE IMPLEMENTATIONS) ---
+
+/// Target of every import stub; guest code reaches it through the IAT, so it uses the Windows ABI
+extern "win64" fn pancho_hle_unimplemented(name: *const c_char) -> ! {
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    eprintln!("Pancho-HLE: unimplemented {}", name);
+    executor::report_unimplemented(&name);
+    std::process::exit(UNIMPLEMENTED_IMPORT_EXIT_CODE);
+}
+
+/// HWND the desktop window has on a stock Windows session
+const DESKTOP_WINDOW: usize = 0x10010;
+
+extern "win64" fn pancho_hle_get_desktop_window() -> usize {
+    DESKTOP_WINDOW
 }
@@ -1,11 +1,16 @@
 use goblin::pe::PE;
-use std::fs::File;
-use std::io::Read;
+use goblin::pe::import::SyntheticImportLookupTableEntry;
+use goblin::pe::options::ParseOptions;
 use std::path::Path;
-use libc::{mmap, PROT_READ, PROT_WRITE, MAP_PRIVATE, MAP_ANONYMOUS};
-use crate::core::vmm::VirtualMemoryManager;
-use crate::core::linker::SymbolicLinker;
-use crate::core::context::ExecutionContext;
+use goblin::pe::section_table::{IMAGE_SCN_MEM_EXECUTE, IMAGE_SCN_MEM_READ, IMAGE_SCN_MEM_WRITE};
+use libc::{PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
+use crate::core::mapped_file::MappedFile;
+use crate::core::linker::{ImportCoverage, ImportSource, ImportStubs, SymbolicLinker};
+use crate::core::trace::TraceThunks;
+use crate::core::vmm::{self, RegionType, PAGE_EXECUTE_WRITECOPY, PAGE_READWRITE};
+use crate::core::authenticode::{self, SignatureInfo};
+use crate::core::classifier::{self, ExecutableClass};
+use crate::core::msi::{self, MsiInfo};
 
 #[derive(serde::Serialize)]
 pub struct ExecutableInfo {
@@ -15,37 +20,96 @@ pub struct ExecutableInfo {
     pub sections: usize,
     pub is_64_bit: bool,
     pub base_address: u64,
+    /// SizeOfImage, the span the loader has to reserve at `base_address`
+    pub image_size: u32,
+    pub class: ExecutableClass,
+    pub class_evidence: Vec<String>,
+    pub subsystem: String,
+    pub manifest: Option<AppManifest>,
+    pub dotnet: Option<DotNetInfo>,
+    /// Virtual addresses of TLS callbacks, which run before the entry point
+    pub tls_callbacks: Vec<u64>,
+    pub linker_version: String,
+    pub os_version: String,
+    pub subsystem_version: String,
+    pub signature: SignatureInfo,
+    /// Set for `.msi` packages, which have no PE headers
+    pub msi: Option<MsiInfo>,
 }
 
-#[derive(serde::Serialize)]
+/// The parts of the embedded application manifest that change how Windows runs the program
+#[derive(serde::Serialize, Debug, Clone, Default)]
+pub struct AppManifest {
+    /// asInvoker, highestAvailable or requireAdministrator
+    pub execution_level: Option<String>,
+    pub ui_access: bool,
+    /// `dpiAwareness` if present, otherwise `dpiAware`
+    pub dpi_awareness: Option<String>,
+    /// Windows versions declared in `<supportedOS>`
+    pub supported_os: Vec<String>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct DotNetInfo {
+    /// Metadata runtime version, e.g. "v4.0.30319"
+    pub runtime_version: String,
+    /// From TargetFrameworkAttribute, e.g. ".NETFramework,Version=v4.8"
+    pub target_framework: Option<String>,
+    pub il_only: bool,
+    pub requires_32_bit: bool,
+    pub prefers_32_bit: bool,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
 pub struct ImportEntry {
     pub dll: String,
+    /// Function name, or `#<ordinal>` when imported by ordinal
     pub function: String,
+    pub ordinal: Option<u16>,
+    pub hint: Option<u16>,
+    /// RVA of the IAT slot the loader writes the resolved address to
+    pub iat_rva: u32,
+    /// Address pre-bound in the IAT at link time, 0 when not bound
     pub address: u64,
 }
 
-pub fn load_executable(path_str: &str) -> Result<ExecutableInfo, String> {
-    let path = Path::new(path_str);
-    let mut file = File::open(path).map_err(|e| e.to_string())?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum ImportKind {
+    Static,
+    DelayLoad,
+}
 
-    let mut vmm = VirtualMemoryManager::new();
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct DllImports {
+    pub dll: String,
+    pub kind: ImportKind,
+    /// TimeDateStamp of the DLL this module was bound against, if any
+    pub bound_timestamp: Option<u32>,
+    pub functions: Vec<ImportEntry>,
+}
 
-    match PE::parse(&buffer) {
+/// Reads everything the UI shows about an executable from a read-only mapping of the file.
+/// Nothing is reserved in this process and the mapping is gone when this returns.
+pub fn analyze_executable(path_str: &str) -> Result<ExecutableInfo, String> {
+    let file = MappedFile::open(Path::new(path_str))?;
+    analyze_image(path_str, &file)
+}
+
+pub fn analyze_image(path_str: &str, buffer: &[u8]) -> Result<ExecutableInfo, String> {
+    let path = Path::new(path_str);
+    if msi::is_msi(buffer) {
+        return load_msi(path_str, buffer);
+    }
+
+    match PE::parse(buffer) {
         Ok(pe) => {
             let opt_header = pe.header.optional_header.ok_or("No optional header")?;
-            let image_size = opt_header.windows_fields.size_of_image as usize;
-            let image_base = opt_header.windows_fields.image_base as u64;
+            let image_base = opt_header.windows_fields.image_base;
 
-            let _ = vmm.reserve_address_space(image_base, image_size, "PE_IMAGE");
-            let _ = vmm.setup_teb_space();
+            let machine = image_machine_name(&pe);
 
-            let machine = match pe.header.coff_header.machine {
-                0x8664 => "x86_64",
-                0x014c => "x86",
-                _ => "Unknown",
-            };
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+            let classification = classifier::classify(buffer, &pe, &file_name);
 
             Ok(ExecutableInfo {
                 path: path_str.to_string(),
@@ -54,87 +118,657 @@ pub fn load_executable(path_str: &str) -> Result<ExecutableInfo, String> {
                 sections: pe.sections.len(),
                 is_64_bit: pe.is_64,
                 base_address: image_base,
+                image_size: opt_header.windows_fields.size_of_image,
+                class: classification.class,
+                class_evidence: classification.evidence,
+                subsystem: subsystem_name(opt_header.windows_fields.subsystem).to_string(),
+                manifest: parse_manifest(&pe),
+                dotnet: parse_dotnet(buffer, &pe),
+                signature: authenticode::verify(&pe),
+                msi: None,
+                tls_callbacks: pe.tls_data.as_ref().map(|t| t.callbacks.clone()).unwrap_or_default(),
+                linker_version: format!(
+                    "{}.{}",
+                    opt_header.standard_fields.major_linker_version,
+                    opt_header.standard_fields.minor_linker_version
+                ),
+                os_version: format!(
+                    "{}.{}",
+                    opt_header.windows_fields.major_operating_system_version,
+                    opt_header.windows_fields.minor_operating_system_version
+                ),
+                subsystem_version: format!(
+                    "{}.{}",
+                    opt_header.windows_fields.major_subsystem_version,
+                    opt_header.windows_fields.minor_subsystem_version
+                ),
             })
         },
         Err(e) => Err(format!("Failed to parse PE file: {}", e)),
     }
 }
 
-pub unsafe fn map_pe_to_memory(pe_data: &[u8]) -> Result<*mut libc::c_void, String> {
+/// Reports an MSI package's properties in place of PE headers
+fn load_msi(path_str: &str, data: &[u8]) -> Result<ExecutableInfo, String> {
+    let info = msi::parse_msi(data)?;
+    let platform = info.platform.as_deref().and_then(|p| p.split(';').next()).unwrap_or_default().to_lowercase();
+    let machine = match platform.as_str() {
+        "intel" | "" => machine_name(0x014c),
+        "x64" | "amd64" | "intel64" => machine_name(0x8664),
+        "arm64" => machine_name(0xAA64),
+        _ => "Unknown",
+    };
+
+    Ok(ExecutableInfo {
+        path: path_str.to_string(),
+        machine: machine.to_string(),
+        entry_point: 0,
+        sections: 0,
+        is_64_bit: info.is_64_bit(),
+        base_address: 0,
+        image_size: 0,
+        class: ExecutableClass::Installer,
+        class_evidence: vec!["MSI package".to_string()],
+        subsystem: "Windows Installer".to_string(),
+        manifest: None,
+        dotnet: None,
+        tls_callbacks: Vec::new(),
+        linker_version: String::new(),
+        os_version: String::new(),
+        subsystem_version: String::new(),
+        signature: authenticode::verify_file(data)?,
+        msi: Some(info),
+    })
+}
+
+pub fn machine_name(machine: u16) -> &'static str {
+    match machine {
+        0x8664 => "x86_64",
+        0x014c => "x86",
+        0xAA64 => "ARM64",
+        0xA641 => "ARM64EC",
+        0xA64E => "ARM64X",
+        _ => "Unknown",
+    }
+}
+
+/// ARM64EC and ARM64X images carry a CHPE metadata pointer in their load config.
+/// ARM64EC images otherwise look like plain x86_64 ones.
+pub fn image_machine_name(pe: &PE) -> &'static str {
+    let has_chpe = pe.load_config_data.as_ref()
+        .and_then(|l| l.directory.chpe_metadata_pointer)
+        .is_some_and(|p| p != 0);
+
+    match pe.header.coff_header.machine {
+        0x8664 if has_chpe => "ARM64EC",
+        0xAA64 if has_chpe => "ARM64X",
+        machine => machine_name(machine),
+    }
+}
+
+fn subsystem_name(subsystem: u16) -> &'static str {
+    match subsystem {
+        1 => "Native",
+        2 => "GUI",
+        3 => "Console",
+        9 => "Windows CE",
+        10..=13 => "EFI",
+        16 => "Boot Application",
+        _ => "Unknown",
+    }
+}
+
+const SUPPORTED_OS_GUIDS: [(&str, &str); 5] = [
+    ("e2011457-1546-43c5-a5fe-008deee3d3f0", "Windows Vista"),
+    ("35138b9a-5d96-4fbd-8e2d-a2440225f93a", "Windows 7"),
+    ("4a2f28e3-53b9-4441-ba9c-d69d4a4a6e38", "Windows 8"),
+    ("1f676c76-80e1-4239-95bb-83d0f6d0da78", "Windows 8.1"),
+    ("8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a", "Windows 10/11"),
+];
+
+pub fn parse_manifest(pe: &PE) -> Option<AppManifest> {
+    let data = pe.resource_data.as_ref()?.manifest_data.as_ref()?.data;
+    let xml = String::from_utf8_lossy(data);
+    let lower = xml.to_lowercase();
+
+    let execution_level = find_tag(&xml, "requestedExecutionLevel").and_then(|tag| xml_attribute(tag, "level"));
+    let ui_access = find_tag(&xml, "requestedExecutionLevel")
+        .and_then(|tag| xml_attribute(tag, "uiAccess"))
+        .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+    let dpi_awareness = xml_element_text(&xml, "dpiAwareness").or_else(|| xml_element_text(&xml, "dpiAware"));
+
+    let supported_os = SUPPORTED_OS_GUIDS.iter()
+        .filter(|(guid, _)| lower.contains(guid))
+        .map(|(_, name)| name.to_string())
+        .collect();
+
+    Some(AppManifest { execution_level, ui_access, dpi_awareness, supported_os })
+}
+
+/// The opening tag of the first `<name ...>` element, ignoring namespace prefixes
+fn find_tag<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let mut search = 0;
+    while let Some(pos) = xml[search..].find(name) {
+        let start = search + pos;
+        search = start + name.len();
+        let before = xml[..start].trim_end_matches(|c: char| c.is_alphanumeric() || c == ':');
+        if before.ends_with('<') {
+            let end = xml[start..].find('>')? + start;
+            return Some(&xml[start..end]);
+        }
+    }
+    None
+}
+
+fn xml_attribute(tag: &str, name: &str) -> Option<String> {
+    let pos = tag.find(&format!("{}=", name))? + name.len() + 1;
+    let quote = tag[pos..].chars().next()?;
+    let value = &tag[pos + 1..];
+    Some(value[..value.find(quote)?].to_string())
+}
+
+fn xml_element_text(xml: &str, name: &str) -> Option<String> {
+    let tag = find_tag(xml, name)?;
+    let start = xml.find(tag)? + tag.len() + 1;
+    let end = xml[start..].find('<')? + start;
+    let text = xml[start..end].trim();
+    if text.is_empty() { None } else { Some(text.to_string()) }
+}
+
+pub fn parse_dotnet(data: &[u8], pe: &PE) -> Option<DotNetInfo> {
+    let clr = pe.clr_data.as_ref()?;
+    let header = &clr.cor20_header;
+
+    // TargetFrameworkAttribute is stored as a string in the #Blob heap. Only that heap is
+    // searched: the same text in a resource or another string would not name the target.
+    let blob = rva_to_offset(pe, header.metadata.virtual_address)
+        .and_then(|offset| data.get(offset..offset.checked_add(header.metadata.size as usize)?))
+        .and_then(|metadata| metadata_stream(metadata, "#Blob"))
+        .unwrap_or_default();
+    let target_framework = [".NETFramework,Version=", ".NETCoreApp,Version=", ".NETStandard,Version="]
+        .iter()
+        .find_map(|prefix| {
+            let start = blob.windows(prefix.len()).position(|w| w == prefix.as_bytes())?;
+            let end = blob[start..].iter()
+                .position(|b| !b.is_ascii_graphic())
+                .map(|len| start + len)
+                .unwrap_or(blob.len());
+            Some(String::from_utf8_lossy(&blob[start..end]).into_owned())
+        });
+
+    Some(DotNetInfo {
+        runtime_version: clr.metadata_header.version.trim_end_matches('\0').to_string(),
+        target_framework,
+        il_only: header.is_il_only(),
+        requires_32_bit: header.is_32bit_required(),
+        prefers_32_bit: header.is_32bit_preferred(),
+    })
+}
+
+/// The named stream of a CLI metadata root (ECMA-335 II.24.2.1), e.g. "#Blob"
+pub(crate) fn metadata_stream<'a>(metadata: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    if read_u32(metadata, 0)? != 0x424A_5342 {
+        return None;
+    }
+    // The version string length is already a multiple of 4
+    let mut offset = 16usize.checked_add(read_u32(metadata, 12)? as usize)?;
+    let streams = read_u16(metadata, offset + 2)?;
+    offset += 4;
+
+    for _ in 0..streams {
+        let stream_offset = read_u32(metadata, offset)? as usize;
+        let stream_size = read_u32(metadata, offset + 4)? as usize;
+        let stream_name = read_cstr(metadata, offset + 8)?;
+        if stream_name == name {
+            return metadata.get(stream_offset..stream_offset.checked_add(stream_size)?);
+        }
+        // The name is null-terminated and padded to a 4-byte boundary
+        offset += 8 + (stream_name.len() + 1).div_ceil(4) * 4;
+    }
+    None
+}
+
+/// An image laid out in memory the way the Windows loader would
+pub struct MappedImage {
+    pub base: *mut libc::c_void,
+    pub size: usize,
+    /// Number of base relocations applied because the image didn't land at its ImageBase
+    pub relocations_applied: usize,
+    /// How each import was bound when the IAT was written
+    pub imports: ImportCoverage,
+    /// Trampolines the IAT points at for unresolved imports; they live as long as the image
+    pub stubs: Option<ImportStubs>,
+    /// Recording thunks between the IAT and resolved imports when the linker traces
+    pub trace_thunks: Option<TraceThunks>,
+}
+
+impl Drop for MappedImage {
+    fn drop(&mut self) {
+        let _ = vmm::process_memory().release(self.base as u64);
+    }
+}
+
+const IMAGE_REL_BASED_ABSOLUTE: u16 = 0;
+const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+const IMAGE_REL_BASED_DIR64: u16 = 10;
+const IMAGE_FILE_RELOCS_STRIPPED: u16 = 0x0001;
+const PAGE_SIZE: usize = 4096;
+
+/// Maps a PE image: headers and sections at their RVAs, `VirtualSize` tails zeroed and
+/// base relocations applied when the preferred base is taken. The image stays writable
+/// and unbound so the exports of mutually dependent DLLs can be registered first.
+pub unsafe fn map_pe_image(pe_data: &[u8]) -> Result<MappedImage, String> {
     let pe = PE::parse(pe_data).map_err(|e| e.to_string())?;
     
     let opt_header = pe.header.optional_header.ok_or("No optional header found")?;
     let image_size = opt_header.windows_fields.size_of_image as usize;
+    let preferred_base = opt_header.windows_fields.image_base;
+    if image_size == 0 {
+        return Err("SizeOfImage is zero".to_string());
+    }
 
-    let addr = mmap(
-        std::ptr::null_mut(),
-        image_size,
-        PROT_READ | PROT_WRITE,
-        MAP_PRIVATE | MAP_ANONYMOUS,
-        -1,
-        0
-    );
+    // An image reservation at the preferred base, or anywhere if that is taken
+    let label = pe.name.unwrap_or("Image");
+    let base = {
+        let mut memory = vmm::process_memory();
+        memory.reserve(preferred_base, image_size, PAGE_EXECUTE_WRITECOPY, RegionType::Image, label)
+            .or_else(|_| memory.reserve(0, image_size, PAGE_EXECUTE_WRITECOPY, RegionType::Image, label))
+            .map_err(|e| format!("Failed to reserve image space: {}", e))?
+    };
+    let addr = base as *mut libc::c_void;
+    // Released on drop from here on, including the error paths
+    let mut mapped = MappedImage {
+        base: addr,
+        size: image_size,
+        relocations_applied: 0,
+        imports: ImportCoverage::default(),
+        stubs: None,
+        trace_thunks: None,
+    };
+    vmm::process_memory().commit(base, image_size, PAGE_READWRITE)
+        .map_err(|e| format!("Failed to commit image space: {}", e))?;
 
-    if addr == libc::MAP_FAILED {
-        return Err("Failed to mmap image space".to_string());
-    }
+    let image = std::slice::from_raw_parts_mut(addr as *mut u8, image_size);
 
-    let header_size = opt_header.windows_fields.size_of_headers as usize;
-    std::ptr::copy_nonoverlapping(pe_data.as_ptr(), addr as *mut u8, header_size);
+    let header_size = (opt_header.windows_fields.size_of_headers as usize).min(pe_data.len()).min(image_size);
+    image[..header_size].copy_from_slice(&pe_data[..header_size]);
 
     for section in &pe.sections {
-        let section_dest = (addr as usize + section.virtual_address as usize) as *mut u8;
-        let section_src_offset = section.pointer_to_raw_data as usize;
-        let size = section.size_of_raw_data as usize;
-        
-        if size > 0 && section_src_offset + size <= pe_data.len() {
-            let section_src = &pe_data[section_src_offset..section_src_offset + size];
-            std::ptr::copy_nonoverlapping(section_src.as_ptr(), section_dest, size);
+        let start = section.virtual_address as usize;
+        let virtual_size = match section.virtual_size {
+            0 => section.size_of_raw_data,
+            size => size,
+        } as usize;
+        let Some(dest) = image.get_mut(start..start + virtual_size) else {
+            let name = section.name().unwrap_or_default().to_string();
+            return Err(format!("Section {} lies outside the image", name));
+        };
+
+        let raw_offset = section.pointer_to_raw_data as usize;
+        let raw_size = (section.size_of_raw_data as usize)
+            .min(virtual_size)
+            .min(pe_data.len().saturating_sub(raw_offset));
+        if raw_size > 0 {
+            dest[..raw_size].copy_from_slice(&pe_data[raw_offset..raw_offset + raw_size]);
         }
+        // Uninitialized data (.bss and friends) must read as zero
+        dest[raw_size..].fill(0);
     }
 
-    patch_iat(addr, &pe)?;
+    let delta = (addr as u64).wrapping_sub(preferred_base);
+    if delta != 0 {
+        if !pe.is_64 && (addr as u64) + image_size as u64 > u32::MAX as u64 {
+            return Err("No room below 4GB for a 32-bit image".to_string());
+        }
+        if pe.header.coff_header.characteristics & IMAGE_FILE_RELOCS_STRIPPED != 0 {
+            return Err(format!("Image has no relocations and 0x{:x} is taken", preferred_base));
+        }
+        let directory = opt_header.data_directories.get_base_relocation_table();
+        let (rva, size) = directory.map(|d| (d.virtual_address as usize, d.size as usize)).unwrap_or((0, 0));
+        mapped.relocations_applied = apply_relocations(image, rva, size, delta)?;
+    }
 
-    Ok(addr)
+    Ok(mapped)
 }
 
-fn patch_iat(_image_base: *mut libc::c_void, pe: &PE) -> Result<(), String> {
-    let linker = SymbolicLinker::new();
+/// Finishes a `map_pe_image` mapping: writes the IAT, then applies section protections
+pub unsafe fn bind_pe_image(mapped: &mut MappedImage, pe_data: &[u8], linker: &SymbolicLinker) -> Result<(), String> {
+    let pe = PE::parse(pe_data).map_err(|e| e.to_string())?;
+    let opt_header = pe.header.optional_header.ok_or("No optional header found")?;
+    let header_size = (opt_header.windows_fields.size_of_headers as usize).min(pe_data.len()).min(mapped.size);
+
+    let image = std::slice::from_raw_parts_mut(mapped.base as *mut u8, mapped.size);
+    let (imports, stubs, trace_thunks) = patch_iat(image, pe_data, &pe, linker)?;
+    mapped.imports = imports;
+    mapped.stubs = stubs;
+    mapped.trace_thunks = trace_thunks;
 
-    for import in pe.imports.iter() {
-        let dll_name = &import.name;
-        if let Some(native_addr) = linker.resolve(dll_name, "GetSystemInfo") {
-            println!("Pancho-Linker: Hooked {}!GetSystemInfo to 0x{:x}", dll_name, native_addr);
+    protect_sections(mapped.base, mapped.size, header_size, &pe)
+}
+
+/// Walks IMAGE_BASE_RELOCATION blocks in an image that is already laid out by RVA
+pub fn apply_relocations(image: &mut [u8], directory_rva: usize, directory_size: usize, delta: u64) -> Result<usize, String> {
+    if directory_size == 0 {
+        return Err("Image must be relocated but has no .reloc data".to_string());
+    }
+    let directory_end = directory_rva.checked_add(directory_size).filter(|end| *end <= image.len())
+        .ok_or("Relocation directory lies outside the image")?;
+
+    let mut applied = 0;
+    let mut block = directory_rva;
+    while block + 8 <= directory_end {
+        let page_rva = read_u32(image, block).unwrap_or(0) as usize;
+        let block_size = read_u32(image, block + 4).unwrap_or(0) as usize;
+        // Some linkers pad the directory with zeros after the last block
+        if block_size == 0 {
+            break;
+        }
+        if block_size < 8 || block + block_size > directory_end {
+            return Err(format!("Malformed relocation block at 0x{:x}", block));
         }
+
+        for entry_offset in (block + 8..block + block_size).step_by(2) {
+            let entry = read_u16(image, entry_offset).unwrap_or(0);
+            let target = page_rva + (entry & 0x0FFF) as usize;
+            match entry >> 12 {
+                IMAGE_REL_BASED_ABSOLUTE => continue,
+                IMAGE_REL_BASED_HIGHLOW => {
+                    let value = read_u32(image, target).ok_or(format!("HIGHLOW relocation at 0x{:x} out of range", target))?;
+                    image[target..target + 4].copy_from_slice(&value.wrapping_add(delta as u32).to_le_bytes());
+                }
+                IMAGE_REL_BASED_DIR64 => {
+                    let value = read_u64(image, target).ok_or(format!("DIR64 relocation at 0x{:x} out of range", target))?;
+                    image[target..target + 8].copy_from_slice(&value.wrapping_add(delta).to_le_bytes());
+                }
+                other => return Err(format!("Unsupported relocation type {} at 0x{:x}", other, target)),
+            }
+            applied += 1;
+        }
+        block += block_size;
+    }
+    Ok(applied)
+}
+
+/// Page protection for a section's characteristics
+pub fn section_protection(characteristics: u32) -> i32 {
+    let mut prot = PROT_NONE;
+    if characteristics & IMAGE_SCN_MEM_READ != 0 {
+        prot |= PROT_READ;
+    }
+    if characteristics & IMAGE_SCN_MEM_WRITE != 0 {
+        prot |= PROT_WRITE;
+    }
+    if characteristics & IMAGE_SCN_MEM_EXECUTE != 0 {
+        // Windows allows reading executable pages even without the READ flag
+        prot |= PROT_EXEC | PROT_READ;
+    }
+    prot
+}
+
+/// Headers become read-only and each page gets the union of the sections that share it
+/// (sections only share pages when SectionAlignment is below the page size)
+unsafe fn protect_sections(addr: *mut libc::c_void, image_size: usize, header_size: usize, pe: &PE) -> Result<(), String> {
+    let pages = image_size.div_ceil(PAGE_SIZE);
+    let mut protections = vec![PROT_NONE; pages];
+    for page in protections.iter_mut().take(header_size.div_ceil(PAGE_SIZE).max(1)) {
+        *page = PROT_READ;
+    }
+    for section in &pe.sections {
+        let start = section.virtual_address as usize;
+        let size = match section.virtual_size {
+            0 => section.size_of_raw_data,
+            size => size,
+        } as usize;
+        if size == 0 {
+            continue;
+        }
+        let prot = section_protection(section.characteristics);
+        for page in protections.iter_mut().take((start + size).div_ceil(PAGE_SIZE)).skip(start / PAGE_SIZE) {
+            *page |= prot;
+        }
+    }
+
+    let mut memory = vmm::process_memory();
+    let mut run_start = 0;
+    while run_start < pages {
+        let prot = protections[run_start];
+        let run_end = (run_start..pages).find(|p| protections[*p] != prot).unwrap_or(pages);
+        let start = addr as u64 + (run_start * PAGE_SIZE) as u64;
+        let len = ((run_end - run_start) * PAGE_SIZE).min(image_size - run_start * PAGE_SIZE);
+        memory.protect(start, len, vmm::windows_protection(prot))
+            .map_err(|e| format!("Failed to protect RVA 0x{:x}: {}", run_start * PAGE_SIZE, e))?;
+        run_start = run_end;
     }
-    
     Ok(())
 }
 
-pub fn launch_binary(entry_point: u64) -> ! {
-    let ctx = ExecutionContext::new(entry_point as usize);
-    unsafe {
-        ctx.launch();
+/// Writes the address of every static import into its IAT slot. Imports nothing
+/// resolves get a stub; delay-load slots are left to the binary's own helper.
+fn patch_iat(image: &mut [u8], pe_data: &[u8], pe: &PE, linker: &SymbolicLinker) -> Result<(ImportCoverage, Option<ImportStubs>, Option<TraceThunks>), String> {
+    let imports: Vec<DllImports> = parse_imports(pe_data, pe)?
+        .into_iter()
+        .filter(|dll| dll.kind == ImportKind::Static)
+        .collect();
+    let mut coverage = linker.coverage(&imports);
+
+    let unresolved: Vec<String> = coverage.imports.iter()
+        .filter(|import| import.source == ImportSource::Stub)
+        .map(|import| format!("{}!{}", import.dll, import.function))
+        .collect();
+    let stubs = if unresolved.is_empty() { None } else { Some(ImportStubs::generate(&unresolved)?) };
+
+    let traced: Vec<(String, usize)> = coverage.imports.iter()
+        .filter(|import| linker.trace && import.source != ImportSource::Stub)
+        .map(|import| (format!("{}!{}", import.dll.to_lowercase(), import.function), import.address))
+        .collect();
+    let trace_thunks = if traced.is_empty() { None } else { Some(TraceThunks::generate(&traced)?) };
+
+    let (mut next_stub, mut next_thunk) = (0, 0);
+    for import in coverage.imports.iter_mut() {
+        if import.source == ImportSource::Stub {
+            import.address = stubs.as_ref().map(|s| s.address(next_stub)).unwrap_or(0);
+            next_stub += 1;
+        }
+        // The coverage report keeps the real address; only the IAT sees the thunk
+        let address = match &trace_thunks {
+            Some(thunks) if import.source != ImportSource::Stub => {
+                next_thunk += 1;
+                thunks.address(next_thunk - 1)
+            }
+            _ => import.address,
+        };
+
+        let slot = import.iat_rva as usize;
+        if pe.is_64 {
+            let dest = image.get_mut(slot..slot + 8).ok_or(format!("IAT slot for {} outside the image", import.function))?;
+            dest.copy_from_slice(&(address as u64).to_le_bytes());
+        } else {
+            let address = u32::try_from(address).map_err(|_| format!("{} resolves above 4GB in a 32-bit image", import.function))?;
+            let dest = image.get_mut(slot..slot + 4).ok_or(format!("IAT slot for {} outside the image", import.function))?;
+            dest.copy_from_slice(&address.to_le_bytes());
+        }
     }
+
+    Ok((coverage, stubs, trace_thunks))
 }
 
-pub fn get_import_table(path_str: &str) -> Result<Vec<ImportEntry>, String> {
-    let path = Path::new(path_str);
-    let mut file = File::open(path).map_err(|e| e.to_string())?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
-
-    let pe = PE::parse(&buffer).map_err(|e| e.to_string())?;
-    let mut imports = Vec::new();
-
-    for import in pe.imports.iter() {
-        let dll = import.name.to_string();
-        imports.push(ImportEntry {
-            dll,
-            function: "Extracted Symbol".to_string(),
-            address: 0,
-        });
+/// How much of an executable's import table the HLE layer covers, without mapping it
+pub fn import_coverage(path_str: &str) -> Result<ImportCoverage, String> {
+    let imports = get_import_table(path_str)?;
+    Ok(SymbolicLinker::new().coverage(&imports))
+}
+
+/// Every imported function grouped by DLL, including delay-loaded DLLs
+pub fn get_import_table(path_str: &str) -> Result<Vec<DllImports>, String> {
+    let file = MappedFile::open(Path::new(path_str))?;
+    let pe = PE::parse(&file).map_err(|e| e.to_string())?;
+    parse_imports(&file, &pe)
+}
+
+pub fn parse_imports(data: &[u8], pe: &PE) -> Result<Vec<DllImports>, String> {
+    let opt_header = pe.header.optional_header.ok_or("No optional header")?;
+    let ptr_size = if pe.is_64 { 8 } else { 4 };
+    let bound = parse_bound_imports(data, pe);
+    let mut result = Vec::new();
+
+    if let Some(import_data) = &pe.import_data {
+        for entry in &import_data.import_data {
+            let directory = &entry.import_directory_entry;
+            let bound_timestamp = bound.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(entry.name))
+                .map(|(_, ts)| *ts)
+                // Old-style binding stores the timestamp in the descriptor itself
+                .or(match directory.time_date_stamp {
+                    0 | 0xFFFF_FFFF => None,
+                    ts => Some(ts),
+                });
+
+            let mut functions = Vec::new();
+            for (i, lookup) in entry.import_lookup_table.iter().flatten().enumerate() {
+                let (function, ordinal, hint) = match lookup {
+                    SyntheticImportLookupTableEntry::OrdinalNumber(ordinal) => {
+                        (format!("#{}", ordinal), Some(*ordinal), None)
+                    }
+                    SyntheticImportLookupTableEntry::HintNameTableRVA((_, hint_name)) => {
+                        (hint_name.name.to_string(), None, Some(hint_name.hint))
+                    }
+                };
+
+                functions.push(ImportEntry {
+                    dll: entry.name.to_string(),
+                    function,
+                    ordinal,
+                    hint,
+                    iat_rva: directory.import_address_table_rva + (i * ptr_size) as u32,
+                    address: match bound_timestamp {
+                        Some(_) => entry.import_address_table.get(i).copied().unwrap_or(0),
+                        None => 0,
+                    },
+                });
+            }
+
+            result.push(DllImports {
+                dll: entry.name.to_string(),
+                kind: ImportKind::Static,
+                bound_timestamp,
+                functions,
+            });
+        }
     }
 
-    Ok(imports)
-}
\ No newline at end of file
+    // Delay-load descriptors (goblin does not parse these)
+    let delay_dir = opt_header.data_directories.get_delay_import_descriptor().copied();
+    if let Some(dir) = delay_dir {
+        let mut offset = rva_to_offset(pe, dir.virtual_address).ok_or("Invalid delay import directory")?;
+        while let (Some(attributes), Some(name_rva), Some(iat_rva), Some(int_rva), Some(timestamp)) = (
+            read_u32(data, offset),
+            read_u32(data, offset + 4),
+            read_u32(data, offset + 12),
+            read_u32(data, offset + 16),
+            read_u32(data, offset + 28),
+        ) {
+            if name_rva == 0 {
+                break;
+            }
+            offset += 32;
+
+            // Attribute bit 0 clear means the old VC6 format, which stores VAs instead of RVAs
+            let to_rva = |value: u32| -> u32 {
+                if attributes & 1 == 0 { value.wrapping_sub(pe.image_base as u32) } else { value }
+            };
+            let Some(dll) = rva_to_offset(pe, to_rva(name_rva)).and_then(|o| read_cstr(data, o)) else { continue };
+
+            let mut functions = Vec::new();
+            if let Some(table) = rva_to_offset(pe, to_rva(int_rva)) {
+                for i in 0.. {
+                    let thunk = if pe.is_64 {
+                        read_u64(data, table + i * 8)
+                    } else {
+                        read_u32(data, table + i * 4).map(|v| v as u64)
+                    };
+                    let Some(thunk) = thunk.filter(|t| *t != 0) else { break };
+
+                    let ordinal_flag = if pe.is_64 { 1u64 << 63 } else { 1u64 << 31 };
+                    let (function, ordinal, hint) = if thunk & ordinal_flag != 0 {
+                        let ordinal = (thunk & 0xFFFF) as u16;
+                        (format!("#{}", ordinal), Some(ordinal), None)
+                    } else {
+                        let hint_offset = rva_to_offset(pe, to_rva(thunk as u32));
+                        let hint = hint_offset.and_then(|o| data.get(o..o + 2)).map(|b| u16::from_le_bytes([b[0], b[1]]));
+                        let name = hint_offset.and_then(|o| read_cstr(data, o + 2)).unwrap_or_default();
+                        (name, None, hint)
+                    };
+
+                    functions.push(ImportEntry {
+                        dll: dll.clone(),
+                        function,
+                        ordinal,
+                        hint,
+                        iat_rva: to_rva(iat_rva) + (i * ptr_size) as u32,
+                        address: 0,
+                    });
+                }
+            }
+
+            result.push(DllImports {
+                dll,
+                kind: ImportKind::DelayLoad,
+                bound_timestamp: if timestamp != 0 { Some(timestamp) } else { None },
+                functions,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+/// (module name, timestamp) pairs from IMAGE_BOUND_IMPORT_DESCRIPTOR entries
+fn parse_bound_imports(data: &[u8], pe: &PE) -> Vec<(String, u32)> {
+    let mut bound = Vec::new();
+    let Some(dir) = pe.header.optional_header.and_then(|o| o.data_directories.get_bound_import_table().copied()) else {
+        return bound;
+    };
+
+    // The bound import directory normally sits in the headers, where RVA == file offset
+    let base = rva_to_offset(pe, dir.virtual_address).unwrap_or(dir.virtual_address as usize);
+    let mut offset = base;
+    while let (Some(timestamp), Some(name_offset), Some(forwarders)) = (
+        read_u32(data, offset),
+        read_u16(data, offset + 4),
+        read_u16(data, offset + 6),
+    ) {
+        if timestamp == 0 && name_offset == 0 {
+            break;
+        }
+        if let Some(name) = read_cstr(data, base + name_offset as usize) {
+            bound.push((name, timestamp));
+        }
+        // Forwarder refs share the descriptor layout and follow it directly
+        offset += 8 * (1 + forwarders as usize);
+    }
+
+    bound
+}
+
+pub fn rva_to_offset(pe: &PE, rva: u32) -> Option<usize> {
+    let file_alignment = pe.header.optional_header?.windows_fields.file_alignment;
+    goblin::pe::utils::find_offset(rva as usize, &pe.sections, file_alignment, &ParseOptions::default())
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+pub(crate) fn read_cstr(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let end = bytes.iter().position(|b| *b == 0)?;
+    Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
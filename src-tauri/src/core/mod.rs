@@ -1,6 +1,14 @@
 pub mod loader;
+pub mod mapped_file;
 pub mod runner;
 pub mod scanner;
+pub mod classifier;
+pub mod installer;
+pub mod msi;
+pub mod dependencies;
+pub mod protection;
+pub mod instruction_sets;
+pub mod authenticode;
 pub mod bottle;
 pub mod engine;
 pub mod patcher;
@@ -8,6 +16,11 @@ pub mod shim;
 pub mod mach_ipc;
 pub mod vmm;
 pub mod linker;
+pub mod kernel32;
+pub mod trace;
+pub mod modules;
+pub mod teb;
 pub mod context;
 pub mod signals;
+pub mod executor;
 pub mod registry_writer;
@@ -0,0 +1,427 @@
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use goblin::pe::PE;
+use crate::core::dependencies::is_wine_placeholder;
+use crate::core::linker::{module_key, ExportTable, SymbolicLinker};
+use crate::core::loader::{bind_pe_image, map_pe_image, parse_imports, ImportKind, MappedImage};
+use crate::core::mapped_file::MappedFile;
+use crate::core::teb::{read_unicode_string, ProcessEnvironment};
+use crate::core::vmm::{self, RegionType, ALLOCATION_GRANULARITY, PAGE_NOACCESS};
+use crate::wine::paths::{is_windows_path, DriveMap};
+
+const DLL_PROCESS_DETACH: u32 = 0;
+const DLL_PROCESS_ATTACH: u32 = 1;
+
+/// The loader the LoadLibrary family of HLE functions works on
+static ACTIVE_LOADER: AtomicPtr<ModuleLoader> = AtomicPtr::new(std::ptr::null_mut());
+
+type DllMain = extern "win64" fn(instance: usize, reason: u32, reserved: usize) -> i32;
+
+pub struct LoadedModule {
+    /// Lowercase file name, e.g. "steam_api64.dll"
+    pub name: String,
+    pub path: PathBuf,
+    pub image: MappedImage,
+    /// Absolute address of AddressOfEntryPoint (DllMain for DLLs), if the image has one
+    pub entry_point: Option<usize>,
+    pub is_dll: bool,
+    /// Imported modules that were loaded from disk rather than provided by the HLE layer
+    pub dependencies: Vec<String>,
+    /// DllMain has run with DLL_PROCESS_ATTACH (always true for the executable)
+    pub attached: bool,
+}
+
+impl LoadedModule {
+    /// The HMODULE Windows code sees
+    pub fn base(&self) -> usize {
+        self.image.base as usize
+    }
+}
+
+/// Loads an image and the PE DLLs it depends on, the way ntdll's loader does:
+/// map everything, register exports, bind imports, then run DllMain dependencies first.
+///
+/// Guest code re-enters the loader through LoadLibrary while a DllMain runs, so everything
+/// that changes during a load sits in a RefCell and no borrow is held across a guest call.
+pub struct ModuleLoader {
+    /// Searched in order: the application directory, then the bottle's system32
+    pub search_paths: Vec<PathBuf>,
+    /// Translates the Windows paths guest code passes to LoadLibrary
+    pub drives: Option<DriveMap>,
+    pub linker: RefCell<SymbolicLinker>,
+    /// Loaded modules in load order
+    pub modules: RefCell<Vec<LoadedModule>>,
+    /// PEB whose loader lists track the modules, if the process has one yet
+    pub environment: RefCell<Option<ProcessEnvironment>>,
+    /// Handles given out for modules the HLE layer stands in for, by lowercase name
+    virtual_modules: RefCell<Vec<(String, usize)>>,
+    /// Set by `activate`; the address may have changed by the time the loader is dropped
+    active: Cell<bool>,
+}
+
+impl ModuleLoader {
+    pub fn new(app_dir: &Path, bottle_path: Option<&Path>) -> Self {
+        let mut search_paths = vec![app_dir.to_path_buf()];
+        if let Some(bottle) = bottle_path {
+            search_paths.push(bottle.join("drive_c/windows/system32"));
+        }
+        ModuleLoader {
+            search_paths,
+            drives: bottle_path.map(DriveMap::load),
+            linker: RefCell::new(SymbolicLinker::new()),
+            modules: RefCell::new(Vec::new()),
+            environment: RefCell::new(None),
+            virtual_modules: RefCell::new(Vec::new()),
+            active: Cell::new(false),
+        }
+    }
+
+    /// Makes this loader the one guest code reaches through LoadLibrary and friends, until
+    /// it's dropped. It must not move in the meantime; there is one per process.
+    pub fn activate(&self) {
+        self.active.set(true);
+        ACTIVE_LOADER.store(self as *const ModuleLoader as *mut ModuleLoader, Ordering::SeqCst);
+    }
+
+    /// Finds a DLL in the search path, ignoring case like Windows does. Wine's builtin and
+    /// placeholder DLLs are skipped: they only work inside Wine, so what they would export
+    /// is left to the HLE layer and its stubs.
+    pub fn find(&self, dll: &str) -> Option<PathBuf> {
+        let wanted = module_key(dll);
+        self.search_paths.iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.flatten())
+            .filter(|entry| entry.file_name().to_string_lossy().to_lowercase() == wanted)
+            .map(|entry| entry.path())
+            .find(|path| !is_wine_placeholder(path))
+    }
+
+    /// The borrow must be dropped before guest code runs
+    pub fn module(&self, name: &str) -> Option<Ref<'_, LoadedModule>> {
+        let key = module_key(name);
+        Ref::filter_map(self.modules.borrow(), |modules| modules.iter().find(|m| m.name == key)).ok()
+    }
+
+    /// Loads `path` and everything it imports, then attaches the new DLLs.
+    /// Returns the module's base; an already loaded module just returns its base.
+    pub fn load(&self, path: &Path) -> Result<usize, String> {
+        let name = module_key(&path.file_name().unwrap_or_default().to_string_lossy());
+        if let Some(module) = self.module(&name) {
+            return Ok(module.base());
+        }
+
+        let first_new = self.modules.borrow().len();
+        let mut files = Vec::new();
+        let mapped = self.map_recursive(path, &mut files).and_then(|_| self.map_forwarder_targets(first_new, &mut files));
+        // Only these are this call's to roll back: a DllMain that calls LoadLibrary adds
+        // modules after them that stay loaded whatever happens here
+        let new = first_new..first_new + files.len();
+        let bound = mapped.and_then(|_| {
+            // Every new module's exports are registered by now, so import cycles bind fully
+            let linker = self.linker.borrow();
+            for (module, file) in self.modules.borrow_mut()[new.clone()].iter_mut().zip(&files) {
+                unsafe { bind_pe_image(&mut module.image, file, &linker) }
+                    .map_err(|e| format!("{}: {}", module.name, e))?;
+            }
+            Ok(())
+        });
+        // Entries go into PEB.Ldr before any DllMain runs, as ntdll does
+        let listed = bound.and_then(|_| {
+            let mut environment = self.environment.borrow_mut();
+            let Some(environment) = environment.as_mut() else { return Ok(()) };
+            for module in &self.modules.borrow()[new.clone()] {
+                let full_name = match &self.drives {
+                    Some(drives) => drives.to_windows(&module.path)
+                        .ok_or(format!("No drive in this bottle reaches {}", module.path.display()))?,
+                    None => module.path.display().to_string(),
+                };
+                environment.add_module(module.base(), module.image.size, module.entry_point, module.is_dll, &full_name)?;
+            }
+            Ok(())
+        });
+        if let Err(e) = listed {
+            self.unload(new);
+            return Err(e);
+        }
+
+        if let Err(e) = self.attach(first_new) {
+            self.unload(new);
+            return Err(e);
+        }
+        Ok(self.modules.borrow()[first_new].base())
+    }
+
+    /// Drops the modules a failed load mapped
+    fn unload(&self, modules: Range<usize>) {
+        let removed: Vec<LoadedModule> = self.modules.borrow_mut().drain(modules).collect();
+        for module in removed {
+            self.linker.borrow_mut().unregister_module(&module.name);
+            if let Some(environment) = self.environment.borrow_mut().as_mut() {
+                environment.remove_module(module.base());
+            }
+        }
+    }
+
+    /// LoadLibrary: a module name is looked up in the search path, a Windows path goes
+    /// through the bottle's drives, and a host path is loaded as is
+    pub fn load_library(&self, name: &str) -> Result<usize, String> {
+        let file_name = name.rsplit(['\\', '/']).next().unwrap_or(name);
+        if let Some(module) = self.module(file_name) {
+            return Ok(module.base());
+        }
+        if self.linker.borrow().implements_module(file_name) {
+            return self.virtual_handle(file_name);
+        }
+        let path = if is_windows_path(name) {
+            self.drives.as_ref().and_then(|drives| drives.to_host(name)).ok_or(format!("{} not found", name))?
+        } else if name.contains('/') {
+            PathBuf::from(name)
+        } else {
+            self.find(name).ok_or(format!("{} not found", name))?
+        };
+        self.load(&path)
+    }
+
+    /// Host location of a path guest code passes to the file APIs. Relative paths start at
+    /// the process's current directory, `\dir` at the root of its drive.
+    pub fn host_path(&self, name: &str) -> Option<PathBuf> {
+        let current_directory = self.environment.borrow().as_ref()
+            .map(|environment| read_unicode_string(&environment.parameters().current_directory.dos_path));
+        let Some(drives) = &self.drives else {
+            // Without a bottle the current directory is a host path too
+            let path = PathBuf::from(name.replace('\\', "/"));
+            return Some(match current_directory {
+                Some(current) if path.is_relative() => PathBuf::from(current.replace('\\', "/")).join(path),
+                _ => path,
+            });
+        };
+        let absolute = is_windows_path(name) || name.starts_with("\\??\\");
+        if absolute {
+            return drives.to_host(name);
+        }
+        let current = current_directory?;
+        let full = if name.starts_with(['\\', '/']) {
+            format!("{}{}", current.get(..2)?, name)
+        } else {
+            format!("{}{}", current, name)
+        };
+        drives.to_host(&full)
+    }
+
+    /// GetModuleHandle: `None` is the executable, i.e. the first module loaded
+    pub fn module_handle(&self, name: Option<&str>) -> Option<usize> {
+        let Some(name) = name else {
+            return self.modules.borrow().first().map(|m| m.base());
+        };
+        let file_name = name.rsplit(['\\', '/']).next().unwrap_or(name);
+        if let Some(module) = self.module(file_name) {
+            return Some(module.base());
+        }
+        // HLE modules count as loaded from the start, like kernel32 on Windows
+        if self.linker.borrow().implements_module(file_name) {
+            return self.virtual_handle(file_name).ok();
+        }
+        None
+    }
+
+    /// GetProcAddress: `function` is a name, or `#<ordinal>`
+    pub fn proc_address(&self, module_base: usize, function: &str) -> Option<usize> {
+        let name = self.modules.borrow().iter()
+            .find(|m| m.base() == module_base)
+            .map(|m| m.name.clone())
+            .or_else(|| self.virtual_modules.borrow().iter().find(|(_, base)| *base == module_base).map(|(name, _)| name.clone()))?;
+        let ordinal = function.strip_prefix('#').and_then(|n| n.parse().ok());
+        self.resolve_import(&name, function, ordinal)
+    }
+
+    /// Resolves an import, first loading any DLL an export forwarder leads into
+    pub fn resolve_import(&self, dll: &str, function: &str, ordinal: Option<u16>) -> Option<usize> {
+        loop {
+            let missing = {
+                let linker = self.linker.borrow();
+                if let Some((address, _, _)) = linker.resolve_import(dll, function, ordinal) {
+                    return Some(address);
+                }
+                linker.missing_forwarder_target(dll, function, ordinal)?
+            };
+            // Once loaded its exports are registered, so each pass gets further down the chain
+            self.load(&self.find(&missing)?).ok()?;
+        }
+    }
+
+    /// Handle of a module the HLE layer stands in for: an image-sized reservation, so it's
+    /// a real address that no allocation reuses
+    fn virtual_handle(&self, name: &str) -> Result<usize, String> {
+        let key = module_key(name);
+        let mut virtual_modules = self.virtual_modules.borrow_mut();
+        if let Some((_, base)) = virtual_modules.iter().find(|(n, _)| *n == key) {
+            return Ok(*base);
+        }
+        let base = vmm::process_memory().reserve(0, ALLOCATION_GRANULARITY, PAGE_NOACCESS, RegionType::Image, &key)
+            .map_err(|e| format!("{}: {}", key, e))? as usize;
+        virtual_modules.push((key, base));
+        Ok(base)
+    }
+
+    /// Maps `path` and, depth first, every PE dependency not loaded yet. A module is added to
+    /// the list before its imports are followed, so a cycle back to it stops there.
+    fn map_recursive(&self, path: &Path, files: &mut Vec<MappedFile>) -> Result<(), String> {
+        let name = module_key(&path.file_name().unwrap_or_default().to_string_lossy());
+        let file = MappedFile::open(path)?;
+
+        let (module, imported) = {
+            let pe = PE::parse(&file).map_err(|e| format!("{}: {}", name, e))?;
+            let image = unsafe { map_pe_image(&file) }.map_err(|e| format!("{}: {}", name, e))?;
+            let mapped = unsafe { std::slice::from_raw_parts(image.base as *const u8, image.size) };
+            self.linker.borrow_mut().register_module(&name, ExportTable::from_image(&pe, mapped, image.base as usize));
+
+            // A DLL can have several import descriptors; the first one fixes its place in
+            // the initialization order
+            let mut seen = HashSet::new();
+            let imported: Vec<String> = parse_imports(&file, &pe)?
+                .into_iter()
+                .filter(|dll| dll.kind == ImportKind::Static)
+                .map(|dll| module_key(&dll.dll))
+                .filter(|dll| seen.insert(dll.clone()))
+                .collect();
+
+            let module = LoadedModule {
+                name: name.clone(),
+                path: path.to_path_buf(),
+                entry_point: (pe.entry != 0).then(|| image.base as usize + pe.entry as usize),
+                image,
+                is_dll: pe.is_lib,
+                dependencies: Vec::new(),
+                attached: !pe.is_lib,
+            };
+            (module, imported)
+        };
+        let index = {
+            let mut modules = self.modules.borrow_mut();
+            modules.push(module);
+            modules.len() - 1
+        };
+        files.push(file);
+
+        let mut dependencies = Vec::new();
+        for dll in imported {
+            // Modules the HLE layer covers stay virtual; the coverage report lists them
+            if self.linker.borrow().implements_module(&dll) {
+                continue;
+            }
+            if self.module(&dll).is_none() {
+                // Anything not found is left to the HLE layer and its stubs
+                let Some(dependency) = self.find(&dll) else { continue };
+                self.map_recursive(&dependency, files)?;
+            }
+            dependencies.push(dll);
+        }
+        self.modules.borrow_mut()[index].dependencies = dependencies;
+        Ok(())
+    }
+
+    /// Maps the DLLs that imports of the new modules (from `first` on) only reach through an
+    /// export forwarder, as ntdll does while snapping them. The importer depends on each one,
+    /// so it's initialized first.
+    fn map_forwarder_targets(&self, first: usize, files: &mut Vec<MappedFile>) -> Result<(), String> {
+        // Targets mapped here are new modules too, and their own imports get the same treatment
+        let mut index = 0;
+        while index < files.len() {
+            let imports = {
+                let file = &files[index];
+                let pe = PE::parse(file).map_err(|e| e.to_string())?;
+                parse_imports(file, &pe)?
+            };
+            for entry in imports.iter().filter(|dll| dll.kind == ImportKind::Static).flat_map(|dll| &dll.functions) {
+                loop {
+                    let missing = self.linker.borrow().missing_forwarder_target(&entry.dll, &entry.function, entry.ordinal);
+                    let Some(missing) = missing else { break };
+                    let Some(target) = self.find(&missing) else { break };
+                    self.map_recursive(&target, files)?;
+                    let mut modules = self.modules.borrow_mut();
+                    let dependencies = &mut modules[first + index].dependencies;
+                    if !dependencies.contains(&missing) {
+                        dependencies.push(missing);
+                    }
+                }
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+
+    /// Runs DllMain(DLL_PROCESS_ATTACH) for the new modules, each after its dependencies.
+    /// Inside a cycle the module reached first is initialized last, as on Windows. If one
+    /// fails, the modules attached before it get DLL_PROCESS_DETACH, latest first.
+    fn attach(&self, root: usize) -> Result<(), String> {
+        let mut order = Vec::new();
+        self.initialization_order(root, &mut HashSet::new(), &mut order);
+
+        let mut attached: Vec<(usize, DllMain)> = Vec::new();
+        for index in order {
+            // DllMain may call LoadLibrary, which pushes onto `modules`, so the borrow ends before the call
+            let (name, base, entry) = {
+                let mut modules = self.modules.borrow_mut();
+                let module = &mut modules[index];
+                if module.attached {
+                    continue;
+                }
+                module.attached = true;
+                (module.name.clone(), module.base(), module.entry_point)
+            };
+            if let Some(environment) = self.environment.borrow_mut().as_mut() {
+                environment.mark_initialized(base);
+            }
+            let Some(entry) = entry else { continue };
+
+            let dll_main: DllMain = unsafe { std::mem::transmute(entry) };
+            if dll_main(base, DLL_PROCESS_ATTACH, 0) == 0 {
+                for (base, dll_main) in attached.into_iter().rev() {
+                    dll_main(base, DLL_PROCESS_DETACH, 0);
+                }
+                return Err(format!("DllMain of {} failed to initialize", name));
+            }
+            attached.push((base, dll_main));
+        }
+        Ok(())
+    }
+
+    fn initialization_order(&self, index: usize, visited: &mut HashSet<usize>, order: &mut Vec<usize>) {
+        if !visited.insert(index) {
+            return;
+        }
+        let modules = self.modules.borrow();
+        for dependency in &modules[index].dependencies {
+            if let Some(dep) = modules.iter().position(|m| &m.name == dependency) {
+                self.initialization_order(dep, visited, order);
+            }
+        }
+        order.push(index);
+    }
+}
+
+impl Drop for ModuleLoader {
+    fn drop(&mut self) {
+        if self.active.get() {
+            ACTIVE_LOADER.store(std::ptr::null_mut(), Ordering::SeqCst);
+        }
+        let mut memory = vmm::process_memory();
+        for (_, base) in self.virtual_modules.get_mut().iter() {
+            let _ = memory.release(*base as u64);
+        }
+    }
+}
+
+/// Runs `f` on the active loader, or returns None before the helper has one.
+/// Guest code calls back in from DllMain, so this can re-enter a loader that is mid-load;
+/// only shared references to it exist, and it holds no RefCell borrow across those calls.
+pub fn with_active_loader<R>(f: impl FnOnce(&ModuleLoader) -> R) -> Option<R> {
+    let loader = ACTIVE_LOADER.load(Ordering::SeqCst);
+    if loader.is_null() {
+        return None;
+    }
+    Some(f(unsafe { &*loader }))
+}
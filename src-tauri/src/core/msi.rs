@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+pub const OLE_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+/// Properties of an MSI package that the installer pipeline cares about
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MsiInfo {
+    pub product_name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product_version: Option<String>,
+    /// `{GUID}` used by `msiexec /x` and as the Uninstall key name
+    pub product_code: Option<String>,
+    pub upgrade_code: Option<String>,
+    /// "1" for per-machine, "2" for per-user-if-allowed, empty/None for per-user
+    pub all_users: Option<String>,
+    /// Template from the summary stream, e.g. "x64;1033" or "Intel;1033"
+    pub platform: Option<String>,
+    /// Has a `\u{5}DigitalSignature` stream
+    pub signed: bool,
+}
+
+/// An MSI package installed into a bottle, kept so it can be removed with `msiexec /x`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MsiProduct {
+    pub product_code: String,
+    pub product_name: String,
+    pub product_version: Option<String>,
+    pub package: PathBuf,
+    pub log: PathBuf,
+}
+
+impl MsiInfo {
+    pub fn is_64_bit(&self) -> bool {
+        self.platform.as_deref().is_some_and(|p| {
+            let arch = p.split(';').next().unwrap_or_default().to_lowercase();
+            arch == "x64" || arch == "intel64" || arch == "arm64" || arch == "amd64"
+        })
+    }
+}
+
+pub fn is_msi(data: &[u8]) -> bool {
+    data.starts_with(&OLE_SIGNATURE)
+}
+
+pub fn read_msi(path: &Path) -> Result<MsiInfo, String> {
+    // Check the magic first so big setup.exe files aren't read for nothing
+    let mut magic = [0u8; 8];
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    file.read_exact(&mut magic).map_err(|e| e.to_string())?;
+    if !is_msi(&magic) {
+        return Err("Not an MSI package".to_string());
+    }
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    parse_msi(&data)
+}
+
+pub fn parse_msi(data: &[u8]) -> Result<MsiInfo, String> {
+    let file = CompoundFile::parse(data)?;
+
+    let pool = file.stream(&encode_table_name("_StringPool")).ok_or("Missing _StringPool")?;
+    let string_data = file.stream(&encode_table_name("_StringData")).ok_or("Missing _StringData")?;
+    let strings = StringTable::parse(&pool, &string_data);
+
+    let table = file.stream(&encode_table_name("Property")).ok_or("Missing Property table")?;
+    let properties = strings.read_property_table(&table);
+    let get = |name: &str| properties.get(name).cloned();
+
+    let platform = file.stream("\u{5}SummaryInformation")
+        .and_then(|summary| summary_string(&summary, 7));
+
+    Ok(MsiInfo {
+        product_name: get("ProductName"),
+        manufacturer: get("Manufacturer"),
+        product_version: get("ProductVersion"),
+        product_code: get("ProductCode"),
+        upgrade_code: get("UpgradeCode"),
+        all_users: get("ALLUSERS"),
+        platform,
+        signed: file.stream("\u{5}DigitalSignature").is_some(),
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]])).unwrap_or(0)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).unwrap_or(0)
+}
+
+const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+const FREE_SECTOR: u32 = 0xFFFF_FFFF;
+
+struct DirEntry {
+    name: String,
+    start: u32,
+    size: u64,
+}
+
+/// Read-only view of an OLE2 compound file (the container format of .msi)
+struct CompoundFile<'a> {
+    data: &'a [u8],
+    sector_size: usize,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    mini_cutoff: u64,
+    entries: Vec<DirEntry>,
+}
+
+impl<'a> CompoundFile<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, String> {
+        if !is_msi(data) || data.len() < 512 {
+            return Err("Not an OLE compound document".to_string());
+        }
+        let sector_shift = read_u16(data, 0x1E);
+        if !(7..=16).contains(&sector_shift) {
+            return Err("Invalid sector size".to_string());
+        }
+        let mut file = CompoundFile {
+            data,
+            sector_size: 1 << sector_shift,
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            mini_stream: Vec::new(),
+            mini_cutoff: read_u32(data, 0x38) as u64,
+            entries: Vec::new(),
+        };
+
+        // The first 109 FAT sector numbers sit in the header, the rest in DIFAT sectors
+        let mut fat_sectors: Vec<u32> = (0..109).map(|i| read_u32(data, 0x4C + i * 4)).collect();
+        let mut difat = read_u32(data, 0x44);
+        let per_sector = file.sector_size / 4;
+        let mut guard = 0;
+        while difat != END_OF_CHAIN && difat != FREE_SECTOR && guard < 10_000 {
+            let Some(sector) = file.sector(difat) else { break };
+            fat_sectors.extend((0..per_sector - 1).map(|i| read_u32(sector, i * 4)));
+            difat = read_u32(sector, (per_sector - 1) * 4);
+            guard += 1;
+        }
+        let fat_count = read_u32(data, 0x2C) as usize;
+        for sector in fat_sectors.into_iter().filter(|s| *s != FREE_SECTOR).take(fat_count) {
+            if let Some(bytes) = file.sector(sector) {
+                file.fat.extend((0..per_sector).map(|i| read_u32(bytes, i * 4)));
+            }
+        }
+
+        let directory = file.chain(read_u32(data, 0x30), u64::MAX);
+        for raw in directory.chunks_exact(128) {
+            let name_len = (read_u16(raw, 0x40) as usize).min(64);
+            let units: Vec<u16> = (0..name_len.saturating_sub(2) / 2).map(|i| read_u16(raw, i * 2)).collect();
+            file.entries.push(DirEntry {
+                name: String::from_utf16_lossy(&units),
+                start: read_u32(raw, 0x74),
+                size: read_u32(raw, 0x78) as u64,
+            });
+        }
+
+        let mini_fat = file.chain(read_u32(data, 0x3C), u64::MAX);
+        file.mini_fat = (0..mini_fat.len() / 4).map(|i| read_u32(&mini_fat, i * 4)).collect();
+        if let Some(root) = file.entries.first() {
+            file.mini_stream = file.chain(root.start, root.size);
+        }
+        Ok(file)
+    }
+
+    fn sector(&self, index: u32) -> Option<&'a [u8]> {
+        let start = (index as usize + 1).checked_mul(self.sector_size)?;
+        self.data.get(start..start + self.sector_size)
+    }
+
+    fn chain(&self, start: u32, size: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut current = start;
+        while current != END_OF_CHAIN && (out.len() as u64) < size && out.len() < self.data.len() {
+            let Some(sector) = self.sector(current) else { break };
+            out.extend_from_slice(sector);
+            current = *self.fat.get(current as usize).unwrap_or(&END_OF_CHAIN);
+        }
+        out.truncate(size.min(out.len() as u64) as usize);
+        out
+    }
+
+    fn mini_chain(&self, start: u32, size: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut current = start;
+        while current != END_OF_CHAIN && (out.len() as u64) < size && out.len() <= self.mini_stream.len() {
+            let offset = current as usize * 64;
+            let Some(block) = self.mini_stream.get(offset..offset + 64) else { break };
+            out.extend_from_slice(block);
+            current = *self.mini_fat.get(current as usize).unwrap_or(&END_OF_CHAIN);
+        }
+        out.truncate(size.min(out.len() as u64) as usize);
+        out
+    }
+
+    fn stream(&self, name: &str) -> Option<Vec<u8>> {
+        // Entry 0 is the root storage, which owns the mini stream
+        let entry = self.entries.iter().skip(1).find(|e| e.name == name)?;
+        Some(if entry.size < self.mini_cutoff {
+            self.mini_chain(entry.start, entry.size)
+        } else {
+            self.chain(entry.start, entry.size)
+        })
+    }
+}
+
+/// MSI packs two name characters into one UTF-16 unit and prefixes tables with U+4840
+pub(crate) fn encode_table_name(name: &str) -> String {
+    fn code(c: u8) -> Option<u16> {
+        Some(match c {
+            b'0'..=b'9' => (c - b'0') as u16,
+            b'A'..=b'Z' => (c - b'A') as u16 + 10,
+            b'a'..=b'z' => (c - b'a') as u16 + 36,
+            b'.' => 62,
+            b'_' => 63,
+            _ => return None,
+        })
+    }
+
+    let bytes = name.as_bytes();
+    let mut units = vec![0x4840u16];
+    let mut i = 0;
+    while i < bytes.len() {
+        let first = code(bytes[i]);
+        let second = bytes.get(i + 1).and_then(|b| code(*b));
+        match (first, second) {
+            (Some(a), Some(b)) => {
+                units.push(0x3800 + a + (b << 6));
+                i += 2;
+            }
+            (Some(a), None) => {
+                units.push(0x4800 + a);
+                i += 1;
+            }
+            (None, _) => {
+                units.push(bytes[i] as u16);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+struct StringTable {
+    strings: Vec<String>,
+    /// String references are 3 bytes wide in databases with more than 65535 strings
+    ref_size: usize,
+}
+
+impl StringTable {
+    /// Same layout Wine's msi.dll reads: (length, refcount) pairs, long strings split over two entries
+    fn parse(pool: &[u8], data: &[u8]) -> Self {
+        let words: Vec<u16> = (0..pool.len() / 2).map(|i| read_u16(pool, i * 2)).collect();
+        let ref_size = if read_u32(pool, 0) & 0x8000_0000 != 0 { 3 } else { 2 };
+        let count = words.len() / 2;
+
+        let mut strings = vec![String::new()];
+        let mut offset = 0usize;
+        let mut i = 1;
+        while i < count {
+            let (len, refs) = (words[i * 2] as usize, words[i * 2 + 1]);
+            if len == 0 && refs == 0 {
+                strings.push(String::new());
+                i += 1;
+                continue;
+            }
+            let len = if len == 0 {
+                let long = ((*words.get(i * 2 + 3).unwrap_or(&0) as usize) << 16) | *words.get(i * 2 + 2).unwrap_or(&0) as usize;
+                i += 2;
+                long
+            } else {
+                i += 1;
+                len
+            };
+            let bytes = data.get(offset..offset + len).unwrap_or_default();
+            // Non-UTF-8 packages use a Windows codepage; Latin-1 keeps ASCII intact
+            let text = match std::str::from_utf8(bytes) {
+                Ok(text) => text.to_string(),
+                Err(_) => bytes.iter().map(|b| *b as char).collect(),
+            };
+            strings.push(text);
+            offset += len;
+        }
+        StringTable { strings, ref_size }
+    }
+
+    fn get(&self, data: &[u8], offset: usize) -> Option<&str> {
+        let index = match self.ref_size {
+            3 => read_u16(data, offset) as usize | ((*data.get(offset + 2)? as usize) << 16),
+            _ => read_u16(data, offset) as usize,
+        };
+        self.strings.get(index).map(|s| s.as_str())
+    }
+
+    /// Property(Property, Value): both string columns, stored column after column
+    fn read_property_table(&self, table: &[u8]) -> HashMap<String, String> {
+        let rows = table.len() / (self.ref_size * 2);
+        let mut properties = HashMap::new();
+        for row in 0..rows {
+            let name = self.get(table, row * self.ref_size);
+            let value = self.get(table, (rows + row) * self.ref_size);
+            if let (Some(name), Some(value)) = (name, value) {
+                properties.insert(name.to_string(), value.to_string());
+            }
+        }
+        properties
+    }
+}
+
+/// Reads a VT_LPSTR property from the SummaryInformation property set
+fn summary_string(stream: &[u8], property_id: u32) -> Option<String> {
+    // Header (28 bytes), then FMTID (16) and the section offset
+    let section = read_u32(stream, 28 + 16) as usize;
+    let count = read_u32(stream, section + 4) as usize;
+    for i in 0..count.min(64) {
+        let entry = section + 8 + i * 8;
+        if read_u32(stream, entry) != property_id {
+            continue;
+        }
+        let value = section + read_u32(stream, entry + 4) as usize;
+        if read_u32(stream, value) != 0x1E {
+            return None;
+        }
+        let len = read_u32(stream, value + 4) as usize;
+        let bytes = stream.get(value + 8..value + 8 + len)?;
+        return Some(String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string());
+    }
+    None
+}
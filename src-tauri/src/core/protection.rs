@@ -0,0 +1,239 @@
+use std::path::{Path, PathBuf};
+use goblin::pe::PE;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+use crate::core::mapped_file::MappedFile;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Known to work under Wine
+    Info,
+    /// Works only in some cases (developer opt-in, activation limits, ...)
+    Warning,
+    /// Kernel drivers or Wine detection; the game will not run
+    Blocking,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ProtectionKind {
+    AntiCheat,
+    Drm,
+    Packer,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProtectionFinding {
+    pub name: String,
+    pub kind: ProtectionKind,
+    pub severity: Severity,
+    pub message: String,
+    pub evidence: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompatibilityReport {
+    pub install_dir: PathBuf,
+    pub findings: Vec<ProtectionFinding>,
+    /// Highest severity found, None when nothing was detected
+    pub severity: Option<Severity>,
+}
+
+struct Signature {
+    name: &'static str,
+    kind: ProtectionKind,
+    severity: Severity,
+    message: &'static str,
+    /// Lowercased file or folder names anywhere in the install
+    files: &'static [&'static str],
+    /// PE section names of the game executable
+    sections: &'static [&'static str],
+}
+
+const SIGNATURES: [Signature; 11] = [
+    Signature {
+        name: "EasyAntiCheat",
+        kind: ProtectionKind::AntiCheat,
+        severity: Severity::Warning,
+        message: "Runs under Wine only if the developer enabled Proton support. Online play may be refused otherwise.",
+        files: &["easyanticheat", "easyanticheat_x64.dll", "easyanticheat_eos_setup.exe", "start_protected_game.exe"],
+        sections: &[],
+    },
+    Signature {
+        name: "BattlEye",
+        kind: ProtectionKind::AntiCheat,
+        severity: Severity::Warning,
+        message: "Runs under Wine only if the developer enabled Proton support. Online play may be refused otherwise.",
+        files: &["battleye", "beservice.exe", "beservice_x64.exe", "beclient_x64.dll", "beclient.dll"],
+        sections: &[],
+    },
+    Signature {
+        name: "nProtect GameGuard",
+        kind: ProtectionKind::AntiCheat,
+        severity: Severity::Blocking,
+        message: "Kernel-level anti-cheat. It cannot load under Wine and the game will refuse to start.",
+        files: &["gameguard", "gamemon.des", "gameguard.des", "npgameguard"],
+        sections: &[],
+    },
+    Signature {
+        name: "Riot Vanguard",
+        kind: ProtectionKind::AntiCheat,
+        severity: Severity::Blocking,
+        message: "Kernel-level anti-cheat. It cannot load under Wine and the game will refuse to start.",
+        files: &["vgc.exe", "vgk.sys", "vanguard"],
+        sections: &[],
+    },
+    Signature {
+        name: "XIGNCODE3",
+        kind: ProtectionKind::AntiCheat,
+        severity: Severity::Blocking,
+        message: "Kernel-level anti-cheat. It cannot load under Wine and the game will refuse to start.",
+        files: &["xigncode", "x3.xem", "xhunter1.sys"],
+        sections: &[],
+    },
+    Signature {
+        name: "Denuvo",
+        kind: ProtectionKind::Drm,
+        severity: Severity::Warning,
+        message: "Usually works, but a new Wine or engine version can count as new hardware and use up activations.",
+        files: &[],
+        sections: &[".arch", ".xcode", ".xpdata", ".srdata"],
+    },
+    Signature {
+        name: "SecuROM",
+        kind: ProtectionKind::Drm,
+        severity: Severity::Warning,
+        message: "Online activation versions usually work. Disc-check versions need a driver and will fail.",
+        files: &["paul.dll", "securom"],
+        sections: &[".securom", ".cms_t", ".cms_d"],
+    },
+    Signature {
+        name: "StarForce",
+        kind: ProtectionKind::Drm,
+        severity: Severity::Blocking,
+        message: "Installs a kernel driver that Wine cannot load.",
+        files: &["sfdrv01.sys", "sfhlp02.sys"],
+        sections: &[".sforce", ".sforce3"],
+    },
+    Signature {
+        name: "VMProtect",
+        kind: ProtectionKind::Packer,
+        severity: Severity::Info,
+        message: "Virtualized code runs slower and some versions detect Wine, but most games start.",
+        files: &[],
+        sections: &[".vmp0", ".vmp1", ".vmp2"],
+    },
+    Signature {
+        name: "Themida / WinLicense",
+        kind: ProtectionKind::Packer,
+        severity: Severity::Warning,
+        message: "Anti-debug checks sometimes trip on Wine. Try a newer engine if the game exits immediately.",
+        files: &[],
+        sections: &[".themida", ".winlice"],
+    },
+    Signature {
+        name: "Steam DRM",
+        kind: ProtectionKind::Drm,
+        severity: Severity::Info,
+        message: "Needs Steam running in the same bottle.",
+        files: &[],
+        sections: &[".bind"],
+    },
+];
+
+// Build folders that sit between the install root and the exe
+const BUILD_DIRS: [&str; 6] = ["win64", "win32", "x64", "bin", "binaries", "shipping"];
+
+// Store clients install every game under their own folder, so only their binary is checked
+const LAUNCHERS: [&str; 3] = ["steam.exe", "epicgameslauncher.exe", "galaxyclient.exe"];
+
+pub struct ProtectionScanner;
+
+impl ProtectionScanner {
+    /// Accepts a game executable or an install directory (Steam titles only have the latter)
+    pub fn scan(path: &Path) -> CompatibilityReport {
+        let exe = if path.is_file() { Some(path) } else { None };
+        let install_dir = Self::install_root(path);
+
+        let mut sections: Vec<String> = Vec::new();
+        if let Some(exe) = exe {
+            if let Ok(data) = MappedFile::open(exe) {
+                if let Ok(pe) = PE::parse(&data) {
+                    sections = pe.sections.iter()
+                        .filter_map(|s| s.name().ok().map(|n| n.to_lowercase()))
+                        .collect();
+                }
+            }
+        }
+
+        let is_launcher = exe.and_then(|e| e.file_name())
+            .is_some_and(|name| LAUNCHERS.contains(&name.to_string_lossy().to_lowercase().as_str()));
+
+        let mut files: Vec<(String, PathBuf)> = Vec::new();
+        if !is_launcher {
+            for entry in WalkDir::new(&install_dir).max_depth(4).into_iter().flatten().take(20_000) {
+                files.push((entry.file_name().to_string_lossy().to_lowercase(), entry.path().to_path_buf()));
+            }
+        }
+
+        let mut findings = Vec::new();
+        for signature in &SIGNATURES {
+            let mut evidence: Vec<String> = Vec::new();
+
+            for section in sections.iter().filter(|s| signature.sections.contains(&s.as_str())) {
+                evidence.push(format!("section {}", section));
+            }
+            for (name, path) in &files {
+                if signature.files.contains(&name.as_str()) {
+                    let relative = path.strip_prefix(&install_dir).unwrap_or(path);
+                    evidence.push(relative.to_string_lossy().to_string());
+                }
+            }
+
+            if !evidence.is_empty() {
+                evidence.truncate(5);
+                findings.push(ProtectionFinding {
+                    name: signature.name.to_string(),
+                    kind: signature.kind.clone(),
+                    severity: signature.severity,
+                    message: signature.message.to_string(),
+                    evidence,
+                });
+            }
+        }
+
+        findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+        CompatibilityReport {
+            severity: findings.first().map(|f| f.severity),
+            install_dir,
+            findings,
+        }
+    }
+
+    /// Walks up from `Binaries/Win64`-style folders to the game's root
+    fn install_root(path: &Path) -> PathBuf {
+        let mut root = if path.is_file() { path.parent().unwrap_or(path) } else { path };
+        while let Some(parent) = root.parent() {
+            let name = root.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+            if !BUILD_DIRS.contains(&name.as_str()) {
+                break;
+            }
+            root = parent;
+        }
+        // Unreal: <Root>/<Project>/Binaries/Win64 -> <Root>
+        match root.parent() {
+            Some(parent) if parent.join("Engine").is_dir() => parent.to_path_buf(),
+            _ => root.to_path_buf(),
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn check_game_compatibility(path: String) -> Result<CompatibilityReport, String> {
+    let path = PathBuf::from(path);
+    if !path.exists() {
+        return Err("Path not found".to_string());
+    }
+    tokio::task::spawn_blocking(move || ProtectionScanner::scan(&path))
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -1,7 +1,8 @@
-use std::process::Command;
-use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
+use std::path::{Path, PathBuf};
 use std::fs;
 use crate::core::patcher;
+use crate::wine::paths::{is_windows_path, DriveMap};
 
 #[derive(serde::Serialize)]
 pub struct RunResult {
@@ -9,6 +10,32 @@ pub struct RunResult {
     pub message: String,
 }
 
+/// A program started in a bottle. One started through a batch file (see `run_command_line`)
+/// has the file deleted once it exits.
+pub struct BottleProcess {
+    child: Child,
+    script: Option<PathBuf>,
+}
+
+impl BottleProcess {
+    pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        let status = self.child.wait();
+        if let Some(script) = self.script.take() {
+            let _ = fs::remove_file(script);
+        }
+        status
+    }
+}
+
+impl From<Child> for BottleProcess {
+    fn from(child: Child) -> Self {
+        BottleProcess { child, script: None }
+    }
+}
+
+/// WINEDLLOVERRIDES passed to every launch
+pub const DLL_OVERRIDES: &str = "d3d11,d3d12,dxgi=n;d3d9=b;dwrite=d;mscoree,mshtml=;winemenubuilder.exe=d;gameoverlayrenderer,gameoverlayrenderer64=d";
+
 pub fn find_runner() -> Option<String> {
     let paths = [
         "/opt/homebrew/bin/wine",
@@ -25,18 +52,76 @@ pub fn find_runner() -> Option<String> {
     None
 }
 
-pub fn run_executable(exe_path: &str, prefix_path: &Path, custom_engine: Option<String>, env_type: &str) -> Result<std::process::Child, String> {
+/// Runs a Windows command line exactly as written, through a batch file in the bottle's
+/// temp folder. Switches like NSIS's `/D=C:\Program Files\Game` can't survive Wine's
+/// per-argument quoting. The exit code of the command is passed through.
+pub fn run_command_line(command_line: &str, working_dir: &str, prefix_path: &Path, custom_engine: Option<String>, env_type: &str) -> Result<BottleProcess, String> {
+    let temp = prefix_path.join("drive_c/windows/temp");
+    fs::create_dir_all(&temp).map_err(|e| e.to_string())?;
+    let stamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let name = format!("pancho-{}.bat", stamp);
+    let script = temp.join(&name);
+    fs::write(&script, batch_script(working_dir, command_line)).map_err(|e| e.to_string())?;
+
+    let args = ["/c".to_string(), format!(r"C:\windows\temp\{}", name)];
+    match run_executable_with_args(r"C:\windows\system32\cmd.exe", &args, None, prefix_path, custom_engine, env_type) {
+        Ok(child) => Ok(BottleProcess { child, script: Some(script) }),
+        Err(e) => {
+            let _ = fs::remove_file(&script);
+            Err(e)
+        }
+    }
+}
+
+/// Batch file running one command line from `working_dir`. `%` is doubled everywhere and
+/// `^ & | < >` are escaped outside quotes, the same way cmd splits the line.
+pub fn batch_script(working_dir: &str, command_line: &str) -> String {
+    let mut escaped = String::with_capacity(command_line.len());
+    let mut quoted = false;
+    for c in command_line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '%' => escaped.push('%'),
+            '^' | '&' | '|' | '<' | '>' if !quoted => escaped.push('^'),
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    [
+        "@echo off".to_string(),
+        "chcp 65001 >nul".to_string(),
+        format!("cd /d \"{}\"", working_dir.replace('%', "%%")),
+        escaped,
+        "exit %ERRORLEVEL%".to_string(),
+        String::new(),
+    ].join("\r\n")
+}
+
+/// Runs an executable with arguments, from `working_dir` or else the executable's folder
+pub fn run_executable_with_args(exe_path: &str, args: &[String], working_dir: Option<&Path>, prefix_path: &Path, custom_engine: Option<String>, env_type: &str) -> Result<std::process::Child, String> {
     let runner = if let Some(engine) = custom_engine {
         engine
     } else {
         find_runner().ok_or("Pancho-Core Engine not found. Please create a Pro bottle to trigger setup.")?
     };
     
-    let exe_path_buf = Path::new(exe_path);
-    let working_dir = exe_path_buf.parent().ok_or("Invalid executable path")?;
+    // Accepts either form; Wine is handed the Windows path so the program sees its real argv[0]
+    let drives = DriveMap::load(prefix_path);
+    let (host_exe, windows_exe) = if is_windows_path(exe_path) {
+        (drives.to_host(exe_path).ok_or(format!("No drive in this bottle for {}", exe_path))?, exe_path.to_string())
+    } else {
+        let windows_exe = drives.to_windows(Path::new(exe_path)).ok_or(format!("No drive in this bottle reaches {}", exe_path))?;
+        (Path::new(exe_path).to_path_buf(), windows_exe)
+    };
+    let working_dir = match working_dir {
+        Some(dir) => dir,
+        None => host_exe.parent().ok_or("Invalid executable path")?,
+    };
+    // Only the client itself, not its uninstaller or anything else with "steam" in the path
+    let is_steam = host_exe.file_name().is_some_and(|name| name.eq_ignore_ascii_case("steam.exe"));
 
     if !prefix_path.exists() {
-        fs::create_dir_all(&prefix_path).map_err(|e| e.to_string())?;
+        fs::create_dir_all(prefix_path).map_err(|e| e.to_string())?;
     }
 
     // ONLY APPLY PANCHO PATCHES FOR PRO BOTTLES IF NOT ALREADY DONE
@@ -48,7 +133,7 @@ pub fn run_executable(exe_path: &str, prefix_path: &Path, custom_engine: Option<
         }
         
         // Steam resets its own registry keys frequently, so we force-patch Steam every launch
-        if exe_path.to_lowercase().contains("steam.exe") {
+        if is_steam {
             let _ = patcher::apply_steam_specific_patches(&runner, prefix_path);
         }
         
@@ -57,13 +142,11 @@ pub fn run_executable(exe_path: &str, prefix_path: &Path, custom_engine: Option<
         }
     }
 
-    let is_steam = exe_path.to_lowercase().contains("steam");
-
     let mut command = Command::new(&runner);
     command.current_dir(working_dir)
            .env("WINEPREFIX", prefix_path.to_str().unwrap())
            // THE "WHISKY/CROSSOVER" STABILITY STACK
-           .env("WINEDLLOVERRIDES", "d3d11,d3d12,dxgi=n;d3d9=b;dwrite=d;mscoree,mshtml=;winemenubuilder.exe=d;gameoverlayrenderer,gameoverlayrenderer64=d")
+           .env("WINEDLLOVERRIDES", DLL_OVERRIDES)
            .env("WINE_SKIP_GECKO_INSTALLATION", "1")
            .env("WINE_SKIP_MONO_INSTALLATION", "1")
            
@@ -85,7 +168,8 @@ pub fn run_executable(exe_path: &str, prefix_path: &Path, custom_engine: Option<
            .env("STEAM_FORCE_DESKTOPUI_OVERRIDE", "1")
            .env("WINE_DISABLE_GPU_FOR_STEAM", "1")
            .env("PANCHO_MACH_PORT", "1")
-           .arg(exe_path);
+           .arg(&windows_exe)
+           .args(args);
 
     if is_steam {
         command.arg("-no-cef-sandbox")
@@ -1,6 +1,14 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
 use serde::{Serialize, Deserialize};
+use walkdir::WalkDir;
+use crate::wine::steam::SteamLauncher;
+use crate::wine::epic::EpicLibrary;
+use crate::wine::gog::GogLibrary;
+use crate::core::classifier::{self, ExecutableClass};
+use crate::wine::paths::DriveMap;
+use crate::wine::shell_link::ShellLink;
+use crate::wine::programs::split_arguments;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct DetectedApp {
@@ -9,6 +17,21 @@ pub struct DetectedApp {
     pub is_priority: bool,
     #[serde(default)]
     pub pinned: bool,
+    #[serde(default)]
+    pub steam_app_id: Option<u32>,
+    #[serde(default)]
+    pub cover: Option<String>,
+    #[serde(default)]
+    pub class: ExecutableClass,
+    /// The path as Windows programs in the bottle see it, e.g. `C:\Games\game.exe`
+    #[serde(default)]
+    pub windows_path: Option<String>,
+    /// Arguments the game's store launches it with
+    #[serde(default)]
+    pub launch_args: Vec<String>,
+    /// Folder the store starts the game in, when it isn't the executable's own
+    #[serde(default)]
+    pub working_dir: Option<String>,
 }
 
 pub fn scan_bottle_for_apps(bottle_path: &Path) -> Vec<DetectedApp> {
@@ -25,23 +48,127 @@ pub fn scan_bottle_for_apps(bottle_path: &Path) -> Vec<DetectedApp> {
         "galaxyclient.exe",
     ];
 
+    let drives = DriveMap::load(bottle_path);
+
     // Recursive scan for .exe files
-    scan_dir(&drive_c, &mut apps, &priority_names);
+    scan_dir(&drive_c, &drives, &mut apps, &priority_names);
+
+    // Start menu and desktop shortcuts give apps the name their installer chose
+    for lnk in shortcut_files(&drive_c) {
+        let Some(target) = ShellLink::read(&lnk).ok().and_then(|link| link.resolve(&drives, &lnk)) else { continue };
+        let is_exe = target.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("exe"));
+        if !is_exe || !target.is_file() || target.components().any(|c| c.as_os_str().eq_ignore_ascii_case("steamapps")) {
+            continue;
+        }
+        let name = lnk.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        if name.to_lowercase().contains("uninstall") {
+            continue;
+        }
+        let exe_path = target.to_str().unwrap_or_default().to_string();
+        match apps.iter_mut().find(|app| app.exe_path == exe_path) {
+            Some(app) => app.name = name,
+            None => apps.push(DetectedApp {
+                name,
+                exe_path,
+                is_priority: false,
+                pinned: false,
+                steam_app_id: None,
+                cover: None,
+                class: classifier::classify_file(&target).class,
+                windows_path: drives.to_windows(&target),
+                launch_args: Vec::new(),
+                working_dir: None,
+            }),
+        }
+    }
+
+    // Steam games are listed from their manifests and launched through Steam,
+    // so their binaries under steamapps/ are skipped by the directory scan
+    for game in SteamLauncher::list_installed_games(bottle_path) {
+        if !game.fully_installed {
+            continue;
+        }
+        let cover = game.artwork.best_cover().map(|p| p.to_string_lossy().to_string());
+        // Pre-launch checks and Wine both need a Windows binary; a game whose
+        // install dir has none (a Linux build, a tool depot) has nothing to run
+        let Some(exe) = game.executable else {
+            continue;
+        };
+        apps.push(DetectedApp {
+            name: game.name,
+            exe_path: exe.to_str().unwrap_or_default().to_string(),
+            is_priority: true,
+            pinned: false,
+            steam_app_id: Some(game.app_id),
+            cover,
+            class: ExecutableClass::Game,
+            windows_path: drives.to_windows(&exe),
+            launch_args: Vec::new(),
+            working_dir: None,
+        });
+    }
+
+    // Epic and GOG titles replace whatever helper binaries the scan found in their folders
+    let store_title = |name: String, exe: PathBuf, args: &str, working_dir: Option<PathBuf>| DetectedApp {
+        name,
+        exe_path: exe.to_str().unwrap_or_default().to_string(),
+        is_priority: true,
+        pinned: false,
+        steam_app_id: None,
+        cover: None,
+        class: ExecutableClass::Game,
+        windows_path: drives.to_windows(&exe),
+        launch_args: split_arguments(args),
+        working_dir: working_dir.map(|dir| dir.to_string_lossy().to_string()),
+    };
+    let mut store_titles: Vec<(PathBuf, DetectedApp)> = Vec::new();
+    for game in EpicLibrary::list_installed_games(bottle_path) {
+        if let Some(exe) = game.exe_path {
+            store_titles.push((game.install_dir, store_title(game.display_name, exe, &game.launch_args, None)));
+        }
+    }
+    for game in GogLibrary::list_installed_games(bottle_path) {
+        if let Some(exe) = game.exe_path {
+            store_titles.push((game.install_dir, store_title(game.name, exe, &game.arguments, game.working_dir)));
+        }
+    }
+
+    for (install_dir, app) in store_titles {
+        apps.retain(|a| a.steam_app_id.is_some() || !Path::new(&a.exe_path).starts_with(&install_dir));
+        apps.push(app);
+    }
 
     apps
 }
 
-fn scan_dir(dir: &Path, apps: &mut Vec<DetectedApp>, priority_names: &[&str]) {
+/// `.lnk` files in the shared Start Menu and in each user's Start Menu and desktop
+fn shortcut_files(drive_c: &Path) -> Vec<PathBuf> {
+    let mut roots = vec![drive_c.join("ProgramData/Microsoft/Windows/Start Menu/Programs")];
+    if let Ok(users) = fs::read_dir(drive_c.join("users")) {
+        for user in users.flatten() {
+            roots.push(user.path().join("Desktop"));
+            roots.push(user.path().join("AppData/Roaming/Microsoft/Windows/Start Menu/Programs"));
+        }
+    }
+    roots.iter()
+        .flat_map(|root| WalkDir::new(root).max_depth(4).into_iter().flatten())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("lnk")))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+fn scan_dir(dir: &Path, drives: &DriveMap, apps: &mut Vec<DetectedApp>, priority_names: &[&str]) {
     if let Ok(entries) = fs::read_dir(dir) {
         for entry in entries.flatten() {
             let path = entry.path();
             if path.is_dir() {
                 // Avoid scanning very deep or irrelevant system dirs to stay fast
                 let dir_name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
-                if dir_name == "windows" || dir_name == "users" {
+                if dir_name == "windows" || dir_name == "users" || dir_name == "steamapps" {
                     continue;
                 }
-                scan_dir(&path, apps, priority_names);
+                scan_dir(&path, drives, apps, priority_names);
             } else if let Some(ext) = path.extension() {
                 if ext.to_string_lossy().to_lowercase() == "exe" {
                     let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
@@ -53,12 +180,19 @@ fn scan_dir(dir: &Path, apps: &mut Vec<DetectedApp>, priority_names: &[&str]) {
 
                     let is_priority = priority_names.contains(&file_name.as_str());
                     let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                    let class = classifier::classify_file(&path).class;
 
                     apps.push(DetectedApp {
                         name,
                         exe_path: path.to_str().unwrap_or_default().to_string(),
                         is_priority,
                         pinned: false,
+                        steam_app_id: None,
+                        cover: None,
+                        class,
+                        windows_path: drives.to_windows(&path),
+                        launch_args: Vec::new(),
+                        working_dir: None,
                     });
                 }
             }
@@ -1,38 +1,228 @@
 use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegValue {
+    String(String),
+    DWord(u32),
+    /// REG_MULTI_SZ, without the empty string that terminates the list
+    MultiString(Vec<String>),
+    Binary(Vec<u8>),
+}
+
+impl RegValue {
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            RegValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_dword(&self) -> Option<u32> {
+        match self {
+            RegValue::DWord(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RegKey {
+    pub path: String,
+    pub values: Vec<(String, RegValue)>,
+}
 
 /// The "Pancho Shim" Registry Parser
 /// Bypasses wineserver by reading the system.reg and user.reg files directly
 pub struct NativeRegistry {
-    pub values: HashMap<String, String>,
+    /// Lowercased key path -> key. Paths are relative to the hive root,
+    /// e.g. `Software\Microsoft\Windows\CurrentVersion` in system.reg (HKLM).
+    pub keys: HashMap<String, RegKey>,
 }
 
 impl NativeRegistry {
-    pub fn new(prefix_path: &std::path::Path) -> Self {
-        let mut values = HashMap::new();
-        let user_reg = prefix_path.join("user.reg");
-        
-        if let Ok(content) = std::fs::read_to_string(user_reg) {
-            let mut current_key = String::new();
-            for line in content.lines() {
-                if line.starts_with('[') && line.ends_with(']') {
-                    current_key = line[1..line.len()-1].to_string();
-                } else if line.contains('=') {
-                    let parts: Vec<&str> = line.splitn(2, '=').collect();
-                    let key = format!("{}\\{}", current_key, parts[0].trim_matches('"'));
-                    values.insert(key, parts[1].to_string());
+    /// HKEY_CURRENT_USER (user.reg)
+    pub fn new(prefix_path: &Path) -> Self {
+        Self::from_file(&prefix_path.join("user.reg"))
+    }
+
+    /// HKEY_LOCAL_MACHINE (system.reg)
+    pub fn system(prefix_path: &Path) -> Self {
+        Self::from_file(&prefix_path.join("system.reg"))
+    }
+
+    pub fn from_file(reg_path: &Path) -> Self {
+        let content = std::fs::read(reg_path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default();
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut keys: HashMap<String, RegKey> = HashMap::new();
+        let mut current: Option<String> = None;
+        let mut pending = String::new();
+
+        for raw_line in content.lines() {
+            // hex data continues on the next line after a trailing backslash
+            if let Some(stripped) = raw_line.trim_end().strip_suffix('\\') {
+                if !pending.is_empty() || stripped.contains("=hex") {
+                    pending.push_str(stripped.trim_start());
+                    continue;
+                }
+            }
+            let line = if pending.is_empty() {
+                raw_line.to_string()
+            } else {
+                let joined = format!("{}{}", pending, raw_line.trim());
+                pending.clear();
+                joined
+            };
+
+            if line.starts_with('[') {
+                // [Software\\Valve\\Steam] 1700000000
+                let Some(end) = line.rfind(']') else { continue };
+                let path = unescape(&line[1..end]);
+                let lower = path.to_ascii_lowercase();
+                keys.entry(lower.clone()).or_insert_with(|| RegKey { path, values: Vec::new() });
+                current = Some(lower);
+            } else if let Some(key) = current.as_ref() {
+                if let Some((name, value)) = parse_value_line(&line) {
+                    if let Some(reg_key) = keys.get_mut(key) {
+                        reg_key.values.push((name, value));
+                    }
                 }
             }
         }
-        NativeRegistry { values }
+
+        NativeRegistry { keys }
     }
 
-    pub fn get(&self, key: &str) -> Option<&String> {
-        self.values.get(key)
+    pub fn key(&self, key_path: &str) -> Option<&RegKey> {
+        self.keys.get(&key_path.to_ascii_lowercase())
+    }
+
+    /// Reads a value, `""` or `"@"` being the key's default value
+    pub fn get(&self, key_path: &str, value_name: &str) -> Option<&RegValue> {
+        let name = if value_name == "@" { "" } else { value_name };
+        self.key(key_path)?
+            .values
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_string(&self, key_path: &str, value_name: &str) -> Option<String> {
+        self.get(key_path, value_name)?.as_string().map(|s| s.to_string())
+    }
+
+    /// Names of the direct children of a key
+    pub fn subkeys(&self, parent: &str) -> Vec<String> {
+        let prefix = format!("{}\\", parent.to_ascii_lowercase().trim_end_matches('\\'));
+        let mut children: Vec<String> = self.keys.iter()
+            .filter(|(lower, _)| lower.starts_with(&prefix) && !lower[prefix.len()..].contains('\\'))
+            .map(|(_, key)| key.path[prefix.len()..].to_string())
+            .collect();
+        children.sort();
+        children
+    }
+}
+
+fn parse_value_line(line: &str) -> Option<(String, RegValue)> {
+    let (name, data) = if let Some(rest) = line.strip_prefix('@') {
+        (String::new(), rest.strip_prefix('=')?)
+    } else if line.starts_with('"') {
+        let end = closing_quote(line, 1)?;
+        (unescape(&line[1..end]), line[end + 1..].strip_prefix('=')?)
+    } else {
+        return None;
+    };
+
+    let value = if data.starts_with('"') {
+        let end = closing_quote(data, 1)?;
+        RegValue::String(unescape(&data[1..end]))
+    } else if let Some(rest) = data.strip_prefix("str(") {
+        // str(2) is REG_EXPAND_SZ, str(7) REG_MULTI_SZ
+        let quote = rest.find('"')?;
+        let end = closing_quote(rest, quote + 1)?;
+        let text = unescape(&rest[quote + 1..end]);
+        if rest.starts_with("7)") { multi_string(&text) } else { RegValue::String(text) }
+    } else if let Some(hex) = data.strip_prefix("dword:") {
+        RegValue::DWord(u32::from_str_radix(hex.trim(), 16).ok()?)
+    } else if data.starts_with("hex") {
+        let (kind, list) = data.split_once(':')?;
+        let bytes: Vec<u8> = list
+            .split(',')
+            .filter_map(|b| u8::from_str_radix(b.trim(), 16).ok())
+            .collect();
+        // regedit exports REG_EXPAND_SZ and REG_MULTI_SZ as hex(2)/hex(7), holding UTF-16LE text
+        match kind {
+            "hex(2)" => RegValue::String(utf16_string(&bytes).trim_end_matches('\0').to_string()),
+            "hex(7)" => multi_string(&utf16_string(&bytes)),
+            _ => RegValue::Binary(bytes),
+        }
+    } else {
+        return None;
+    };
+
+    Some((name, value))
+}
+
+fn utf16_string(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn multi_string(text: &str) -> RegValue {
+    RegValue::MultiString(text.split('\0').filter(|s| !s.is_empty()).map(str::to_string).collect())
+}
+
+fn closing_quote(s: &str, start: usize) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('0') => out.push('\0'),
+            Some('x') => {
+                // \xABCD: UTF-16 code unit written by Wine for non-ASCII characters
+                let mut hex = String::new();
+                while hex.len() < 4 && chars.peek().is_some_and(|h| h.is_ascii_hexdigit()) {
+                    hex.push(chars.next().unwrap());
+                }
+                if let Some(ch) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    out.push(ch);
+                }
+            }
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
     }
+    out
 }
 
 /// The System Call Bridge
 /// This will eventually be compiled into a .dylib and injected
+#[allow(dead_code)]
 pub fn translate_sys_call(call_id: u32) -> &'static str {
     match call_id {
         0x1 => "NtCreateProcess",
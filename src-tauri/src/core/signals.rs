@@ -1,35 +1,74 @@
-use libc::{sigaction, siginfo_t, c_void, SIGSEGV, SIGILL, SIGBUS, SA_SIGINFO};
+use libc::{sigaction, siginfo_t, c_void, SIGSEGV, SIGILL, SIGBUS, SIGFPE, SA_SIGINFO, SA_ONSTACK};
 use std::ptr;
+use crate::core::{executor, vmm};
+
+/// Big enough for the handler to format its report after a guest stack overflow
+const ALTERNATE_STACK_SIZE: usize = 256 * 1024;
+
+/// Installs the crash reporter for the HLE helper process.
+/// Never call this in the app itself: the handlers are process-wide and end the process.
+pub unsafe fn install_crash_reporter() {
+    // Guest stack overflows leave no stack to run the handler on
+    let stack = libc::mmap(
+        ptr::null_mut(),
+        ALTERNATE_STACK_SIZE,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+        -1,
+        0
+    );
+    if stack != libc::MAP_FAILED {
+        let alternate = libc::stack_t { ss_sp: stack, ss_flags: 0, ss_size: ALTERNATE_STACK_SIZE };
+        libc::sigaltstack(&alternate, ptr::null_mut());
+    }
 
-pub unsafe fn setup_signal_handler() {
     let mut sa: sigaction = std::mem::zeroed();
     sa.sa_sigaction = pancho_signal_handler as *const () as usize;
-    sa.sa_flags = SA_SIGINFO;
+    // Guard page hits return and keep the handler; the signal stays blocked while it runs,
+    // so a fault inside the handler itself kills the process
+    sa.sa_flags = SA_SIGINFO | SA_ONSTACK;
 
     sigaction(SIGSEGV, &sa, ptr::null_mut());
     sigaction(SIGILL, &sa, ptr::null_mut());
     sigaction(SIGBUS, &sa, ptr::null_mut());
-
-    println!("Pancho-Signals: Native Exception Translator active.");
+    sigaction(SIGFPE, &sa, ptr::null_mut());
 }
 
 /// The Signal Translator
-/// Catches native macOS crashes and translates them to Windows Exceptions (SEH)
-extern "C" fn pancho_signal_handler(sig: i32, _info: *mut siginfo_t, _context: *mut c_void) {
-    match sig {
-        SIGSEGV => {
-            println!("Pancho-Signals: Intercepted SIGSEGV (Access Violation). Translating to 0xC0000005...");
-            // Here we would modify the _context (ucontext_t) to point to the game's SEH handler
-        },
-        SIGILL => {
-            println!("Pancho-Signals: Intercepted SIGILL (Illegal Instruction). Likely an AVX call.");
-            // This is where we would implement the AVX -> NEON fallback or emulation
-        },
-        _ => {
-            println!("Pancho-Signals: Intercepted signal {}. Attempting recovery...", sig);
-        }
+/// A first touch of a guard page lifts the guard and resumes the faulting instruction.
+/// Anything else is reported to the app over the helper's report pipe, then re-raised so
+/// the app sees the helper die from the original signal.
+/// The report is built without allocating (see `executor::report_crash`).
+extern "C" fn pancho_signal_handler(sig: i32, info: *mut siginfo_t, context: *mut c_void) {
+    let (fault_address, instruction_pointer) = unsafe { fault_details(info, context) };
+    // If the faulting thread holds the address space lock, it wasn't a guest guard page
+    if sig == SIGSEGV && vmm::try_process_memory().is_some_and(|mut memory| memory.take_guard_page(fault_address)) {
+        return;
     }
+    executor::report_crash(sig, fault_address, instruction_pointer);
+
+    unsafe {
+        libc::signal(sig, libc::SIG_DFL);
+        let mut unblock: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut unblock);
+        libc::sigaddset(&mut unblock, sig);
+        libc::pthread_sigmask(libc::SIG_UNBLOCK, &unblock, ptr::null_mut());
+        libc::raise(sig);
+        libc::_exit(128 + sig);
+    }
+}
+
+/// The accessed address and, where the context layout is known, the faulting instruction
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn fault_details(info: *mut siginfo_t, context: *mut c_void) -> (u64, Option<u64>) {
+    let fault_address = if info.is_null() { 0 } else { (*info).si_addr() as u64 };
+    let instruction_pointer = (!context.is_null())
+        .then(|| (*(context as *mut libc::ucontext_t)).uc_mcontext.gregs[libc::REG_RIP as usize] as u64);
+    (fault_address, instruction_pointer)
+}
 
-    // For now, we exit gracefully instead of hard crashing the whole Pancho app
-    std::process::exit(sig);
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+unsafe fn fault_details(info: *mut siginfo_t, _context: *mut c_void) -> (u64, Option<u64>) {
+    let fault_address = if info.is_null() { 0 } else { (*info).si_addr as u64 };
+    (fault_address, None)
 }
@@ -0,0 +1,570 @@
+use std::cell::Cell;
+use std::mem::{offset_of, size_of};
+use crate::core::kernel32;
+use crate::core::vmm::{self, RegionType, PAGE_READWRITE, PAGE_SIZE};
+
+/// sizeof(TEB) and sizeof(PEB) on 64-bit Windows 10
+pub const TEB_SIZE: usize = 0x1838;
+pub const PEB_SIZE: usize = 0x7C8;
+pub const TLS_MINIMUM_AVAILABLE: usize = 64;
+
+/// Reserved for the PEB, loader data, process parameters and their strings; committed as used
+const PROCESS_ARENA_SIZE: usize = 0x100000;
+
+const RTL_USER_PROC_PARAMS_NORMALIZED: u32 = 0x1;
+const LDRP_IMAGE_DLL: u32 = 0x0000_0004;
+const LDRP_ENTRY_PROCESSED: u32 = 0x0000_4000;
+const LDRP_PROCESS_ATTACH_CALLED: u32 = 0x0008_0000;
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const ARCH_SET_GS: libc::c_int = 0x1001;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ListEntry {
+    pub flink: usize,
+    pub blink: usize,
+}
+
+/// UNICODE_STRING; lengths are in bytes and exclude the terminator
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UnicodeString {
+    pub length: u16,
+    pub maximum_length: u16,
+    pub buffer: usize,
+}
+
+#[repr(C)]
+pub struct NtTib {
+    /// Head of the SEH chain; usize::MAX ends it
+    pub exception_list: usize,
+    /// Top of the stack (highest address)
+    pub stack_base: usize,
+    /// Lowest committed stack address
+    pub stack_limit: usize,
+    pub sub_system_tib: usize,
+    pub fiber_data: usize,
+    pub arbitrary_user_pointer: usize,
+    /// Linear address of the TEB, read through `gs:[0x30]`
+    pub self_pointer: usize,
+}
+
+#[repr(C)]
+pub struct ClientId {
+    pub unique_process: usize,
+    pub unique_thread: usize,
+}
+
+/// The parts of the x64 TEB Windows code reads directly; the rest stays zeroed
+#[repr(C)]
+pub struct Teb {
+    pub nt_tib: NtTib,
+    pub environment_pointer: usize,
+    pub client_id: ClientId,
+    pub active_rpc_handle: usize,
+    pub thread_local_storage_pointer: usize,
+    pub process_environment_block: usize,
+    pub last_error_value: u32,
+    _reserved1: [u8; 0x1480 - 0x6C],
+    pub tls_slots: [usize; TLS_MINIMUM_AVAILABLE],
+    pub tls_links: ListEntry,
+    _reserved2: [u8; 0x1780 - 0x1690],
+    pub tls_expansion_slots: usize,
+    _reserved3: [u8; TEB_SIZE - 0x1788],
+}
+
+#[repr(C)]
+pub struct RtlBitmap {
+    pub size_of_bitmap: u32,
+    pub buffer: usize,
+}
+
+#[repr(C)]
+pub struct Peb {
+    pub inherited_address_space: u8,
+    pub read_image_file_exec_options: u8,
+    pub being_debugged: u8,
+    pub bit_field: u8,
+    _padding0: [u8; 4],
+    pub mutant: usize,
+    pub image_base_address: usize,
+    pub ldr: usize,
+    pub process_parameters: usize,
+    pub sub_system_data: usize,
+    pub process_heap: usize,
+    _reserved1: [u8; 0x78 - 0x38],
+    pub tls_bitmap: usize,
+    pub tls_bitmap_bits: [u32; 2],
+    _reserved2: [u8; 0xB8 - 0x88],
+    pub number_of_processors: u32,
+    pub nt_global_flag: u32,
+    _reserved3: [u8; 0x118 - 0xC0],
+    pub os_major_version: u32,
+    pub os_minor_version: u32,
+    pub os_build_number: u16,
+    pub os_csd_version: u16,
+    pub os_platform_id: u32,
+    pub image_subsystem: u32,
+    pub image_subsystem_major_version: u32,
+    pub image_subsystem_minor_version: u32,
+    _reserved4: [u8; 0x2C0 - 0x134],
+    pub session_id: u32,
+    _reserved5: [u8; PEB_SIZE - 0x2C4],
+}
+
+/// PEB_LDR_DATA: heads of the three module lists
+#[repr(C)]
+pub struct PebLdrData {
+    pub length: u32,
+    pub initialized: u8,
+    pub ss_handle: usize,
+    pub in_load_order_module_list: ListEntry,
+    pub in_memory_order_module_list: ListEntry,
+    pub in_initialization_order_module_list: ListEntry,
+    pub entry_in_progress: usize,
+    pub shutdown_in_progress: u8,
+    pub shutdown_thread_id: usize,
+}
+
+/// LDR_DATA_TABLE_ENTRY, up to the fields tools walking the lists use
+#[repr(C)]
+pub struct LdrDataTableEntry {
+    pub in_load_order_links: ListEntry,
+    pub in_memory_order_links: ListEntry,
+    pub in_initialization_order_links: ListEntry,
+    pub dll_base: usize,
+    pub entry_point: usize,
+    pub size_of_image: u32,
+    pub full_dll_name: UnicodeString,
+    pub base_dll_name: UnicodeString,
+    pub flags: u32,
+    pub obsolete_load_count: u16,
+    pub tls_index: u16,
+    pub hash_links: ListEntry,
+    pub time_date_stamp: u32,
+    _reserved: [u8; 0x120 - 0x84],
+}
+
+#[repr(C)]
+pub struct CurDir {
+    pub dos_path: UnicodeString,
+    pub handle: usize,
+}
+
+/// RTL_USER_PROCESS_PARAMETERS, normalized (buffers are absolute pointers)
+#[repr(C)]
+pub struct RtlUserProcessParameters {
+    pub maximum_length: u32,
+    pub length: u32,
+    pub flags: u32,
+    pub debug_flags: u32,
+    pub console_handle: usize,
+    pub console_flags: u32,
+    pub standard_input: usize,
+    pub standard_output: usize,
+    pub standard_error: usize,
+    pub current_directory: CurDir,
+    pub dll_path: UnicodeString,
+    pub image_path_name: UnicodeString,
+    pub command_line: UnicodeString,
+    pub environment: usize,
+    _window_placement: [u32; 8],
+    pub show_window_flags: u32,
+    pub window_title: UnicodeString,
+    pub desktop_info: UnicodeString,
+    pub shell_info: UnicodeString,
+    pub runtime_data: UnicodeString,
+    _current_directories: [u8; 0x300],
+    pub environment_size: usize,
+    pub environment_version: usize,
+}
+
+// Windows code hard-codes these offsets, so the layouts must not drift
+const _: () = {
+    assert!(offset_of!(Teb, nt_tib.self_pointer) == 0x30);
+    assert!(offset_of!(Teb, thread_local_storage_pointer) == 0x58);
+    assert!(offset_of!(Teb, process_environment_block) == 0x60);
+    assert!(offset_of!(Teb, last_error_value) == 0x68);
+    assert!(offset_of!(Teb, tls_slots) == 0x1480);
+    assert!(offset_of!(Teb, tls_expansion_slots) == 0x1780);
+    assert!(size_of::<Teb>() == TEB_SIZE);
+    assert!(offset_of!(Peb, ldr) == 0x18);
+    assert!(offset_of!(Peb, process_parameters) == 0x20);
+    assert!(offset_of!(Peb, tls_bitmap_bits) == 0x80);
+    assert!(offset_of!(Peb, number_of_processors) == 0xB8);
+    assert!(offset_of!(Peb, os_major_version) == 0x118);
+    assert!(offset_of!(Peb, session_id) == 0x2C0);
+    assert!(size_of::<Peb>() == PEB_SIZE);
+    assert!(size_of::<PebLdrData>() == 0x58);
+    assert!(offset_of!(LdrDataTableEntry, dll_base) == 0x30);
+    assert!(offset_of!(LdrDataTableEntry, full_dll_name) == 0x48);
+    assert!(offset_of!(LdrDataTableEntry, base_dll_name) == 0x58);
+    assert!(offset_of!(LdrDataTableEntry, time_date_stamp) == 0x80);
+    assert!(offset_of!(RtlUserProcessParameters, current_directory) == 0x38);
+    assert!(offset_of!(RtlUserProcessParameters, image_path_name) == 0x60);
+    assert!(offset_of!(RtlUserProcessParameters, command_line) == 0x70);
+    assert!(offset_of!(RtlUserProcessParameters, window_title) == 0xB0);
+    assert!(offset_of!(RtlUserProcessParameters, environment_size) == 0x3F0);
+};
+
+thread_local! {
+    /// TEB installed as this thread's GS base, 0 if none
+    static CURRENT_TEB: Cell<usize> = const { Cell::new(0) };
+    /// Last error for threads that run HLE code without a TEB
+    static LAST_ERROR: Cell<u32> = const { Cell::new(0) };
+}
+
+/// What the new process sees in RTL_USER_PROCESS_PARAMETERS. Paths are Windows paths.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessParameters {
+    pub image_path: String,
+    pub command_line: String,
+    pub current_directory: String,
+    pub environment: Vec<(String, String)>,
+}
+
+/// Bump allocator over a reservation in the process address space, committing pages as it grows
+struct Arena {
+    base: u64,
+    used: usize,
+    committed: usize,
+}
+
+impl Arena {
+    fn new(label: &str) -> Result<Self, String> {
+        let base = vmm::process_memory()
+            .reserve(0, PROCESS_ARENA_SIZE, PAGE_READWRITE, RegionType::Private, label)
+            .map_err(|e| format!("Failed to reserve the {}: {}", label, e))?;
+        Ok(Arena { base, used: 0, committed: 0 })
+    }
+
+    /// Zeroed memory for `size` bytes
+    fn alloc(&mut self, size: usize, align: usize) -> Result<usize, String> {
+        let start = self.used.div_ceil(align) * align;
+        let end = start + size;
+        if end > PROCESS_ARENA_SIZE {
+            return Err("Process environment is too large".to_string());
+        }
+        if end > self.committed {
+            let grow = (end - self.committed).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+            vmm::process_memory()
+                .commit(self.base + self.committed as u64, grow, PAGE_READWRITE)
+                .map_err(|e| e.to_string())?;
+            self.committed += grow;
+        }
+        self.used = end;
+        Ok(self.base as usize + start)
+    }
+
+    fn alloc_struct<T>(&mut self) -> Result<&'static mut T, String> {
+        let address = self.alloc(size_of::<T>(), std::mem::align_of::<T>())?;
+        Ok(unsafe { &mut *(address as *mut T) })
+    }
+
+    /// NUL-terminated UTF-16 copy of `units`
+    fn wide(&mut self, units: &[u16]) -> Result<usize, String> {
+        let address = self.alloc((units.len() + 1) * 2, 2)?;
+        unsafe { std::ptr::copy_nonoverlapping(units.as_ptr(), address as *mut u16, units.len()) };
+        Ok(address)
+    }
+
+    fn unicode_string(&mut self, text: &str) -> Result<UnicodeString, String> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        let length = units.len() * 2;
+        if length + 2 > u16::MAX as usize {
+            return Err(format!("String is too long for a UNICODE_STRING: {}", text));
+        }
+        Ok(UnicodeString {
+            length: length as u16,
+            maximum_length: (length + 2) as u16,
+            buffer: self.wide(&units)?,
+        })
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        let _ = vmm::process_memory().release(self.base);
+    }
+}
+
+/// The process-wide structures behind PEB: loader lists, parameters and TLS bitmap.
+/// Lives as long as any thread may still read it through its TEB.
+pub struct ProcessEnvironment {
+    pub peb: usize,
+    arena: Arena,
+    /// LDR entry of each module, by base
+    entries: Vec<(usize, usize)>,
+}
+
+impl ProcessEnvironment {
+    pub fn new(parameters: &ProcessParameters) -> Result<Self, String> {
+        let mut arena = Arena::new("PEB")?;
+        let peb = arena.alloc_struct::<Peb>()?;
+
+        let ldr = arena.alloc_struct::<PebLdrData>()?;
+        ldr.length = size_of::<PebLdrData>() as u32;
+        ldr.initialized = 1;
+        for head in [&mut ldr.in_load_order_module_list, &mut ldr.in_memory_order_module_list, &mut ldr.in_initialization_order_module_list] {
+            let address = head as *mut ListEntry as usize;
+            *head = ListEntry { flink: address, blink: address };
+        }
+
+        let params = arena.alloc_struct::<RtlUserProcessParameters>()?;
+        params.maximum_length = size_of::<RtlUserProcessParameters>() as u32;
+        params.length = params.maximum_length;
+        params.flags = RTL_USER_PROC_PARAMS_NORMALIZED;
+        // The DOS path of the current directory always ends in a backslash
+        let mut current_directory = parameters.current_directory.clone();
+        if !current_directory.ends_with('\\') {
+            current_directory.push('\\');
+        }
+        params.current_directory.dos_path = arena.unicode_string(&current_directory)?;
+        params.image_path_name = arena.unicode_string(&parameters.image_path)?;
+        params.command_line = arena.unicode_string(&parameters.command_line)?;
+        params.window_title = arena.unicode_string(&parameters.image_path)?;
+        params.desktop_info = arena.unicode_string("WinSta0\\Default")?;
+
+        // KEY=VALUE\0 ... \0, sorted case-insensitively like Windows keeps it
+        let mut environment = parameters.environment.clone();
+        environment.sort_by_key(|(key, _)| key.to_uppercase());
+        let block: Vec<u16> = environment.iter()
+            .flat_map(|(key, value)| format!("{}={}\0", key, value).encode_utf16().collect::<Vec<_>>())
+            .collect();
+        params.environment = arena.wide(&block)?;
+        params.environment_size = (block.len() + 1) * 2;
+
+        let tls_bitmap = arena.alloc_struct::<RtlBitmap>()?;
+        tls_bitmap.size_of_bitmap = TLS_MINIMUM_AVAILABLE as u32;
+        tls_bitmap.buffer = peb.tls_bitmap_bits.as_ptr() as usize;
+
+        peb.ldr = ldr as *mut PebLdrData as usize;
+        peb.process_parameters = params as *mut RtlUserProcessParameters as usize;
+        peb.process_heap = kernel32::pancho_hle_get_process_heap();
+        peb.tls_bitmap = tls_bitmap as *mut RtlBitmap as usize;
+        peb.number_of_processors = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as u32;
+        // Windows 10 22H2
+        peb.os_major_version = 10;
+        peb.os_minor_version = 0;
+        peb.os_build_number = 19045;
+        peb.os_platform_id = 2;
+        peb.session_id = 1;
+
+        Ok(ProcessEnvironment { peb: peb as *mut Peb as usize, arena, entries: Vec::new() })
+    }
+
+    pub fn peb(&self) -> &Peb {
+        unsafe { &*(self.peb as *const Peb) }
+    }
+
+    pub fn parameters(&self) -> &RtlUserProcessParameters {
+        unsafe { &*(self.peb().process_parameters as *const RtlUserProcessParameters) }
+    }
+
+    /// Adds a mapped module to the load and memory order lists. The first non-DLL becomes
+    /// the process image.
+    pub fn add_module(&mut self, base: usize, size: usize, entry_point: Option<usize>, is_dll: bool, full_name: &str) -> Result<(), String> {
+        if self.entries.iter().any(|(b, _)| *b == base) {
+            return Ok(());
+        }
+        let base_name = full_name.rsplit(['\\', '/']).next().unwrap_or(full_name);
+
+        let entry = self.arena.alloc_struct::<LdrDataTableEntry>()?;
+        entry.dll_base = base;
+        entry.entry_point = entry_point.unwrap_or(0);
+        entry.size_of_image = size as u32;
+        entry.full_dll_name = self.arena.unicode_string(full_name)?;
+        entry.base_dll_name = self.arena.unicode_string(base_name)?;
+        entry.flags = if is_dll { LDRP_IMAGE_DLL } else { 0 };
+        entry.obsolete_load_count = if is_dll { 1 } else { u16::MAX };
+        let hash_links = &mut entry.hash_links as *mut ListEntry as usize;
+        entry.hash_links = ListEntry { flink: hash_links, blink: hash_links };
+
+        let peb = self.peb as *mut Peb;
+        unsafe {
+            let ldr = (*peb).ldr as *mut PebLdrData;
+            insert_tail(&mut (*ldr).in_load_order_module_list, &mut entry.in_load_order_links);
+            insert_tail(&mut (*ldr).in_memory_order_module_list, &mut entry.in_memory_order_links);
+            if !is_dll && (*peb).image_base_address == 0 {
+                (*peb).image_base_address = base;
+            }
+        }
+        self.entries.push((base, entry as *mut LdrDataTableEntry as usize));
+        Ok(())
+    }
+
+    /// Called before a DLL's DllMain(DLL_PROCESS_ATTACH); appends it to the initialization order list
+    pub fn mark_initialized(&mut self, base: usize) {
+        let Some((_, address)) = self.entries.iter().find(|(b, _)| *b == base) else { return };
+        let entry = unsafe { &mut *(*address as *mut LdrDataTableEntry) };
+        if entry.flags & LDRP_IMAGE_DLL == 0 || entry.flags & LDRP_ENTRY_PROCESSED != 0 {
+            return;
+        }
+        entry.flags |= LDRP_ENTRY_PROCESSED | LDRP_PROCESS_ATTACH_CALLED;
+        let ldr = self.peb().ldr as *mut PebLdrData;
+        unsafe { insert_tail(&mut (*ldr).in_initialization_order_module_list, &mut entry.in_initialization_order_links) };
+    }
+
+    /// Unlinks a module whose load failed from every loader list
+    pub fn remove_module(&mut self, base: usize) {
+        let Some(position) = self.entries.iter().position(|(b, _)| *b == base) else { return };
+        let (_, address) = self.entries.remove(position);
+        let entry = unsafe { &mut *(address as *mut LdrDataTableEntry) };
+        unsafe {
+            remove_entry(&mut entry.in_load_order_links);
+            remove_entry(&mut entry.in_memory_order_links);
+            if entry.flags & LDRP_ENTRY_PROCESSED != 0 {
+                remove_entry(&mut entry.in_initialization_order_links);
+            }
+            let peb = self.peb as *mut Peb;
+            if (*peb).image_base_address == base {
+                (*peb).image_base_address = 0;
+            }
+        }
+    }
+}
+
+/// Loader list views for tests
+#[cfg(test)]
+impl ProcessEnvironment {
+    pub fn ldr(&self) -> &PebLdrData {
+        unsafe { &*(self.peb().ldr as *const PebLdrData) }
+    }
+
+    /// Base names in InLoadOrderModuleList, the way code walking PEB.Ldr sees them
+    pub fn load_order(&self) -> Vec<String> {
+        self.walk(&self.ldr().in_load_order_module_list, offset_of!(LdrDataTableEntry, in_load_order_links))
+    }
+
+    pub fn memory_order(&self) -> Vec<String> {
+        self.walk(&self.ldr().in_memory_order_module_list, offset_of!(LdrDataTableEntry, in_memory_order_links))
+    }
+
+    pub fn initialization_order(&self) -> Vec<String> {
+        self.walk(&self.ldr().in_initialization_order_module_list, offset_of!(LdrDataTableEntry, in_initialization_order_links))
+    }
+
+    fn walk(&self, head: &ListEntry, links_offset: usize) -> Vec<String> {
+        let head_address = head as *const ListEntry as usize;
+        let mut names = Vec::new();
+        let mut link = head.flink;
+        while link != head_address {
+            let entry = unsafe { &*((link - links_offset) as *const LdrDataTableEntry) };
+            names.push(read_unicode_string(&entry.base_dll_name));
+            link = unsafe { (*(link as *const ListEntry)).flink };
+        }
+        names
+    }
+}
+
+/// One thread's TEB, pointing at the shared PEB
+pub struct ThreadEnvironment {
+    pub teb: usize,
+}
+
+impl ThreadEnvironment {
+    /// `stack_base` is the top of the thread's stack and `stack_limit` its lowest address
+    pub fn new(process: &ProcessEnvironment, stack_base: usize, stack_limit: usize) -> Result<Self, String> {
+        let mut memory = vmm::process_memory();
+        let address = memory.reserve(0, TEB_SIZE, PAGE_READWRITE, RegionType::Private, "TEB")
+            .and_then(|base| memory.commit(base, TEB_SIZE, PAGE_READWRITE))
+            .map_err(|e| format!("Failed to allocate a TEB: {}", e))?;
+        drop(memory);
+
+        let teb = unsafe { &mut *(address as *mut Teb) };
+        teb.nt_tib.exception_list = usize::MAX;
+        teb.nt_tib.stack_base = stack_base;
+        teb.nt_tib.stack_limit = stack_limit;
+        teb.nt_tib.self_pointer = address as usize;
+        teb.client_id.unique_process = kernel32::pancho_hle_get_current_process_id() as usize;
+        teb.client_id.unique_thread = kernel32::pancho_hle_get_current_thread_id() as usize;
+        teb.process_environment_block = process.peb;
+        teb.tls_links = ListEntry { flink: address as usize + offset_of!(Teb, tls_links), blink: address as usize + offset_of!(Teb, tls_links) };
+
+        Ok(ThreadEnvironment { teb: address as usize })
+    }
+
+    #[cfg(test)]
+    pub fn teb(&self) -> &Teb {
+        unsafe { &*(self.teb as *const Teb) }
+    }
+
+    /// Points the calling thread's GS base at this TEB so `gs:[0x30]` and friends resolve.
+    /// Rust keeps its own thread locals in FS on x86_64 Linux, so GS is free for Windows code.
+    pub fn install(&self) -> Result<(), String> {
+        set_gs_base(self.teb)?;
+        // Carry over an error HLE code set before the TEB existed
+        unsafe { (*(self.teb as *mut Teb)).last_error_value = LAST_ERROR.with(|e| e.get()) };
+        CURRENT_TEB.with(|current| current.set(self.teb));
+        Ok(())
+    }
+}
+
+impl Drop for ThreadEnvironment {
+    fn drop(&mut self) {
+        if CURRENT_TEB.with(|current| current.get()) == self.teb {
+            let _ = set_gs_base(0);
+            CURRENT_TEB.with(|current| current.set(0));
+        }
+        let _ = vmm::process_memory().release(self.teb as u64);
+    }
+}
+
+/// The calling thread's TEB, if one is installed
+pub fn current_teb() -> Option<&'static mut Teb> {
+    let address = CURRENT_TEB.with(|current| current.get());
+    (address != 0).then(|| unsafe { &mut *(address as *mut Teb) })
+}
+
+/// GetLastError: TEB.LastErrorValue, where Windows code also reads it directly
+pub fn last_error() -> u32 {
+    match current_teb() {
+        Some(teb) => teb.last_error_value,
+        None => LAST_ERROR.with(|e| e.get()),
+    }
+}
+
+pub fn set_last_error(code: u32) {
+    match current_teb() {
+        Some(teb) => teb.last_error_value = code,
+        None => LAST_ERROR.with(|e| e.set(code)),
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn set_gs_base(address: usize) -> Result<(), String> {
+    let result = unsafe { libc::syscall(libc::SYS_arch_prctl, ARCH_SET_GS, address) };
+    if result != 0 {
+        return Err(format!("arch_prctl(ARCH_SET_GS) failed: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn set_gs_base(_address: usize) -> Result<(), String> {
+    Err("Setting the GS base is only supported on Linux x86_64".to_string())
+}
+
+unsafe fn insert_tail(head: *mut ListEntry, entry: *mut ListEntry) {
+    let tail = (*head).blink as *mut ListEntry;
+    (*entry).flink = head as usize;
+    (*entry).blink = tail as usize;
+    (*tail).flink = entry as usize;
+    (*head).blink = entry as usize;
+}
+
+unsafe fn remove_entry(entry: *mut ListEntry) {
+    let flink = (*entry).flink as *mut ListEntry;
+    let blink = (*entry).blink as *mut ListEntry;
+    (*blink).flink = flink as usize;
+    (*flink).blink = blink as usize;
+}
+
+pub fn read_unicode_string(string: &UnicodeString) -> String {
+    if string.buffer == 0 {
+        return String::new();
+    }
+    let units = unsafe { std::slice::from_raw_parts(string.buffer as *const u16, string.length as usize / 2) };
+    String::from_utf16_lossy(units)
+}
@@ -0,0 +1,388 @@
+use std::arch::naked_asm;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::core::kernel32::pancho_hle_get_current_thread_id;
+use crate::core::linker::CodePage;
+
+/// Completed calls kept in the ring before the oldest are overwritten
+pub const TRACE_CAPACITY: usize = 8192;
+/// Integer register arguments recorded per call (rcx, rdx, r8, r9)
+pub const TRACED_ARGS: usize = 4;
+
+const THUNK_SIZE: usize = 32;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceEvent {
+    /// Position in the trace; consecutive unless the ring overflowed between reads
+    pub seq: u64,
+    /// "kernel32.dll!GetTickCount"
+    pub function: String,
+    pub args: [u64; TRACED_ARGS],
+    pub ret: u64,
+    /// Nanoseconds since the first traced call
+    pub start_ns: u64,
+    pub duration_ns: u64,
+    pub thread_id: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TraceBatch {
+    pub events: Vec<TraceEvent>,
+    /// Pass back as `since` to continue after this batch
+    pub next: u64,
+    /// Calls overwritten before they could be read
+    pub dropped: u64,
+}
+
+/// One ring entry, guarded by a per-slot sequence number so writers never block
+#[derive(Default)]
+struct Slot {
+    /// 2 * index + 1 while being written, 2 * index + 2 once complete
+    seq: AtomicU64,
+    function: AtomicU64,
+    args: [AtomicU64; TRACED_ARGS],
+    ret: AtomicU64,
+    start: AtomicU64,
+    duration: AtomicU64,
+    thread: AtomicU64,
+}
+
+pub(crate) struct Record {
+    pub function: u32,
+    pub args: [u64; TRACED_ARGS],
+    pub ret: u64,
+    pub start: u64,
+    pub duration: u64,
+    pub thread: u32,
+}
+
+/// Lock-free multi-producer ring of completed calls
+pub struct TraceRing {
+    head: AtomicU64,
+    slots: Box<[Slot]>,
+}
+
+impl TraceRing {
+    pub fn new(capacity: usize) -> Self {
+        TraceRing {
+            head: AtomicU64::new(0),
+            slots: (0..capacity).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    pub(crate) fn push(&self, record: &Record) {
+        let index = self.head.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[index as usize % self.slots.len()];
+
+        slot.seq.store(2 * index + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.function.store(record.function as u64, Ordering::Relaxed);
+        for (dest, value) in slot.args.iter().zip(record.args) {
+            dest.store(value, Ordering::Relaxed);
+        }
+        slot.ret.store(record.ret, Ordering::Relaxed);
+        slot.start.store(record.start, Ordering::Relaxed);
+        slot.duration.store(record.duration, Ordering::Relaxed);
+        slot.thread.store(record.thread as u64, Ordering::Relaxed);
+        slot.seq.store(2 * index + 2, Ordering::Release);
+    }
+
+    /// Completed calls from `since` on. Slots still being written end the batch early;
+    /// they are picked up by the next read.
+    pub fn read_since(&self, since: u64) -> TraceBatch {
+        let functions = function_names().lock().unwrap_or_else(|e| e.into_inner());
+        self.read(since, &functions.names)
+    }
+
+    /// Calls with an id missing from `names` are named "#<id>"
+    fn read(&self, since: u64, names: &[String]) -> TraceBatch {
+        let mut events = Vec::new();
+        let (next, dropped) = self.scan(since, |seq, record| {
+            events.push(TraceEvent {
+                seq,
+                function: names.get(record.function as usize).cloned().unwrap_or_else(|| format!("#{}", record.function)),
+                args: record.args,
+                ret: record.ret,
+                start_ns: record.start,
+                duration_ns: record.duration,
+                thread_id: record.thread,
+            });
+            true
+        });
+        TraceBatch { events, next, dropped }
+    }
+
+    /// Hands the completed calls from `since` on to `f` until it returns false or a slot is
+    /// still being written. Returns where to continue and how many calls were overwritten
+    /// before they could be read. Doesn't allocate, so the crash handler can use it.
+    pub(crate) fn scan(&self, since: u64, mut f: impl FnMut(u64, &Record) -> bool) -> (u64, u64) {
+        let head = self.head.load(Ordering::Acquire);
+        let oldest = head.saturating_sub(self.slots.len() as u64);
+        let start = since.max(oldest);
+
+        let mut next = start;
+        for index in start..head {
+            let slot = &self.slots[index as usize % self.slots.len()];
+            let before = slot.seq.load(Ordering::Acquire);
+            let record = Record {
+                function: slot.function.load(Ordering::Relaxed) as u32,
+                args: std::array::from_fn(|i| slot.args[i].load(Ordering::Relaxed)),
+                ret: slot.ret.load(Ordering::Relaxed),
+                start: slot.start.load(Ordering::Relaxed),
+                duration: slot.duration.load(Ordering::Relaxed),
+                thread: slot.thread.load(Ordering::Relaxed) as u32,
+            };
+            fence(Ordering::Acquire);
+            if before != 2 * index + 2 || slot.seq.load(Ordering::Relaxed) != before || !f(index, &record) {
+                break;
+            }
+            next = index + 1;
+        }
+        (next, start - since.min(start))
+    }
+
+    /// Position of the next call to be recorded
+    pub fn head(&self) -> u64 {
+        self.head.load(Ordering::Acquire)
+    }
+
+    /// The most recent `count` completed calls
+    pub fn last(&self, count: usize) -> Vec<TraceEvent> {
+        self.read_since(self.head().saturating_sub(count as u64)).events
+    }
+}
+
+/// Runs `f` on the function names by id. For signal handlers: never waits on the table,
+/// whose holder may be the interrupted thread, and passes no names if it is taken.
+pub(crate) fn with_names_without_blocking<R>(f: impl FnOnce(&[String]) -> R) -> R {
+    match function_names().try_lock() {
+        Ok(functions) => f(&functions.names),
+        Err(_) => f(&[]),
+    }
+}
+
+/// Function names by id; written when thunks are generated, never on the call path
+#[derive(Default)]
+pub(crate) struct FunctionNames {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+}
+
+static FUNCTIONS: OnceLock<Mutex<FunctionNames>> = OnceLock::new();
+static RING: OnceLock<TraceRing> = OnceLock::new();
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+/// Every call the last HLE run streamed back, on the app side
+static RECORDED: Mutex<Vec<TraceEvent>> = Mutex::new(Vec::new());
+
+pub fn ring() -> &'static TraceRing {
+    RING.get_or_init(|| TraceRing::new(TRACE_CAPACITY))
+}
+
+pub(crate) fn function_names() -> &'static Mutex<FunctionNames> {
+    FUNCTIONS.get_or_init(Default::default)
+}
+
+pub(crate) fn function_id(name: &str) -> u32 {
+    let mut functions = function_names().lock().unwrap_or_else(|e| e.into_inner());
+    let FunctionNames { names, ids } = &mut *functions;
+    *ids.entry(name.to_string()).or_insert_with(|| {
+        names.push(name.to_string());
+        (names.len() - 1) as u32
+    })
+}
+
+fn now_ns() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+/// Calls in progress on this thread, innermost last
+struct Frame {
+    return_address: usize,
+    function: u32,
+    args: [u64; TRACED_ARGS],
+    start: u64,
+}
+
+thread_local! {
+    static SHADOW_STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Per-function data a thunk hands to the trampoline in rax
+#[repr(C)]
+struct ThunkInfo {
+    target: usize,
+    function: u32,
+}
+
+/// One thunk per traced import: `mov rax, info; mov r11, trampoline; jmp r11`.
+/// The trampoline records the arguments, swaps the return address for its exit
+/// path and jumps to the real function with the stack untouched, so stack arguments still line up.
+pub struct TraceThunks {
+    code: CodePage,
+    /// Thunks embed pointers into this; it is never resized
+    _infos: Vec<ThunkInfo>,
+}
+
+impl TraceThunks {
+    /// `imports` are ("DLL!Function", address) pairs
+    pub fn generate(imports: &[(String, usize)]) -> Result<Self, String> {
+        let infos: Vec<ThunkInfo> = imports.iter()
+            .map(|(name, target)| ThunkInfo { target: *target, function: function_id(name) })
+            .collect();
+        let trampoline = pancho_trace_enter as *const () as u64;
+        let code = CodePage::generate("Trace thunks", infos.len(), THUNK_SIZE, |i, thunk| {
+            thunk[0..2].copy_from_slice(&[0x48, 0xB8]);
+            thunk[2..10].copy_from_slice(&(&infos[i] as *const ThunkInfo as u64).to_le_bytes());
+            thunk[10..12].copy_from_slice(&[0x49, 0xBB]);
+            thunk[12..20].copy_from_slice(&trampoline.to_le_bytes());
+            thunk[20..23].copy_from_slice(&[0x41, 0xFF, 0xE3]);
+        })?;
+        Ok(TraceThunks { code, _infos: infos })
+    }
+
+    pub fn address(&self, index: usize) -> usize {
+        self.code.address(index * THUNK_SIZE)
+    }
+}
+
+/// Entered from a thunk with rax = ThunkInfo and the caller's stack as it was at the call.
+/// Saves the argument registers (integer and xmm0-3) around `trace_call_start`.
+#[unsafe(naked)]
+unsafe extern "win64" fn pancho_trace_enter() {
+    naked_asm!(
+        "push rcx",
+        "push rdx",
+        "push r8",
+        "push r9",
+        "sub rsp, 0x68",
+        "movdqu [rsp + 0x20], xmm0",
+        "movdqu [rsp + 0x30], xmm1",
+        "movdqu [rsp + 0x40], xmm2",
+        "movdqu [rsp + 0x50], xmm3",
+        "mov rcx, rax",
+        "lea rdx, [rsp + 0x68]",
+        "lea r8, [rsp + 0x88]",
+        "call {start}",
+        "movdqu xmm0, [rsp + 0x20]",
+        "movdqu xmm1, [rsp + 0x30]",
+        "movdqu xmm2, [rsp + 0x40]",
+        "movdqu xmm3, [rsp + 0x50]",
+        "add rsp, 0x68",
+        "pop r9",
+        "pop r8",
+        "pop rdx",
+        "pop rcx",
+        "jmp rax",
+        start = sym trace_call_start,
+    );
+}
+
+/// Where a traced function returns to. Keeps rax/rdx/xmm0 intact for the real caller.
+#[unsafe(naked)]
+unsafe extern "win64" fn pancho_trace_exit() {
+    naked_asm!(
+        "sub rsp, 0x40",
+        "mov [rsp + 0x30], rax",
+        "mov [rsp + 0x38], rdx",
+        "movdqu [rsp + 0x20], xmm0",
+        "mov rcx, rax",
+        "call {end}",
+        "mov r11, rax",
+        "movdqu xmm0, [rsp + 0x20]",
+        "mov rdx, [rsp + 0x38]",
+        "mov rax, [rsp + 0x30]",
+        "add rsp, 0x40",
+        "jmp r11",
+        end = sym trace_call_end,
+    );
+}
+
+/// `saved` holds r9, r8, rdx, rcx as pushed; `return_slot` is the caller's return address
+extern "win64" fn trace_call_start(info: *const ThunkInfo, saved: *const u64, return_slot: *mut usize) -> usize {
+    let info = unsafe { &*info };
+    let saved = unsafe { std::slice::from_raw_parts(saved, TRACED_ARGS) };
+    let frame = Frame {
+        return_address: unsafe { *return_slot },
+        function: info.function,
+        args: [saved[3], saved[2], saved[1], saved[0]],
+        start: now_ns(),
+    };
+    SHADOW_STACK.with(|stack| stack.borrow_mut().push(frame));
+    unsafe { *return_slot = pancho_trace_exit as *const () as usize };
+    info.target
+}
+
+extern "win64" fn trace_call_end(ret: u64) -> usize {
+    let end = now_ns();
+    let Some(frame) = SHADOW_STACK.with(|stack| stack.borrow_mut().pop()) else {
+        // Only reachable if guest code unwound past a traced call; there is nowhere to return to
+        eprintln!("Pancho-Trace: return without a matching traced call");
+        std::process::abort();
+    };
+
+    ring().push(&Record {
+        function: frame.function,
+        args: frame.args,
+        ret,
+        start: frame.start,
+        duration: end - frame.start,
+        thread: pancho_hle_get_current_thread_id(),
+    });
+    frame.return_address
+}
+
+/// Plain JSON array of calls
+pub fn to_json(events: &[TraceEvent]) -> Result<String, String> {
+    serde_json::to_string_pretty(events).map_err(|e| e.to_string())
+}
+
+/// Chrome trace event format, for chrome://tracing and Perfetto
+pub fn to_chrome_trace(events: &[TraceEvent]) -> Result<String, String> {
+    let pid = std::process::id();
+    let trace_events: Vec<serde_json::Value> = events.iter()
+        .map(|event| {
+            let (dll, function) = event.function.split_once('!').unwrap_or(("", &event.function));
+            serde_json::json!({
+                "name": function,
+                "cat": dll,
+                "ph": "X",
+                "ts": event.start_ns as f64 / 1000.0,
+                "dur": event.duration_ns as f64 / 1000.0,
+                "pid": pid,
+                "tid": event.thread_id,
+                "args": {
+                    "rcx": format!("0x{:x}", event.args[0]),
+                    "rdx": format!("0x{:x}", event.args[1]),
+                    "r8": format!("0x{:x}", event.args[2]),
+                    "r9": format!("0x{:x}", event.args[3]),
+                    "ret": format!("0x{:x}", event.ret),
+                },
+            })
+        })
+        .collect();
+
+    serde_json::to_string(&serde_json::json!({ "traceEvents": trace_events, "displayTimeUnit": "ns" }))
+        .map_err(|e| e.to_string())
+}
+
+/// Keeps a run's full trace for `export_api_trace`, replacing the previous run's
+pub fn record_run(events: Vec<TraceEvent>) {
+    *RECORDED.lock().unwrap_or_else(|e| e.into_inner()) = events;
+}
+
+/// Writes every call traced during the last HLE run; `format` is "json" or "chrome"
+#[tauri::command]
+pub fn export_api_trace(path: String, format: String) -> Result<usize, String> {
+    let events = RECORDED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let content = match format.as_str() {
+        "json" => to_json(&events)?,
+        "chrome" => to_chrome_trace(&events)?,
+        other => return Err(format!("Unknown trace format {}", other)),
+    };
+    std::fs::write(&path, content).map_err(|e| e.to_string())?;
+    Ok(events.len())
+}
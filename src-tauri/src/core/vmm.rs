@@ -1,58 +1,457 @@
-use libc::{mmap, PROT_NONE, MAP_PRIVATE, MAP_ANONYMOUS};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use libc::{mmap, mprotect, munmap, MAP_ANONYMOUS, MAP_FAILED, MAP_FIXED, MAP_NORESERVE, MAP_PRIVATE};
+use libc::{PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
+use crate::core::kernel32::{ERROR_INVALID_ADDRESS, ERROR_INVALID_PARAMETER, ERROR_NOT_ENOUGH_MEMORY};
 
-pub struct VirtualMemoryManager {
-    pub reserved_regions: Vec<MemoryRegion>,
+pub const PAGE_SIZE: usize = 4096;
+/// Reservations start on 64K boundaries, as on Windows
+pub const ALLOCATION_GRANULARITY: usize = 0x10000;
+/// Lowest and highest addresses Windows hands out to a 64-bit process
+pub const MIN_APPLICATION_ADDRESS: u64 = 0x10000;
+pub const MAX_APPLICATION_ADDRESS: u64 = 0x7fff_fffe_ffff;
+
+pub const MEM_COMMIT: u32 = 0x0000_1000;
+pub const MEM_RESERVE: u32 = 0x0000_2000;
+pub const MEM_DECOMMIT: u32 = 0x0000_4000;
+pub const MEM_RELEASE: u32 = 0x0000_8000;
+pub const MEM_FREE: u32 = 0x0001_0000;
+pub const MEM_PRIVATE: u32 = 0x0002_0000;
+pub const MEM_IMAGE: u32 = 0x0100_0000;
+
+pub const PAGE_NOACCESS: u32 = 0x01;
+pub const PAGE_READONLY: u32 = 0x02;
+pub const PAGE_READWRITE: u32 = 0x04;
+pub const PAGE_WRITECOPY: u32 = 0x08;
+pub const PAGE_EXECUTE: u32 = 0x10;
+pub const PAGE_EXECUTE_READ: u32 = 0x20;
+pub const PAGE_EXECUTE_READWRITE: u32 = 0x40;
+pub const PAGE_EXECUTE_WRITECOPY: u32 = 0x80;
+pub const PAGE_GUARD: u32 = 0x100;
+pub const PAGE_NOCACHE: u32 = 0x200;
+pub const PAGE_WRITECOMBINE: u32 = 0x400;
+
+/// Why a VirtualAlloc-style call failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmError {
+    InvalidParameter,
+    InvalidAddress,
+    NotEnoughMemory,
+}
+
+impl VmError {
+    /// The code GetLastError reports for this failure
+    pub fn win32_code(self) -> u32 {
+        match self {
+            VmError::InvalidParameter => ERROR_INVALID_PARAMETER,
+            VmError::InvalidAddress => ERROR_INVALID_ADDRESS,
+            VmError::NotEnoughMemory => ERROR_NOT_ENOUGH_MEMORY,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::InvalidParameter => write!(f, "invalid parameter"),
+            VmError::InvalidAddress => write!(f, "invalid address"),
+            VmError::NotEnoughMemory => write!(f, "not enough memory"),
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionType {
+    Image,
+    Private,
 }
 
+impl RegionType {
+    pub fn mem_type(self) -> u32 {
+        match self {
+            RegionType::Image => MEM_IMAGE,
+            RegionType::Private => MEM_PRIVATE,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionState {
+    Commit,
+    Reserve,
+    Free,
+}
+
+impl RegionState {
+    pub fn mem_state(self) -> u32 {
+        match self {
+            RegionState::Commit => MEM_COMMIT,
+            RegionState::Reserve => MEM_RESERVE,
+            RegionState::Free => MEM_FREE,
+        }
+    }
+}
+
+/// One reservation: a 64K-aligned range made of reserved and committed pages
 #[derive(serde::Serialize, Clone)]
 pub struct MemoryRegion {
     pub start: u64,
     pub size: usize,
     pub label: String,
+    pub kind: RegionType,
+    pub allocation_protect: u32,
+    /// Protection of each page, 0 while it is only reserved
+    #[serde(skip)]
+    pages: Vec<u32>,
+}
+
+impl MemoryRegion {
+    fn end(&self) -> u64 {
+        self.start + self.size as u64
+    }
+
+    fn page_index(&self, address: u64) -> usize {
+        ((address - self.start) / PAGE_SIZE as u64) as usize
+    }
+}
+
+/// What VirtualQuery reports: the run of pages starting at an address that share state and protection
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBasicInformation {
+    pub base_address: u64,
+    pub allocation_base: u64,
+    pub allocation_protect: u32,
+    pub region_size: usize,
+    pub state: RegionState,
+    pub protect: u32,
+    pub kind: Option<RegionType>,
+}
+
+/// Address space shared by everything running guest code in this process
+static PROCESS_MEMORY: Mutex<VirtualMemoryManager> = Mutex::new(VirtualMemoryManager::new());
+
+pub fn process_memory() -> MutexGuard<'static, VirtualMemoryManager> {
+    PROCESS_MEMORY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// `process_memory` for signal handlers, which must not wait on the thread they interrupted
+pub fn try_process_memory() -> Option<MutexGuard<'static, VirtualMemoryManager>> {
+    match PROCESS_MEMORY.try_lock() {
+        Ok(memory) => Some(memory),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Windows-style view of the guest address space: reservations keyed by base,
+/// each tracking which of its pages are committed and with what protection.
+pub struct VirtualMemoryManager {
+    regions: BTreeMap<u64, MemoryRegion>,
+}
+
+impl Default for VirtualMemoryManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VirtualMemoryManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         VirtualMemoryManager {
-            reserved_regions: Vec::new(),
-        }
-    }
-
-    /// Reserves a specific address range for the PE image
-    /// This is critical for preventing macOS from allocating 
-    /// its own libraries in the game's expected memory space.
-    pub fn reserve_address_space(&mut self, base: u64, size: usize, label: &str) -> Result<(), String> {
-        unsafe {
-            let addr = mmap(
-                base as *mut libc::c_void,
-                size,
-                PROT_NONE,
-                MAP_PRIVATE | MAP_ANONYMOUS,
-                -1,
-                0
-            );
-
-            if addr == libc::MAP_FAILED {
-                return Err(format!("VMM: Failed to reserve {} at 0x{:x}", label, base));
+            regions: BTreeMap::new(),
+        }
+    }
+
+    /// MEM_RESERVE: claims address space without backing it. A non-zero `address` is rounded
+    /// down to the allocation granularity and must not overlap anything already mapped;
+    /// otherwise the kernel picks a 64K-aligned spot.
+    pub fn reserve(&mut self, address: u64, size: usize, protect: u32, kind: RegionType, label: &str) -> Result<u64, VmError> {
+        if size == 0 || host_protection(protect).is_none() {
+            return Err(VmError::InvalidParameter);
+        }
+
+        let (base, len) = if address != 0 {
+            let base = align_down(address, ALLOCATION_GRANULARITY);
+            let end = align_up(address.checked_add(size as u64).ok_or(VmError::InvalidParameter)?, PAGE_SIZE);
+            if base < MIN_APPLICATION_ADDRESS || end > MAX_APPLICATION_ADDRESS + 1 {
+                return Err(VmError::InvalidAddress);
+            }
+            if self.overlaps(base, end) {
+                return Err(VmError::InvalidAddress);
+            }
+            (base, (end - base) as usize)
+        } else {
+            (0, align_up(size as u64, PAGE_SIZE) as usize)
+        };
+
+        let start = unsafe {
+            if base != 0 {
+                let mapped = map_reserved(base, len)?;
+                if mapped != base {
+                    munmap(mapped as *mut libc::c_void, len);
+                    return Err(VmError::InvalidAddress);
+                }
+                base
+            } else {
+                // mmap only guarantees page alignment; over-reserve and trim to a 64K boundary
+                let slack = ALLOCATION_GRANULARITY - PAGE_SIZE;
+                let mapped = map_reserved(0, len + slack)?;
+                let aligned = align_up(mapped, ALLOCATION_GRANULARITY);
+                if aligned > mapped {
+                    munmap(mapped as *mut libc::c_void, (aligned - mapped) as usize);
+                }
+                let tail = mapped + (len + slack) as u64 - (aligned + len as u64);
+                if tail > 0 {
+                    munmap((aligned + len as u64) as *mut libc::c_void, tail as usize);
+                }
+                aligned
+            }
+        };
+
+        self.regions.insert(start, MemoryRegion {
+            start,
+            size: len,
+            label: label.to_string(),
+            kind,
+            allocation_protect: protect,
+            pages: vec![0; len / PAGE_SIZE],
+        });
+        Ok(start)
+    }
+
+    /// MEM_COMMIT: backs the pages covering `address..address + size` inside one reservation.
+    /// Pages that are already committed keep their contents and take the new protection.
+    pub fn commit(&mut self, address: u64, size: usize, protect: u32) -> Result<u64, VmError> {
+        let prot = host_protection(protect).ok_or(VmError::InvalidParameter)?;
+        let (start, end) = page_range(address, size)?;
+        let region = self.region_containing_mut(start, end)?;
+
+        if unsafe { mprotect(start as *mut libc::c_void, (end - start) as usize, prot) } != 0 {
+            return Err(VmError::NotEnoughMemory);
+        }
+        let (first, last) = (region.page_index(start), region.page_index(end));
+        region.pages[first..last].fill(protect);
+        Ok(start)
+    }
+
+    /// VirtualAlloc: MEM_RESERVE and/or MEM_COMMIT. Committing without an address reserves too.
+    pub fn allocate(&mut self, address: u64, size: usize, allocation_type: u32, protect: u32) -> Result<u64, VmError> {
+        if size == 0 || allocation_type & (MEM_COMMIT | MEM_RESERVE) == 0 {
+            return Err(VmError::InvalidParameter);
+        }
+        let reserve = allocation_type & MEM_RESERVE != 0 || address == 0;
+        if !reserve {
+            return self.commit(address, size, protect);
+        }
+
+        let base = self.reserve(address, size, protect, RegionType::Private, "VirtualAlloc")?;
+        if allocation_type & MEM_COMMIT != 0 {
+            let start = if address == 0 { base } else { align_down(address, PAGE_SIZE) };
+            let end = if address == 0 { base + size as u64 } else { address + size as u64 };
+            if let Err(e) = self.commit(start, (end - start) as usize, protect) {
+                let _ = self.release(base);
+                return Err(e);
             }
+        }
+        Ok(base)
+    }
+
+    /// MEM_DECOMMIT: returns pages to the reserved state and drops their contents.
+    /// A size of 0 decommits the whole reservation and needs its base address.
+    pub fn decommit(&mut self, address: u64, size: usize) -> Result<(), VmError> {
+        let (start, end) = if size == 0 {
+            let region = self.regions.get(&address).ok_or(VmError::InvalidAddress)?;
+            (region.start, region.end())
+        } else {
+            page_range(address, size)?
+        };
+        let region = self.region_containing_mut(start, end)?;
+
+        // Replacing the pages drops their contents, as decommitting does
+        let remapped = unsafe {
+            mmap(start as *mut libc::c_void, (end - start) as usize, PROT_NONE,
+                MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE | MAP_FIXED, -1, 0)
+        };
+        if remapped == MAP_FAILED {
+            return Err(VmError::NotEnoughMemory);
+        }
+        let (first, last) = (region.page_index(start), region.page_index(end));
+        region.pages[first..last].fill(0);
+        Ok(())
+    }
 
-            self.reserved_regions.push(MemoryRegion {
-                start: base,
-                size,
-                label: label.to_string(),
+    /// MEM_RELEASE: unmaps a whole reservation, given its base
+    pub fn release(&mut self, address: u64) -> Result<(), VmError> {
+        let region = self.regions.remove(&address).ok_or(VmError::InvalidAddress)?;
+        unsafe { munmap(region.start as *mut libc::c_void, region.size) };
+        Ok(())
+    }
+
+    /// VirtualFree: MEM_RELEASE takes the reservation base and a size of 0
+    pub fn free(&mut self, address: u64, size: usize, free_type: u32) -> Result<(), VmError> {
+        match free_type {
+            MEM_RELEASE if size == 0 => self.release(address),
+            MEM_DECOMMIT => self.decommit(address, size),
+            _ => Err(VmError::InvalidParameter),
+        }
+    }
+
+    /// VirtualProtect: every page in the range must be committed. Returns the old protection of the first page.
+    pub fn protect(&mut self, address: u64, size: usize, protect: u32) -> Result<u32, VmError> {
+        let prot = host_protection(protect).ok_or(VmError::InvalidParameter)?;
+        let (start, end) = page_range(address, size)?;
+        let region = self.region_containing_mut(start, end)?;
+
+        let (first, last) = (region.page_index(start), region.page_index(end));
+        if region.pages[first..last].contains(&0) {
+            return Err(VmError::InvalidAddress);
+        }
+        if unsafe { mprotect(start as *mut libc::c_void, (end - start) as usize, prot) } != 0 {
+            return Err(VmError::NotEnoughMemory);
+        }
+        let old = region.pages[first];
+        region.pages[first..last].fill(protect);
+        Ok(old)
+    }
+
+    /// VirtualQuery: the run of pages from `address` with the same state and protection.
+    /// Addresses outside every reservation report the free gap up to the next one.
+    pub fn query(&self, address: u64) -> Option<MemoryBasicInformation> {
+        if address > MAX_APPLICATION_ADDRESS {
+            return None;
+        }
+        let page = align_down(address, PAGE_SIZE);
+
+        let Some(region) = self.region_at(page) else {
+            let next = self.regions.range(page..).next().map(|(start, _)| *start).unwrap_or(MAX_APPLICATION_ADDRESS + 1);
+            return Some(MemoryBasicInformation {
+                base_address: page,
+                allocation_base: 0,
+                allocation_protect: 0,
+                region_size: (next - page) as usize,
+                state: RegionState::Free,
+                protect: PAGE_NOACCESS,
+                kind: None,
             });
+        };
+
+        let first = region.page_index(page);
+        let protect = region.pages[first];
+        let run = region.pages[first..].iter().take_while(|p| **p == protect).count();
+        Some(MemoryBasicInformation {
+            base_address: page,
+            allocation_base: region.start,
+            allocation_protect: region.allocation_protect,
+            region_size: run * PAGE_SIZE,
+            state: if protect == 0 { RegionState::Reserve } else { RegionState::Commit },
+            protect,
+            kind: Some(region.kind),
+        })
+    }
+
+    /// Called when an access faults at `address`. If it hit a guard page, the guard is
+    /// lifted (one-shot, as on Windows) and the access can be retried.
+    pub fn take_guard_page(&mut self, address: u64) -> bool {
+        let page = align_down(address, PAGE_SIZE);
+        let Some(region) = self.regions.range_mut(..=page).next_back().map(|(_, r)| r).filter(|r| page < r.end()) else {
+            return false;
+        };
+        let index = region.page_index(page);
+        let protect = region.pages[index];
+        if protect & PAGE_GUARD == 0 {
+            return false;
+        }
+
+        let unguarded = protect & !PAGE_GUARD;
+        let prot = host_protection(unguarded).unwrap_or(PROT_NONE);
+        if unsafe { mprotect(page as *mut libc::c_void, PAGE_SIZE, prot) } != 0 {
+            return false;
+        }
+        region.pages[index] = unguarded;
+        true
+    }
+
+    fn region_at(&self, address: u64) -> Option<&MemoryRegion> {
+        self.regions.range(..=address).next_back().map(|(_, r)| r).filter(|r| address < r.end())
+    }
+
+    /// The reservation holding all of `start..end`
+    fn region_containing_mut(&mut self, start: u64, end: u64) -> Result<&mut MemoryRegion, VmError> {
+        self.regions.range_mut(..=start).next_back()
+            .map(|(_, r)| r)
+            .filter(|r| end <= r.end())
+            .ok_or(VmError::InvalidAddress)
+    }
 
-            println!("Pancho-VMM: Reserved {} at 0x{:x} ({} bytes)", label, base, size);
-            Ok(())
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.regions.range(..end).next_back().is_some_and(|(_, r)| r.end() > start)
+    }
+}
+
+impl Drop for VirtualMemoryManager {
+    fn drop(&mut self) {
+        for region in self.regions.values() {
+            unsafe { munmap(region.start as *mut libc::c_void, region.size) };
         }
     }
+}
 
-    /// Set up the TEB (Thread Environment Block) space
-    /// Windows apps expect this at a very specific offset in the segment registers
-    pub fn setup_teb_space(&mut self) -> Result<u64, String> {
-        let teb_size = 4096; // One page for TEB
-        let teb_addr = 0x7ff000000000u64; // High address for 64-bit TEB
-        self.reserve_address_space(teb_addr, teb_size, "TEB")?;
-        Ok(teb_addr)
+/// Host protection for a PAGE_* value. Guard pages stay inaccessible until their first touch.
+pub fn host_protection(protect: u32) -> Option<i32> {
+    let base = protect & !(PAGE_GUARD | PAGE_NOCACHE | PAGE_WRITECOMBINE);
+    let prot = match base {
+        PAGE_NOACCESS => PROT_NONE,
+        PAGE_READONLY => PROT_READ,
+        // Mappings are private, so copy-on-write is what plain writes already do
+        PAGE_READWRITE | PAGE_WRITECOPY => PROT_READ | PROT_WRITE,
+        PAGE_EXECUTE => PROT_EXEC,
+        PAGE_EXECUTE_READ => PROT_READ | PROT_EXEC,
+        PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY => PROT_READ | PROT_WRITE | PROT_EXEC,
+        _ => return None,
+    };
+    match protect & PAGE_GUARD {
+        0 => Some(prot),
+        _ if base == PAGE_NOACCESS => None,
+        _ => Some(PROT_NONE),
     }
 }
+
+/// PAGE_* value for host protection bits; write implies read, as Windows has no write-only pages
+pub fn windows_protection(prot: i32) -> u32 {
+    let read = prot & (PROT_READ | PROT_WRITE) != 0;
+    match (prot & PROT_EXEC != 0, prot & PROT_WRITE != 0, read) {
+        (true, true, _) => PAGE_EXECUTE_READWRITE,
+        (true, false, true) => PAGE_EXECUTE_READ,
+        (true, false, false) => PAGE_EXECUTE,
+        (false, true, _) => PAGE_READWRITE,
+        (false, false, true) => PAGE_READONLY,
+        (false, false, false) => PAGE_NOACCESS,
+    }
+}
+
+/// Inaccessible, unbacked mapping; `hint` of 0 lets the kernel choose
+unsafe fn map_reserved(hint: u64, len: usize) -> Result<u64, VmError> {
+    let mapped = mmap(hint as *mut libc::c_void, len, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE, -1, 0);
+    if mapped == MAP_FAILED {
+        return Err(VmError::NotEnoughMemory);
+    }
+    Ok(mapped as u64)
+}
+
+/// Pages covering `address..address + size`
+fn page_range(address: u64, size: usize) -> Result<(u64, u64), VmError> {
+    if address == 0 {
+        return Err(VmError::InvalidAddress);
+    }
+    let end = address.checked_add(size.max(1) as u64).ok_or(VmError::InvalidParameter)?;
+    Ok((align_down(address, PAGE_SIZE), align_up(end, PAGE_SIZE)))
+}
+
+fn align_down(value: u64, align: usize) -> u64 {
+    value & !(align as u64 - 1)
+}
+
+fn align_up(value: u64, align: usize) -> u64 {
+    value.div_ceil(align as u64) * align as u64
+}
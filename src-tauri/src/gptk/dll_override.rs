@@ -6,6 +6,7 @@ use crate::bottle::template::{RegistryEntry, RegistryValueType};
 use crate::gptk::d3dmetal::D3DMetalManager;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum GraphicsBackend {
     D3DMetal,
     DXVK,
@@ -74,6 +75,7 @@ impl DllOverrideManager {
         RegistryManager::write_entries(bottle_path, wine_path, &entries).await
     }
 
+    #[allow(dead_code)]
     pub async fn get_current_backend(_bottle_path: &Path, _wine_path: &Path) -> Result<GraphicsBackend, String> {
         // This is a bit tricky without querying the registry.
         // For now, we'll assume WineD3D unless we verify otherwise later (e.g. by checking reg files).
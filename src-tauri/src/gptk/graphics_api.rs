@@ -0,0 +1,203 @@
+use std::path::Path;
+use goblin::pe::PE;
+use serde::{Deserialize, Serialize};
+use crate::core::classifier::find_bytes;
+use crate::core::loader;
+use crate::gptk::dll_override::GraphicsBackend;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GraphicsApi {
+    D3D8,
+    D3D9,
+    D3D10,
+    D3D11,
+    D3D12,
+    OpenGL,
+    Vulkan,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GraphicsProfile {
+    pub apis: Vec<GraphicsApi>,
+    /// "Unity" or "Unreal Engine" when the game ships with a known engine
+    pub engine: Option<String>,
+    pub recommended_backend: GraphicsBackend,
+    /// Winetricks verbs for native components the game expects
+    pub components: Vec<String>,
+    pub evidence: Vec<String>,
+}
+
+const API_DLLS: [(&str, GraphicsApi); 10] = [
+    ("d3d8.dll", GraphicsApi::D3D8),
+    ("d3d9.dll", GraphicsApi::D3D9),
+    ("d3d10.dll", GraphicsApi::D3D10),
+    ("d3d10_1.dll", GraphicsApi::D3D10),
+    ("d3d10core.dll", GraphicsApi::D3D10),
+    ("d3d11.dll", GraphicsApi::D3D11),
+    ("d3d12.dll", GraphicsApi::D3D12),
+    ("dxgi.dll", GraphicsApi::D3D11),
+    ("opengl32.dll", GraphicsApi::OpenGL),
+    ("vulkan-1.dll", GraphicsApi::Vulkan),
+];
+
+// Engines load their renderer with LoadLibrary, so the entry points only show up as strings
+const API_MARKERS: [(&[u8], GraphicsApi); 7] = [
+    (b"Direct3DCreate8", GraphicsApi::D3D8),
+    (b"Direct3DCreate9", GraphicsApi::D3D9),
+    (b"D3D10CreateDevice", GraphicsApi::D3D10),
+    (b"D3D11CreateDevice", GraphicsApi::D3D11),
+    (b"D3D12CreateDevice", GraphicsApi::D3D12),
+    (b"wglCreateContext", GraphicsApi::OpenGL),
+    (b"vkCreateInstance", GraphicsApi::Vulkan),
+];
+
+// Don't string-search binaries larger than this
+const MARKER_SCAN_LIMIT: u64 = 512 * 1024 * 1024;
+
+pub struct GraphicsDetector;
+
+impl GraphicsDetector {
+    pub fn analyze(exe_path: &Path) -> Result<GraphicsProfile, String> {
+        let data = std::fs::read(exe_path).map_err(|e| e.to_string())?;
+        let pe = PE::parse(&data).map_err(|e| e.to_string())?;
+        let app_dir = exe_path.parent().unwrap_or(Path::new("."));
+
+        let mut apis: Vec<GraphicsApi> = Vec::new();
+        let mut evidence = Vec::new();
+        let mut add = |api: GraphicsApi, reason: String| {
+            if !apis.contains(&api) {
+                apis.push(api);
+            }
+            evidence.push(reason);
+        };
+
+        let imported: Vec<String> = loader::parse_imports(&data, &pe)?
+            .iter()
+            .map(|d| d.dll.to_lowercase())
+            .collect();
+
+        for dll in &imported {
+            if let Some((_, api)) = API_DLLS.iter().find(|(name, _)| name == dll) {
+                add(*api, format!("imports {}", dll));
+            }
+        }
+
+        // Engine runtimes hold the renderer; the game exe is only a stub
+        let engine_binaries = ["unityplayer.dll", "gameassembly.dll"];
+        let mut engine = None;
+        let mut scan_targets = vec![exe_path.to_path_buf()];
+
+        let bundled = list_files(app_dir);
+        if bundled.iter().any(|f| f == "unityplayer.dll") || has_unity_data_dir(app_dir, exe_path) {
+            engine = Some("Unity".to_string());
+            // Matched case-insensitively but opened under their real name, for case-sensitive volumes
+            let entries = std::fs::read_dir(app_dir).into_iter().flatten().flatten();
+            scan_targets.extend(entries
+                .filter(|e| engine_binaries.contains(&e.file_name().to_string_lossy().to_lowercase().as_str()))
+                .map(|e| e.path()));
+        } else if is_unreal(exe_path, &data) {
+            engine = Some("Unreal Engine".to_string());
+        }
+
+        for target in &scan_targets {
+            let size = std::fs::metadata(target).map(|m| m.len()).unwrap_or(0);
+            if size > MARKER_SCAN_LIMIT {
+                continue;
+            }
+            let other;
+            let bytes: &[u8] = if target == exe_path {
+                &data
+            } else {
+                other = std::fs::read(target).unwrap_or_default();
+                &other
+            };
+            let file_name = target.file_name().unwrap_or_default().to_string_lossy().to_string();
+            for (marker, api) in API_MARKERS {
+                if find_bytes(bytes, marker) {
+                    add(api, format!("{} references {}", file_name, String::from_utf8_lossy(marker)));
+                }
+            }
+        }
+
+        apis.sort();
+
+        let mut components: Vec<String> = Vec::new();
+        let mut need = |verb: &str| {
+            if !components.iter().any(|c| c == verb) {
+                components.push(verb.to_string());
+            }
+        };
+        for dll in &imported {
+            if bundled.contains(dll) {
+                continue;
+            }
+            if dll.starts_with("d3dx9_") {
+                need("d3dx9");
+            } else if dll.starts_with("d3dx10_") {
+                need("d3dx10");
+            } else if dll == "d3dx11_43.dll" || dll == "d3dx11_42.dll" {
+                need("d3dx11_43");
+            } else if dll == "d3dcompiler_47.dll" {
+                need("d3dcompiler_47");
+            } else if dll == "d3dcompiler_43.dll" {
+                need("d3dcompiler_43");
+            } else if dll.starts_with("xaudio2_") || dll.starts_with("x3daudio") || dll.starts_with("xactengine") {
+                need("xact");
+            }
+        }
+        // Unity and Unreal compile shaders at runtime on D3D11/12
+        let modern_d3d = apis.iter().any(|a| matches!(a, GraphicsApi::D3D11 | GraphicsApi::D3D12));
+        if engine.is_some() && modern_d3d && !bundled.iter().any(|f| f == "d3dcompiler_47.dll") {
+            need("d3dcompiler_47");
+        }
+
+        Ok(GraphicsProfile {
+            recommended_backend: recommend_backend(&apis),
+            apis,
+            engine,
+            components,
+            evidence,
+        })
+    }
+}
+
+/// D3DMetal covers D3D11/12; everything older stays on Wine's own implementation,
+/// since the launch overrides pin d3d9 to builtin and the DXVK backend only replaces d3d11/dxgi
+pub fn recommend_backend(apis: &[GraphicsApi]) -> GraphicsBackend {
+    let newest_d3d = apis.iter()
+        .filter(|a| !matches!(a, GraphicsApi::OpenGL | GraphicsApi::Vulkan))
+        .max();
+
+    match newest_d3d {
+        Some(GraphicsApi::D3D12) | Some(GraphicsApi::D3D11) => GraphicsBackend::D3DMetal,
+        Some(_) => GraphicsBackend::WineD3D,
+        // Nothing found: keep the default
+        None if apis.is_empty() => GraphicsBackend::D3DMetal,
+        None => GraphicsBackend::WineD3D,
+    }
+}
+
+fn list_files(dir: &Path) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|e| e.file_name().to_string_lossy().to_lowercase()).collect())
+        .unwrap_or_default()
+}
+
+/// Unity games ship `<ExeName>_Data/` next to the exe
+fn has_unity_data_dir(app_dir: &Path, exe_path: &Path) -> bool {
+    let stem = exe_path.file_stem().unwrap_or_default().to_string_lossy();
+    app_dir.join(format!("{}_Data", stem)).join("globalgamemanagers").exists()
+}
+
+/// Unreal shipping builds are named `<Project>-Win64-Shipping.exe` and live in `Binaries/Win64`
+fn is_unreal(exe_path: &Path, data: &[u8]) -> bool {
+    let name = exe_path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    name.ends_with("-shipping.exe")
+        || exe_path.parent().and_then(|p| p.parent()).is_some_and(|p| p.ends_with("Binaries"))
+        || find_bytes(data, b"FEngineLoop")
+}
+
+#[tauri::command]
+pub fn analyze_graphics(path: String) -> Result<GraphicsProfile, String> {
+    GraphicsDetector::analyze(Path::new(&path))
+}
@@ -1,2 +1,3 @@
 pub mod d3dmetal;
 pub mod dll_override;
+pub mod graphics_api;
@@ -5,7 +5,14 @@ mod tests {
     use crate::wine::registry::RegistryManager;
     use crate::gptk::d3dmetal::D3DMetalManager;
     use crate::wine::steam::SteamLauncher;
+    use crate::core::loader::{apply_relocations, bind_pe_image, map_pe_image, MappedImage};
+    use crate::core::linker::{ExportTable, SymbolicLinker};
+    use crate::core::kernel32::{self, SystemInfo};
+    use crate::core::vmm::{self, VirtualMemoryManager, RegionState, RegionType, VmError};
+    use crate::core::trace::{self, Record, TraceEvent, TraceRing};
+    use libc::c_void;
     use std::fs;
+    use std::path::{Path, PathBuf};
     use tempfile::tempdir;
 
     #[tokio::test]
@@ -31,6 +38,2816 @@ mod tests {
         // 5. Test Steam Status (M9)
         let temp_bottle = tempdir().unwrap();
         let status = SteamLauncher::check_status(temp_bottle.path());
-        assert_eq!(status.is_installed, false);
+        assert!(!status.is_installed);
+    }
+
+    fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+        buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    const FIXTURE_RELOCATED_U64: usize = 0x2000;
+    const FIXTURE_RELOCATED_U32: usize = 0x2008;
+
+    /// Minimal PE32+ with .text (RX), .data (RW, VirtualSize > raw) and .reloc holding
+    /// one DIR64 and one HIGHLOW fixup into .data
+    fn pe64_fixture(image_base: u64, relocs_stripped: bool) -> Vec<u8> {
+        let mut pe = vec![0u8; 0x800];
+        put(&mut pe, 0, b"MZ");
+        put(&mut pe, 0x3c, &0x40u32.to_le_bytes());
+        put(&mut pe, 0x40, b"PE\0\0");
+
+        // COFF header
+        let characteristics: u16 = 0x0022 | if relocs_stripped { 0x0001 } else { 0 };
+        put(&mut pe, 0x44, &0x8664u16.to_le_bytes());
+        put(&mut pe, 0x46, &3u16.to_le_bytes());
+        put(&mut pe, 0x54, &240u16.to_le_bytes());
+        put(&mut pe, 0x56, &characteristics.to_le_bytes());
+
+        // Optional header
+        let opt = 0x58;
+        put(&mut pe, opt, &0x20bu16.to_le_bytes());
+        put(&mut pe, opt + 16, &0x1000u32.to_le_bytes()); // AddressOfEntryPoint
+        put(&mut pe, opt + 20, &0x1000u32.to_le_bytes()); // BaseOfCode
+        put(&mut pe, opt + 24, &image_base.to_le_bytes());
+        put(&mut pe, opt + 32, &0x1000u32.to_le_bytes()); // SectionAlignment
+        put(&mut pe, opt + 36, &0x200u32.to_le_bytes()); // FileAlignment
+        put(&mut pe, opt + 40, &6u16.to_le_bytes());
+        put(&mut pe, opt + 48, &6u16.to_le_bytes());
+        put(&mut pe, opt + 56, &0x4000u32.to_le_bytes()); // SizeOfImage
+        put(&mut pe, opt + 60, &0x200u32.to_le_bytes()); // SizeOfHeaders
+        put(&mut pe, opt + 68, &3u16.to_le_bytes()); // console subsystem
+        put(&mut pe, opt + 108, &16u32.to_le_bytes());
+        // Data directory 5: base relocations
+        put(&mut pe, opt + 112 + 5 * 8, &0x3000u32.to_le_bytes());
+        put(&mut pe, opt + 112 + 5 * 8 + 4, &16u32.to_le_bytes());
+
+        let sections: [(&[u8], u32, u32, u32, u32); 3] = [
+            (b".text", 0x1000, 0x10, 0x200, 0x6000_0020),
+            (b".data", 0x2000, 0x1000, 0x400, 0xC000_0040),
+            (b".reloc", 0x3000, 0x10, 0x600, 0x4200_0040),
+        ];
+        for (i, (name, rva, virtual_size, raw, characteristics)) in sections.iter().enumerate() {
+            let header = 0x148 + i * 40;
+            put(&mut pe, header, name);
+            put(&mut pe, header + 8, &virtual_size.to_le_bytes());
+            put(&mut pe, header + 12, &rva.to_le_bytes());
+            put(&mut pe, header + 16, &0x200u32.to_le_bytes());
+            put(&mut pe, header + 20, &raw.to_le_bytes());
+            put(&mut pe, header + 36, &characteristics.to_le_bytes());
+        }
+
+        // .text: ret
+        pe[0x200] = 0xC3;
+        // .data: absolute pointers to the entry point, then filler up to the raw size
+        pe[0x400..0x600].fill(0xAA);
+        put(&mut pe, 0x400, &(image_base + 0x1000).to_le_bytes());
+        put(&mut pe, 0x408, &(image_base as u32 + 0x1000).to_le_bytes());
+        // .reloc: one block for the .data page
+        put(&mut pe, 0x600, &0x2000u32.to_le_bytes());
+        put(&mut pe, 0x604, &16u32.to_le_bytes());
+        put(&mut pe, 0x608, &(10u16 << 12).to_le_bytes());
+        put(&mut pe, 0x60a, &((3u16 << 12) | 8).to_le_bytes());
+        pe
+    }
+
+    #[test]
+    fn test_vdf_text_parser() {
+        use crate::wine::vdf::{self, VdfValue};
+
+        let doc = vdf::parse_text(concat!(
+            "\u{feff}// libraryfolders.vdf\n",
+            "\"LibraryFolders\"\n{\n",
+            "\t\"0\"\n\t{\n",
+            "\t\t\"path\"\t\t\"C:\\\\Program Files (x86)\\\\Steam\"\n",
+            "\t\t\"apps\" { \"10\" \"1024\" \"20\" \"2048\" }\n",
+            "\t}\n",
+            "\t\"say \\\"hi\\\"\"\t\"line\\none\\ttab\"\n",
+            "\t\"gamma\"\t\"1.0\" [$WIN32]\n",
+            "\t\"gamma\"\t\"2.2\" [$OSX]\n",
+            "\tunquoted value\n",
+            "}\n",
+        )).unwrap();
+
+        let root = doc.get("libraryfolders").unwrap();
+        let library = root.get("0").unwrap();
+        assert_eq!(library.get_str("PATH"), Some("C:\\Program Files (x86)\\Steam"));
+        assert_eq!(library.get("apps").unwrap().get_int("20"), Some(2048));
+        assert_eq!(root.get_str("say \"hi\""), Some("line\none\ttab"));
+        // Conditionals are dropped, so both platform variants stay and the first one wins
+        assert_eq!(root.entries().iter().filter(|(k, _)| k == "gamma").count(), 2);
+        assert_eq!(root.get_str("gamma"), Some("1.0"));
+        assert_eq!(root.get_str("unquoted"), Some("value"));
+        assert_eq!(root.entries().len(), 5);
+        assert!(matches!(library.get("apps"), Some(VdfValue::Object(apps)) if apps.len() == 2));
+
+        assert!(vdf::parse_text("\"a\" { \"b\" \"c\"").is_err());
+        assert!(vdf::parse_text("\"a\" \"unterminated").is_err());
+        assert!(vdf::parse_text("\"a\"").is_err());
+        assert!(vdf::parse_text("}").is_err());
+
+        // Runaway nesting is an error, not a stack overflow
+        let deep = format!("{}{}", "\"k\" { ".repeat(100_000), "} ".repeat(100_000));
+        assert!(vdf::parse_text(&deep).is_err());
+        let nested = format!("{}{}", "\"k\" { ".repeat(10), "} ".repeat(10));
+        assert!(vdf::parse_text(&nested).is_ok());
+    }
+
+    #[test]
+    fn test_steam_games_resolve_launch_executable() {
+        use crate::wine::vdf::{self, VdfValue};
+
+        let bottle = tempdir().unwrap();
+        let steam = bottle.path().join("drive_c/Program Files (x86)/Steam");
+        let steamapps = steam.join("steamapps");
+        fs::create_dir_all(steamapps.join("common/Game/Bin")).unwrap();
+        fs::create_dir_all(steamapps.join("common/Other")).unwrap();
+        fs::create_dir_all(steamapps.join("common/Native")).unwrap();
+        fs::create_dir_all(steam.join("appcache")).unwrap();
+        fs::write(steamapps.join("common/Game/Bin/Game.exe"), b"MZ").unwrap();
+        fs::write(steamapps.join("common/Game/launcher.exe"), b"MZ").unwrap();
+        fs::write(steamapps.join("common/Other/small.exe"), b"MZ").unwrap();
+        fs::write(steamapps.join("common/Other/other.exe"), vec![0u8; 4096]).unwrap();
+        fs::write(steamapps.join("common/Native/native.sh"), b"#!/bin/sh").unwrap();
+        for (app_id, name, dir) in [(10, "Game", "Game"), (20, "Other", "Other"), (30, "Native", "Native")] {
+            let manifest = format!("\"AppState\" {{ \"appid\" \"{}\" \"name\" \"{}\" \"installdir\" \"{}\" \"StateFlags\" \"4\" }}", app_id, name, dir);
+            fs::write(steamapps.join(format!("appmanifest_{}.acf", app_id)), manifest).unwrap();
+        }
+
+        // appinfo.vdf v28 holding app 10's launch options; the Linux one comes first
+        let object = |entries: Vec<(&str, VdfValue)>| VdfValue::Object(entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+        let string = |s: &str| VdfValue::String(s.to_string());
+        let info = object(vec![("appinfo", object(vec![("config", object(vec![("launch", object(vec![
+            ("0", object(vec![("executable", string("game.sh")), ("config", object(vec![("oslist", string("linux"))]))])),
+            ("1", object(vec![("executable", string("bin\\GAME.exe")), ("type", string("default")), ("config", object(vec![("oslist", string("windows"))]))])),
+        ]))]))]))]);
+        let blob = vdf::write_binary(&vdf::BinaryVdf::new(info.clone())).unwrap();
+        let mut appinfo = Vec::new();
+        appinfo.extend_from_slice(&0x07564428u32.to_le_bytes());
+        appinfo.extend_from_slice(&1u32.to_le_bytes());
+        for (app_id, blob) in [(5u32, vdf::write_binary(&vdf::BinaryVdf::new(object(vec![]))).unwrap()), (10, blob)] {
+            appinfo.extend_from_slice(&app_id.to_le_bytes());
+            appinfo.extend_from_slice(&((60 + blob.len()) as u32).to_le_bytes());
+            appinfo.extend_from_slice(&[0u8; 60]);
+            appinfo.extend_from_slice(&blob);
+        }
+        appinfo.extend_from_slice(&0u32.to_le_bytes());
+        fs::write(steam.join("appcache/appinfo.vdf"), &appinfo).unwrap();
+
+        let apps = vdf::parse_appinfo(&appinfo, &|app_id| app_id == 10).unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].1, info);
+
+        let games = SteamLauncher::list_installed_games(bottle.path());
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].executable.as_deref(), Some(steamapps.join("common/Game/Bin/Game.exe").as_path()));
+        // No launch option in appinfo: the biggest executable in the install dir
+        assert_eq!(games[2].executable.as_deref(), Some(steamapps.join("common/Other/other.exe").as_path()));
+        assert_eq!(games[1].executable, None);
+
+        // The game without a Windows binary never reaches the app list as a directory
+        let apps = crate::core::scanner::scan_bottle_for_apps(bottle.path());
+        let steam_apps: Vec<_> = apps.iter().filter(|app| app.steam_app_id.is_some()).collect();
+        assert_eq!(steam_apps.len(), 2);
+        assert!(steam_apps.iter().all(|app| app.exe_path.ends_with(".exe")));
+        assert!(!apps.iter().any(|app| app.steam_app_id == Some(30)));
+    }
+
+    #[test]
+    fn test_steam_artwork_lookup() {
+        let bottle = tempdir().unwrap();
+        let cache = bottle.path().join("drive_c/Program Files (x86)/Steam/appcache/librarycache");
+        // Old clients: flat `<appid>_<name>` files
+        fs::create_dir_all(&cache).unwrap();
+        fs::write(cache.join("10_header.jpg"), b"jpg").unwrap();
+        fs::write(cache.join("10_logo.png"), b"png").unwrap();
+        // New clients: a folder per app, with some assets in hashed subfolders
+        fs::create_dir_all(cache.join("20/4f1c2a")).unwrap();
+        fs::write(cache.join("20/header.jpg"), b"jpg").unwrap();
+        fs::write(cache.join("20/4f1c2a/library_600x900.jpg"), b"jpg").unwrap();
+        fs::write(cache.join("20/library_hero.jpg"), b"jpg").unwrap();
+
+        let flat = SteamLauncher::find_artwork(bottle.path(), 10);
+        assert_eq!(flat.header, Some(cache.join("10_header.jpg")));
+        assert_eq!(flat.logo, Some(cache.join("10_logo.png")));
+        assert_eq!(flat.capsule, None);
+        assert_eq!(flat.best_cover(), Some(&cache.join("10_header.jpg")));
+
+        // The portrait capsule wins over the banners
+        let nested = SteamLauncher::find_artwork(bottle.path(), 20);
+        assert_eq!(nested.capsule, Some(cache.join("20/4f1c2a/library_600x900.jpg")));
+        assert_eq!(nested.hero, Some(cache.join("20/library_hero.jpg")));
+        assert_eq!(nested.best_cover(), Some(&cache.join("20/4f1c2a/library_600x900.jpg")));
+
+        assert_eq!(SteamLauncher::find_artwork(bottle.path(), 30).best_cover(), None);
+    }
+
+    /// shortcuts.vdf the way Steam writes it, with every object closed by `end`
+    fn shortcuts_fixture(entries: &[(u32, &str, &str)], end: u8) -> Vec<u8> {
+        let cstr = |out: &mut Vec<u8>, s: &str| {
+            out.extend_from_slice(s.as_bytes());
+            out.push(0);
+        };
+        let mut out = vec![0x00];
+        cstr(&mut out, "shortcuts");
+        for (index, (app_id, name, exe)) in entries.iter().enumerate() {
+            out.push(0x00);
+            cstr(&mut out, &index.to_string());
+            out.push(0x02);
+            cstr(&mut out, "appid");
+            out.extend_from_slice(&app_id.to_le_bytes());
+            for (key, value) in [("AppName", *name), ("Exe", *exe), ("StartDir", "\"C:\\Games\\\"")] {
+                out.push(0x01);
+                cstr(&mut out, key);
+                cstr(&mut out, value);
+            }
+            out.push(0x02);
+            cstr(&mut out, "IsHidden");
+            out.extend_from_slice(&1u32.to_le_bytes());
+            out.push(0x07);
+            cstr(&mut out, "LastPlayTime");
+            out.extend_from_slice(&0x1_0000_0001u64.to_le_bytes());
+            out.push(0x00);
+            cstr(&mut out, "tags");
+            out.push(0x01);
+            cstr(&mut out, "0");
+            cstr(&mut out, "favorite");
+            out.extend_from_slice(&[end, end]);
+        }
+        out.extend_from_slice(&[end, end]);
+        out
+    }
+
+    #[test]
+    fn test_binary_vdf_round_trips() {
+        use crate::wine::vdf;
+
+        for end in [0x08, 0x0B] {
+            let data = shortcuts_fixture(&[(0x8000_0001, "Game", "\"C:\\Games\\game.exe\""), (0x8000_0002, "Tool", "tool.exe")], end);
+            let doc = vdf::parse_binary(&data).unwrap();
+            assert_eq!(doc.end_tag, end);
+            let first = doc.root.get("shortcuts").unwrap().get("0").unwrap();
+            assert_eq!(first.get_int("appid"), Some(0x8000_0001u32 as i32 as i64));
+            assert_eq!(first.get_int("LastPlayTime"), Some(0x1_0000_0001));
+            assert_eq!(first.get("tags").unwrap().get_str("0"), Some("favorite"));
+            assert_eq!(vdf::write_binary(&doc).unwrap(), data);
+        }
+
+        assert!(vdf::parse_binary(&[0x01, b'k', 0]).is_err());
+        assert!(vdf::parse_binary(&[0x09, b'k', 0, 0]).is_err());
+
+        // A missing end tag is only tolerated at the top level
+        assert!(vdf::parse_binary(&[0x01, b'k', 0, b'v', 0]).is_ok());
+        assert!(vdf::parse_binary(&[0x00, b'o', 0, 0x01, b'k', 0, b'v', 0]).is_err());
+
+        let deep: Vec<u8> = [0x00, b'o', 0].repeat(100_000).into_iter().chain([0x08; 100_000]).collect();
+        assert!(vdf::parse_binary(&deep).is_err());
+
+        // An appinfo entry whose size points past the end of the file
+        let mut appinfo = Vec::new();
+        appinfo.extend_from_slice(&0x07564428u32.to_le_bytes());
+        appinfo.extend_from_slice(&1u32.to_le_bytes());
+        appinfo.extend_from_slice(&7u32.to_le_bytes());
+        appinfo.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(vdf::parse_appinfo(&appinfo, &|_| false).is_err());
+        assert!(vdf::parse_appinfo(&appinfo, &|_| true).is_err());
+    }
+
+    #[test]
+    fn test_removing_a_steam_shortcut_keeps_the_rest_intact() {
+        use crate::wine::steam_shortcuts::SteamShortcutManager;
+
+        let bottle = tempdir().unwrap();
+        let config = bottle.path().join("drive_c/Program Files (x86)/Steam/userdata/12345/config");
+        fs::create_dir_all(&config).unwrap();
+        let shortcuts = [
+            (0x8000_0001, "First", "first.exe"),
+            (0x8000_0002, "Second", "second.exe"),
+            (0x8000_0003, "Third", "third.exe"),
+        ];
+        fs::write(config.join("shortcuts.vdf"), shortcuts_fixture(&shortcuts, 0x0B)).unwrap();
+
+        assert!(SteamShortcutManager::remove(bottle.path(), 0x8000_0002).unwrap());
+        // The remaining entries are renumbered "0", "1" and otherwise written back unchanged
+        let expected = shortcuts_fixture(&[shortcuts[0], shortcuts[2]], 0x0B);
+        assert_eq!(fs::read(config.join("shortcuts.vdf")).unwrap(), expected);
+
+        let listed = SteamShortcutManager::list(bottle.path()).unwrap();
+        assert_eq!(listed.iter().map(|s| s.app_name.as_str()).collect::<Vec<_>>(), ["First", "Third"]);
+        assert!(listed.iter().all(|s| s.is_hidden && s.tags == ["favorite"] && s.user_id == "12345"));
+
+        assert!(!SteamShortcutManager::remove(bottle.path(), 0x8000_0002).unwrap());
+        assert_eq!(fs::read(config.join("shortcuts.vdf")).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_adding_steam_shortcuts_counts_each_app_once() {
+        use crate::core::classifier::ExecutableClass;
+        use crate::core::scanner::DetectedApp;
+        use crate::wine::steam_shortcuts::SteamShortcutManager;
+
+        let bottle = tempdir().unwrap();
+        for user in ["111", "222"] {
+            fs::create_dir_all(bottle.path().join("drive_c/Program Files (x86)/Steam/userdata").join(user).join("config")).unwrap();
+        }
+        let app = |name: &str| DetectedApp {
+            name: name.to_string(),
+            exe_path: String::new(),
+            is_priority: false,
+            pinned: false,
+            steam_app_id: None,
+            cover: None,
+            class: ExecutableClass::default(),
+            windows_path: Some(format!("C:\\Games\\{}.exe", name)),
+            launch_args: Vec::new(),
+            working_dir: None,
+        };
+
+        // Two apps for two users is still two apps
+        assert_eq!(SteamShortcutManager::add_apps(bottle.path(), &[app("First"), app("Second")]).unwrap(), 2);
+        let listed = SteamShortcutManager::list(bottle.path()).unwrap();
+        assert_eq!(listed.len(), 4);
+        assert_eq!(SteamShortcutManager::add_apps(bottle.path(), &[app("First"), app("Third")]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_steam_shortcuts_survive_gaps_and_unreadable_users() {
+        use crate::core::classifier::ExecutableClass;
+        use crate::core::scanner::DetectedApp;
+        use crate::wine::steam_shortcuts::SteamShortcutManager;
+        use crate::wine::vdf;
+
+        let bottle = tempdir().unwrap();
+        let userdata = bottle.path().join("drive_c/Program Files (x86)/Steam/userdata");
+        for user in ["111", "222"] {
+            fs::create_dir_all(userdata.join(user).join("config")).unwrap();
+        }
+        // User 111's list was edited by another tool and has keys "0" and "5"
+        let mut doc = vdf::parse_binary(&shortcuts_fixture(&[(0x8000_0001, "First", "first.exe"), (0x8000_0002, "Second", "second.exe")], 0x08)).unwrap();
+        doc.root.entries_mut().unwrap()[0].1.entries_mut().unwrap()[1].0 = "5".to_string();
+        fs::write(userdata.join("111/config/shortcuts.vdf"), vdf::write_binary(&doc).unwrap()).unwrap();
+        fs::write(userdata.join("222/config/shortcuts.vdf"), [0x00, b's', 0, 0x09]).unwrap();
+
+        // The unreadable file hides only its own user's shortcuts
+        let listed = SteamShortcutManager::list(bottle.path()).unwrap();
+        assert_eq!(listed.iter().map(|s| s.app_name.as_str()).collect::<Vec<_>>(), ["First", "Second"]);
+        assert!(listed.iter().all(|s| s.user_id == "111"));
+
+        fs::remove_file(userdata.join("222/config/shortcuts.vdf")).unwrap();
+        let app = DetectedApp {
+            name: "Third".to_string(),
+            exe_path: String::new(),
+            is_priority: false,
+            pinned: false,
+            steam_app_id: None,
+            cover: None,
+            class: ExecutableClass::default(),
+            windows_path: Some("C:\\Games\\Third.exe".to_string()),
+            launch_args: Vec::new(),
+            working_dir: None,
+        };
+        assert_eq!(SteamShortcutManager::add_apps(bottle.path(), &[app]).unwrap(), 1);
+        let doc = vdf::parse_binary(&fs::read(userdata.join("111/config/shortcuts.vdf")).unwrap()).unwrap();
+        let keys: Vec<_> = doc.root.get("shortcuts").unwrap().entries().iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["0", "5", "6"]);
+        assert_eq!(SteamShortcutManager::list(bottle.path()).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_native_registry_parser() {
+        use crate::core::shim::{NativeRegistry, RegValue};
+
+        let registry = NativeRegistry::parse(concat!(
+            "WINE REGISTRY Version 2\n",
+            ";; All keys relative to \\\\Machine\n\n",
+            "[Software\\\\Microsoft\\\\Windows\\\\CurrentVersion\\\\Uninstall\\\\Game] 1700000000\n",
+            "#time=1d9a4c1e2b3f4a0\n",
+            "\"DisplayName\"=\"Game \\\"Deluxe\\\" Edition\"\n",
+            "\"InstallLocation\"=\"C:\\\\Games\\\\Game\\\\\"\n",
+            "\"EstimatedSize\"=dword:00001f40\n",
+            "\"UninstallString\"=hex(2):43,00,3a,00,5c,00,75,00,6e,00,69,00,6e,00,73,00,2e,00,\\\n",
+            "  65,00,78,00,65,00,00,00\n",
+            "\"Paths\"=hex(7):61,00,00,00,62,00,00,00,00,00\n",
+            "\"Expand\"=str(2):\"%SystemRoot%\\\\system32\"\n",
+            "\"Multi\"=str(7):\"one\\0two\\0\"\n",
+            "\"Blob\"=hex:01,02,\\\n",
+            "  03\n",
+            "@=\"default\"\n",
+            "\"Caf\\x00e9\"=\"\\\"quoted\\\"\"\n\n",
+            "[Software\\\\Microsoft\\\\Windows\\\\CurrentVersion\\\\Uninstall\\\\Other] 1700000001\n",
+            "[Software\\\\Microsoft\\\\Windows\\\\CurrentVersion\\\\Uninstall\\\\Other\\\\Nested] 1700000002\n",
+        ));
+
+        let key = "software\\MICROSOFT\\windows\\currentversion\\uninstall\\game";
+        assert_eq!(registry.get_string(key, "displayname").as_deref(), Some("Game \"Deluxe\" Edition"));
+        assert_eq!(registry.get_string(key, "InstallLocation").as_deref(), Some("C:\\Games\\Game\\"));
+        assert_eq!(registry.get(key, "EstimatedSize").and_then(|v| v.as_dword()), Some(8000));
+        assert_eq!(registry.get_string(key, "UninstallString").as_deref(), Some("C:\\unins.exe"));
+        assert_eq!(registry.get(key, "Paths"), Some(&RegValue::MultiString(vec!["a".to_string(), "b".to_string()])));
+        assert_eq!(registry.get_string(key, "Expand").as_deref(), Some("%SystemRoot%\\system32"));
+        assert_eq!(registry.get(key, "Multi"), Some(&RegValue::MultiString(vec!["one".to_string(), "two".to_string()])));
+        assert_eq!(registry.get(key, "Blob"), Some(&RegValue::Binary(vec![1, 2, 3])));
+        assert_eq!(registry.get_string(key, "@").as_deref(), Some("default"));
+        assert_eq!(registry.get_string(key, "Caf\u{e9}").as_deref(), Some("\"quoted\""));
+        assert_eq!(registry.key(key).unwrap().values.len(), 10);
+
+        assert_eq!(registry.subkeys("Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\"), ["Game", "Other"]);
+        assert_eq!(registry.subkeys("Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\Other"), ["Nested"]);
+    }
+
+    #[test]
+    fn test_epic_and_gog_launch_options() {
+        use crate::wine::epic::EpicLibrary;
+        use crate::wine::gog::GogLibrary;
+
+        let bottle = tempdir().unwrap();
+        let drive_c = bottle.path().join("drive_c");
+        let manifests = EpicLibrary::manifests_dir(bottle.path());
+        fs::create_dir_all(&manifests).unwrap();
+        fs::create_dir_all(drive_c.join("Epic/Fortress/Bin")).unwrap();
+        fs::write(drive_c.join("Epic/Fortress/Bin/Fortress.exe"), b"MZ").unwrap();
+        fs::write(manifests.join("A1.item"), r#"{
+            "AppName": "Fortress", "DisplayName": "Fortress", "InstallLocation": "C:\\Epic\\Fortress",
+            "LaunchExecutable": "Bin\\Fortress.exe", "LaunchCommand": " -nosplash \"-lang=en us\" ",
+            "AppCategories": ["public", "games", "applications"], "bIsIncompleteInstall": false
+        }"#).unwrap();
+        // DLC and half-downloaded titles are not launchable games
+        fs::write(manifests.join("B2.item"), r#"{
+            "AppName": "FortressDlc", "MainGameAppName": "Fortress", "InstallLocation": "C:\\Epic\\Fortress",
+            "LaunchExecutable": "Bin\\Fortress.exe"
+        }"#).unwrap();
+        fs::write(manifests.join("C3.item"), r#"{
+            "AppName": "Partial", "InstallLocation": "C:\\Epic\\Partial", "LaunchExecutable": "p.exe", "bIsIncompleteInstall": true
+        }"#).unwrap();
+
+        let epic = EpicLibrary::list_installed_games(bottle.path());
+        assert_eq!(epic.len(), 1);
+        assert_eq!(epic[0].exe_path.as_deref(), Some(drive_c.join("Epic/Fortress/Bin/Fortress.exe").as_path()));
+        assert_eq!(epic[0].launch_args, "-nosplash \"-lang=en us\"");
+
+        // Galaxy registers installs under Wow6432Node; the play task picks the exe, args and cwd
+        fs::create_dir_all(drive_c.join("GOG/Quest/bin")).unwrap();
+        fs::write(drive_c.join("GOG/Quest/bin/quest.exe"), b"MZ").unwrap();
+        fs::write(bottle.path().join("system.reg"), concat!(
+            "WINE REGISTRY Version 2\n\n",
+            "[Software\\\\Wow6432Node\\\\GOG.com\\\\Games\\\\1207] 1700000000\n",
+            "\"path\"=\"C:\\\\GOG\\\\Quest\"\n",
+        )).unwrap();
+        fs::write(drive_c.join("GOG/Quest/goggame-1207.info"), r#"{
+            "gameId": "1207", "rootGameId": "1207", "name": "Quest",
+            "playTasks": [
+                { "type": "URLTask", "isPrimary": true, "link": "https://www.gog.com" },
+                { "type": "FileTask", "isPrimary": true, "path": "bin\\quest.exe", "arguments": "-windowed -lang en", "workingDir": "bin" }
+            ]
+        }"#).unwrap();
+        fs::write(drive_c.join("GOG/Quest/goggame-1208.info"), r#"{ "gameId": "1208", "rootGameId": "1207", "name": "Quest DLC" }"#).unwrap();
+
+        let gog = GogLibrary::list_installed_games(bottle.path());
+        assert_eq!(gog.len(), 1);
+        assert_eq!(gog[0].exe_path.as_deref(), Some(drive_c.join("GOG/Quest/bin/quest.exe").as_path()));
+        assert_eq!(gog[0].arguments, "-windowed -lang en");
+        assert_eq!(gog[0].working_dir.as_deref(), Some(drive_c.join("GOG/Quest/bin").as_path()));
+
+        // Both reach the scanned app, where the launch passes them to Wine
+        let apps = crate::core::scanner::scan_bottle_for_apps(bottle.path());
+        let fortress = apps.iter().find(|a| a.name == "Fortress").unwrap();
+        assert_eq!(fortress.launch_args, ["-nosplash", "-lang=en us"]);
+        assert_eq!(fortress.working_dir, None);
+        let quest = apps.iter().find(|a| a.name == "Quest").unwrap();
+        assert_eq!(quest.launch_args, ["-windowed", "-lang", "en"]);
+        assert_eq!(quest.working_dir.as_deref(), drive_c.join("GOG/Quest/bin").to_str());
+    }
+
+    #[test]
+    fn test_classifier_evidence() {
+        use crate::core::classifier::{classify_file, ExecutableClass};
+
+        let dir = tempdir().unwrap();
+        let write = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+        let console = pe64_fixture(0x1_4000_0000, false);
+        let mut gui = console.clone();
+        gui[0x58 + 68] = 2;
+
+        let tool = classify_file(&write("server.exe", &console));
+        assert_eq!(tool.class, ExecutableClass::Tool);
+        assert_eq!(tool.evidence, ["console subsystem"]);
+
+        let mut nsis = gui.clone();
+        nsis.extend_from_slice(b"\0\0\0\0\xEF\xBE\xAD\xDENullsoftInst");
+        let installer = classify_file(&write("game_v1.exe", &nsis));
+        assert_eq!(installer.class, ExecutableClass::Installer);
+        assert_eq!(installer.evidence, ["NSIS installer"]);
+
+        let mut inno = gui.clone();
+        inno.extend_from_slice(b"Inno Setup Setup Data (6.2.0)");
+        assert_eq!(classify_file(&write("game_v2.exe", &inno)).evidence, ["Inno Setup installer"]);
+
+        // Uninstallers are not installers, even with the installer's overlay
+        let inno_uninstaller = classify_file(&write("unins000.exe", &inno));
+        assert_eq!(inno_uninstaller.class, ExecutableClass::Uninstaller);
+        assert_eq!(inno_uninstaller.evidence, ["uninstaller naming"]);
+        assert_eq!(classify_file(&write("Uninstall.exe", &nsis)).class, ExecutableClass::Uninstaller);
+        assert_eq!(classify_file(&write("uninst.exe", &gui)).class, ExecutableClass::Uninstaller);
+        assert_eq!(classify_file(&write("setup.exe", &gui)).class, ExecutableClass::Installer);
+
+        assert_eq!(classify_file(&write("VC_redist.x64.exe", &gui)).class, ExecutableClass::Redistributable);
+        assert_eq!(classify_file(&write("Steam.exe", &gui)).class, ExecutableClass::Launcher);
+        assert_eq!(classify_file(&write("game.exe", &gui)).class, ExecutableClass::Unknown);
+        assert_eq!(classify_file(&write("notes.exe", b"not a PE")).class, ExecutableClass::Unknown);
+
+        // Sparse file: classifying it must not read the gigabyte of zeroes behind the headers
+        let large = write("Game-Win64-Shipping.exe", &gui);
+        fs::OpenOptions::new().write(true).open(&large).unwrap().set_len(1 << 30).unwrap();
+        let game = classify_file(&large);
+        assert_eq!(game.class, ExecutableClass::Game);
+        assert_eq!(game.evidence, ["large GUI binary"]);
+
+        // SteamSetup.exe without version info is still recognised by name
+        let setup = write("SteamSetup.exe", &nsis);
+        assert!(classify_file(&setup).is_steam_setup(&setup));
+        assert!(!classify_file(&dir.path().join("game_v1.exe")).is_steam_setup(&dir.path().join("game_v1.exe")));
+    }
+
+    #[test]
+    fn test_graphics_api_detection_picks_a_backend() {
+        use crate::gptk::dll_override::GraphicsBackend;
+        use crate::gptk::graphics_api::{recommend_backend, GraphicsApi, GraphicsDetector};
+        use FixtureImport::Name;
+
+        let dir = tempdir().unwrap();
+
+        // A D3D9 game stays on WineD3D and needs the D3DX runtime it doesn't bundle
+        let d3d9_dir = dir.path().join("Old");
+        fs::create_dir_all(&d3d9_dir).unwrap();
+        let imports: [(&str, &[FixtureImport]); 2] = [
+            ("d3d9.dll", &[Name("Direct3DCreate9")]),
+            ("d3dx9_43.dll", &[Name("D3DXCreateTexture")]),
+        ];
+        fs::write(d3d9_dir.join("old.exe"), dll_fixture("old.exe", None, &[], &imports)).unwrap();
+        let old = GraphicsDetector::analyze(&d3d9_dir.join("old.exe")).unwrap();
+        assert_eq!(old.apis, [GraphicsApi::D3D9]);
+        assert_eq!(old.recommended_backend, GraphicsBackend::WineD3D);
+        assert_eq!(old.components, ["d3dx9"]);
+        assert_eq!(old.engine, None);
+
+        // A Unity stub exe: the renderer entry points are only in UnityPlayer.dll
+        let unity_dir = dir.path().join("Unity");
+        fs::create_dir_all(&unity_dir).unwrap();
+        fs::write(unity_dir.join("Game.exe"), dll_fixture("Game.exe", None, &[], &[])).unwrap();
+        fs::write(unity_dir.join("UnityPlayer.dll"), b"MZ..D3D11CreateDevice..vkCreateInstance..").unwrap();
+        let unity = GraphicsDetector::analyze(&unity_dir.join("Game.exe")).unwrap();
+        assert_eq!(unity.engine.as_deref(), Some("Unity"));
+        assert_eq!(unity.apis, [GraphicsApi::D3D11, GraphicsApi::Vulkan]);
+        assert_eq!(unity.recommended_backend, GraphicsBackend::D3DMetal);
+        assert_eq!(unity.components, ["d3dcompiler_47"]);
+
+        // The newest Direct3D version decides; GL and Vulkan alone keep WineD3D
+        assert_eq!(recommend_backend(&[GraphicsApi::D3D9, GraphicsApi::D3D12]), GraphicsBackend::D3DMetal);
+        assert_eq!(recommend_backend(&[GraphicsApi::D3D10, GraphicsApi::Vulkan]), GraphicsBackend::WineD3D);
+        assert_eq!(recommend_backend(&[GraphicsApi::OpenGL]), GraphicsBackend::WineD3D);
+        assert_eq!(recommend_backend(&[]), GraphicsBackend::D3DMetal);
+    }
+
+    #[test]
+    fn test_protection_scan_finds_anti_cheat_and_drm() {
+        use crate::core::protection::{ProtectionKind, ProtectionScanner, Severity};
+
+        // Unreal layout: <Root>/Engine next to <Root>/<Project>/Binaries/Win64
+        let root = tempdir().unwrap();
+        let binaries = root.path().join("Game/Binaries/Win64");
+        fs::create_dir_all(&binaries).unwrap();
+        fs::create_dir_all(root.path().join("Engine")).unwrap();
+        fs::create_dir_all(root.path().join("EasyAntiCheat")).unwrap();
+        fs::write(root.path().join("EasyAntiCheat/EasyAntiCheat_x64.dll"), b"MZ").unwrap();
+        fs::write(binaries.join("GameGuard.des"), b"").unwrap();
+        // Denuvo's section name on the game binary
+        let mut exe = dll_fixture("Game-Win64-Shipping.exe", None, &[], &[]);
+        put(&mut exe, 0x148 + 40, b".arch\0\0\0");
+        fs::write(binaries.join("Game-Win64-Shipping.exe"), &exe).unwrap();
+
+        let report = ProtectionScanner::scan(&binaries.join("Game-Win64-Shipping.exe"));
+        assert_eq!(report.install_dir, root.path());
+        assert_eq!(report.severity, Some(Severity::Blocking));
+        let found: Vec<(&str, &ProtectionKind, Severity)> = report.findings.iter().map(|f| (f.name.as_str(), &f.kind, f.severity)).collect();
+        assert_eq!(found, [
+            ("nProtect GameGuard", &ProtectionKind::AntiCheat, Severity::Blocking),
+            ("EasyAntiCheat", &ProtectionKind::AntiCheat, Severity::Warning),
+            ("Denuvo", &ProtectionKind::Drm, Severity::Warning),
+        ]);
+        assert_eq!(report.findings[1].evidence, ["EasyAntiCheat", "EasyAntiCheat/EasyAntiCheat_x64.dll"]);
+        assert_eq!(report.findings[2].evidence, ["section .arch"]);
+
+        // A clean game has nothing to report
+        let clean = tempdir().unwrap();
+        fs::write(clean.path().join("game.exe"), dll_fixture("game.exe", None, &[], &[])).unwrap();
+        let report = ProtectionScanner::scan(&clean.path().join("game.exe"));
+        assert!(report.findings.is_empty());
+        assert_eq!(report.severity, None);
+    }
+
+    #[test]
+    fn test_instruction_scan_against_cpu_profiles() {
+        use crate::core::instruction_sets::{CpuProfile, InstructionScanner, InstructionSet};
+
+        let dir = tempdir().unwrap();
+        let with_code = |name: &str, code: &[u8]| {
+            let mut pe = dll_fixture(name, None, &[], &[]);
+            put(&mut pe, 0x200, code);
+            fs::write(dir.path().join(name), pe).unwrap();
+        };
+        // Built with /arch:AVX2: vaddps ymm0, ymm1, ymm2 (AVX) and vpaddd ymm0, ymm1, ymm2 (AVX2), no CPUID
+        with_code("game.exe", &[0xC5, 0xF4, 0x58, 0xC2, 0xC5, 0xF4, 0x58, 0xC2, 0xC5, 0xF5, 0xFE, 0xC2, 0xC3]);
+        // A library that checks CPUID before its one vaddps zmm0, zmm1, zmm2 (AVX-512)
+        with_code("simd.dll", &[0x0F, 0xA2, 0x62, 0xF1, 0x74, 0x48, 0x58, 0xC2, 0xC3]);
+        let exe = dir.path().join("game.exe");
+
+        let rosetta = InstructionScanner::scan(&exe, &CpuProfile::preset("rosetta2").unwrap()).unwrap();
+        assert_eq!(rosetta.modules, ["game.exe", "simd.dll"]);
+        assert_eq!(rosetta.unsupported, [InstructionSet::Avx, InstructionSet::Avx2]);
+        assert_eq!(rosetta.dispatched, [InstructionSet::Avx512]);
+        assert_eq!(rosetta.runtime_dispatch, ["simd.dll"]);
+        let avx = rosetta.usage.iter().find(|u| u.set == InstructionSet::Avx).unwrap();
+        assert_eq!(avx.count, 2);
+        assert_eq!(avx.modules, ["game.exe"]);
+        assert_eq!((avx.sites[0].rva, avx.sites[0].mnemonic.as_str()), (0x1000, "vaddps"));
+        assert_eq!(rosetta.usage.iter().find(|u| u.set == InstructionSet::Avx512).unwrap().sites[0].rva, 0x1002);
+
+        // Translators with AVX2 run the game; AVX-512 is still only behind CPUID
+        let avx2 = InstructionScanner::scan(&exe, &CpuProfile::preset("rosetta2-avx").unwrap()).unwrap();
+        assert!(avx2.unsupported.is_empty());
+        assert_eq!(avx2.dispatched, [InstructionSet::Avx512]);
+        let v4 = InstructionScanner::scan(&exe, &CpuProfile::preset("x86-64-v4").unwrap()).unwrap();
+        assert!(v4.unsupported.is_empty() && v4.dispatched.is_empty());
+
+        assert!(CpuProfile::preset("pentium").is_none());
+    }
+
+    #[test]
+    fn test_metadata_streams() {
+        use crate::core::loader::metadata_stream;
+
+        // Metadata root with a 12-byte version string, then #Strings and #Blob
+        let mut metadata = vec![0u8; 0x60];
+        put(&mut metadata, 0, b"BSJB");
+        put(&mut metadata, 12, &12u32.to_le_bytes());
+        put(&mut metadata, 16, b"v4.0.30319\0\0");
+        put(&mut metadata, 30, &2u16.to_le_bytes());
+        put(&mut metadata, 32, &0x40u32.to_le_bytes());
+        put(&mut metadata, 36, &0x10u32.to_le_bytes());
+        put(&mut metadata, 40, b"#Strings\0\0\0\0");
+        put(&mut metadata, 52, &0x50u32.to_le_bytes());
+        put(&mut metadata, 56, &0x10u32.to_le_bytes());
+        put(&mut metadata, 60, b"#Blob\0\0\0");
+        put(&mut metadata, 0x50, b"\x0e.NETCoreApp,V");
+
+        assert_eq!(metadata_stream(&metadata, "#Blob"), Some(&metadata[0x50..0x60]));
+        assert_eq!(metadata_stream(&metadata, "#Strings"), Some(&metadata[0x40..0x50]));
+        assert_eq!(metadata_stream(&metadata, "#GUID"), None);
+        // A stream running past the metadata is not returned
+        put(&mut metadata, 56, &0x11u32.to_le_bytes());
+        assert_eq!(metadata_stream(&metadata, "#Blob"), None);
+        assert_eq!(metadata_stream(b"MZ", "#Blob"), None);
+    }
+
+    #[test]
+    fn test_unattended_install_command_line_keeps_spaces() {
+        use crate::core::installer::{unattended_command, InstallerKind};
+        use crate::core::runner::{batch_script, run_command_line};
+
+        let bottle = tempdir().unwrap();
+        let setup_dir = bottle.path().join("drive_c/Setup Files");
+        fs::create_dir_all(&setup_dir).unwrap();
+        let mut nsis = pe64_fixture(0x1_4000_0000, false);
+        nsis[0x58 + 68] = 2;
+        nsis.extend_from_slice(b"\0\0\0\0\xEF\xBE\xAD\xDENullsoftInst");
+        let setup = setup_dir.join("setup.exe");
+        fs::write(&setup, &nsis).unwrap();
+
+        // /D= must reach NSIS last and unquoted, spaces included
+        let line = unattended_command(bottle.path(), &setup, Some(r"C:\Program Files\My Game")).unwrap();
+        assert_eq!(line, r#""C:\Setup Files\setup.exe" /S /D=C:\Program Files\My Game"#);
+
+        // Host paths are translated first
+        let host_target = bottle.path().join("drive_c/Games & More/My Game");
+        let line = unattended_command(bottle.path(), &setup, host_target.to_str()).unwrap();
+        assert_eq!(line, r#""C:\Setup Files\setup.exe" /S /D=C:\Games & More\My Game"#);
+
+        let script = batch_script(r"C:\Setup Files", &line);
+        let lines: Vec<&str> = script.split("\r\n").collect();
+        assert_eq!(lines[2], r#"cd /d "C:\Setup Files""#);
+        assert_eq!(lines[3], r#""C:\Setup Files\setup.exe" /S /D=C:\Games ^& More\My Game"#);
+        assert_eq!(lines[4], "exit %ERRORLEVEL%");
+
+        let dir = Some(r"C:\Program Files\My Game");
+        assert_eq!(
+            InstallerKind::InstallShield.silent_args(dir),
+            ["/s", r#"/v"/qn INSTALLDIR=\"C:\Program Files\My Game\"""#],
+        );
+        assert_eq!(InstallerKind::InnoSetup.silent_args(dir).last().unwrap(), r#"/DIR="C:\Program Files\My Game""#);
+        assert_eq!(InstallerKind::Msi.silent_args(dir).last().unwrap(), r#"TARGETDIR="C:\Program Files\My Game""#);
+
+        // Inside quotes only % needs escaping
+        assert_eq!(batch_script("C:\\", r#""C:\a & b\100%.exe" x>y"#).split("\r\n").nth(3).unwrap(), r#""C:\a & b\100%%.exe" x^>y"#);
+
+        // The batch file is gone once the command has run; /bin/true stands in for Wine
+        fs::create_dir_all(bottle.path().join("drive_c/windows/system32")).unwrap();
+        let temp = bottle.path().join("drive_c/windows/temp");
+        let mut process = run_command_line(&line, r"C:\Setup Files", bottle.path(), Some("/bin/true".to_string()), "standard").unwrap();
+        assert_eq!(fs::read_dir(&temp).unwrap().count(), 1);
+        assert!(process.wait().unwrap().success());
+        assert_eq!(fs::read_dir(&temp).unwrap().count(), 0);
+    }
+
+    /// OLE compound file with 512-byte sectors. Streams under the 4096-byte cutoff go
+    /// into the mini stream, larger ones get regular sector chains
+    fn compound_file(streams: &[(String, Vec<u8>)]) -> Vec<u8> {
+        const END: u32 = 0xFFFF_FFFE;
+        fn chain(fat: &mut Vec<u32>, sectors: &mut Vec<Vec<u8>>, data: &[u8]) -> u32 {
+            let first = sectors.len() as u32;
+            let count = data.len().div_ceil(512);
+            for (i, chunk) in data.chunks(512).enumerate() {
+                let mut sector = chunk.to_vec();
+                sector.resize(512, 0);
+                sectors.push(sector);
+                fat.push(if i + 1 == count { END } else { first + i as u32 + 1 });
+            }
+            first
+        }
+
+        // Sector 0 holds the FAT itself
+        let mut fat = vec![0xFFFF_FFFDu32];
+        let mut sectors = vec![Vec::new()];
+        let mut mini_stream = Vec::new();
+        let mut mini_fat: Vec<u32> = Vec::new();
+        let mut placed = Vec::new();
+        for (name, data) in streams {
+            let start = if data.len() < 4096 {
+                let first = mini_fat.len() as u32;
+                let blocks = data.len().div_ceil(64);
+                mini_fat.extend((1..=blocks as u32).map(|i| if i as usize == blocks { END } else { first + i }));
+                mini_stream.extend_from_slice(data);
+                mini_stream.resize(mini_fat.len() * 64, 0);
+                first
+            } else {
+                chain(&mut fat, &mut sectors, data)
+            };
+            placed.push((name.as_str(), start, data.len()));
+        }
+        let root_start = chain(&mut fat, &mut sectors, &mini_stream);
+        let mini_fat_bytes: Vec<u8> = mini_fat.iter().flat_map(|e| e.to_le_bytes()).collect();
+        let mini_fat_start = chain(&mut fat, &mut sectors, &mini_fat_bytes);
+
+        let mut directory = Vec::new();
+        for (i, (name, start, size)) in [("Root Entry", root_start, mini_stream.len())].into_iter().chain(placed).enumerate() {
+            let mut entry = [0u8; 128];
+            let units: Vec<u8> = name.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+            put(&mut entry, 0, &units);
+            put(&mut entry, 0x40, &(units.len() as u16 + 2).to_le_bytes());
+            entry[0x42] = if i == 0 { 5 } else { 2 };
+            put(&mut entry, 0x74, &start.to_le_bytes());
+            put(&mut entry, 0x78, &(size as u32).to_le_bytes());
+            directory.extend_from_slice(&entry);
+        }
+        let directory_start = chain(&mut fat, &mut sectors, &directory);
+
+        assert!(fat.len() <= 128, "fixture needs a second FAT sector");
+        fat.resize(128, 0xFFFF_FFFF);
+        sectors[0] = fat.iter().flat_map(|e| e.to_le_bytes()).collect();
+
+        let mut header = vec![0u8; 512];
+        put(&mut header, 0, &crate::core::msi::OLE_SIGNATURE);
+        put(&mut header, 0x18, &0x3Eu16.to_le_bytes());
+        put(&mut header, 0x1A, &3u16.to_le_bytes());
+        put(&mut header, 0x1C, &0xFFFEu16.to_le_bytes());
+        put(&mut header, 0x1E, &9u16.to_le_bytes());
+        put(&mut header, 0x20, &6u16.to_le_bytes());
+        put(&mut header, 0x2C, &1u32.to_le_bytes());
+        put(&mut header, 0x30, &directory_start.to_le_bytes());
+        put(&mut header, 0x38, &4096u32.to_le_bytes());
+        put(&mut header, 0x3C, &mini_fat_start.to_le_bytes());
+        put(&mut header, 0x40, &1u32.to_le_bytes());
+        put(&mut header, 0x44, &END.to_le_bytes());
+        header[0x4C..].fill(0xFF);
+        put(&mut header, 0x4C, &0u32.to_le_bytes());
+
+        header.into_iter().chain(sectors.into_iter().flatten()).collect()
+    }
+
+    /// MSI database with a Property table, a summary stream naming the platform and,
+    /// optionally, a digital signature stream
+    fn msi_fixture(signed: bool) -> Vec<u8> {
+        use crate::core::msi::encode_table_name;
+
+        // Index 3 is an unused slot; the Manufacturer is Latin-1, not UTF-8
+        let strings: [&[u8]; 9] = [
+            b"ProductName", b"Test Game", b"", b"ProductCode", b"{8A1F0C2E-4B7D-4E21-9A3C-5D6E7F809A1B}",
+            b"ALLUSERS", b"1", b"Manufacturer", b"Caf\xE9 Ltd",
+        ];
+        let mut pool: Vec<u8> = vec![0; 4];
+        let mut string_data = Vec::new();
+        for string in strings {
+            let refs: u16 = if string.is_empty() { 0 } else { 1 };
+            pool.extend_from_slice(&(string.len() as u16).to_le_bytes());
+            pool.extend_from_slice(&refs.to_le_bytes());
+            string_data.extend_from_slice(string);
+        }
+        // Property(Property, Value), column after column
+        let property: Vec<u8> = [1u16, 4, 6, 8, 2, 5, 7, 9].iter().flat_map(|r| r.to_le_bytes()).collect();
+
+        // Property set header, one section with PID 7 (template) as VT_LPSTR
+        let mut summary = vec![0u8; 48];
+        summary[0..2].copy_from_slice(&0xFFFEu16.to_le_bytes());
+        summary[24..28].copy_from_slice(&1u32.to_le_bytes());
+        summary[44..48].copy_from_slice(&48u32.to_le_bytes());
+        let template = b"x64;1033\0";
+        for value in [40 + template.len() as u32, 1, 7, 16, 0x1E, template.len() as u32] {
+            summary.extend_from_slice(&value.to_le_bytes());
+        }
+        summary.extend_from_slice(template);
+
+        let mut streams = vec![
+            (encode_table_name("_StringPool"), pool),
+            (encode_table_name("_StringData"), string_data),
+            (encode_table_name("Property"), property),
+            ("\u{5}SummaryInformation".to_string(), summary),
+        ];
+        if signed {
+            streams.push(("\u{5}DigitalSignature".to_string(), vec![0x30; 5000]));
+        }
+        compound_file(&streams)
+    }
+
+    #[test]
+    fn test_msi_database_reader() {
+        use crate::core::authenticode::{verify_file, SignatureStatus};
+        use crate::core::msi::{encode_table_name, parse_msi};
+
+        // Two characters per unit after the 0x4840 table marker; odd lengths end in a single one
+        let units = |name: &str| encode_table_name(name).encode_utf16().collect::<Vec<_>>();
+        assert_eq!(units("Property"), [0x4840, 0x4559, 0x44F2, 0x4568, 0x4737]);
+        assert_eq!(units("_Tables").last(), Some(&(0x4800 + 54)));
+
+        let signed = msi_fixture(true);
+        let info = parse_msi(&signed).unwrap();
+        assert_eq!(info.product_name.as_deref(), Some("Test Game"));
+        assert_eq!(info.product_code.as_deref(), Some("{8A1F0C2E-4B7D-4E21-9A3C-5D6E7F809A1B}"));
+        assert_eq!(info.manufacturer.as_deref(), Some("Café Ltd"));
+        assert_eq!(info.all_users.as_deref(), Some("1"));
+        assert_eq!(info.product_version, None);
+        assert_eq!(info.platform.as_deref(), Some("x64;1033"));
+        assert!(info.is_64_bit());
+        assert!(info.signed);
+        assert_eq!(verify_file(&signed).unwrap().status, SignatureStatus::SignedUnverified);
+
+        let unsigned = msi_fixture(false);
+        assert!(!parse_msi(&unsigned).unwrap().signed);
+        assert_eq!(verify_file(&unsigned).unwrap().status, SignatureStatus::Unsigned);
+
+        assert!(parse_msi(&signed[..511]).is_err());
+    }
+
+    /// DER element with a short or long form length
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        match content.len() {
+            len if len < 0x80 => out.push(len as u8),
+            len if len < 0x100 => out.extend_from_slice(&[0x81, len as u8]),
+            len => out.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    #[test]
+    fn test_authenticode_digest_match_and_tamper() {
+        use crate::core::authenticode::{verify_file, SignatureStatus};
+        use sha2::{Digest, Sha256};
+
+        const SEQ: u8 = 0x30;
+        let oid = |bytes: &[u8]| der(0x06, bytes);
+        let name = |cn: &str| der(SEQ, &[
+            der(0x31, &der(SEQ, &[oid(&[0x55, 0x04, 0x06]), der(0x13, b"US")].concat())),
+            der(0x31, &der(SEQ, &[oid(&[0x55, 0x04, 0x03]), der(0x0C, cn.as_bytes())].concat())),
+        ].concat());
+        let sha256 = der(SEQ, &[oid(&[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01]), vec![0x05, 0x00]].concat());
+        let serial = der(0x02, &[0x01, 0x23]);
+        let placeholder = [0xAB; 32];
+
+        // ContentInfo { signedData, [0] SignedData { version, algorithms, SpcIndirectDataContent, [0] certs, signerInfos } }
+        let indirect = der(SEQ, &[der(SEQ, &oid(&[0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x01, 0x0F])), der(SEQ, &[sha256.clone(), der(0x04, &placeholder)].concat())].concat());
+        let content_info = der(SEQ, &[oid(&[0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x01, 0x04]), der(0xA0, &indirect)].concat());
+        let tbs = der(SEQ, &[der(0xA0, &der(0x02, &[2])), serial.clone(), sha256.clone(), name("Test CA"), der(SEQ, &[]), name("Test Publisher")].concat());
+        let certificate = der(SEQ, &tbs);
+        let signing_time = der(SEQ, &[oid(&[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x05]), der(0x31, &der(0x17, b"260101120000Z"))].concat());
+        let signer = der(SEQ, &[der(0x02, &[1]), der(SEQ, &[name("Test CA"), serial].concat()), sha256.clone(), der(0xA0, &signing_time)].concat());
+        let signed_data = der(SEQ, &[der(0x02, &[1]), der(0x31, &sha256), content_info, der(0xA0, &certificate), der(0x31, &signer)].concat());
+        let pkcs7 = der(SEQ, &[oid(&[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02]), der(0xA0, &signed_data)].concat());
+
+        // WIN_CERTIFICATE { dwLength, wRevision 2.0, WIN_CERT_TYPE_PKCS_SIGNED_DATA } after the sections
+        let mut pe = dll_fixture("setup.exe", None, &[], &[]);
+        let unsigned = pe.clone();
+        let table = pe.len();
+        let length = 8 + pkcs7.len();
+        pe.extend_from_slice(&(length as u32).to_le_bytes());
+        pe.extend_from_slice(&0x0200u16.to_le_bytes());
+        pe.extend_from_slice(&0x0002u16.to_le_bytes());
+        pe.extend_from_slice(&pkcs7);
+        pe.resize(pe.len().next_multiple_of(8), 0);
+        put(&mut pe, 0x58 + 112 + 4 * 8, &(table as u32).to_le_bytes());
+        let table_size = pe.len() - table;
+        put(&mut pe, 0x58 + 116 + 4 * 8, &(table_size as u32).to_le_bytes());
+
+        // The certificate table is outside the hashed ranges, so the digest can be filled in afterwards
+        let digest = {
+            let parsed = goblin::pe::PE::parse(&pe).unwrap();
+            let mut hasher = Sha256::new();
+            for range in parsed.authenticode_ranges() {
+                hasher.update(range);
+            }
+            hasher.finalize().to_vec()
+        };
+        let at = pe.windows(32).position(|w| w == placeholder).unwrap();
+        put(&mut pe, at, &digest);
+
+        let info = verify_file(&pe).unwrap();
+        assert_eq!(info.status, SignatureStatus::DigestMatches);
+        assert_eq!(info.digest_algorithm.as_deref(), Some("SHA-256"));
+        assert_eq!(info.file_digest, info.signed_digest);
+        assert_eq!(info.subject.as_deref(), Some("CN=Test Publisher, C=US"));
+        assert_eq!(info.issuer.as_deref(), Some("CN=Test CA, C=US"));
+        assert_eq!(info.timestamp.as_deref(), Some("260101120000Z"));
+
+        // One byte changed in .rdata after signing
+        let mut tampered = pe.clone();
+        tampered[0x500] ^= 0xFF;
+        let info = verify_file(&tampered).unwrap();
+        assert_eq!(info.status, SignatureStatus::Tampered);
+        assert_ne!(info.file_digest, info.signed_digest);
+        assert_eq!(info.subject.as_deref(), Some("CN=Test Publisher, C=US"));
+
+        assert_eq!(verify_file(&unsigned).unwrap().status, SignatureStatus::Unsigned);
+        // A certificate that isn't PKCS#7 DER can't be checked
+        let mut garbage = pe.clone();
+        put(&mut garbage, table + 8, &[0x30, 0x80]);
+        assert_eq!(verify_file(&garbage).unwrap().status, SignatureStatus::Unreadable);
+    }
+
+    #[test]
+    fn test_mapped_file_is_read_only_and_analyzed_in_place() {
+        use crate::core::loader::analyze_executable;
+        use crate::core::mapped_file::MappedFile;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("game.exe");
+        let pe = pe64_fixture(0x6f_0000_0000, false);
+        fs::write(&path, &pe).unwrap();
+
+        let file = MappedFile::open(&path).unwrap();
+        assert_eq!(&file[..], &pe[..]);
+        assert_eq!(page_permissions(file.as_ptr() as usize), "r--p");
+
+        // Analysis reads the mapping and leaves nothing reserved at the image base
+        let info = analyze_executable(path.to_str().unwrap()).unwrap();
+        assert!(info.is_64_bit);
+        assert!(info.image_size > 0);
+        let maps = fs::read_to_string("/proc/self/maps").unwrap();
+        assert!(!maps.lines().any(|line| line.starts_with(&format!("{:x}-", info.base_address))));
+
+        let empty = dir.path().join("empty.exe");
+        fs::write(&empty, b"").unwrap();
+        assert!(MappedFile::open(&empty).unwrap().is_empty());
+
+        assert!(MappedFile::open(&dir.path().join("missing.exe")).is_err());
+        assert!(analyze_executable(dir.path().join("missing.exe").to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_uninstall_commands() {
+        use crate::wine::programs::{split_command_line, InstalledProgram, ProgramRegistry};
+
+        // Quoted programs end at the closing quote; unquoted ones at the first ".exe"
+        assert_eq!(
+            split_command_line(r#""C:\Program Files\Game\unins000.exe" /SILENT "/LOG=C:\a b.txt""#),
+            Some((r"C:\Program Files\Game\unins000.exe".to_string(), vec!["/SILENT".to_string(), r"/LOG=C:\a b.txt".to_string()]))
+        );
+        assert_eq!(
+            split_command_line(r"C:\Program Files\Tool\uninst.exe /S"),
+            Some((r"C:\Program Files\Tool\uninst.exe".to_string(), vec!["/S".to_string()]))
+        );
+        assert_eq!(split_command_line("rundll32 setupapi.dll,Remove"), Some(("rundll32".to_string(), vec!["setupapi.dll,Remove".to_string()])));
+        assert_eq!(split_command_line("   "), None);
+        assert_eq!(split_command_line(r#""" /x"#), None);
+
+        let bottle = tempdir().unwrap();
+        let program = |uninstall: Option<&str>| InstalledProgram {
+            key: "{8A1F0C2E-4B7D-4E21-9A3C-5D6E7F809A1B}".to_string(),
+            display_name: "Test Game".to_string(),
+            publisher: None,
+            display_version: None,
+            install_location: None,
+            display_icon: None,
+            uninstall_string: uninstall.map(String::from),
+            quiet_uninstall_string: None,
+            estimated_size_kb: None,
+            is_32_bit: false,
+            per_user: false,
+        };
+        let drive_c = bottle.path().join("drive_c");
+
+        // MSI entries record a repair ("/I"); the uninstaller is asked for a removal instead
+        let (exe, args) = ProgramRegistry::uninstall_command(bottle.path(), &program(Some("MsiExec.exe /I{8A1F0C2E-4B7D-4E21-9A3C-5D6E7F809A1B}"))).unwrap();
+        assert_eq!(exe, drive_c.join("windows/system32/msiexec.exe"));
+        assert_eq!(args, ["/X{8A1F0C2E-4B7D-4E21-9A3C-5D6E7F809A1B}"]);
+        let (_, args) = ProgramRegistry::uninstall_command(bottle.path(), &program(Some("msiexec /i {GUID} /qb"))).unwrap();
+        assert_eq!(args, ["/X", "{GUID}", "/qb"]);
+
+        // Other uninstallers keep their switches and resolve through the bottle's drives
+        let (exe, args) = ProgramRegistry::uninstall_command(bottle.path(), &program(Some(r#""C:\Games\Test Game\unins000.exe" /I"#))).unwrap();
+        assert_eq!(exe, drive_c.join("Games/Test Game/unins000.exe"));
+        assert_eq!(args, ["/I"]);
+
+        assert!(ProgramRegistry::uninstall_command(bottle.path(), &program(None)).is_err());
+    }
+
+    #[test]
+    fn test_msi_products_are_recorded_once_per_product_code() {
+        use crate::core::bottle::{update_bottle_config, Bottle};
+        use crate::core::msi::MsiProduct;
+
+        let dir = tempdir().unwrap();
+        let bottle = Bottle {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            path: dir.path().to_path_buf(),
+            created_at: 0,
+            app_registry: Vec::new(),
+            cover: String::new(),
+            engine_path: None,
+            environment_type: "pro".to_string(),
+            msi_products: Vec::new(),
+        };
+        fs::write(dir.path().join("pancho.json"), serde_json::to_string(&bottle).unwrap()).unwrap();
+        let product = |code: &str, version: &str| MsiProduct {
+            product_code: code.to_string(),
+            product_name: "Test Game".to_string(),
+            product_version: Some(version.to_string()),
+            package: dir.path().join("game.msi"),
+            log: dir.path().join("game.log"),
+        };
+        let read = || serde_json::from_str::<Bottle>(&fs::read_to_string(dir.path().join("pancho.json")).unwrap()).unwrap();
+
+        update_bottle_config(dir.path(), |b| b.record_msi_product(product("{AAAA}", "1.0"))).unwrap();
+        update_bottle_config(dir.path(), |b| b.record_msi_product(product("{BBBB}", "1.0"))).unwrap();
+        // Reinstalling (product codes compare case-insensitively) replaces the old record
+        update_bottle_config(dir.path(), |b| b.record_msi_product(product("{aaaa}", "1.1"))).unwrap();
+        let products = read().msi_products;
+        assert_eq!(products.len(), 2);
+        assert_eq!(products[1].product_code, "{aaaa}");
+        assert_eq!(products[1].product_version.as_deref(), Some("1.1"));
+
+        update_bottle_config(dir.path(), |b| b.forget_msi_product("{BBBB}")).unwrap();
+        assert_eq!(read().msi_products.iter().map(|p| p.product_code.as_str()).collect::<Vec<_>>(), ["{aaaa}"]);
+        assert_eq!(read().name, "Test");
+
+        assert!(update_bottle_config(&dir.path().join("missing"), |_| {}).is_err());
+    }
+
+    /// Permissions column of the /proc/self/maps line covering `addr`
+    fn page_permissions(addr: usize) -> String {
+        let maps = fs::read_to_string("/proc/self/maps").unwrap();
+        maps.lines()
+            .find_map(|line| {
+                let (range, rest) = line.split_once(' ')?;
+                let (start, end) = range.split_once('-')?;
+                let start = usize::from_str_radix(start, 16).ok()?;
+                let end = usize::from_str_radix(end, 16).ok()?;
+                (start <= addr && addr < end).then(|| rest[..4].to_string())
+            })
+            .unwrap()
+    }
+
+    /// Maps and binds a PE the way ModuleLoader does for an image without dependencies
+    unsafe fn map_pe(pe_data: &[u8], linker: &SymbolicLinker) -> Result<MappedImage, String> {
+        let mut image = map_pe_image(pe_data)?;
+        bind_pe_image(&mut image, pe_data, linker)?;
+        Ok(image)
+    }
+
+    #[test]
+    fn test_map_pe_applies_relocations_and_protections() {
+        let preferred = 0x1_4100_0000u64;
+        let fixture = pe64_fixture(preferred, false);
+        let linker = SymbolicLinker::new();
+
+        // Hold the preferred base so the second copy has to be relocated
+        let first = unsafe { map_pe(&fixture, &linker) }.unwrap();
+        let image = unsafe { map_pe(&fixture, &linker) }.unwrap();
+        assert_ne!(image.base as u64, preferred);
+        assert_eq!(image.relocations_applied, 2);
+
+        let base = image.base as usize;
+        let delta = (base as u64).wrapping_sub(preferred);
+        let mapped = unsafe { std::slice::from_raw_parts(base as *const u8, image.size) };
+        let pointer = u64::from_le_bytes(mapped[FIXTURE_RELOCATED_U64..FIXTURE_RELOCATED_U64 + 8].try_into().unwrap());
+        let pointer32 = u32::from_le_bytes(mapped[FIXTURE_RELOCATED_U32..FIXTURE_RELOCATED_U32 + 4].try_into().unwrap());
+        assert_eq!(pointer, base as u64 + 0x1000);
+        assert_eq!(pointer32, (preferred as u32 + 0x1000).wrapping_add(delta as u32));
+
+        // Raw data is copied, the rest of VirtualSize is zero
+        assert_eq!(mapped[0x21FF], 0xAA);
+        assert!(mapped[0x2200..0x3000].iter().all(|b| *b == 0));
+        assert_eq!(mapped[0x1000], 0xC3);
+
+        assert_eq!(page_permissions(base), "r--p");
+        assert_eq!(page_permissions(base + 0x1000), "r-xp");
+        assert_eq!(page_permissions(base + 0x2000), "rw-p");
+        assert_eq!(page_permissions(base + 0x3000), "r--p");
+
+        // VirtualQuery sees the image and its section protections
+        let memory = vmm::process_memory();
+        let headers = memory.query(base as u64).unwrap();
+        assert_eq!((headers.state, headers.kind, headers.allocation_base), (RegionState::Commit, Some(RegionType::Image), base as u64));
+        assert_eq!((headers.protect, headers.region_size), (vmm::PAGE_READONLY, 0x1000));
+        assert_eq!(memory.query(base as u64 + 0x1000).unwrap().protect, vmm::PAGE_EXECUTE_READ);
+        let data = memory.query(base as u64 + 0x2345).unwrap();
+        assert_eq!((data.base_address, data.protect, data.allocation_base), (base as u64 + 0x2000, vmm::PAGE_READWRITE, base as u64));
+        drop(memory);
+
+        // Dropping the image frees its range
+        drop(image);
+        assert_eq!(vmm::process_memory().query(base as u64).unwrap().state, RegionState::Free);
+
+        // An image at its preferred base is left untouched
+        if first.base as u64 == preferred {
+            assert_eq!(first.relocations_applied, 0);
+            let original = unsafe { *((first.base as usize + FIXTURE_RELOCATED_U64) as *const u64) };
+            assert_eq!(original, preferred + 0x1000);
+        }
+    }
+
+    #[test]
+    fn test_map_pe_rejects_stripped_relocations_when_base_is_taken() {
+        let preferred = 0x1_4200_0000u64;
+        let fixture = pe64_fixture(preferred, true);
+        let linker = SymbolicLinker::new();
+
+        let first = unsafe { map_pe(&fixture, &linker) }.unwrap();
+        assert_eq!(first.base as u64, preferred);
+        let second = unsafe { map_pe(&fixture, &linker) };
+        assert!(second.is_err());
+    }
+
+    #[test]
+    fn test_guard_pages_fault_once_and_resume() {
+        let report = run_as_helper(|| {
+            let mut memory = vmm::process_memory();
+            let page = memory.allocate(0, vmm::PAGE_SIZE, vmm::MEM_COMMIT, vmm::PAGE_READWRITE | vmm::PAGE_GUARD).unwrap();
+            drop(memory);
+
+            // The first touch lifts the guard and the write goes through
+            unsafe { std::ptr::write_volatile(page as *mut u64, 0x1234) };
+            let value = unsafe { std::ptr::read_volatile(page as *const u64) };
+            let protect = vmm::process_memory().query(page).unwrap().protect;
+            // Asserting here would unwind through the forked test harness
+            if value != 0x1234 || protect != vmm::PAGE_READWRITE {
+                unsafe { libc::_exit(3) };
+            }
+        });
+        assert_eq!((report.exit_code, report.signal), (Some(0), None));
+
+        // A fault outside any guard page is still a crash
+        let crash = run_as_helper(|| {
+            let page = vmm::process_memory().allocate(0, vmm::PAGE_SIZE, vmm::MEM_COMMIT, vmm::PAGE_NOACCESS).unwrap();
+            unsafe { std::ptr::write_volatile(page as *mut u64, 1) };
+        });
+        assert_eq!(crash.signal, Some(libc::SIGSEGV));
+    }
+
+    /// One IMAGE_BASE_RELOCATION block at `offset` of `image`
+    fn relocation_block(image: &mut [u8], offset: usize, page_rva: u32, entries: &[u16]) -> usize {
+        let size = 8 + entries.len() * 2;
+        put(image, offset, &page_rva.to_le_bytes());
+        put(image, offset + 4, &(size as u32).to_le_bytes());
+        for (i, entry) in entries.iter().enumerate() {
+            put(image, offset + 8 + i * 2, &entry.to_le_bytes());
+        }
+        size
+    }
+
+    #[test]
+    fn test_apply_relocations_highlow_and_dir64() {
+        let mut image = vec![0u8; 0x2000];
+        put(&mut image, 0x1010, &0x0040_1000u32.to_le_bytes());
+        put(&mut image, 0x1ffc, &0xFFFF_FFF0u32.to_le_bytes());
+        put(&mut image, 0x1020, &0x1_4000_1000u64.to_le_bytes());
+        // ABSOLUTE entries pad a block to a 4-byte boundary and are skipped
+        let size = relocation_block(&mut image, 0x100, 0x1000, &[(3 << 12) | 0x010, (3 << 12) | 0xffc, (10 << 12) | 0x020, 0]);
+        // Zero padding after the last block ends the directory
+        let applied = apply_relocations(&mut image, 0x100, size + 8, 0x1_0000_0020).unwrap();
+        assert_eq!(applied, 3);
+
+        // HIGHLOW adds the low half of the delta and wraps at 32 bits
+        assert_eq!(&image[0x1010..0x1014], &0x0040_1020u32.to_le_bytes());
+        assert_eq!(&image[0x1ffc..0x2000], &0x0000_0010u32.to_le_bytes());
+        assert_eq!(&image[0x1020..0x1028], &0x2_4000_1020u64.to_le_bytes());
+
+        // A negative delta moves pointers down
+        apply_relocations(&mut image, 0x100, size, 0u64.wrapping_sub(0x20)).unwrap();
+        assert_eq!(&image[0x1010..0x1014], &0x0040_1000u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_apply_relocations_rejects_malformed_blocks() {
+        let mut image = vec![0u8; 0x2000];
+
+        // SizeOfBlock below the header size
+        put(&mut image, 0x100, &0x1000u32.to_le_bytes());
+        put(&mut image, 0x104, &4u32.to_le_bytes());
+        assert!(apply_relocations(&mut image, 0x100, 0x10, 1).is_err());
+
+        // Block runs past the end of the directory
+        let size = relocation_block(&mut image, 0x100, 0x1000, &[(10 << 12) | 0x10, (10 << 12) | 0x18]);
+        assert!(apply_relocations(&mut image, 0x100, size - 2, 1).is_err());
+
+        // Directory runs past the end of the image
+        assert!(apply_relocations(&mut image, 0x1ff8, 0x10, 1).is_err());
+
+        // Target slot crosses the end of the image
+        relocation_block(&mut image, 0x100, 0x1000, &[(10 << 12) | 0xffc]);
+        assert!(apply_relocations(&mut image, 0x100, 10, 1).is_err());
+
+        // Unknown relocation type
+        relocation_block(&mut image, 0x100, 0x1000, &[(7 << 12) | 0x10]);
+        assert!(apply_relocations(&mut image, 0x100, 10, 1).is_err());
+
+        // An image that must move but has no .reloc data
+        assert!(apply_relocations(&mut image, 0, 0, 1).is_err());
+        assert!(image[0x1000..].iter().all(|b| *b == 0));
+    }
+
+    /// Where a `dll_fixture` export leads
+    enum FixtureExport {
+        /// A `jmp` to this host address
+        Code(usize),
+        /// "MODULE.Function" or "MODULE.#ordinal"
+        Forwarder(&'static str),
+    }
+
+    enum FixtureImport {
+        Name(&'static str),
+        Ordinal(u16),
+    }
+
+    /// Code thunk `index` of a `dll_fixture`; 0 is DllMain, exports with code follow in order
+    const fn fixture_thunk(index: usize) -> usize {
+        0x1000 + index * 16
+    }
+
+    /// PE32+ DLL whose entry point (if any) jumps to `dll_main`, with exports numbered from
+    /// ordinal 1 (an empty name exports by ordinal only) and static imports
+    fn dll_fixture(name: &str, dll_main: Option<usize>, exports: &[(&str, FixtureExport)], imports: &[(&str, &[FixtureImport])]) -> Vec<u8> {
+        const RDATA: usize = 0x2000;
+        let mut pe = vec![0u8; 0xC00];
+        put(&mut pe, 0, b"MZ");
+        put(&mut pe, 0x3c, &0x40u32.to_le_bytes());
+        put(&mut pe, 0x40, b"PE\0\0");
+        put(&mut pe, 0x44, &0x8664u16.to_le_bytes());
+        put(&mut pe, 0x46, &2u16.to_le_bytes());
+        put(&mut pe, 0x54, &240u16.to_le_bytes());
+        put(&mut pe, 0x56, &0x2022u16.to_le_bytes());
+
+        let opt = 0x58;
+        put(&mut pe, opt, &0x20bu16.to_le_bytes());
+        put(&mut pe, opt + 16, &(if dll_main.is_some() { 0x1000u32 } else { 0 }).to_le_bytes());
+        put(&mut pe, opt + 24, &0x1_8000_0000u64.to_le_bytes());
+        put(&mut pe, opt + 32, &0x1000u32.to_le_bytes());
+        put(&mut pe, opt + 36, &0x200u32.to_le_bytes());
+        put(&mut pe, opt + 40, &6u16.to_le_bytes());
+        put(&mut pe, opt + 48, &6u16.to_le_bytes());
+        put(&mut pe, opt + 56, &0x3000u32.to_le_bytes());
+        put(&mut pe, opt + 60, &0x200u32.to_le_bytes());
+        put(&mut pe, opt + 68, &2u16.to_le_bytes());
+        put(&mut pe, opt + 108, &16u32.to_le_bytes());
+
+        // .text: `mov rax, imm64; jmp rax` thunks
+        let mut thunk = |index: usize, target: usize| {
+            let offset = 0x200 + index * 16;
+            put(&mut pe, offset, &[0x48, 0xB8]);
+            put(&mut pe, offset + 2, &target.to_le_bytes());
+            put(&mut pe, offset + 10, &[0xFF, 0xE0]);
+        };
+        if let Some(target) = dll_main {
+            thunk(0, target);
+        }
+        let mut next_thunk = 1;
+        let code_rvas: Vec<Option<usize>> = exports.iter().map(|(_, export)| match export {
+            FixtureExport::Code(target) => {
+                thunk(next_thunk, *target);
+                next_thunk += 1;
+                Some(fixture_thunk(next_thunk - 1))
+            }
+            FixtureExport::Forwarder(_) => None,
+        }).collect();
+
+        // .rdata, laid out by a bump allocator that returns RVAs
+        let mut rdata = vec![0u8; 0];
+        let alloc = |rdata: &mut Vec<u8>, bytes: &[u8]| {
+            rdata.resize(rdata.len().next_multiple_of(8), 0);
+            let rva = RDATA + rdata.len();
+            rdata.extend_from_slice(bytes);
+            rva
+        };
+        let string = |s: &str| [s.as_bytes(), b"\0"].concat();
+        let u32_at = |rdata: &mut Vec<u8>, rva: usize, value: usize| put(rdata, rva - RDATA, &(value as u32).to_le_bytes());
+        let u64_at = |rdata: &mut Vec<u8>, rva: usize, value: u64| put(rdata, rva - RDATA, &value.to_le_bytes());
+
+        // An empty relocation block, so the DLL can load anywhere
+        let relocations = alloc(&mut rdata, &[0, 0x10, 0, 0, 8, 0, 0, 0]);
+
+        let named: Vec<(usize, &str)> = exports.iter().enumerate()
+            .filter(|(_, (name, _))| !name.is_empty())
+            .map(|(i, (name, _))| (i, *name))
+            .collect();
+        let export_directory = alloc(&mut rdata, &[0; 40]);
+        let functions = alloc(&mut rdata, &vec![0; 4 * exports.len()]);
+        let names = alloc(&mut rdata, &vec![0; 4 * named.len()]);
+        let ordinals = alloc(&mut rdata, &vec![0; 2 * named.len()]);
+        let dll_name = alloc(&mut rdata, &string(name));
+        for (i, (_, export)) in exports.iter().enumerate() {
+            let rva = match export {
+                FixtureExport::Code(_) => code_rvas[i].unwrap(),
+                // Forwarders are told apart by pointing inside the export directory
+                FixtureExport::Forwarder(target) => alloc(&mut rdata, &string(target)),
+            };
+            u32_at(&mut rdata, functions + i * 4, rva);
+        }
+        for (slot, (index, export_name)) in named.iter().enumerate() {
+            let rva = alloc(&mut rdata, &string(export_name));
+            u32_at(&mut rdata, names + slot * 4, rva);
+            put(&mut rdata, ordinals - RDATA + slot * 2, &(*index as u16).to_le_bytes());
+        }
+        let export_size = RDATA + rdata.len() - export_directory;
+        for (offset, value) in [(12, dll_name), (16, 1), (20, exports.len()), (24, named.len()), (28, functions), (32, names), (36, ordinals)] {
+            u32_at(&mut rdata, export_directory + offset, value);
+        }
+
+        let descriptors = alloc(&mut rdata, &vec![0; 20 * (imports.len() + 1)]);
+        for (d, (dll, functions)) in imports.iter().enumerate() {
+            let lookup = alloc(&mut rdata, &vec![0; 8 * (functions.len() + 1)]);
+            let iat = alloc(&mut rdata, &vec![0; 8 * (functions.len() + 1)]);
+            let dll_name = alloc(&mut rdata, &string(dll));
+            for (i, function) in functions.iter().enumerate() {
+                let entry = match function {
+                    FixtureImport::Name(name) => alloc(&mut rdata, &[&[0, 0], string(name).as_slice()].concat()) as u64,
+                    FixtureImport::Ordinal(ordinal) => (1 << 63) | *ordinal as u64,
+                };
+                u64_at(&mut rdata, lookup + i * 8, entry);
+                u64_at(&mut rdata, iat + i * 8, entry);
+            }
+            let descriptor = descriptors + d * 20;
+            u32_at(&mut rdata, descriptor, lookup);
+            u32_at(&mut rdata, descriptor + 12, dll_name);
+            u32_at(&mut rdata, descriptor + 16, iat);
+        }
+        assert!(rdata.len() <= 0x800);
+        put(&mut pe, 0x400, &rdata);
+
+        for (index, (rva, size)) in [(0, (export_directory, export_size)), (1, (descriptors, 20 * (imports.len() + 1))), (5, (relocations, 8))] {
+            put(&mut pe, opt + 112 + index * 8, &(rva as u32).to_le_bytes());
+            put(&mut pe, opt + 116 + index * 8, &(size as u32).to_le_bytes());
+        }
+        let sections: [(&[u8], u32, u32, u32, u32); 2] = [
+            (b".text", 0x1000, 0x200, 0x200, 0x6000_0020),
+            (b".rdata", 0x2000, 0x800, 0x400, 0x4000_0040),
+        ];
+        for (i, (name, rva, size, raw, characteristics)) in sections.iter().enumerate() {
+            let header = 0x148 + i * 40;
+            put(&mut pe, header, name);
+            put(&mut pe, header + 8, &size.to_le_bytes());
+            put(&mut pe, header + 12, &rva.to_le_bytes());
+            put(&mut pe, header + 16, &size.to_le_bytes());
+            put(&mut pe, header + 20, &raw.to_le_bytes());
+            put(&mut pe, header + 36, &characteristics.to_le_bytes());
+        }
+        pe
+    }
+
+    extern "win64" fn fixture_export() -> i32 {
+        1
+    }
+
+    /// Maps a `dll_fixture` and registers its exports under `name` for images linked after it
+    unsafe fn map_fixture_dll(linker: &mut SymbolicLinker, name: &str, pe_data: &[u8]) -> MappedImage {
+        let image = map_pe(pe_data, linker).unwrap();
+        let pe = goblin::pe::PE::parse(pe_data).unwrap();
+        let mapped = std::slice::from_raw_parts(image.base as *const u8, image.size);
+        linker.register_module(name, ExportTable::from_image(&pe, mapped, image.base as usize));
+        image
+    }
+
+    #[test]
+    fn test_linker_follows_ordinals_and_forwarder_chains() {
+        use crate::core::linker::ImportSource;
+        use FixtureExport::{Code, Forwarder};
+
+        let code = fixture_export as *const () as usize;
+        let a_exports = [
+            ("Direct", Code(code)),
+            ("", Code(code)),
+            ("ToB", Forwarder("b.Target")),
+            ("ToBOrdinal", Forwarder("B.#2")),
+            ("Loop", Forwarder("b.Loop")),
+            ("ToKernel32", Forwarder("KERNEL32.GetTickCount")),
+            ("Dangling", Forwarder("b.Missing")),
+            ("Chain", Forwarder("b.ToA")),
+        ];
+        let b_exports = [
+            ("Target", Code(code)),
+            ("", Code(code)),
+            ("Loop", Forwarder("a.Loop")),
+            ("ToA", Forwarder("a.#2")),
+        ];
+        let mut linker = SymbolicLinker::new();
+        let a_image = unsafe { map_fixture_dll(&mut linker, "a.dll", &dll_fixture("a.dll", None, &a_exports, &[])) };
+        let b_image = unsafe { map_fixture_dll(&mut linker, "b.dll", &dll_fixture("b.dll", None, &b_exports, &[])) };
+        let (a, b) = (a_image.base as usize, b_image.base as usize);
+        let get_tick_count = linker.resolve("KERNEL32.dll", "GetTickCount").unwrap();
+        let forwarded = |to: &str| Some(to.to_string());
+
+        assert_eq!(linker.resolve_import("A.DLL", "Direct", None), Some((a + fixture_thunk(1), ImportSource::Export, None)));
+        assert_eq!(linker.resolve_import("a.dll", "#1", Some(1)), Some((a + fixture_thunk(1), ImportSource::Export, None)));
+        assert_eq!(linker.resolve_import("a", "#2", Some(2)), Some((a + fixture_thunk(2), ImportSource::Export, None)));
+        assert_eq!(linker.resolve_import("a.dll", "ToB", None), Some((b + fixture_thunk(1), ImportSource::Export, forwarded("b.Target"))));
+        // Ordinal 3 is ToB, and forwarders can point at ordinals themselves
+        assert_eq!(linker.resolve_import("a.dll", "#3", Some(3)), Some((b + fixture_thunk(1), ImportSource::Export, forwarded("b.Target"))));
+        assert_eq!(linker.resolve_import("a.dll", "ToBOrdinal", None), Some((b + fixture_thunk(2), ImportSource::Export, forwarded("B.#2"))));
+        // The first forwarder taken is reported, not the last
+        assert_eq!(linker.resolve_import("a.dll", "Chain", None), Some((a + fixture_thunk(2), ImportSource::Export, forwarded("b.ToA"))));
+        assert_eq!(linker.resolve_import("a.dll", "ToKernel32", None), Some((get_tick_count, ImportSource::Hle, forwarded("KERNEL32.GetTickCount"))));
+
+        // Cycles and dead ends stay unresolved instead of looping
+        assert_eq!(linker.resolve_import("a.dll", "Loop", None), None);
+        assert_eq!(linker.resolve_import("b.dll", "Loop", None), None);
+        assert_eq!(linker.resolve_import("a.dll", "Dangling", None), None);
+        assert_eq!(linker.resolve_import("a.dll", "#9", Some(9)), None);
+        assert_eq!(linker.resolve_import("a.dll", "direct", None), None);
+        assert_eq!(linker.resolve_import("missing.dll", "Direct", None), None);
+    }
+
+    #[test]
+    fn test_iat_slots_match_their_imports() {
+        use crate::core::linker::ImportSource;
+        use FixtureImport::{Name, Ordinal};
+
+        let code = fixture_export as *const () as usize;
+        let exports = [
+            ("First", FixtureExport::Code(code)),
+            ("", FixtureExport::Code(code)),
+            ("Loop", FixtureExport::Forwarder("a.Loop")),
+        ];
+        let mut linker = SymbolicLinker::new();
+        let a_image = unsafe { map_fixture_dll(&mut linker, "a.dll", &dll_fixture("a.dll", None, &exports, &[])) };
+        let a = a_image.base as usize;
+        // Interleaved sources, so a slot written for the wrong import shows up
+        let imports: [(&str, &[FixtureImport]); 2] = [
+            ("kernel32.dll", &[Name("NotImplementedAnywhere"), Name("GetTickCount")]),
+            ("a.dll", &[Ordinal(2), Name("Loop"), Name("First"), Ordinal(7)]),
+        ];
+        let app = unsafe { map_pe(&dll_fixture("app.dll", None, &[], &imports), &linker) }.unwrap();
+        let coverage = &app.imports;
+        assert_eq!((coverage.total, coverage.hle, coverage.exported, coverage.unresolved), (6, 1, 2, 3));
+
+        let functions: Vec<&str> = coverage.imports.iter().map(|i| i.function.as_str()).collect();
+        assert_eq!(functions, ["NotImplementedAnywhere", "GetTickCount", "#2", "Loop", "First", "#7"]);
+        let get_tick_count = linker.resolve("KERNEL32.dll", "GetTickCount").unwrap();
+        let expected = [None, Some(get_tick_count), Some(a + fixture_thunk(2)), None, Some(a + fixture_thunk(1)), None];
+        let mut stubs = Vec::new();
+        for (import, expected) in coverage.imports.iter().zip(expected) {
+            let slot = unsafe { *((app.base as usize + import.iat_rva as usize) as *const usize) };
+            assert_eq!(slot, import.address, "{}", import.function);
+            match expected {
+                Some(address) => assert_eq!(import.address, address, "{}", import.function),
+                None => {
+                    assert_eq!(import.source, ImportSource::Stub, "{}", import.function);
+                    stubs.push(import.address);
+                }
+            }
+        }
+        // Every unresolved import gets its own stub
+        stubs.dedup();
+        assert_eq!(stubs.len(), 3);
+        assert!(stubs.iter().all(|stub| *stub != 0));
+    }
+
+    #[test]
+    fn test_import_table_lists_static_bound_and_delay_loaded_imports() {
+        use crate::core::loader::{get_import_table, ImportKind};
+        use FixtureImport::{Name, Ordinal};
+
+        let imports: [(&str, &[FixtureImport]); 2] = [
+            ("KERNEL32.dll", &[Name("GetTickCount"), Ordinal(17)]),
+            ("USER32.dll", &[Name("MessageBoxA")]),
+        ];
+        let mut pe = dll_fixture("app.dll", None, &[], &imports);
+        let opt = 0x58;
+
+        // Bound import directory in the headers: KERNEL32.dll was bound against timestamp 0x5F000000
+        put(&mut pe, 0x1C0, &0x5F00_0000u32.to_le_bytes());
+        put(&mut pe, 0x1C4, &16u16.to_le_bytes());
+        put(&mut pe, 0x1D0, b"kernel32.dll\0");
+        put(&mut pe, opt + 112 + 11 * 8, &0x1C0u32.to_le_bytes());
+        put(&mut pe, opt + 116 + 11 * 8, &24u32.to_le_bytes());
+
+        // A delay-load descriptor (RVA format) at the end of .rdata, which maps RVA 0x2000 to offset 0x400
+        let at = |rva: usize| rva - 0x2000 + 0x400;
+        assert!(pe[at(0x2700)..at(0x27C0)].iter().all(|b| *b == 0));
+        for (offset, value) in [(0, 1u32), (4, 0x2740), (12, 0x27A0), (16, 0x2750), (28, 0x1234)] {
+            put(&mut pe, at(0x2700 + offset), &value.to_le_bytes());
+        }
+        put(&mut pe, at(0x2740), b"dinput8.dll\0");
+        put(&mut pe, at(0x2750), &0x2770u64.to_le_bytes());
+        put(&mut pe, at(0x2758), &((1u64 << 63) | 5).to_le_bytes());
+        put(&mut pe, at(0x2770), &7u16.to_le_bytes());
+        put(&mut pe, at(0x2772), b"DirectInput8Create\0");
+        put(&mut pe, opt + 112 + 13 * 8, &0x2700u32.to_le_bytes());
+        put(&mut pe, opt + 116 + 13 * 8, &64u32.to_le_bytes());
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.exe");
+        fs::write(&path, &pe).unwrap();
+        let table = get_import_table(path.to_str().unwrap()).unwrap();
+
+        let dlls: Vec<(&str, ImportKind)> = table.iter().map(|d| (d.dll.as_str(), d.kind)).collect();
+        assert_eq!(dlls, [("KERNEL32.dll", ImportKind::Static), ("USER32.dll", ImportKind::Static), ("dinput8.dll", ImportKind::DelayLoad)]);
+
+        let kernel32 = &table[0];
+        assert_eq!(kernel32.bound_timestamp, Some(0x5F00_0000));
+        let functions: Vec<(&str, Option<u16>)> = kernel32.functions.iter().map(|f| (f.function.as_str(), f.ordinal)).collect();
+        assert_eq!(functions, [("GetTickCount", None), ("#17", Some(17))]);
+        assert_eq!(kernel32.functions[1].iat_rva, kernel32.functions[0].iat_rva + 8);
+        // Bound imports keep the address written into the IAT at link time
+        assert!(kernel32.functions.iter().all(|f| f.address != 0));
+
+        let user32 = &table[1];
+        assert_eq!(user32.bound_timestamp, None);
+        assert_eq!(user32.functions[0].address, 0);
+
+        let dinput = &table[2];
+        assert_eq!(dinput.bound_timestamp, Some(0x1234));
+        let functions: Vec<(&str, Option<u16>, Option<u16>, u32)> = dinput.functions.iter()
+            .map(|f| (f.function.as_str(), f.hint, f.ordinal, f.iat_rva))
+            .collect();
+        assert_eq!(functions, [("DirectInput8Create", Some(7), None, 0x27A0), ("#5", None, Some(5), 0x27A8)]);
+    }
+
+    #[test]
+    fn test_dependency_check_skips_dlls_of_the_wrong_architecture() {
+        use crate::core::dependencies::{DependencyIssue, DependencyResolver, DllSource};
+        use FixtureImport::Name;
+
+        let bottle = tempdir().unwrap();
+        let system32 = bottle.path().join("drive_c/windows/system32");
+        let app_dir = bottle.path().join("drive_c/Games/Game");
+        fs::create_dir_all(&system32).unwrap();
+        fs::create_dir_all(&app_dir).unwrap();
+
+        let code = fixture_export as *const () as usize;
+        let x64 = |name: &str| dll_fixture(name, None, &[("Present", FixtureExport::Code(code))], &[]);
+        let x86 = |name: &str| {
+            let mut dll = x64(name);
+            put(&mut dll, 0x44, &0x14cu16.to_le_bytes());
+            dll
+        };
+        // helper.dll: a 32-bit copy next to the game, the right one in system32
+        fs::write(app_dir.join("helper.dll"), x86("helper.dll")).unwrap();
+        fs::write(system32.join("helper.dll"), x64("helper.dll")).unwrap();
+        // legacy.dll only exists as a 32-bit DLL
+        fs::write(app_dir.join("legacy.dll"), x86("legacy.dll")).unwrap();
+        // A fresh prefix's stand-in for a builtin
+        let mut placeholder = vec![0u8; 0x80];
+        put(&mut placeholder, 0, b"MZ");
+        put(&mut placeholder, 0x40, b"Wine placeholder DLL");
+        fs::write(system32.join("xinput1_3.dll"), placeholder).unwrap();
+
+        let imports: [(&str, &[FixtureImport]); 4] = [
+            ("HELPER.dll", &[Name("Present"), Name("Absent")]),
+            ("legacy.dll", &[Name("Present")]),
+            ("xinput1_3.dll", &[Name("XInputGetState")]),
+            ("d3dx9_43.dll", &[Name("D3DXCreateTexture")]),
+        ];
+        let exe = app_dir.join("game.exe");
+        fs::write(&exe, dll_fixture("game.exe", None, &[], &imports)).unwrap();
+
+        let report = DependencyResolver::check(bottle.path(), &exe, None).unwrap();
+        assert!(report.is_64_bit);
+        let resolved: Vec<(&str, &DllSource)> = report.resolved.iter().map(|r| (r.name.as_str(), &r.source)).collect();
+        assert_eq!(resolved, [("helper.dll", &DllSource::SystemDirectory), ("xinput1_3.dll", &DllSource::WineBuiltin)]);
+        assert_eq!(report.resolved[0].path.as_deref(), Some(system32.join("helper.dll").as_path()));
+
+        // The 32-bit helper.dll is not an issue since system32 has the right one
+        let mismatches: Vec<&str> = report.issues.iter().filter_map(|issue| match issue {
+            DependencyIssue::ArchitectureMismatch { dll_name, found, expected, .. } => {
+                assert_eq!((found.as_str(), expected.as_str()), ("x86", "x86_64"));
+                Some(dll_name.as_str())
+            }
+            _ => None,
+        }).collect();
+        assert_eq!(mismatches, ["legacy.dll"]);
+        let missing: Vec<&str> = report.issues.iter().filter_map(|issue| match issue {
+            DependencyIssue::MissingDll { dll_name, delay_load: false, .. } => Some(dll_name.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(missing, ["legacy.dll", "d3dx9_43.dll"]);
+        assert!(report.issues.iter().any(|issue| matches!(issue,
+            DependencyIssue::MissingExport { dll_name, function, .. } if dll_name == "helper.dll" && function == "Absent")));
+        assert_eq!(report.winetricks, ["d3dx9_43"]);
+    }
+
+    #[test]
+    fn test_import_stubs_report_the_missing_function() {
+        use crate::core::linker::{ImportStubs, UNIMPLEMENTED_IMPORT_EXIT_CODE};
+
+        let stubs = ImportStubs::generate(&["ntdll.dll!NtFirst".to_string(), "user32.dll!Nul\0Name".to_string()]).unwrap();
+        assert_eq!(stubs.address(1) - stubs.address(0), 32);
+
+        // Each stub loads its own "DLL!Function" into rcx before jumping to the shared handler
+        let name_of = |stub: usize| {
+            let code = unsafe { std::slice::from_raw_parts(stub as *const u8, 22) };
+            assert_eq!(&code[0..2], &[0x48, 0xB9]);
+            assert_eq!(&code[10..12], &[0x48, 0xB8]);
+            assert_eq!(&code[20..22], &[0xFF, 0xE0]);
+            let name = u64::from_le_bytes(code[2..10].try_into().unwrap()) as *const std::ffi::c_char;
+            unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy().to_string()
+        };
+        assert_eq!(name_of(stubs.address(0)), "ntdll.dll!NtFirst");
+        assert_eq!(name_of(stubs.address(1)), "user32.dll!NulName");
+        assert_eq!(page_permissions(stubs.address(0)), "r-xp");
+
+        // Calling one reports the import, then ends the process with the dedicated exit code
+        let report = run_as_helper(|| {
+            let stubs = ImportStubs::generate(&["user32.dll!MessageBoxA".to_string()]).unwrap();
+            let stub: extern "win64" fn() = unsafe { std::mem::transmute(stubs.address(0)) };
+            stub();
+        });
+        assert_eq!(report.exit_code, Some(UNIMPLEMENTED_IMPORT_EXIT_CODE));
+        assert_eq!(report.unimplemented.as_deref(), Some("user32.dll!MessageBoxA"));
+        assert_eq!((report.signal, report.error), (None, None));
+    }
+
+    thread_local! {
+        /// (instance, reason) of every fixture DllMain call on this thread
+        static DLL_MAIN_CALLS: std::cell::RefCell<Vec<(usize, u32)>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    /// The LoadLibrary and file shims reach one process-wide loader, so tests that activate one take turns
+    static ACTIVE_LOADER_TESTS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    extern "win64" fn record_dll_main(instance: usize, reason: u32, _reserved: usize) -> i32 {
+        DLL_MAIN_CALLS.with(|calls| calls.borrow_mut().push((instance, reason)));
+        1
+    }
+
+    extern "win64" fn failing_dll_main(instance: usize, reason: u32, reserved: usize) -> i32 {
+        record_dll_main(instance, reason, reserved);
+        0
+    }
+
+    fn dll_main_calls() -> Vec<(usize, u32)> {
+        DLL_MAIN_CALLS.with(|calls| calls.take())
+    }
+
+    #[test]
+    fn test_module_loader_attaches_dependencies_first_and_binds_cycles() {
+        use crate::core::linker::ImportSource;
+        use crate::core::modules::ModuleLoader;
+        use FixtureImport::Name;
+
+        let dir = tempdir().unwrap();
+        let code = record_dll_main as *const () as usize;
+        let dll_main = Some(record_dll_main as *const () as usize);
+        let mut builtin = vec![0u8; 0x60];
+        put(&mut builtin, 0, b"MZ");
+        put(&mut builtin, 0x40, b"Wine builtin DLL");
+        let files = [
+            ("app.dll", dll_fixture("app.dll", dll_main, &[("FromApp", FixtureExport::Code(code))],
+                &[("b.dll", &[Name("FromB")]), ("C.DLL", &[Name("FromC")]), ("KERNEL32.dll", &[Name("GetTickCount")]), ("d3d9.dll", &[Name("Direct3DCreate9")]),
+                    // A second descriptor for b doesn't make it a dependency twice
+                    ("B.dll", &[Name("FromB")])])),
+            ("b.dll", dll_fixture("b.dll", dll_main, &[("FromB", FixtureExport::Code(code))],
+                &[("c.dll", &[Name("FromC")]), ("app.dll", &[Name("FromApp")])])),
+            ("c.dll", dll_fixture("c.dll", dll_main, &[("FromC", FixtureExport::Code(code))], &[])),
+            // Never read: the HLE layer stands in for kernel32
+            ("kernel32.dll", b"not a PE".to_vec()),
+            // Only works inside Wine, so it isn't loaded
+            ("d3d9.dll", builtin),
+        ];
+        for (name, data) in &files {
+            fs::write(dir.path().join(name), data).unwrap();
+        }
+
+        let loader = ModuleLoader::new(dir.path(), None);
+        let base = loader.load(&dir.path().join("app.dll")).unwrap();
+        let modules = loader.modules.borrow();
+        let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["app.dll", "b.dll", "c.dll"]);
+        let [app, b, c] = [&modules[0], &modules[1], &modules[2]];
+        assert_eq!(base, app.base());
+        assert_eq!(app.dependencies, ["b.dll", "c.dll"]);
+        assert_eq!(b.dependencies, ["c.dll", "app.dll"]);
+
+        // Dependencies first; inside the app <-> b cycle, app was reached first so it goes last
+        assert_eq!(dll_main_calls(), [(c.base(), 1), (b.base(), 1), (app.base(), 1)]);
+
+        // Both sides of the cycle are bound to each other's exports
+        let bound = |module: &crate::core::modules::LoadedModule, function: &str| {
+            let import = module.image.imports.imports.iter().find(|i| i.function == function).unwrap();
+            (import.source, import.address)
+        };
+        assert_eq!(bound(b, "FromApp"), (ImportSource::Export, app.base() + fixture_thunk(1)));
+        assert_eq!(bound(app, "FromB"), (ImportSource::Export, b.base() + fixture_thunk(1)));
+        assert_eq!(bound(app, "FromC"), (ImportSource::Export, c.base() + fixture_thunk(1)));
+        assert_eq!(bound(app, "GetTickCount").0, ImportSource::Hle);
+        assert_eq!(bound(app, "Direct3DCreate9").0, ImportSource::Stub);
+        assert_eq!(app.image.imports.emulated_modules, ["kernel32.dll"]);
+
+        // A second load of a loaded module neither maps nor attaches anything
+        let b_base = b.base();
+        drop(modules);
+        assert_eq!(loader.load(&dir.path().join("B.DLL")), Ok(b_base));
+        assert_eq!(loader.modules.borrow().len(), 3);
+        assert!(dll_main_calls().is_empty());
+    }
+
+    #[test]
+    fn test_module_loader_rolls_back_failed_loads() {
+        use crate::core::modules::ModuleLoader;
+        use crate::core::teb::{ProcessEnvironment, ProcessParameters};
+        use FixtureImport::Name;
+
+        let dir = tempdir().unwrap();
+        let dll_main = Some(record_dll_main as *const () as usize);
+        let files = [
+            ("app.dll", dll_fixture("app.dll", dll_main, &[], &[("c.dll", &[Name("FromC")]), ("b.dll", &[Name("FromB")])])),
+            ("b.dll", dll_fixture("b.dll", Some(failing_dll_main as *const () as usize), &[("FromB", FixtureExport::Code(0))], &[])),
+            ("c.dll", dll_fixture("c.dll", dll_main, &[("FromC", FixtureExport::Code(0))], &[])),
+            ("broken_app.dll", dll_fixture("broken_app.dll", dll_main, &[], &[("c.dll", &[Name("FromC")]), ("broken.dll", &[Name("Missing")])])),
+            ("broken.dll", b"MZ, but nothing else".to_vec()),
+        ];
+        for (name, data) in &files {
+            fs::write(dir.path().join(name), data).unwrap();
+        }
+
+        let mut loader = ModuleLoader::new(dir.path(), None);
+        *loader.environment.get_mut() = Some(ProcessEnvironment::new(&ProcessParameters {
+            image_path: "C:\\app.exe".to_string(),
+            command_line: "app.exe".to_string(),
+            current_directory: "C:\\".to_string(),
+            environment: Vec::new(),
+        }).unwrap());
+
+        // b's DllMain fails after c's succeeded: c is detached again and all three are dropped
+        let error = loader.load(&dir.path().join("app.dll")).unwrap_err();
+        assert!(error.contains("b.dll"), "{}", error);
+        let calls = dll_main_calls();
+        assert_eq!(calls.iter().map(|(_, reason)| *reason).collect::<Vec<_>>(), [1, 1, 0]);
+        assert_eq!(calls[0].0, calls[2].0);
+        assert!(loader.modules.borrow().is_empty());
+        assert!(loader.linker.borrow().modules.is_empty());
+        {
+            let environment = loader.environment.borrow();
+            let environment = environment.as_ref().unwrap();
+            assert!(environment.load_order().is_empty());
+            assert!(environment.initialization_order().is_empty());
+        }
+
+        // A dependency that fails to map leaves nothing behind either, and no DllMain runs
+        assert!(loader.load(&dir.path().join("broken_app.dll")).is_err());
+        assert!(loader.modules.borrow().is_empty());
+        assert!(loader.linker.borrow().modules.is_empty());
+        assert!(dll_main_calls().is_empty());
+
+        // The failed loads don't get in the way of loading c on its own
+        let base = loader.load(&dir.path().join("c.dll")).unwrap();
+        assert_eq!(dll_main_calls(), [(base, 1)]);
+        assert_eq!(loader.environment.borrow().as_ref().unwrap().load_order(), ["c.dll"]);
+    }
+
+    #[test]
+    fn test_module_loader_loads_forwarder_targets() {
+        use crate::core::linker::ImportSource;
+        use crate::core::modules::ModuleLoader;
+        use FixtureImport::Name;
+
+        let dir = tempdir().unwrap();
+        let code = record_dll_main as *const () as usize;
+        let dll_main = Some(record_dll_main as *const () as usize);
+        let files = [
+            ("app.dll", dll_fixture("app.dll", dll_main, &[], &[("a.dll", &[Name("ToB")])])),
+            // Nothing imports b or c directly; only a's forwarders lead there
+            ("a.dll", dll_fixture("a.dll", dll_main, &[("ToB", FixtureExport::Forwarder("b.Target")), ("ToC", FixtureExport::Forwarder("C.FromC"))], &[])),
+            ("b.dll", dll_fixture("b.dll", dll_main, &[("Target", FixtureExport::Code(code))], &[])),
+            ("c.dll", dll_fixture("c.dll", dll_main, &[("FromC", FixtureExport::Code(code))], &[])),
+        ];
+        for (name, data) in &files {
+            fs::write(dir.path().join(name), data).unwrap();
+        }
+
+        // Binding app's import maps b in the same load, and b is initialized before app
+        let loader = ModuleLoader::new(dir.path(), None);
+        loader.load(&dir.path().join("app.dll")).unwrap();
+        let (app, a, b) = {
+            let modules = loader.modules.borrow();
+            let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+            assert_eq!(names, ["app.dll", "a.dll", "b.dll"]);
+            assert_eq!(modules[0].dependencies, ["a.dll", "b.dll"]);
+            let import = &modules[0].image.imports.imports[0];
+            assert_eq!((import.source, import.address), (ImportSource::Export, modules[2].base() + fixture_thunk(1)));
+            assert_eq!(import.forwarded_to.as_deref(), Some("b.Target"));
+            (modules[0].base(), modules[1].base(), modules[2].base())
+        };
+        assert_eq!(dll_main_calls(), [(a, 1), (b, 1), (app, 1)]);
+
+        // GetProcAddress on a forwarder into a DLL nobody loaded yet loads and attaches it
+        let to_c = loader.proc_address(a, "ToC").unwrap();
+        let c = loader.module("c.dll").map(|m| m.base()).unwrap();
+        assert_eq!(to_c, c + fixture_thunk(1));
+        assert_eq!(dll_main_calls(), [(c, 1)]);
+        assert_eq!(loader.proc_address(a, "ToC"), Some(to_c));
+        assert_eq!(loader.modules.borrow().len(), 4);
+    }
+
+    /// Fails DLL_PROCESS_ATTACH after loading late.dll itself
+    extern "win64" fn loading_dll_main(instance: usize, reason: u32, reserved: usize) -> i32 {
+        record_dll_main(instance, reason, reserved);
+        let load_library_a: extern "win64" fn(*const std::ffi::c_char) -> usize = kernel32_fn("LoadLibraryA");
+        if reason == 1 {
+            assert_ne!(load_library_a(c"late.dll".as_ptr()), 0);
+        }
+        0
+    }
+
+    #[test]
+    fn test_module_loader_rollback_keeps_modules_loaded_from_dll_main() {
+        use crate::core::modules::ModuleLoader;
+        use crate::core::teb::{ProcessEnvironment, ProcessParameters};
+        use FixtureImport::Name;
+
+        let dir = tempdir().unwrap();
+        let dll_main = Some(record_dll_main as *const () as usize);
+        let files = [
+            ("app.dll", dll_fixture("app.dll", dll_main, &[], &[("loader.dll", &[Name("Export")])])),
+            ("loader.dll", dll_fixture("loader.dll", Some(loading_dll_main as *const () as usize), &[("Export", FixtureExport::Code(0))], &[])),
+            ("late.dll", dll_fixture("late.dll", dll_main, &[], &[])),
+        ];
+        for (name, data) in &files {
+            fs::write(dir.path().join(name), data).unwrap();
+        }
+
+        let _active = ACTIVE_LOADER_TESTS.lock().unwrap_or_else(|e| e.into_inner());
+        let mut loader = ModuleLoader::new(dir.path(), None);
+        *loader.environment.get_mut() = Some(ProcessEnvironment::new(&ProcessParameters {
+            image_path: "C:\\app.exe".to_string(),
+            command_line: "app.exe".to_string(),
+            current_directory: "C:\\".to_string(),
+            environment: Vec::new(),
+        }).unwrap());
+        loader.activate();
+
+        // loader.dll's DllMain loads late.dll and then fails: app and loader.dll go, late.dll stays
+        assert!(loader.load(&dir.path().join("app.dll")).is_err());
+        let late = loader.module("late.dll").map(|m| m.base()).unwrap();
+        let calls = dll_main_calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!((calls[1], calls[0].1), ((late, 1), 1));
+        let names: Vec<String> = loader.modules.borrow().iter().map(|m| m.name.clone()).collect();
+        assert_eq!(names, ["late.dll"]);
+        assert!(loader.module("late.dll").unwrap().attached);
+        assert!(loader.linker.borrow().modules.contains_key("late.dll"));
+        assert!(!loader.linker.borrow().modules.contains_key("loader.dll"));
+        assert_eq!(loader.environment.borrow().as_ref().unwrap().load_order(), ["late.dll"]);
+    }
+
+    /// Looks a kernel32 function up the way the IAT binder does and casts it to its Win64 signature
+    fn kernel32_fn<F: Copy>(function: &str) -> F {
+        assert_eq!(std::mem::size_of::<F>(), std::mem::size_of::<usize>());
+        let address = SymbolicLinker::new().resolve("KERNEL32.dll", function).unwrap();
+        unsafe { std::mem::transmute_copy(&address) }
+    }
+
+    #[test]
+    fn test_hle_get_system_info() {
+        let get_system_info: extern "win64" fn(*mut SystemInfo) = kernel32_fn("GetSystemInfo");
+        let mut info = SystemInfo::default();
+        get_system_info(&mut info);
+
+        assert_eq!(std::mem::size_of::<SystemInfo>(), 48);
+        assert_eq!(info.processor_architecture, 9);
+        assert_eq!(info.page_size, 4096);
+        assert_eq!(info.allocation_granularity, 0x10000);
+        assert!(info.number_of_processors >= 1);
+        assert_eq!(info.active_processor_mask.count_ones(), info.number_of_processors);
+        assert!(info.minimum_application_address < info.maximum_application_address);
+    }
+
+    #[test]
+    fn test_hle_timers() {
+        let get_tick_count: extern "win64" fn() -> u32 = kernel32_fn("GetTickCount");
+        let get_tick_count64: extern "win64" fn() -> u64 = kernel32_fn("GetTickCount64");
+        let query_counter: extern "win64" fn(*mut i64) -> i32 = kernel32_fn("QueryPerformanceCounter");
+        let query_frequency: extern "win64" fn(*mut i64) -> i32 = kernel32_fn("QueryPerformanceFrequency");
+        let get_last_error: extern "win64" fn() -> u32 = kernel32_fn("GetLastError");
+
+        let (ticks, ticks64) = (get_tick_count(), get_tick_count64());
+        let (mut frequency, mut start, mut end) = (0i64, 0i64, 0i64);
+        assert_eq!(query_frequency(&mut frequency), 1);
+        assert_eq!(query_counter(&mut start), 1);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(query_counter(&mut end), 1);
+
+        // Regression: the old shim measured an Instant it had just created and always returned 0
+        assert!(ticks64 > 0);
+        assert_eq!(ticks, ticks64 as u32);
+        assert!(get_tick_count64() - ticks64 >= 45);
+        assert!(get_tick_count().wrapping_sub(ticks) >= 45);
+        assert_eq!(frequency, 10_000_000);
+        let elapsed_ms = (end - start) * 1000 / frequency;
+        assert!((45..1000).contains(&elapsed_ms));
+
+        assert_eq!(query_counter(std::ptr::null_mut()), 0);
+        assert_eq!(get_last_error(), kernel32::ERROR_INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_hle_process_thread_and_last_error() {
+        let get_process_id: extern "win64" fn() -> u32 = kernel32_fn("GetCurrentProcessId");
+        let get_thread_id: extern "win64" fn() -> u32 = kernel32_fn("GetCurrentThreadId");
+        let get_last_error: extern "win64" fn() -> u32 = kernel32_fn("GetLastError");
+        let set_last_error: extern "win64" fn(u32) = kernel32_fn("SetLastError");
+
+        assert_eq!(get_process_id(), std::process::id());
+        let thread_id = get_thread_id();
+        assert_eq!(thread_id, get_thread_id());
+        assert_eq!(thread_id % 4, 0);
+
+        set_last_error(1234);
+        assert_eq!(get_last_error(), 1234);
+        // Both are per thread
+        let (other_thread_id, other_error) = std::thread::spawn(move || (get_thread_id(), get_last_error())).join().unwrap();
+        assert_ne!(other_thread_id, thread_id);
+        assert_eq!(other_error, 0);
+        assert_eq!(get_last_error(), 1234);
+    }
+
+    #[test]
+    fn test_hle_heap() {
+        let get_process_heap: extern "win64" fn() -> usize = kernel32_fn("GetProcessHeap");
+        let heap_alloc: extern "win64" fn(usize, u32, usize) -> *mut c_void = kernel32_fn("HeapAlloc");
+        let heap_free: extern "win64" fn(usize, u32, *mut c_void) -> i32 = kernel32_fn("HeapFree");
+
+        let heap = get_process_heap();
+        assert_ne!(heap, 0);
+        assert_eq!(heap, get_process_heap());
+
+        let block = heap_alloc(heap, kernel32::HEAP_ZERO_MEMORY, 256);
+        assert!(!block.is_null());
+        assert_eq!(block as usize % 16, 0);
+        let bytes = unsafe { std::slice::from_raw_parts_mut(block as *mut u8, 256) };
+        assert!(bytes.iter().all(|b| *b == 0));
+        bytes.fill(0x5A);
+        assert_eq!(heap_free(heap, 0, block), 1);
+
+        let empty = heap_alloc(heap, 0, 0);
+        assert!(!empty.is_null());
+        assert_eq!(heap_free(heap, 0, empty), 1);
+        assert_eq!(heap_free(heap, 0, std::ptr::null_mut()), 1);
+    }
+
+    #[test]
+    fn test_hle_virtual_alloc() {
+        let virtual_alloc: extern "win64" fn(*mut c_void, usize, u32, u32) -> *mut c_void = kernel32_fn("VirtualAlloc");
+        let virtual_free: extern "win64" fn(*mut c_void, usize, u32) -> i32 = kernel32_fn("VirtualFree");
+        let get_last_error: extern "win64" fn() -> u32 = kernel32_fn("GetLastError");
+
+        // Reserve, then commit a page in the middle
+        let reserved = virtual_alloc(std::ptr::null_mut(), 0x40000, vmm::MEM_RESERVE, vmm::PAGE_NOACCESS);
+        assert!(!reserved.is_null());
+        assert_eq!(page_permissions(reserved as usize), "---p");
+        let page = (reserved as usize + 0x2000) as *mut c_void;
+        assert_eq!(virtual_alloc(page, 0x1000, vmm::MEM_COMMIT, vmm::PAGE_READWRITE), page);
+        assert_eq!(page_permissions(page as usize), "rw-p");
+        unsafe { *(page as *mut u64) = 0xDEAD_BEEF };
+
+        // Decommitting drops the contents
+        assert_eq!(virtual_free(page, 0x1000, vmm::MEM_DECOMMIT), 1);
+        assert_eq!(page_permissions(page as usize), "---p");
+        assert_eq!(virtual_alloc(page, 0x1000, vmm::MEM_COMMIT, vmm::PAGE_READONLY), page);
+        assert_eq!(unsafe { *(page as *const u64) }, 0);
+        assert_eq!(page_permissions(page as usize), "r--p");
+
+        // MEM_RELEASE needs the base and size 0
+        assert_eq!(virtual_free(page, 0, vmm::MEM_RELEASE), 0);
+        assert_eq!(get_last_error(), kernel32::ERROR_INVALID_ADDRESS);
+        assert_eq!(virtual_free(reserved, 0, vmm::MEM_RELEASE), 1);
+
+        // Committing outside any reservation fails
+        assert!(virtual_alloc(page, 0x1000, vmm::MEM_COMMIT, vmm::PAGE_READWRITE).is_null());
+        assert_eq!(get_last_error(), kernel32::ERROR_INVALID_ADDRESS);
+
+        let committed = virtual_alloc(std::ptr::null_mut(), 100, vmm::MEM_RESERVE | vmm::MEM_COMMIT, vmm::PAGE_EXECUTE_READWRITE);
+        assert_eq!(committed as usize % 0x1000, 0);
+        assert_eq!(page_permissions(committed as usize), "rwxp");
+        assert_eq!(virtual_free(committed, 0, vmm::MEM_RELEASE), 1);
+    }
+
+    #[test]
+    fn test_hle_virtual_protect_and_query() {
+        let virtual_alloc: extern "win64" fn(*mut c_void, usize, u32, u32) -> *mut c_void = kernel32_fn("VirtualAlloc");
+        let virtual_free: extern "win64" fn(*mut c_void, usize, u32) -> i32 = kernel32_fn("VirtualFree");
+        let virtual_protect: extern "win64" fn(*mut c_void, usize, u32, *mut u32) -> i32 = kernel32_fn("VirtualProtect");
+        let virtual_query: extern "win64" fn(*const c_void, *mut kernel32::MemoryBasicInformation, usize) -> usize = kernel32_fn("VirtualQuery");
+        assert_eq!(std::mem::size_of::<kernel32::MemoryBasicInformation>(), 48);
+
+        let base = virtual_alloc(std::ptr::null_mut(), 0x2000, vmm::MEM_RESERVE | vmm::MEM_COMMIT, vmm::PAGE_READWRITE);
+        let mut old = 0;
+        assert_eq!(virtual_protect(base, 0x1000, vmm::PAGE_READONLY, &mut old), 1);
+        assert_eq!(old, vmm::PAGE_READWRITE);
+
+        let mut info = kernel32::MemoryBasicInformation::default();
+        assert_eq!(virtual_query(base, &mut info, std::mem::size_of_val(&info)), 48);
+        assert_eq!((info.base_address, info.allocation_base), (base as usize, base as usize));
+        assert_eq!((info.region_size, info.state, info.protect, info.mem_type), (0x1000, vmm::MEM_COMMIT, vmm::PAGE_READONLY, vmm::MEM_PRIVATE));
+        assert_eq!(virtual_free(base, 0, vmm::MEM_RELEASE), 1);
+    }
+
+    #[test]
+    fn test_vmm_reserve_commit_and_query() {
+        let mut vmm = VirtualMemoryManager::new();
+        let base = vmm.reserve(0, 0x30000, vmm::PAGE_READWRITE, RegionType::Private, "test").unwrap();
+        assert_eq!(base as usize % vmm::ALLOCATION_GRANULARITY, 0);
+
+        let info = vmm.query(base + 0x10).unwrap();
+        assert_eq!((info.base_address, info.allocation_base), (base, base));
+        assert_eq!((info.state, info.region_size, info.protect), (RegionState::Reserve, 0x30000, 0));
+        assert_eq!(info.kind, Some(RegionType::Private));
+
+        // Committing a page in the middle splits the reservation into three runs
+        assert_eq!(vmm.commit(base + 0x1800, 0x10, vmm::PAGE_READWRITE), Ok(base + 0x1000));
+        assert_eq!(page_permissions(base as usize + 0x1000), "rw-p");
+        assert_eq!(vmm.query(base).unwrap().region_size, 0x1000);
+        let committed = vmm.query(base + 0x1000).unwrap();
+        assert_eq!((committed.state, committed.region_size, committed.protect), (RegionState::Commit, 0x1000, vmm::PAGE_READWRITE));
+        assert_eq!(vmm.query(base + 0x2000).unwrap().region_size, 0x2E000);
+        unsafe { *((base + 0x1000) as *mut u64) = 0xDEAD_BEEF };
+
+        // Decommit drops the contents; a recommit comes back zeroed
+        vmm.decommit(base + 0x1000, 0x1000).unwrap();
+        assert_eq!(vmm.query(base + 0x1000).unwrap().state, RegionState::Reserve);
+        assert_eq!(page_permissions(base as usize + 0x1000), "---p");
+        vmm.commit(base + 0x1000, 0x1000, vmm::PAGE_READONLY).unwrap();
+        assert_eq!(unsafe { *((base + 0x1000) as *const u64) }, 0);
+
+        // The gap after a reservation is free up to whatever comes next
+        let free = vmm.query(base + 0x30000).unwrap();
+        assert_eq!((free.state, free.allocation_base, free.kind), (RegionState::Free, 0, None));
+
+        assert_eq!(vmm.release(base + 0x1000), Err(VmError::InvalidAddress));
+        vmm.release(base).unwrap();
+        assert_eq!(vmm.query(base).unwrap().state, RegionState::Free);
+        assert_eq!(vmm.commit(base, 0x1000, vmm::PAGE_READWRITE), Err(VmError::InvalidAddress));
+    }
+
+    #[test]
+    fn test_vmm_granularity_and_overlap() {
+        let mut vmm = VirtualMemoryManager::new();
+        // Find a free 64K-aligned spot, then ask for it back at an unaligned address
+        let spot = vmm.reserve(0, 0x40000, vmm::PAGE_NOACCESS, RegionType::Private, "probe").unwrap();
+        vmm.release(spot).unwrap();
+
+        let base = vmm.reserve(spot + 0x1234, 0x2000, vmm::PAGE_READWRITE, RegionType::Private, "test").unwrap();
+        assert_eq!(base, spot);
+        let info = vmm.query(base).unwrap();
+        assert_eq!(info.region_size, 0x4000);
+
+        // Anything touching an existing reservation is refused
+        assert_eq!(vmm.reserve(spot + 0x2000, 0x1000, vmm::PAGE_READWRITE, RegionType::Private, "overlap"), Err(VmError::InvalidAddress));
+        assert_eq!(vmm.commit(base + 0x3000, 0x2000, vmm::PAGE_READWRITE), Err(VmError::InvalidAddress));
+        assert_eq!(vmm.reserve(0, 0, vmm::PAGE_READWRITE, RegionType::Private, "empty"), Err(VmError::InvalidParameter));
+        assert_eq!(vmm.reserve(0, 0x1000, 0x3, RegionType::Private, "bad protect"), Err(VmError::InvalidParameter));
+
+        // The next granule is still available
+        let next = vmm.reserve(spot + 0x10000, 0x1000, vmm::PAGE_READWRITE, RegionType::Private, "next").unwrap();
+        assert_eq!(next, spot + 0x10000);
+        assert_eq!(vmm.query(next).unwrap().region_size, 0x1000);
+    }
+
+    #[test]
+    fn test_vmm_protect_and_guard_pages() {
+        let mut vmm = VirtualMemoryManager::new();
+        let base = vmm.allocate(0, 0x3000, vmm::MEM_RESERVE, vmm::PAGE_READWRITE).unwrap();
+        vmm.commit(base, 0x2000, vmm::PAGE_READWRITE).unwrap();
+
+        assert_eq!(vmm.protect(base, 0x1000, vmm::PAGE_EXECUTE_READ), Ok(vmm::PAGE_READWRITE));
+        assert_eq!(page_permissions(base as usize), "r-xp");
+        // Reserved pages can't change protection
+        assert_eq!(vmm.protect(base + 0x1000, 0x2000, vmm::PAGE_READONLY), Err(VmError::InvalidAddress));
+        assert_eq!(page_permissions(base as usize + 0x1000), "rw-p");
+
+        // A guard page is inaccessible until its first touch, then reverts to its base protection
+        vmm.protect(base + 0x1000, 0x1000, vmm::PAGE_READWRITE | vmm::PAGE_GUARD).unwrap();
+        assert_eq!(page_permissions(base as usize + 0x1000), "---p");
+        assert_eq!(vmm.query(base + 0x1000).unwrap().protect, vmm::PAGE_READWRITE | vmm::PAGE_GUARD);
+        assert!(!vmm.take_guard_page(base + 0x10));
+        assert!(vmm.take_guard_page(base + 0x1010));
+        assert_eq!(page_permissions(base as usize + 0x1000), "rw-p");
+        assert_eq!(vmm.query(base + 0x1000).unwrap().protect, vmm::PAGE_READWRITE);
+        assert!(!vmm.take_guard_page(base + 0x1010));
+
+        assert_eq!(vmm.protect(base, 0x1000, vmm::PAGE_NOACCESS | vmm::PAGE_GUARD), Err(VmError::InvalidParameter));
+        assert_eq!(vmm.free(base, 0x1000, vmm::MEM_RELEASE), Err(VmError::InvalidParameter));
+        vmm.free(base, 0, vmm::MEM_RELEASE).unwrap();
+    }
+
+    #[test]
+    fn test_teb_and_peb_installed_as_gs_base() {
+        use crate::core::teb::{read_unicode_string, ProcessEnvironment, ProcessParameters, ThreadEnvironment};
+
+        // GS is per thread, so the test thread's own state is left alone
+        std::thread::spawn(|| {
+            let mut process = ProcessEnvironment::new(&ProcessParameters {
+                image_path: "C:\\Games\\game.exe".to_string(),
+                command_line: "\"C:\\Games\\game.exe\" -windowed".to_string(),
+                current_directory: "C:\\Games".to_string(),
+                environment: vec![("windir".to_string(), "C:\\windows".to_string()), ("ComSpec".to_string(), "cmd.exe".to_string())],
+            }).unwrap();
+            process.add_module(0x1_4000_0000, 0x5000, Some(0x1_4000_1000), false, "C:\\Games\\game.exe").unwrap();
+            process.add_module(0x1_8000_0000, 0x3000, None, true, "C:\\Games\\steam_api64.dll").unwrap();
+            process.add_module(0x1_9000_0000, 0x3000, None, true, "C:\\Games\\dep.dll").unwrap();
+            process.mark_initialized(0x1_9000_0000);
+            process.mark_initialized(0x1_8000_0000);
+            process.mark_initialized(0x1_4000_0000);
+
+            let peb = process.peb();
+            assert_eq!(peb.image_base_address, 0x1_4000_0000);
+            assert_eq!((peb.os_major_version, peb.os_build_number), (10, 19045));
+            assert_eq!(process.load_order(), ["game.exe", "steam_api64.dll", "dep.dll"]);
+            assert_eq!(process.memory_order(), process.load_order());
+            // The executable never appears in the initialization order list
+            assert_eq!(process.initialization_order(), ["dep.dll", "steam_api64.dll"]);
+
+            let params = process.parameters();
+            assert_eq!(read_unicode_string(&params.command_line), "\"C:\\Games\\game.exe\" -windowed");
+            assert_eq!(read_unicode_string(&params.current_directory.dos_path), "C:\\Games\\");
+            let block = unsafe { std::slice::from_raw_parts(params.environment as *const u16, params.environment_size / 2) };
+            assert_eq!(String::from_utf16_lossy(block), "ComSpec=cmd.exe\0windir=C:\\windows\0\0");
+
+            let stack = [0u8; 64];
+            let stack_limit = stack.as_ptr() as usize;
+            let thread = ThreadEnvironment::new(&process, stack_limit + stack.len(), stack_limit).unwrap();
+            thread.install().unwrap();
+
+            let (teb_self, peb_pointer, stack_base): (usize, usize, usize);
+            unsafe {
+                std::arch::asm!("mov {}, gs:[0x30]", out(reg) teb_self);
+                std::arch::asm!("mov {}, gs:[0x60]", out(reg) peb_pointer);
+                std::arch::asm!("mov {}, gs:[0x08]", out(reg) stack_base);
+            }
+            assert_eq!(teb_self, thread.teb);
+            assert_eq!(peb_pointer, process.peb);
+            assert_eq!(stack_base, stack_limit + stack.len());
+            assert_eq!(thread.teb().client_id.unique_process, std::process::id() as usize);
+
+            // SetLastError lands in TEB.LastErrorValue, where Windows code reads it inline
+            let set_last_error: extern "win64" fn(u32) = kernel32_fn("SetLastError");
+            let get_last_error: extern "win64" fn() -> u32 = kernel32_fn("GetLastError");
+            set_last_error(1234);
+            let last_error: u32;
+            unsafe { std::arch::asm!("mov {:e}, gs:[0x68]", out(reg) last_error) };
+            assert_eq!(last_error, 1234);
+            assert_eq!(get_last_error(), 1234);
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn test_hle_output_debug_string() {
+        let output_a: extern "win64" fn(*const std::ffi::c_char) = kernel32_fn("OutputDebugStringA");
+        let output_w: extern "win64" fn(*const u16) = kernel32_fn("OutputDebugStringW");
+
+        output_a(c"narrow message\n".as_ptr());
+        let wide: Vec<u16> = "wide message \u{00e9}\0".encode_utf16().collect();
+        output_w(wide.as_ptr());
+        output_a(std::ptr::null());
+        output_w(std::ptr::null());
+    }
+
+    #[test]
+    fn test_hle_exit_process() {
+        let exit_process: extern "win64" fn(u32) -> ! = kernel32_fn("ExitProcess");
+
+        // ExitProcess ends the whole process, so call it in a forked child
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            exit_process(42);
+        }
+        let mut status = 0;
+        unsafe { libc::waitpid(pid, &mut status, 0) };
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 42);
+    }
+
+    #[test]
+    fn test_hle_load_library_and_get_proc_address() {
+        use crate::core::kernel32::{ERROR_MOD_NOT_FOUND, ERROR_PROC_NOT_FOUND};
+        use crate::core::modules::ModuleLoader;
+        use std::ffi::c_char;
+
+        let dir = tempdir().unwrap();
+        let exports = [
+            ("FromC", FixtureExport::Code(record_dll_main as *const () as usize)),
+            ("", FixtureExport::Code(failing_dll_main as *const () as usize)),
+        ];
+        fs::write(dir.path().join("c.dll"), dll_fixture("c.dll", None, &exports, &[])).unwrap();
+
+        let load_library_a: extern "win64" fn(*const c_char) -> usize = kernel32_fn("LoadLibraryA");
+        let load_library_w: extern "win64" fn(*const u16) -> usize = kernel32_fn("LoadLibraryW");
+        let get_module_handle_a: extern "win64" fn(*const c_char) -> usize = kernel32_fn("GetModuleHandleA");
+        let get_module_handle_w: extern "win64" fn(*const u16) -> usize = kernel32_fn("GetModuleHandleW");
+        let get_proc_address: extern "win64" fn(usize, *const c_char) -> usize = kernel32_fn("GetProcAddress");
+        let get_last_error: extern "win64" fn() -> u32 = kernel32_fn("GetLastError");
+        let wide = |s: &str| s.encode_utf16().chain([0]).collect::<Vec<u16>>();
+
+        let _active = ACTIVE_LOADER_TESTS.lock().unwrap_or_else(|e| e.into_inner());
+        let loader = ModuleLoader::new(dir.path(), None);
+        loader.activate();
+
+        let c = load_library_a(c"c.dll".as_ptr());
+        assert_ne!(c, 0);
+        assert_eq!(Some(c), loader.module("c.dll").map(|m| m.base()));
+        assert_eq!(load_library_w(wide("C.DLL").as_ptr()), c);
+        assert_eq!(get_module_handle_a(c"c".as_ptr()), c);
+        assert_eq!(get_module_handle_w(std::ptr::null()), c);
+
+        assert_eq!(get_proc_address(c, c"FromC".as_ptr()), c + fixture_thunk(1));
+        assert_eq!(get_proc_address(c, 2 as *const c_char), c + fixture_thunk(2));
+        assert_eq!(get_proc_address(c, c"fromc".as_ptr()), 0);
+        assert_eq!(get_last_error(), ERROR_PROC_NOT_FOUND);
+
+        // kernel32 is never mapped but still has a stable handle that GetProcAddress accepts
+        let kernel32 = get_module_handle_a(c"kernel32.dll".as_ptr());
+        assert_ne!(kernel32, 0);
+        assert_eq!(load_library_a(c"C:\\windows\\system32\\KERNEL32".as_ptr()), kernel32);
+        let get_tick_count: usize = kernel32_fn("GetTickCount");
+        assert_eq!(get_proc_address(kernel32, c"GetTickCount".as_ptr()), get_tick_count);
+        assert_eq!(vmm::process_memory().query(kernel32 as u64).and_then(|info| info.kind), Some(RegionType::Image));
+
+        assert_eq!(load_library_a(c"missing.dll".as_ptr()), 0);
+        assert_eq!(get_last_error(), ERROR_MOD_NOT_FOUND);
+        assert_eq!(get_module_handle_a(c"missing.dll".as_ptr()), 0);
+        assert_eq!(get_last_error(), ERROR_MOD_NOT_FOUND);
+
+        // Nothing answers once the loader is gone
+        drop(loader);
+        assert_eq!(get_module_handle_a(c"c.dll".as_ptr()), 0);
+    }
+
+    /// Bottle with Wine's drive symlinks: C: and a D: inside drive_c (both relative, like
+    /// winecfg writes them), E: at an absolute host directory, Z: at / and one UNC share
+    fn bottle_with_drives() -> tempfile::TempDir {
+        use std::os::unix::fs::symlink;
+
+        let bottle = tempdir().unwrap();
+        let root = bottle.path();
+        fs::create_dir_all(root.join("drive_c/Program Files/Steam")).unwrap();
+        fs::write(root.join("drive_c/Program Files/Steam/steam.exe"), b"MZ").unwrap();
+        fs::create_dir_all(root.join("drive_c/Games/Café")).unwrap();
+        fs::create_dir_all(root.join("media/SteamLibrary")).unwrap();
+        fs::create_dir_all(root.join("dosdevices/unc/nas/share/Setup")).unwrap();
+        symlink("../drive_c", root.join("dosdevices/c:")).unwrap();
+        symlink("../drive_c/Games", root.join("dosdevices/d:")).unwrap();
+        symlink(root.join("media"), root.join("dosdevices/e:")).unwrap();
+        symlink("/", root.join("dosdevices/z:")).unwrap();
+        // Raw devices behind a drive are not drives themselves
+        symlink("/dev/null", root.join("dosdevices/e::")).unwrap();
+        bottle
+    }
+
+    #[test]
+    fn test_drive_map_windows_to_host() {
+        use crate::wine::paths::{is_windows_path, resolve_relative, DriveMap};
+
+        let bottle = bottle_with_drives();
+        let root = bottle.path();
+        let drives = DriveMap::load(root);
+        let host = |path: &str| drives.to_host(path).map(|p| p.components().collect::<PathBuf>());
+        let expected = |path: PathBuf| Some(path.components().collect::<PathBuf>());
+
+        // Components match case-insensitively; the missing tail is kept as written
+        assert_eq!(host("c:\\PROGRAM FILES\\steam\\STEAM.EXE"), expected(root.join("drive_c/Program Files/Steam/steam.exe")));
+        assert_eq!(host("C:/Program Files/Steam/New Folder/Log.TXT"), expected(root.join("drive_c/Program Files/Steam/New Folder/Log.TXT")));
+        assert_eq!(host("C:\\games\\CAFÉ"), expected(root.join("drive_c/Games/Café")));
+
+        // `..` stops at the drive root
+        assert_eq!(host("C:\\..\\..\\Program Files"), expected(root.join("drive_c/Program Files")));
+        assert_eq!(host("C:\\Games\\..\\..\\..\\Games\\.\\café"), expected(root.join("drive_c/Games/Café")));
+        assert_eq!(host("E:\\..\\SteamLibrary"), expected(root.join("media/SteamLibrary")));
+        assert_eq!(host("Z:\\..\\tmp"), Some(PathBuf::from("/tmp")));
+
+        // UNC and the NT namespace prefixes
+        assert_eq!(host("\\\\NAS\\Share\\setup\\x.msi"), expected(root.join("dosdevices/unc/nas/share/Setup/x.msi")));
+        assert_eq!(host("\\\\?\\UNC\\nas\\share\\Setup"), expected(root.join("dosdevices/unc/nas/share/Setup")));
+        assert_eq!(host("\\\\?\\C:\\Games"), expected(root.join("drive_c/Games")));
+        assert_eq!(host("\\??\\c:\\games"), expected(root.join("drive_c/Games")));
+        assert_eq!(host("\\\\?\\unix\\tmp"), Some(PathBuf::from("/tmp")));
+
+        // Unmapped drives and relative paths have no host location
+        assert_eq!(drives.to_host("Q:\\Games"), None);
+        assert_eq!(drives.to_host("Games\\x.exe"), None);
+        assert_eq!(drives.drive_root('E'), Some(root.join("media").as_path()));
+        assert!(drives.drive_root('f').is_none());
+
+        assert_eq!(resolve_relative(&root.join("drive_c"), "..\\PROGRAM FILES/steam"), root.join("drive_c/Program Files/Steam"));
+        assert!(is_windows_path("c:\\x") && is_windows_path("\\\\server\\share"));
+        assert!(!is_windows_path("/home/user") && !is_windows_path("x.exe"));
+    }
+
+    #[test]
+    fn test_drive_map_host_to_windows() {
+        use crate::wine::paths::DriveMap;
+
+        let bottle = bottle_with_drives();
+        let root = bottle.path();
+        let drives = DriveMap::load(root);
+
+        assert_eq!(drives.to_windows(&root.join("drive_c/Program Files/Steam/steam.exe")).as_deref(), Some("C:\\Program Files\\Steam\\steam.exe"));
+        // The most specific drive wins: D: over C:, E: over Z:
+        assert_eq!(drives.to_windows(&root.join("drive_c/Games/Café")).as_deref(), Some("D:\\Café"));
+        assert_eq!(drives.to_windows(&root.join("media/SteamLibrary/../SteamLibrary")).as_deref(), Some("E:\\SteamLibrary"));
+        assert_eq!(drives.to_windows(&root.join("dosdevices/c:/Games")).as_deref(), Some("C:\\Games"));
+        assert_eq!(drives.to_windows(Path::new("/usr/share")).as_deref(), Some("Z:\\usr\\share"));
+        assert_eq!(drives.to_windows(&root.join("dosdevices/unc/nas/share/Setup")).as_deref(), Some("\\\\nas\\share\\Setup"));
+
+        // Round trip through both directions
+        let windows = drives.to_windows(&root.join("drive_c/Program Files/Steam")).unwrap();
+        assert_eq!(drives.to_host(&windows).unwrap().components().collect::<Vec<_>>(), root.join("drive_c/Program Files/Steam").components().collect::<Vec<_>>());
+
+        // A prefix wineboot hasn't set up yet gets C: and Z: by default
+        let fresh = tempdir().unwrap();
+        let defaults = DriveMap::load(fresh.path());
+        assert_eq!(defaults.drive_root('c'), Some(fresh.path().join("drive_c").as_path()));
+        assert_eq!(defaults.drive_root('z'), Some(Path::new("/")));
+        assert_eq!(defaults.to_windows(&fresh.path().join("drive_c/windows")).as_deref(), Some("C:\\windows"));
+
+        // Without Z: nothing outside the drives has a Windows name
+        let no_z = tempdir().unwrap();
+        fs::create_dir_all(no_z.path().join("dosdevices")).unwrap();
+        std::os::unix::fs::symlink("../drive_c", no_z.path().join("dosdevices/c:")).unwrap();
+        assert_eq!(DriveMap::load(no_z.path()).to_windows(Path::new("/usr/share")), None);
+        assert!(crate::wine::paths::host_to_windows_path(no_z.path(), Path::new("/usr/share")).is_err());
+    }
+
+    /// MS-SHLLINK file pointing at `base` + `suffix` through LinkInfo, with an ID list to skip
+    /// and Unicode string data. `unicode_info` adds the Unicode copies of the LinkInfo paths.
+    fn shell_link_fixture(base: &str, suffix: &str, unicode_info: bool, relative: Option<&str>, arguments: &str) -> Vec<u8> {
+        let mut flags: u32 = 0x01 | 0x02 | 0x10 | 0x20 | 0x80;
+        if relative.is_some() {
+            flags |= 0x08;
+        }
+        let mut data = vec![0u8; 0x4C];
+        put(&mut data, 0, &0x4Cu32.to_le_bytes());
+        put(&mut data, 4, &[0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46]);
+        put(&mut data, 20, &flags.to_le_bytes());
+        // IDList: one opaque item, then the terminator
+        data.extend_from_slice(&8u16.to_le_bytes());
+        data.extend_from_slice(&[6, 0, 0x1F, 0x50, 0, 0, 0, 0]);
+
+        let ansi = |text: &str| text.chars().map(|c| c as u8).chain([0]).collect::<Vec<u8>>();
+        let wide = |text: &str| text.encode_utf16().chain([0]).flat_map(|u| u.to_le_bytes()).collect::<Vec<u8>>();
+        let header_size: u32 = if unicode_info { 0x24 } else { 0x1C };
+        let volume_id = [0x11u32.to_le_bytes(), 3u32.to_le_bytes(), 0x1234u32.to_le_bytes(), 0x10u32.to_le_bytes()].concat();
+        let mut info = vec![0u8; header_size as usize];
+        let volume_offset = info.len();
+        info.extend_from_slice(&volume_id);
+        info.push(0);
+        let base_offset = info.len();
+        info.extend_from_slice(&ansi(base));
+        let suffix_offset = info.len();
+        info.extend_from_slice(&ansi(suffix));
+        let mut offsets = vec![(4, header_size), (8, 1), (12, volume_offset as u32), (16, base_offset as u32), (24, suffix_offset as u32)];
+        if unicode_info {
+            let base_unicode = info.len();
+            info.extend_from_slice(&wide(base));
+            let suffix_unicode = info.len();
+            info.extend_from_slice(&wide(suffix));
+            offsets.extend([(28, base_unicode as u32), (32, suffix_unicode as u32)]);
+        }
+        offsets.push((0, info.len() as u32));
+        for (offset, value) in offsets {
+            put(&mut info, offset, &value.to_le_bytes());
+        }
+        data.extend_from_slice(&info);
+
+        let strings = [relative, Some("C:\\Games"), Some(arguments)];
+        for text in strings.into_iter().flatten() {
+            let units: Vec<u16> = text.encode_utf16().collect();
+            data.extend_from_slice(&(units.len() as u16).to_le_bytes());
+            data.extend(units.iter().flat_map(|u| u.to_le_bytes()));
+        }
+        data
+    }
+
+    #[test]
+    fn test_shell_link_parser_and_resolver() {
+        use crate::wine::paths::DriveMap;
+        use crate::wine::shell_link::ShellLink;
+
+        let bottle = bottle_with_drives();
+        let root = bottle.path();
+        fs::write(root.join("drive_c/Games/Café/Game.exe"), b"MZ").unwrap();
+        let drives = DriveMap::load(root);
+
+        // ANSI LinkInfo in the Windows-1252 code page
+        let link = ShellLink::parse(&shell_link_fixture("C:\\GAMES\\café\\", "game.exe", false, None, "-windowed -lang \"fr\"")).unwrap();
+        assert_eq!(link.target.as_deref(), Some("C:\\GAMES\\café\\game.exe"));
+        assert_eq!(link.working_dir.as_deref(), Some("C:\\Games"));
+        assert_eq!(link.arguments.as_deref(), Some("-windowed -lang \"fr\""));
+        assert_eq!(link.relative_path, None);
+        let lnk_path = root.join("drive_c/users/Public/Desktop/Game.lnk");
+        assert_eq!(link.resolve(&drives, &lnk_path), Some(root.join("drive_c/Games/Café/Game.exe")));
+
+        // Unicode LinkInfo wins over the ANSI copy, which can't hold every character
+        let link = ShellLink::parse(&shell_link_fixture("C:\\Games\\Café", "Game.exe", true, None, "")).unwrap();
+        assert_eq!(link.target.as_deref(), Some("C:\\Games\\Café\\Game.exe"));
+        assert_eq!(link.arguments.as_deref(), Some(""));
+
+        // A target on a drive that's gone falls back to the relative path next to the shortcut
+        let start_menu = root.join("drive_c/ProgramData/Microsoft/Windows/Start Menu/Programs");
+        let link = ShellLink::parse(&shell_link_fixture("Q:\\Old\\", "Game.exe", false, Some("..\\..\\..\\..\\..\\..\\Games\\CAFÉ\\game.exe"), "")).unwrap();
+        assert_eq!(link.resolve(&drives, &start_menu.join("Game.lnk")), Some(root.join("drive_c/Games/Café/Game.exe")));
+
+        assert!(ShellLink::parse(b"L\0\0\0 not a shell link").is_err());
+        let truncated = shell_link_fixture("C:\\Games\\", "x.exe", false, None, "args");
+        assert!(ShellLink::parse(&truncated[..truncated.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn test_scanner_names_apps_after_their_shortcuts() {
+        use crate::core::scanner::scan_bottle_for_apps;
+
+        let bottle = bottle_with_drives();
+        let root = bottle.path();
+        fs::write(root.join("drive_c/Games/Café/Game.exe"), b"MZ").unwrap();
+        // Under users/, which the directory scan skips
+        fs::create_dir_all(root.join("drive_c/users/steamuser/AppData/Local/Tool")).unwrap();
+        fs::write(root.join("drive_c/users/steamuser/AppData/Local/Tool/tool.exe"), b"MZ").unwrap();
+
+        let start_menu = root.join("drive_c/ProgramData/Microsoft/Windows/Start Menu/Programs/Café Studio");
+        fs::create_dir_all(&start_menu).unwrap();
+        fs::write(start_menu.join("Café Adventure.lnk"), shell_link_fixture("C:\\Games\\Café\\", "game.exe", true, None, "")).unwrap();
+        fs::write(start_menu.join("Uninstall Café Adventure.lnk"), shell_link_fixture("C:\\Games\\Café\\", "game.exe", true, None, "")).unwrap();
+        let desktop = root.join("drive_c/users/steamuser/Desktop");
+        fs::create_dir_all(&desktop).unwrap();
+        fs::write(desktop.join("Tool.lnk"), shell_link_fixture("C:\\users\\steamuser\\AppData\\Local\\Tool\\", "TOOL.EXE", false, None, "")).unwrap();
+        fs::write(desktop.join("Broken.lnk"), b"not a link").unwrap();
+
+        let apps = scan_bottle_for_apps(root);
+        let game = apps.iter().find(|a| a.exe_path.ends_with("Game.exe")).unwrap();
+        assert_eq!(game.name, "Café Adventure");
+        assert_eq!(game.windows_path.as_deref(), Some("D:\\Café\\Game.exe"));
+        let tool = apps.iter().find(|a| a.exe_path.ends_with("tool.exe")).unwrap();
+        assert_eq!(tool.name, "Tool");
+        assert_eq!(apps.iter().filter(|a| a.exe_path.ends_with("Game.exe")).count(), 1);
+    }
+
+    #[test]
+    fn test_hle_file_apis_use_the_bottle_drives() {
+        use crate::core::kernel32::*;
+        use crate::core::modules::ModuleLoader;
+        use crate::core::teb::{ProcessEnvironment, ProcessParameters};
+        use std::ffi::c_char;
+
+        type CreateFileA = extern "win64" fn(*const c_char, u32, u32, usize, u32, u32, usize) -> usize;
+        type CreateFileW = extern "win64" fn(*const u16, u32, u32, usize, u32, u32, usize) -> usize;
+        let create_file_a: CreateFileA = kernel32_fn("CreateFileA");
+        let create_file_w: CreateFileW = kernel32_fn("CreateFileW");
+        let read_file: extern "win64" fn(usize, *mut u8, u32, *mut u32, usize) -> i32 = kernel32_fn("ReadFile");
+        let write_file: extern "win64" fn(usize, *const u8, u32, *mut u32, usize) -> i32 = kernel32_fn("WriteFile");
+        let close_handle: extern "win64" fn(usize) -> i32 = kernel32_fn("CloseHandle");
+        let get_file_size_ex: extern "win64" fn(usize, *mut i64) -> i32 = kernel32_fn("GetFileSizeEx");
+        let set_file_pointer_ex: extern "win64" fn(usize, i64, *mut i64, u32) -> i32 = kernel32_fn("SetFilePointerEx");
+        let get_file_attributes_a: extern "win64" fn(*const c_char) -> u32 = kernel32_fn("GetFileAttributesA");
+        let get_file_attributes_w: extern "win64" fn(*const u16) -> u32 = kernel32_fn("GetFileAttributesW");
+        let get_last_error: extern "win64" fn() -> u32 = kernel32_fn("GetLastError");
+        let wide = |s: &str| s.encode_utf16().chain([0]).collect::<Vec<u16>>();
+
+        let bottle = bottle_with_drives();
+        let root = bottle.path();
+        let _active = ACTIVE_LOADER_TESTS.lock().unwrap_or_else(|e| e.into_inner());
+        let mut loader = ModuleLoader::new(&root.join("drive_c/Games"), Some(root));
+        *loader.environment.get_mut() = Some(ProcessEnvironment::new(&ProcessParameters {
+            image_path: "D:\\Café\\Game.exe".to_string(),
+            command_line: "Game.exe".to_string(),
+            current_directory: "D:\\Café".to_string(),
+            environment: Vec::new(),
+        }).unwrap());
+        loader.activate();
+
+        // A drive-absolute path with the wrong case lands in the existing folder
+        let handle = create_file_w(wide("c:\\GAMES\\café\\save.dat").as_ptr(), GENERIC_WRITE, 0, 0, CREATE_ALWAYS, 0, 0);
+        assert_ne!(handle, INVALID_HANDLE_VALUE);
+        assert_eq!(get_last_error(), 0);
+        let mut written = 0;
+        assert_eq!(write_file(handle, b"hello world".as_ptr(), 11, &mut written, 0), 1);
+        assert_eq!(written, 11);
+        assert_eq!(close_handle(handle), 1);
+        assert_eq!(close_handle(handle), 0);
+        assert_eq!(get_last_error(), ERROR_INVALID_HANDLE);
+        assert_eq!(fs::read(root.join("drive_c/Games/Café/save.dat")).unwrap(), b"hello world");
+
+        // Relative to the current directory, and rooted at its drive
+        let handle = create_file_a(c"SAVE.DAT".as_ptr(), GENERIC_READ, 1, 0, OPEN_EXISTING, 0, 0);
+        assert_ne!(handle, INVALID_HANDLE_VALUE);
+        let mut size = 0;
+        assert_eq!(get_file_size_ex(handle, &mut size), 1);
+        assert_eq!(size, 11);
+        let (mut buffer, mut read) = ([0u8; 32], 0);
+        let mut position = 0;
+        assert_eq!(set_file_pointer_ex(handle, 6, &mut position, FILE_BEGIN), 1);
+        assert_eq!(position, 6);
+        assert_eq!(read_file(handle, buffer.as_mut_ptr(), 32, &mut read, 0), 1);
+        assert_eq!(&buffer[..read as usize], b"world");
+        // At the end of the file a read succeeds with nothing in it
+        assert_eq!(read_file(handle, buffer.as_mut_ptr(), 32, &mut read, 0), 1);
+        assert_eq!(read, 0);
+        assert_eq!(set_file_pointer_ex(handle, -5, &mut position, FILE_END), 1);
+        assert_eq!(position, 6);
+        assert_eq!(write_file(handle, b"x".as_ptr(), 1, &mut written, 0), 0);
+        assert_eq!(close_handle(handle), 1);
+        assert_eq!(get_file_attributes_a(c"\\Café\\save.dat".as_ptr()), FILE_ATTRIBUTE_NORMAL);
+
+        // Dispositions and their errors
+        assert_eq!(create_file_a(c"save.dat".as_ptr(), GENERIC_WRITE, 0, 0, CREATE_NEW, 0, 0), INVALID_HANDLE_VALUE);
+        assert_eq!(get_last_error(), ERROR_FILE_EXISTS);
+        let handle = create_file_a(c"save.dat".as_ptr(), GENERIC_READ, 0, 0, OPEN_ALWAYS, 0, 0);
+        assert_eq!(get_last_error(), ERROR_ALREADY_EXISTS);
+        close_handle(handle);
+        assert_eq!(create_file_a(c"missing.dat".as_ptr(), GENERIC_READ, 0, 0, OPEN_EXISTING, 0, 0), INVALID_HANDLE_VALUE);
+        assert_eq!(get_last_error(), ERROR_FILE_NOT_FOUND);
+        assert_eq!(create_file_a(c"C:\\No\\Such\\Dir\\x.dat".as_ptr(), GENERIC_WRITE, 0, 0, CREATE_ALWAYS, 0, 0), INVALID_HANDLE_VALUE);
+        assert_eq!(get_last_error(), ERROR_PATH_NOT_FOUND);
+        let handle = create_file_a(c"created.dat".as_ptr(), GENERIC_READ, 0, 0, OPEN_ALWAYS, 0, 0);
+        assert_eq!(get_last_error(), 0);
+        close_handle(handle);
+        assert!(root.join("drive_c/Games/Café/created.dat").exists());
+
+        assert_eq!(get_file_attributes_w(wide("C:\\Program Files").as_ptr()), FILE_ATTRIBUTE_DIRECTORY);
+        assert_eq!(get_file_attributes_w(wide("\\\\nas\\share\\setup").as_ptr()), FILE_ATTRIBUTE_DIRECTORY);
+        assert_eq!(get_file_attributes_a(c"Q:\\nowhere".as_ptr()), INVALID_FILE_ATTRIBUTES);
+        assert_eq!(get_last_error(), ERROR_PATH_NOT_FOUND);
+        assert_eq!(get_file_attributes_a(c"C:\\nowhere".as_ptr()), INVALID_FILE_ATTRIBUTES);
+        assert_eq!(get_last_error(), ERROR_FILE_NOT_FOUND);
+    }
+
+    fn trace_record(function: u32, start: u64) -> Record {
+        Record { function, args: [1, 2, 3, 4], ret: 0, start, duration: 10, thread: 7 }
+    }
+
+    #[test]
+    fn test_trace_ring_overflow_and_cursor() {
+        let ring = TraceRing::new(4);
+        let function = trace::function_id("kernel32.dll!GetTickCount");
+        assert_eq!(ring.read_since(0).events.len(), 0);
+
+        for i in 0..6 {
+            ring.push(&trace_record(function, i));
+        }
+        // The two oldest calls were overwritten before anyone read them
+        let batch = ring.read_since(0);
+        assert_eq!(batch.events.iter().map(|e| e.seq).collect::<Vec<_>>(), [2, 3, 4, 5]);
+        assert_eq!(batch.events.iter().map(|e| e.start_ns).collect::<Vec<_>>(), [2, 3, 4, 5]);
+        assert_eq!((batch.next, batch.dropped), (6, 2));
+        assert_eq!(batch.events[0].function, "kernel32.dll!GetTickCount");
+        assert_eq!((batch.events[0].args, batch.events[0].thread_id), ([1, 2, 3, 4], 7));
+
+        // Reading from `next` only returns calls made since
+        let batch = ring.read_since(6);
+        assert!(batch.events.is_empty());
+        assert_eq!((batch.next, batch.dropped), (6, 0));
+
+        for i in 6..9 {
+            ring.push(&trace_record(function, i));
+        }
+        let batch = ring.read_since(6);
+        assert_eq!(batch.events.iter().map(|e| e.seq).collect::<Vec<_>>(), [6, 7, 8]);
+        assert_eq!((batch.next, batch.dropped), (9, 0));
+
+        // A stale cursor skips ahead to the oldest call still in the ring
+        let batch = ring.read_since(3);
+        assert_eq!(batch.events.first().map(|e| e.seq), Some(5));
+        assert_eq!((batch.next, batch.dropped), (9, 2));
+
+        assert_eq!(ring.last(2).iter().map(|e| e.seq).collect::<Vec<_>>(), [7, 8]);
+        assert_eq!(ring.last(100).len(), 4);
+    }
+
+    #[test]
+    fn test_trace_ring_concurrent_writers() {
+        let ring = TraceRing::new(trace::TRACE_CAPACITY);
+        let function = trace::function_id("kernel32.dll!Sleep");
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for i in 0..1000 {
+                        ring.push(&trace_record(function, i));
+                    }
+                });
+            }
+        });
+
+        let batch = ring.read_since(0);
+        assert_eq!(batch.events.len(), 4000);
+        assert!(batch.events.iter().enumerate().all(|(i, e)| e.seq == i as u64 && e.function == "kernel32.dll!Sleep"));
+        assert_eq!((batch.next, batch.dropped), (4000, 0));
+    }
+
+    #[test]
+    fn test_chrome_trace_export() {
+        let event = |function: &str, start_ns, duration_ns| TraceEvent {
+            seq: 0,
+            function: function.to_string(),
+            args: [0x10, 0x20, 0, 0xffff_ffff],
+            ret: 0x1,
+            start_ns,
+            duration_ns,
+            thread_id: 42,
+        };
+        let chrome = trace::to_chrome_trace(&[event("kernel32.dll!Sleep", 1500, 2500), event("Anonymous", 0, 0)]).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&chrome).unwrap();
+
+        assert_eq!(json["displayTimeUnit"], "ns");
+        let events = json["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        // Complete events in microseconds, the DLL as category
+        assert_eq!(events[0]["name"], "Sleep");
+        assert_eq!(events[0]["cat"], "kernel32.dll");
+        assert_eq!(events[0]["ph"], "X");
+        assert_eq!(events[0]["ts"], 1.5);
+        assert_eq!(events[0]["dur"], 2.5);
+        assert_eq!(events[0]["tid"], 42);
+        assert_eq!(events[0]["pid"], std::process::id());
+        assert_eq!(events[0]["args"]["rcx"], "0x10");
+        assert_eq!(events[0]["args"]["r9"], "0xffffffff");
+        assert_eq!(events[0]["args"]["ret"], "0x1");
+        assert_eq!((&events[1]["name"], &events[1]["cat"]), (&serde_json::json!("Anonymous"), &serde_json::json!("")));
+    }
+
+    /// Runs `body` in a forked child set up like the HLE helper and returns what the app would report
+    fn run_as_helper(body: fn()) -> crate::core::executor::ExecutionReport {
+        use crate::core::executor;
+        use std::io::Read;
+        use std::os::unix::io::FromRawFd;
+        use std::os::unix::process::ExitStatusExt;
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            unsafe {
+                libc::dup2(fds[1], executor::REPORT_FD);
+                executor::start_reporting();
+            }
+            body();
+            unsafe { libc::_exit(0) };
+        }
+        unsafe { libc::close(fds[1]) };
+        let mut reports = String::new();
+        unsafe { fs::File::from_raw_fd(fds[0]) }.read_to_string(&mut reports).unwrap();
+        let mut status = 0;
+        unsafe { libc::waitpid(pid, &mut status, 0) };
+        executor::build_report(std::process::ExitStatus::from_raw(status), &reports)
+    }
+
+    #[test]
+    fn test_cancelling_a_run_leaves_other_runs_alone() {
+        use crate::core::executor::{cancel_run, RunHandle};
+        use std::sync::atomic::Ordering;
+
+        let first = RunHandle::register("test-run-1").unwrap();
+        let second = RunHandle::register("test-run-2").unwrap();
+        assert!(RunHandle::register("test-run-1").is_err());
+
+        assert!(cancel_run("test-run-1"));
+        assert!(first.cancel.load(Ordering::SeqCst));
+        assert!(!second.cancel.load(Ordering::SeqCst));
+
+        // A run that finished can't be cancelled, and its id is free again
+        drop(first);
+        assert!(!cancel_run("test-run-1"));
+        let again = RunHandle::register("test-run-1").unwrap();
+        assert!(!again.cancel.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_waiting_for_the_helper_ends_on_exit_cancel_or_timeout() {
+        use crate::core::executor::{drain, wait_for_helper, OUTPUT_LIMIT};
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::Command;
+        use std::sync::atomic::AtomicBool;
+        use std::time::{Duration, Instant};
+
+        let never = AtomicBool::new(false);
+        let mut done = Command::new("true").spawn().unwrap();
+        let exit = wait_for_helper(&mut done, &never, Duration::from_secs(60)).unwrap();
+        assert_eq!((exit.status.code(), exit.cancelled, exit.timed_out), (Some(0), false, false));
+
+        // A helper that hangs is killed at the deadline, not left running
+        let started = Instant::now();
+        let mut hung = Command::new("sleep").arg("60").spawn().unwrap();
+        let exit = wait_for_helper(&mut hung, &never, Duration::from_millis(100)).unwrap();
+        assert_eq!((exit.status.signal(), exit.cancelled, exit.timed_out), (Some(libc::SIGKILL), false, true));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let mut cancelled = Command::new("sleep").arg("60").spawn().unwrap();
+        let exit = wait_for_helper(&mut cancelled, &AtomicBool::new(true), Duration::from_secs(60)).unwrap();
+        assert_eq!((exit.status.signal(), exit.cancelled, exit.timed_out), (Some(libc::SIGKILL), true, false));
+
+        // Only the end of a flood of output is kept
+        let mut output = vec![b'a'; OUTPUT_LIMIT];
+        output.extend(vec![b'b'; OUTPUT_LIMIT / 2]);
+        let (kept, dropped) = drain(Some(std::io::Cursor::new(output))).join().unwrap();
+        assert_eq!((kept.len(), dropped), (OUTPUT_LIMIT, OUTPUT_LIMIT / 2));
+        assert!(kept[OUTPUT_LIMIT / 2..].iter().all(|b| *b == b'b'));
+        assert_eq!(drain(Some(std::io::Cursor::new(b"short".to_vec()))).join().unwrap(), (b"short".to_vec(), 0));
+    }
+
+    #[test]
+    fn test_helper_reports_crashes_and_exits() {
+        let crash = run_as_helper(|| unsafe { std::ptr::write_volatile(0x10 as *mut u64, 1) });
+        assert_eq!(crash.signal, Some(libc::SIGSEGV));
+        assert_eq!(crash.exit_code, None);
+        assert_eq!(crash.fault_address, Some(0x10));
+        assert!(crash.instruction_pointer.is_some_and(|ip| ip != 0));
+        assert_eq!(crash.error, None);
+
+        let exited = run_as_helper(|| {
+            let exit_process: extern "win64" fn(u32) -> ! = kernel32_fn("ExitProcess");
+            exit_process(7);
+        });
+        assert_eq!((exited.exit_code, exited.signal, exited.fault_address), (Some(7), None, None));
+
+        // A helper that dies without reporting still gets its exit status through
+        let silent = run_as_helper(|| unsafe { libc::_exit(9) });
+        assert_eq!((silent.exit_code, silent.error), (Some(9), None));
+    }
+
+    #[test]
+    fn test_helper_sends_the_whole_trace() {
+        // More calls than an exit report carries; whatever wasn't streamed yet goes out before the exit
+        let exited = run_as_helper(|| {
+            let function = trace::function_id("kernel32.dll!FlushFileBuffers");
+            for i in 0..200 {
+                trace::ring().push(&trace_record(function, i));
+            }
+            let exit_process: extern "win64" fn(u32) -> ! = kernel32_fn("ExitProcess");
+            exit_process(0);
+        });
+        assert_eq!(exited.exit_code, Some(0));
+        assert_eq!(exited.last_calls.len(), crate::core::executor::LAST_CALLS);
+        let flushed: Vec<&TraceEvent> = exited.trace.iter().filter(|e| e.function == "kernel32.dll!FlushFileBuffers").collect();
+        assert_eq!(flushed.len(), 200);
+        assert!(flushed.windows(2).all(|pair| pair[1].seq == pair[0].seq + 1));
+        assert_eq!(exited.trace_dropped, 0);
+    }
+
+    #[test]
+    fn test_crash_reports_stream_every_call_from_a_fixed_buffer() {
+        // Far more unsent calls than one line of the crash handler's buffer holds
+        let crashed = run_as_helper(|| {
+            let function = trace::function_id("kernel32.dll!\"Quoted\"\\Name");
+            for i in 0..2000 {
+                trace::ring().push(&trace_record(function, i));
+            }
+            unsafe { std::ptr::write_volatile(0x10 as *mut u64, 1) };
+        });
+        assert_eq!((crashed.signal, crashed.error), (Some(libc::SIGSEGV), None));
+        let quoted: Vec<&TraceEvent> = crashed.trace.iter().filter(|e| e.function == "kernel32.dll!\"Quoted\"\\Name").collect();
+        assert_eq!(quoted.len(), 2000);
+        assert!(quoted.windows(2).all(|pair| pair[1].seq == pair[0].seq + 1));
+        assert_eq!((quoted[0].args, quoted[0].start_ns, quoted[0].duration_ns, quoted[0].thread_id), ([1, 2, 3, 4], 0, 10, 7));
+        assert_eq!(crashed.last_calls.len(), crate::core::executor::LAST_CALLS);
+        assert_eq!(crashed.last_calls.last().unwrap().seq, quoted.last().unwrap().seq);
+
+        // The crashing thread holds the name table: the calls are still reported, by id
+        let crashed = run_as_helper(|| {
+            let function = trace::function_id("user32.dll!MessageBoxA");
+            trace::ring().push(&trace_record(function, 0));
+            let _names = trace::function_names().lock().unwrap();
+            unsafe { std::ptr::write_volatile(0x10 as *mut u64, 1) };
+        });
+        assert_eq!((crashed.signal, crashed.error), (Some(libc::SIGSEGV), None));
+        let last = crashed.last_calls.last().unwrap();
+        assert!(last.function.starts_with('#'), "{}", last.function);
+        assert_eq!(crashed.trace.last().unwrap().function, last.function);
     }
 }
@@ -15,35 +15,78 @@ fn greet(name: &str) -> String {
 #[tauri::command]
 async fn launch_installer(path: &str) -> Result<core::loader::ExecutableInfo, String> {
     println!("Request to launch installer: {}", path);
-    core::loader::load_executable(path)
+    core::loader::analyze_executable(path)
 }
 
 #[tauri::command]
-async fn run_installer(path: &str, bottle_id: &str, handle: tauri::AppHandle) -> Result<core::runner::RunResult, String> {
+async fn get_import_table(path: &str) -> Result<Vec<core::loader::DllImports>, String> {
+    core::loader::get_import_table(path)
+}
+
+#[tauri::command]
+async fn get_import_coverage(path: &str) -> Result<core::linker::ImportCoverage, String> {
+    core::loader::import_coverage(path)
+}
+
+#[tauri::command]
+async fn run_installer(
+    path: &str,
+    bottle_id: &str,
+    unattended: Option<bool>,
+    target_dir: Option<String>,
+    args: Option<Vec<String>>,
+    working_dir: Option<String>,
+    handle: tauri::AppHandle,
+) -> Result<core::runner::RunResult, String> {
     let bottles = core::bottle::list_bottles(&handle)?;
     let bottle = bottles.iter().find(|b| b.id == bottle_id)
         .ok_or("Bottle not found")?;
     
-    let custom_engine = if let Some(path) = &bottle.engine_path {
-        Some(path.to_str().unwrap().to_string())
-    } else if let Some(pro_path) = core::engine::get_pro_engine_path(&handle) {
-        Some(pro_path.to_str().unwrap().to_string())
+    let custom_engine = core::engine::engine_for_bottle(&handle, bottle);
+
+    let installer = std::path::Path::new(path);
+    let msi_info = core::msi::read_msi(installer).ok();
+
+    let installer_dir = installer.parent().map(|dir| wine::paths::host_to_windows_path(&bottle.path, dir)).transpose()?.unwrap_or_default();
+    let mut child = if unattended.unwrap_or(false) {
+        let command_line = core::installer::unattended_command(&bottle.path, installer, target_dir.as_deref())?;
+        core::runner::run_command_line(&command_line, &installer_dir, &bottle.path, custom_engine, &bottle.environment_type)?
+    } else if msi_info.is_some() {
+        let command_line = core::installer::msiexec_command(&bottle.path, installer, Vec::new())?;
+        core::runner::run_command_line(&command_line, &installer_dir, &bottle.path, custom_engine, &bottle.environment_type)?
     } else {
-        None
+        let working_dir = working_dir.as_deref().map(std::path::Path::new);
+        core::runner::run_executable_with_args(path, &args.unwrap_or_default(), working_dir, &bottle.path, custom_engine, &bottle.environment_type)?.into()
     };
-
-    let mut child = core::runner::run_executable(path, &bottle.path, custom_engine, &bottle.environment_type)?;
+    let bottle_path = bottle.path.clone();
     
     let handle_clone = handle.clone();
     let bottle_id_str = bottle_id.to_string();
     let installer_path = path.to_string();
+    let installer_info = core::classifier::classify_file(std::path::Path::new(path));
 
     // Spawn a monitor that ACTUALLY waits for the process to exit
     std::thread::spawn(move || {
         let _ = handle_clone.emit("status-update", "Installer active. Monitoring for completion...");
         
         // Wait for the main installer process to close
-        let _ = child.wait();
+        let status = child.wait();
+
+        // msiexec exits with 0, or 3010 when a reboot is pending
+        let installed = status.is_ok_and(|s| matches!(s.code(), Some(0) | Some(3010)));
+        if let (true, Some(info)) = (installed, &msi_info) {
+            if let Some(product_code) = info.product_code.clone() {
+                let package = std::path::PathBuf::from(&installer_path);
+                let product = core::msi::MsiProduct {
+                    product_name: info.product_name.clone().unwrap_or_else(|| product_code.clone()),
+                    product_version: info.product_version.clone(),
+                    log: core::installer::msi_log_path(&bottle_path, &package),
+                    package,
+                    product_code,
+                };
+                let _ = core::bottle::record_msi_product(&handle_clone, &bottle_id_str, product);
+            }
+        }
         
         let _ = handle_clone.emit("status-update", "Installer finished. Synchronizing library...");
 
@@ -53,9 +96,10 @@ async fn run_installer(path: &str, bottle_id: &str, handle: tauri::AppHandle) ->
             let apps = core::scanner::scan_bottle_for_apps(&b.path);
             
             // Logic to detect if Steam.exe appeared
-            let is_steam_setup = installer_path.to_lowercase().contains("steam");
-            if is_steam_setup {
-                let has_steam = apps.iter().any(|a| a.exe_path.to_lowercase().contains("steam.exe") && !a.exe_path.to_lowercase().contains("setup"));
+            if installer_info.is_steam_setup(std::path::Path::new(&installer_path)) {
+                let has_steam = apps.iter().any(|a| {
+                    a.class == core::classifier::ExecutableClass::Launcher && a.exe_path.to_lowercase().ends_with("/steam.exe")
+                });
                 if has_steam {
                     // It's installed! Hide the setup card.
                     let _ = core::bottle::remove_pinned_app(&handle_clone, &bottle_id_str, &installer_path);
@@ -73,6 +117,46 @@ async fn run_installer(path: &str, bottle_id: &str, handle: tauri::AppHandle) ->
     })
 }
 
+#[tauri::command]
+async fn uninstall_program(bottle_id: &str, key: &str, handle: tauri::AppHandle) -> Result<core::runner::RunResult, String> {
+    let bottles = core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id)
+        .ok_or("Bottle not found")?;
+
+    let programs = wine::programs::ProgramRegistry::list_for_bottle(bottle);
+    let program = programs.iter().find(|p| p.key == key)
+        .ok_or("Program not found")?;
+    let (exe, args) = wine::programs::ProgramRegistry::uninstall_command(&bottle.path, program)?;
+
+    let custom_engine = core::engine::engine_for_bottle(&handle, bottle);
+    let mut child = core::runner::run_executable_with_args(
+        exe.to_str().unwrap_or_default(),
+        &args,
+        None,
+        &bottle.path,
+        custom_engine,
+        &bottle.environment_type,
+    )?;
+
+    let handle_clone = handle.clone();
+    let bottle_id_str = bottle_id.to_string();
+    let product_code = bottle.msi_products.iter()
+        .find(|p| p.product_code.eq_ignore_ascii_case(key))
+        .map(|p| p.product_code.clone());
+    std::thread::spawn(move || {
+        let status = child.wait();
+        if let (true, Some(code)) = (status.is_ok_and(|s| s.success()), product_code) {
+            let _ = core::bottle::forget_msi_product(&handle_clone, &bottle_id_str, &code);
+        }
+        let _ = handle_clone.emit("library-changed", &bottle_id_str);
+    });
+
+    Ok(core::runner::RunResult {
+        success: true,
+        message: format!("Uninstalling {}", program.display_name),
+    })
+}
+
 #[tauri::command]
 async fn run_shell_command(command: String, description: String) -> Result<(), String> {
     println!("Executing: {}", description);
@@ -135,7 +219,14 @@ async fn scan_for_apps(bottle_id: &str, handle: tauri::AppHandle) -> Result<Vec<
     let bottle = bottles.iter().find(|b| b.id == bottle_id)
         .ok_or("Bottle not found")?;
     
-    Ok(core::scanner::scan_bottle_for_apps(&bottle.path))
+    let mut apps = core::scanner::scan_bottle_for_apps(&bottle.path);
+
+    // Steam titles without cached artwork fall back to the bottle's cover
+    for app in apps.iter_mut().filter(|a| a.steam_app_id.is_some() && a.cover.is_none()) {
+        app.cover = Some(bottle.cover.clone());
+    }
+
+    Ok(apps)
 }
 
 #[tauri::command]
@@ -145,7 +236,7 @@ async fn open_bottle_dir(bottle_id: &str, handle: tauri::AppHandle) -> Result<()
         .ok_or("Bottle not found")?;
     
     // Open the directory using the tauri-plugin-opener
-    tauri_plugin_opener::reveal_item_in_dir(bottle.path.to_str().unwrap());
+    tauri_plugin_opener::reveal_item_in_dir(bottle.path.to_str().unwrap()).map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -293,15 +384,17 @@ async fn initialize_pro_bottle(bottle_id: &str, handle: tauri::AppHandle) -> Res
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // The same binary doubles as the HLE helper process, which never starts the UI
+    if std::env::args().nth(1).as_deref() == Some(core::executor::HELPER_FLAG) {
+        std::process::exit(core::executor::run_helper());
+    }
+
     // Start the Pancho-Mach IPC Broker
     if let Ok(broker) = core::mach_ipc::MachBroker::new() {
         broker.start_listening();
     }
 
-    // Initialize the Native Exception Translator
-    unsafe {
-        core::signals::setup_signal_handler();
-    }
+    // No crash handlers here: foreign code only ever runs in the helper process
 
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
@@ -309,7 +402,21 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             greet, 
             launch_installer, 
+            get_import_table,
+            get_import_coverage,
+            core::trace::export_api_trace,
+            core::executor::run_hle,
+            core::executor::cancel_hle,
+            core::executor::is_hle_supported,
+            core::dependencies::check_dependencies,
+            gptk::graphics_api::analyze_graphics,
+            core::protection::check_game_compatibility,
+            core::instruction_sets::scan_instruction_sets,
+            core::instruction_sets::list_cpu_profiles,
+            core::authenticode::check_installer_signature,
+            core::installer::detect_installer,
             run_installer,
+            uninstall_program,
             kill_wine_processes,
             get_bottles,
             create_bottle,
@@ -340,6 +447,14 @@ pub fn run() {
             wine::steam::install_steam,
             wine::steam::launch_steam,
             wine::steam::check_steam_status,
+            wine::steam::get_steam_games,
+            wine::steam::launch_steam_game,
+            wine::steam_shortcuts::get_steam_shortcuts,
+            wine::steam_shortcuts::add_steam_shortcuts,
+            wine::steam_shortcuts::remove_steam_shortcut,
+            wine::epic::get_epic_games,
+            wine::gog::get_gog_games,
+            wine::programs::get_installed_programs,
             process::manager::get_active_processes,
             process::manager::kill_all_bottle_processes,
             process::manager::is_bottle_running,
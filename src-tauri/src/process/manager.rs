@@ -30,7 +30,7 @@ impl ProcessManager {
 
         // Skip header line
         for line in stdout.lines().skip(1) {
-            let parts: Vec<&str> = line.trim().split_whitespace().collect();
+            let parts: Vec<&str> = line.split_whitespace().collect();
             if parts.len() < 3 {
                 continue;
             }
@@ -38,7 +38,7 @@ impl ProcessManager {
             let pid = parts[0].parse::<u32>().unwrap_or(0);
             let ppid = parts[1].parse::<u32>().unwrap_or(0);
             let command = parts[2..].join(" ");
-            let name = parts[2].split('/').last().unwrap_or("").to_string();
+            let name = parts[2].split('/').next_back().unwrap_or("").to_string();
 
             processes.push(ProcessInfo {
                 pid,
@@ -86,13 +86,12 @@ impl ProcessManager {
         while added {
             added = false;
             for proc in &all_procs_full {
-                if bottle_procs.iter().any(|bp| bp.pid == proc.parent_pid) {
-                    if !bottle_procs.iter().any(|bp| bp.pid == proc.pid) {
-                        let mut p = proc.clone();
-                        p.bottle_path = Some(bottle_path_str.clone());
-                        bottle_procs.push(p);
-                        added = true;
-                    }
+                if bottle_procs.iter().any(|bp| bp.pid == proc.parent_pid)
+                    && !bottle_procs.iter().any(|bp| bp.pid == proc.pid) {
+                    let mut p = proc.clone();
+                    p.bottle_path = Some(bottle_path_str.clone());
+                    bottle_procs.push(p);
+                    added = true;
                 }
             }
         }
@@ -12,6 +12,7 @@ pub enum FixResult {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[allow(dead_code)]
 pub struct FixAttempt {
     pub timestamp: u64,
     pub error: WineError,
@@ -20,6 +21,27 @@ pub struct FixAttempt {
 
 pub struct AutoRecovery;
 
+/// Winetricks verb that provides a DLL, if we know one
+pub fn winetricks_package(dll_name: &str) -> Option<&'static str> {
+    let package = match dll_name.to_lowercase().as_str() {
+        "d3dcompiler_47.dll" => "d3dcompiler_47",
+        "d3dcompiler_43.dll" => "d3dcompiler_43",
+        "vcruntime140.dll" | "msvcp140.dll" | "mfc140.dll" | "mfc140u.dll" => "vcrun2015",
+        "vcruntime140_1.dll" | "msvcp140_1.dll" | "msvcp140_2.dll" => "vcrun2019",
+        "msvcr120.dll" | "msvcp120.dll" => "vcrun2013",
+        "msvcr110.dll" | "msvcp110.dll" => "vcrun2012",
+        "msvcr100.dll" | "msvcp100.dll" => "vcrun2010",
+        "msvcr90.dll" | "msvcp90.dll" => "vcrun2008",
+        "x3daudio1_7.dll" | "xaudio2_7.dll" | "xinput1_3.dll" => "directx9",
+        "d3dx9_43.dll" => "d3dx9_43",
+        "d3dx11_43.dll" => "d3dx11_43",
+        "xact3.dll" | "xactengine3_7.dll" => "xact",
+        "physxloader.dll" => "physx",
+        _ => return None,
+    };
+    Some(package)
+}
+
 impl AutoRecovery {
     pub async fn attempt_fix(bottle_path: &Path, error: WineError) -> Result<FixResult, String> {
         match error {
@@ -40,12 +62,8 @@ impl AutoRecovery {
             
             WineError::MissingDLL { dll_name } => {
                 // Heuristic: If it's a known winetricks package, try to install it
-                let package = match dll_name.to_lowercase().as_str() {
-                    "d3dcompiler_47.dll" => "d3dcompiler_47",
-                    "vcruntime140.dll" => "vcrun2015",
-                    "mfc140.dll" => "vcrun2015",
-                    "x3daudio1_7.dll" => "directx9",
-                    _ => return Ok(FixResult::ManualRequired { 
+                let Some(package) = winetricks_package(&dll_name) else {
+                    return Ok(FixResult::ManualRequired { 
                         instructions: vec![format!("Please install '{}' manually via Winetricks.", dll_name)] 
                     })
                 };
@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::wine::paths::windows_to_host_path;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EpicGame {
+    pub app_name: String,
+    pub display_name: String,
+    pub install_dir: PathBuf,
+    pub exe_path: Option<PathBuf>,
+    pub launch_args: String,
+    pub install_size: u64,
+    pub catalog_namespace: String,
+    pub catalog_item_id: String,
+}
+
+/// Fields we read from an Epic `.item` manifest. The launcher writes many more.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EpicManifest {
+    #[serde(default)]
+    app_name: String,
+    #[serde(default)]
+    display_name: String,
+    #[serde(default)]
+    install_location: String,
+    #[serde(default)]
+    launch_executable: String,
+    #[serde(default)]
+    launch_command: String,
+    #[serde(default)]
+    install_size: u64,
+    #[serde(default)]
+    catalog_namespace: String,
+    #[serde(default)]
+    catalog_item_id: String,
+    #[serde(default)]
+    main_game_app_name: String,
+    #[serde(default)]
+    app_categories: Vec<String>,
+    #[serde(default, rename = "bIsIncompleteInstall")]
+    is_incomplete_install: bool,
+}
+
+pub struct EpicLibrary;
+
+impl EpicLibrary {
+    pub fn manifests_dir(bottle_path: &Path) -> PathBuf {
+        bottle_path.join("drive_c/ProgramData/Epic/EpicGamesLauncher/Data/Manifests")
+    }
+
+    /// Installed titles from the launcher's `*.item` manifests (DLC and partial installs are skipped)
+    pub fn list_installed_games(bottle_path: &Path) -> Vec<EpicGame> {
+        let mut games = Vec::new();
+        let Ok(entries) = std::fs::read_dir(Self::manifests_dir(bottle_path)) else { return games };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_item = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("item"));
+            if !is_item {
+                continue;
+            }
+
+            let Ok(content) = std::fs::read_to_string(&path) else { continue };
+            let Ok(manifest) = serde_json::from_str::<EpicManifest>(&content) else { continue };
+
+            if manifest.is_incomplete_install {
+                continue;
+            }
+            // DLCs point at their base game through MainGameAppName
+            if !manifest.main_game_app_name.is_empty() && manifest.main_game_app_name != manifest.app_name {
+                continue;
+            }
+            if !manifest.app_categories.is_empty() && !manifest.app_categories.iter().any(|c| c == "games") {
+                continue;
+            }
+
+            let Some(install_dir) = windows_to_host_path(bottle_path, &manifest.install_location) else { continue };
+
+            let exe_path = if manifest.launch_executable.is_empty() {
+                None
+            } else {
+                let relative: PathBuf = manifest.launch_executable
+                    .split(['\\', '/'])
+                    .filter(|part| !part.is_empty())
+                    .collect();
+                Some(install_dir.join(relative))
+            };
+
+            let display_name = if manifest.display_name.is_empty() {
+                manifest.app_name.clone()
+            } else {
+                manifest.display_name
+            };
+
+            games.push(EpicGame {
+                app_name: manifest.app_name,
+                display_name,
+                install_dir,
+                exe_path,
+                launch_args: manifest.launch_command.trim().to_string(),
+                install_size: manifest.install_size,
+                catalog_namespace: manifest.catalog_namespace,
+                catalog_item_id: manifest.catalog_item_id,
+            });
+        }
+
+        games.sort_by_key(|g| g.display_name.to_lowercase());
+        games
+    }
+}
+
+#[tauri::command]
+pub fn get_epic_games(bottle_id: String, handle: tauri::AppHandle) -> Result<Vec<EpicGame>, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+
+    Ok(EpicLibrary::list_installed_games(&bottle.path))
+}
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::core::shim::NativeRegistry;
+use crate::wine::paths::windows_to_host_path;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GogGame {
+    pub game_id: String,
+    pub name: String,
+    pub install_dir: PathBuf,
+    pub exe_path: Option<PathBuf>,
+    pub arguments: String,
+    pub working_dir: Option<PathBuf>,
+}
+
+/// `goggame-<id>.info`, written next to every GOG install
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GogInfo {
+    game_id: String,
+    #[serde(default)]
+    root_game_id: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    play_tasks: Vec<GogPlayTask>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GogPlayTask {
+    #[serde(default)]
+    is_primary: bool,
+    #[serde(default, rename = "type")]
+    task_type: String,
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    arguments: String,
+    #[serde(default)]
+    working_dir: String,
+}
+
+// Galaxy is 32-bit, so on a win64 prefix its keys land under Wow6432Node
+const GOG_REGISTRY_KEYS: [&str; 2] = [
+    r"Software\Wow6432Node\GOG.com\Games",
+    r"Software\GOG.com\Games",
+];
+
+pub struct GogLibrary;
+
+impl GogLibrary {
+    /// Installed titles, found through the GOG registry keys and the default install folders.
+    /// Each install is described by its `goggame-<id>.info`; DLC info files are skipped.
+    pub fn list_installed_games(bottle_path: &Path) -> Vec<GogGame> {
+        let registry = NativeRegistry::system(bottle_path);
+        let mut install_dirs: Vec<PathBuf> = Vec::new();
+
+        for parent in GOG_REGISTRY_KEYS {
+            for game_id in registry.subkeys(parent) {
+                let key = format!(r"{}\{}", parent, game_id);
+                let Some(win_path) = registry.get_string(&key, "path") else { continue };
+                if let Some(dir) = windows_to_host_path(bottle_path, &win_path) {
+                    install_dirs.push(dir);
+                }
+            }
+        }
+
+        let default_roots = [
+            bottle_path.join("drive_c/GOG Games"),
+            bottle_path.join("drive_c/Program Files (x86)/GOG Galaxy/Games"),
+        ];
+        for root in default_roots {
+            if let Ok(entries) = std::fs::read_dir(root) {
+                install_dirs.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()));
+            }
+        }
+
+        let mut games: Vec<GogGame> = Vec::new();
+        for dir in install_dirs {
+            for game in Self::read_info_files(&dir) {
+                if !games.iter().any(|g| g.game_id == game.game_id) {
+                    games.push(game);
+                }
+            }
+        }
+
+        games.sort_by_key(|g| g.name.to_lowercase());
+        games
+    }
+
+    fn read_info_files(install_dir: &Path) -> Vec<GogGame> {
+        let mut games = Vec::new();
+        let Ok(entries) = std::fs::read_dir(install_dir) else { return games };
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_lowercase();
+            if !file_name.starts_with("goggame-") || !file_name.ends_with(".info") {
+                continue;
+            }
+
+            let Ok(content) = std::fs::read_to_string(entry.path()) else { continue };
+            let Ok(info) = serde_json::from_str::<GogInfo>(&content) else { continue };
+
+            if info.root_game_id.as_deref().is_some_and(|root| root != info.game_id) {
+                continue;
+            }
+
+            let task = info.play_tasks.iter()
+                .filter(|t| t.task_type == "FileTask")
+                .find(|t| t.is_primary)
+                .or_else(|| info.play_tasks.iter().find(|t| t.task_type == "FileTask"));
+
+            let resolve = |relative: &str| -> PathBuf {
+                let parts: PathBuf = relative.split(['\\', '/']).filter(|p| !p.is_empty()).collect();
+                install_dir.join(parts)
+            };
+
+            games.push(GogGame {
+                game_id: info.game_id,
+                name: info.name,
+                install_dir: install_dir.to_path_buf(),
+                exe_path: task.map(|t| resolve(&t.path)),
+                arguments: task.map(|t| t.arguments.clone()).unwrap_or_default(),
+                working_dir: task.filter(|t| !t.working_dir.is_empty()).map(|t| resolve(&t.working_dir)),
+            });
+        }
+
+        games
+    }
+}
+
+#[tauri::command]
+pub fn get_gog_games(bottle_id: String, handle: tauri::AppHandle) -> Result<Vec<GogGame>, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+
+    Ok(GogLibrary::list_installed_games(&bottle.path))
+}
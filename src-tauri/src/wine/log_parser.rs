@@ -1,7 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
-use std::io::BufRead;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum LogLevel {
@@ -41,6 +40,7 @@ impl LogParser {
         }
     }
 
+    #[allow(dead_code)]
     pub fn parse_line(&self, line: &str) -> Option<LogEntry> {
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
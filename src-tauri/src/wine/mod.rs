@@ -1,4 +1,11 @@
 pub mod runner;
 pub mod registry;
 pub mod steam;
+pub mod paths;
+pub mod shell_link;
+pub mod steam_shortcuts;
 pub mod log_parser;
+pub mod vdf;
+pub mod epic;
+pub mod gog;
+pub mod programs;
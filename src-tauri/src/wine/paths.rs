@@ -0,0 +1,200 @@
+use std::path::{Component, Path, PathBuf};
+
+/// A bottle's drive letters, read from the `dosdevices` symlinks Wine keeps in the prefix
+/// (`c:` -> `../drive_c`, `z:` -> `/`, plus whatever the user mapped).
+#[derive(Debug, Clone)]
+pub struct DriveMap {
+    bottle_path: PathBuf,
+    /// Lowercase drive letter and the host directory it points to
+    drives: Vec<(char, PathBuf)>,
+}
+
+impl DriveMap {
+    pub fn load(bottle_path: &Path) -> Self {
+        let dosdevices = bottle_path.join("dosdevices");
+        let mut drives = Vec::new();
+
+        if let Ok(entries) = std::fs::read_dir(&dosdevices) {
+            for entry in entries.flatten() {
+                // "c:" is a drive, "d::" is the raw device behind it
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                let mut chars = name.chars();
+                let (Some(letter), Some(':'), None) = (chars.next(), chars.next(), chars.next()) else { continue };
+                if !letter.is_ascii_lowercase() {
+                    continue;
+                }
+                let Ok(target) = std::fs::read_link(entry.path()) else { continue };
+                drives.push((letter, normalize(&dosdevices.join(target))));
+            }
+        }
+
+        // Prefixes wineboot hasn't finished setting up still get Wine's defaults
+        if !drives.iter().any(|(letter, _)| *letter == 'c') {
+            drives.push(('c', bottle_path.join("drive_c")));
+        }
+        if !dosdevices.exists() {
+            drives.push(('z', PathBuf::from("/")));
+        }
+        drives.sort();
+
+        DriveMap { bottle_path: bottle_path.to_path_buf(), drives }
+    }
+
+    pub fn drive_root(&self, letter: char) -> Option<&Path> {
+        let letter = letter.to_ascii_lowercase();
+        self.drives.iter().find(|(l, _)| *l == letter).map(|(_, root)| root.as_path())
+    }
+
+    /// Host location of an absolute Windows path: `C:\...`, `\\server\share\...`,
+    /// and the `\\?\` / `\??\` forms of both. Components are matched case-insensitively;
+    /// whatever doesn't exist yet is appended as written.
+    pub fn to_host(&self, win_path: &str) -> Option<PathBuf> {
+        let path = win_path.replace('/', "\\");
+        let path = ["\\\\?\\", "\\??\\", "\\\\.\\"].iter()
+            .find_map(|prefix| path.strip_prefix(prefix).map(|rest| (true, rest)))
+            .unwrap_or((false, path.as_str()));
+
+        let (root, rest) = match path {
+            (true, rest) if starts_with_ignore_case(rest, "unix\\") => {
+                (PathBuf::from("/"), &rest[5..])
+            }
+            (true, rest) if starts_with_ignore_case(rest, "unc\\") => {
+                (self.bottle_path.join("dosdevices").join("unc"), &rest[4..])
+            }
+            (false, rest) if rest.starts_with("\\\\") => {
+                (self.bottle_path.join("dosdevices").join("unc"), &rest[2..])
+            }
+            (_, rest) => {
+                let mut chars = rest.chars();
+                let (Some(letter), Some(':')) = (chars.next(), chars.next()) else { return None };
+                if !letter.is_ascii_alphabetic() {
+                    return None;
+                }
+                (self.drive_root(letter)?.to_path_buf(), &rest[2..])
+            }
+        };
+
+        Some(lookup_ignore_case(&root, rest.split('\\')))
+    }
+
+    /// Windows form of a host path, using the most specific drive that contains it.
+    /// Paths under `dosdevices/unc` become UNC paths. None if no drive reaches the path,
+    /// e.g. outside the bottle once the user removed Z:.
+    pub fn to_windows(&self, host_path: &Path) -> Option<String> {
+        let host_path = normalize(host_path);
+
+        let dosdevices = self.bottle_path.join("dosdevices");
+        if let Ok(relative) = host_path.strip_prefix(&dosdevices) {
+            let mut components = relative.components();
+            let device = components.next().map(|c| c.as_os_str().to_string_lossy().to_lowercase()).unwrap_or_default();
+            // Through a drive symlink itself, e.g. `dosdevices/c:/windows`
+            if let [letter, b':'] = device.as_bytes() {
+                if letter.is_ascii_lowercase() {
+                    return Some(format!("{}:\\{}", letter.to_ascii_uppercase() as char, join_windows(components.as_path())));
+                }
+            }
+            if device == "unc" {
+                return Some(format!("\\\\{}", join_windows(components.as_path())));
+            }
+        }
+
+        self.drives.iter()
+            .filter_map(|(letter, root)| host_path.strip_prefix(root).ok().map(|rest| (letter, root, rest)))
+            .max_by_key(|(_, root, _)| root.components().count())
+            .map(|(letter, _, relative)| format!("{}:\\{}", letter.to_ascii_uppercase(), join_windows(relative)))
+    }
+}
+
+/// Maps a Windows path from an app's config (`C:\Program Files (x86)\Steam`, `D:\SteamLibrary`)
+/// to its location on the host, following the bottle's drive layout.
+pub fn windows_to_host_path(bottle_path: &Path, win_path: &str) -> Option<PathBuf> {
+    DriveMap::load(bottle_path).to_host(win_path)
+}
+
+/// What Windows programs in the bottle call a host path
+pub fn host_to_windows_path(bottle_path: &Path, host_path: &Path) -> Result<String, String> {
+    DriveMap::load(bottle_path).to_windows(host_path)
+        .ok_or(format!("No drive in this bottle reaches {}", host_path.display()))
+}
+
+/// Host location of a relative Windows path below `root`, e.g. an executable from a game's launch config
+pub fn resolve_relative(root: &Path, win_path: &str) -> PathBuf {
+    lookup_ignore_case(root, win_path.split(['\\', '/']))
+}
+
+/// Whether a string is an absolute Windows path rather than a host path
+pub fn is_windows_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':') || path.starts_with("\\\\")
+}
+
+/// Walks `parts` below `root`, matching each component case-insensitively like NTFS.
+/// `..` never climbs above `root`.
+fn lookup_ignore_case<'a>(root: &Path, parts: impl Iterator<Item = &'a str>) -> PathBuf {
+    let mut path = root.to_path_buf();
+    let mut depth = 0;
+    let mut exists = true;
+
+    for part in parts {
+        match part {
+            "" | "." => continue,
+            ".." => {
+                if depth > 0 {
+                    path.pop();
+                    depth -= 1;
+                    // Climbing out of a folder that doesn't exist can land in one that does
+                    exists = path.exists();
+                }
+                continue;
+            }
+            _ => {}
+        }
+        depth += 1;
+
+        let exact = path.join(part);
+        if !exists || exact.exists() {
+            exists = exists && exact.exists();
+            path = exact;
+            continue;
+        }
+
+        let wanted = part.to_lowercase();
+        let found = std::fs::read_dir(&path).ok().and_then(|entries| {
+            entries.flatten().find(|e| e.file_name().to_string_lossy().to_lowercase() == wanted)
+        });
+        match found {
+            Some(entry) => path.push(entry.file_name()),
+            None => {
+                exists = false;
+                path.push(part);
+            }
+        }
+    }
+    path
+}
+
+/// Resolves `.` and `..` without touching the filesystem, so symlinked drive roots stay as written
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn join_windows(relative: &Path) -> String {
+    relative.components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("\\")
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.len() >= prefix.len() && text.is_char_boundary(prefix.len()) && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
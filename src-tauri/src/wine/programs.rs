@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::core::bottle::Bottle;
+use crate::core::shim::NativeRegistry;
+use crate::wine::paths::windows_to_host_path;
+
+/// An entry from Add/Remove Programs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstalledProgram {
+    /// Uninstall subkey name, e.g. a product GUID or "Steam"
+    pub key: String,
+    pub display_name: String,
+    pub publisher: Option<String>,
+    pub display_version: Option<String>,
+    pub install_location: Option<PathBuf>,
+    pub display_icon: Option<PathBuf>,
+    pub uninstall_string: Option<String>,
+    pub quiet_uninstall_string: Option<String>,
+    pub estimated_size_kb: Option<u32>,
+    /// Registered by a 32-bit installer (Wow6432Node)
+    pub is_32_bit: bool,
+    /// Registered in HKCU instead of HKLM
+    pub per_user: bool,
+}
+
+const UNINSTALL_KEYS: [(&str, bool); 2] = [
+    (r"Software\Microsoft\Windows\CurrentVersion\Uninstall", false),
+    (r"Software\Wow6432Node\Microsoft\Windows\CurrentVersion\Uninstall", true),
+];
+
+pub struct ProgramRegistry;
+
+impl ProgramRegistry {
+    /// Programs registered under the Uninstall keys of system.reg (and user.reg for per-user installs).
+    /// System components and updates attached to a parent product are hidden, like Windows does.
+    pub fn list(bottle_path: &Path) -> Vec<InstalledProgram> {
+        let hives = [
+            (NativeRegistry::system(bottle_path), false),
+            (NativeRegistry::new(bottle_path), true),
+        ];
+
+        let mut programs: Vec<InstalledProgram> = Vec::new();
+        for (registry, per_user) in &hives {
+            for (parent, is_32_bit) in UNINSTALL_KEYS {
+                for name in registry.subkeys(parent) {
+                    let key = format!(r"{}\{}", parent, name);
+                    let text = |value: &str| registry.get_string(&key, value).filter(|s| !s.trim().is_empty());
+                    let dword = |value: &str| registry.get(&key, value).and_then(|v| v.as_dword());
+
+                    let Some(display_name) = text("DisplayName") else { continue };
+                    if dword("SystemComponent") == Some(1) || text("ParentKeyName").is_some() {
+                        continue;
+                    }
+                    if programs.iter().any(|p| p.key.eq_ignore_ascii_case(&name)) {
+                        continue;
+                    }
+
+                    let display_icon = text("DisplayIcon").and_then(|icon| {
+                        // "C:\Foo\app.exe,0" -> icon index is not part of the path
+                        let path = match icon.rsplit_once(',') {
+                            Some((path, index)) if index.trim().parse::<i32>().is_ok() => path.to_string(),
+                            _ => icon,
+                        };
+                        windows_to_host_path(bottle_path, path.trim_matches('"'))
+                    });
+
+                    programs.push(InstalledProgram {
+                        key: name.clone(),
+                        display_name,
+                        publisher: text("Publisher"),
+                        display_version: text("DisplayVersion"),
+                        install_location: text("InstallLocation")
+                            .and_then(|loc| windows_to_host_path(bottle_path, loc.trim_matches('"'))),
+                        display_icon,
+                        uninstall_string: text("UninstallString"),
+                        quiet_uninstall_string: text("QuietUninstallString"),
+                        estimated_size_kb: dword("EstimatedSize"),
+                        is_32_bit,
+                        per_user: *per_user,
+                    });
+                }
+            }
+        }
+
+        programs.sort_by_key(|p| p.display_name.to_lowercase());
+        programs
+    }
+
+    /// `list` plus MSI packages Pancho installed that never made it into the registry
+    pub fn list_for_bottle(bottle: &Bottle) -> Vec<InstalledProgram> {
+        let mut programs = Self::list(&bottle.path);
+        for product in &bottle.msi_products {
+            if programs.iter().any(|p| p.key.eq_ignore_ascii_case(&product.product_code)) {
+                continue;
+            }
+            programs.push(InstalledProgram {
+                key: product.product_code.clone(),
+                display_name: product.product_name.clone(),
+                publisher: None,
+                display_version: product.product_version.clone(),
+                install_location: None,
+                display_icon: None,
+                uninstall_string: Some(format!("MsiExec.exe /X{}", product.product_code)),
+                quiet_uninstall_string: None,
+                estimated_size_kb: None,
+                is_32_bit: false,
+                per_user: false,
+            });
+        }
+        programs.sort_by_key(|p| p.display_name.to_lowercase());
+        programs
+    }
+
+    /// Resolves a program's UninstallString into a host executable and its arguments
+    pub fn uninstall_command(bottle_path: &Path, program: &InstalledProgram) -> Result<(PathBuf, Vec<String>), String> {
+        let command_line = program.uninstall_string.as_deref()
+            .ok_or(format!("{} has no uninstaller", program.display_name))?;
+        let (exe, mut args) = split_command_line(command_line).ok_or("Empty UninstallString")?;
+
+        // Bare names such as MsiExec.exe or rundll32.exe live in system32
+        let host_exe = if exe.contains('\\') {
+            windows_to_host_path(bottle_path, &exe)
+        } else {
+            Some(bottle_path.join("drive_c/windows/system32").join(exe.to_lowercase()))
+        }.ok_or(format!("Cannot resolve uninstaller path {}", exe))?;
+
+        // MSI entries often record "/I{GUID}" (repair/modify); ask for a removal instead
+        if exe.to_lowercase().starts_with("msiexec") {
+            for arg in args.iter_mut() {
+                if arg.len() > 2 && arg[..2].eq_ignore_ascii_case("/i") {
+                    *arg = format!("/X{}", &arg[2..]);
+                } else if arg.eq_ignore_ascii_case("/i") {
+                    *arg = "/X".to_string();
+                }
+            }
+        }
+
+        Ok((host_exe, args))
+    }
+}
+
+/// Splits a Windows command line into program and arguments.
+/// Unquoted programs may contain spaces ("C:\Program Files\X\uninst.exe /S"),
+/// so the program ends at the first ".exe".
+pub fn split_command_line(command_line: &str) -> Option<(String, Vec<String>)> {
+    let trimmed = command_line.trim();
+    let (exe, rest) = if let Some(stripped) = trimmed.strip_prefix('"') {
+        let end = stripped.find('"')?;
+        (stripped[..end].to_string(), &stripped[end + 1..])
+    } else {
+        match trimmed.to_ascii_lowercase().find(".exe") {
+            Some(idx) => (trimmed[..idx + 4].to_string(), &trimmed[idx + 4..]),
+            None => {
+                let end = trimmed.find(' ').unwrap_or(trimmed.len());
+                (trimmed[..end].to_string(), &trimmed[end..])
+            }
+        }
+    };
+
+    if exe.is_empty() {
+        return None;
+    }
+
+    Some((exe, split_arguments(rest)))
+}
+
+/// Splits the arguments part of a Windows command line; quotes group words and are dropped
+pub fn split_arguments(arguments: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in arguments.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+
+    args
+}
+
+#[tauri::command]
+pub fn get_installed_programs(bottle_id: String, handle: tauri::AppHandle) -> Result<Vec<InstalledProgram>, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+
+    Ok(ProgramRegistry::list_for_bottle(bottle))
+}
@@ -64,6 +64,7 @@ impl RegistryManager {
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub async fn set_dll_override(bottle_path: &Path, wine_path: &Path, dll: &str, mode: &str) -> Result<(), String> {
         let entry = RegistryEntry {
             key: r"HKEY_CURRENT_USER\Software\Wine\DllOverrides".to_string(),
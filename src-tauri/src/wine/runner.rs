@@ -4,6 +4,7 @@ use tokio::process::Command;
 use std::collections::HashSet;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum WineRunnerType {
     Standard,
     GPTK,
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+use crate::wine::paths::DriveMap;
+
+/// ShellLinkHeader.HeaderSize, followed by CLSID_ShellLink
+const HEADER_SIZE: u32 = 0x4C;
+const SHELL_LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+// LinkFlags
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x01;
+const HAS_LINK_INFO: u32 = 0x02;
+const HAS_NAME: u32 = 0x04;
+const HAS_RELATIVE_PATH: u32 = 0x08;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+
+// LinkInfoFlags
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x01;
+
+/// The parts of a Windows `.lnk` shortcut needed to launch what it points to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShellLink {
+    /// Absolute Windows path of the target, e.g. `C:\Games\Game\game.exe`
+    pub target: Option<String>,
+    /// Target relative to the shortcut's folder
+    pub relative_path: Option<String>,
+    pub working_dir: Option<String>,
+    pub arguments: Option<String>,
+    pub description: Option<String>,
+    pub icon_location: Option<String>,
+}
+
+impl ShellLink {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| e.to_string())?;
+        Self::parse(&data)
+    }
+
+    /// Parses the header, LinkInfo and StringData sections of an MS-SHLLINK file
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if read_u32(data, 0) != Some(HEADER_SIZE) || data.get(4..20) != Some(&SHELL_LINK_CLSID) {
+            return Err("Not a shell link".to_string());
+        }
+        let flags = read_u32(data, 20).ok_or("Truncated shell link header")?;
+        let mut offset = HEADER_SIZE as usize;
+        let mut link = ShellLink::default();
+
+        if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+            let size = read_u16(data, offset).ok_or("Truncated IDList")? as usize;
+            offset += 2 + size;
+        }
+
+        if flags & HAS_LINK_INFO != 0 {
+            let info = data.get(offset..).ok_or("Truncated LinkInfo")?;
+            let size = read_u32(info, 0).ok_or("Truncated LinkInfo")? as usize;
+            let info = info.get(..size).ok_or("Truncated LinkInfo")?;
+            link.target = link_info_target(info);
+            offset += size;
+        }
+
+        let unicode = flags & IS_UNICODE != 0;
+        for (flag, field) in [
+            (HAS_NAME, &mut link.description),
+            (HAS_RELATIVE_PATH, &mut link.relative_path),
+            (HAS_WORKING_DIR, &mut link.working_dir),
+            (HAS_ARGUMENTS, &mut link.arguments),
+            (HAS_ICON_LOCATION, &mut link.icon_location),
+        ] {
+            if flags & flag == 0 {
+                continue;
+            }
+            // CountCharacters, then that many characters without a terminator
+            let count = read_u16(data, offset).ok_or("Truncated StringData")? as usize;
+            let bytes = if unicode { count * 2 } else { count };
+            let text = data.get(offset + 2..offset + 2 + bytes).ok_or("Truncated StringData")?;
+            *field = Some(if unicode { decode_utf16(text) } else { decode_ansi(text) });
+            offset += 2 + bytes;
+        }
+        Ok(link)
+    }
+
+    /// Host location of the target: the absolute path through the bottle's drives, or
+    /// the relative path from the folder `lnk_path` is in
+    pub fn resolve(&self, drives: &DriveMap, lnk_path: &Path) -> Option<PathBuf> {
+        let absolute = self.target.as_deref().and_then(|target| drives.to_host(target));
+        if absolute.as_ref().is_some_and(|path| path.exists()) {
+            return absolute;
+        }
+        // Relative paths may climb out of the shortcut's folder, up to the drive root
+        let relative = self.relative_path.as_deref().and_then(|relative| {
+            let folder = drives.to_windows(lnk_path.parent()?)?;
+            drives.to_host(&format!("{}\\{}", folder.trim_end_matches('\\'), relative))
+        });
+        match relative {
+            Some(path) if path.exists() => Some(path),
+            _ => absolute.or(relative),
+        }
+    }
+}
+
+/// LocalBasePath plus CommonPathSuffix, preferring the Unicode copies when present
+fn link_info_target(info: &[u8]) -> Option<String> {
+    let header_size = read_u32(info, 4)?;
+    let flags = read_u32(info, 8)?;
+    if flags & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+        return None;
+    }
+
+    let (base, suffix) = if header_size >= 0x24 {
+        let base = read_u32(info, 28)? as usize;
+        let suffix = read_u32(info, 32)? as usize;
+        (read_utf16z(info, base)?, read_utf16z(info, suffix).unwrap_or_default())
+    } else {
+        let base = read_u32(info, 16)? as usize;
+        let suffix = read_u32(info, 24)? as usize;
+        (read_ansiz(info, base)?, read_ansiz(info, suffix).unwrap_or_default())
+    };
+    if suffix.is_empty() {
+        return Some(base);
+    }
+    let separator = if base.ends_with('\\') { "" } else { "\\" };
+    Some(format!("{}{}{}", base, separator, suffix))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_ansiz(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let len = bytes.iter().position(|b| *b == 0)?;
+    Some(decode_ansi(&bytes[..len]))
+}
+
+fn read_utf16z(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let len = bytes.chunks_exact(2).position(|c| c == [0, 0])?;
+    Some(decode_utf16(&bytes[..len * 2]))
+}
+
+/// The ANSI code page is Windows-1252 in a Western prefix; Latin-1 matches it for paths
+fn decode_ansi(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+fn decode_utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
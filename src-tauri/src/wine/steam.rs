@@ -1,8 +1,14 @@
 use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 use tokio::process::Command;
+use crate::core::installer::InstallerKind;
 use crate::bottle::template::{RegistryEntry, RegistryValueType};
 use crate::wine::registry::RegistryManager;
+use crate::wine::paths::{resolve_relative, windows_to_host_path};
+use crate::wine::vdf;
+
+/// Where SteamSetup installs the client
+const STEAM_DIR: &str = r"C:\Program Files (x86)\Steam";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum SteamLaunchMode {
@@ -18,6 +24,45 @@ pub struct SteamStatus {
     pub path: Option<PathBuf>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SteamGame {
+    pub app_id: u32,
+    pub name: String,
+    pub install_dir: PathBuf,
+    pub library_path: PathBuf,
+    pub size_on_disk: u64,
+    pub state_flags: u32,
+    pub fully_installed: bool,
+    pub artwork: SteamArtwork,
+    /// Windows binary Steam starts for `-applaunch`, when it could be found
+    pub executable: Option<PathBuf>,
+}
+
+/// Images the Steam client caches under `appcache/librarycache`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SteamArtwork {
+    pub capsule: Option<PathBuf>,
+    pub header: Option<PathBuf>,
+    pub hero: Option<PathBuf>,
+    pub logo: Option<PathBuf>,
+    pub icon: Option<PathBuf>,
+}
+
+impl SteamArtwork {
+    /// Portrait capsule first since library cards are 2:3, then the wide banners
+    pub fn best_cover(&self) -> Option<&PathBuf> {
+        self.capsule.as_ref()
+            .or(self.header.as_ref())
+            .or(self.hero.as_ref())
+    }
+}
+
+// appmanifest StateFlags bits (EAppState)
+const STATE_FULLY_INSTALLED: u32 = 4;
+
+// "Steamworks Common Redistributables" ships in every library but is not a game
+const REDIST_APP_ID: u32 = 228980;
+
 pub struct SteamLauncher;
 
 impl SteamLauncher {
@@ -43,7 +88,7 @@ impl SteamLauncher {
         let output = Command::new(wine_path)
             .env("WINEPREFIX", bottle_path)
             .arg(&installer_path)
-            .arg("/S")
+            .args(InstallerKind::Nsis.silent_args(None))
             .output()
             .await
             .map_err(|e| e.to_string())?;
@@ -58,13 +103,13 @@ impl SteamLauncher {
                 key: r"HKEY_CURRENT_USER\Software\Valve\Steam".to_string(),
                 value_name: "SteamPath".to_string(),
                 value_type: RegistryValueType::String,
-                value_data: r"C:\Program Files (x86)\Steam".to_string(),
+                value_data: STEAM_DIR.to_string(),
             },
             RegistryEntry {
                 key: r"HKEY_CURRENT_USER\Software\Valve\Steam".to_string(),
                 value_name: "SteamExe".to_string(),
                 value_type: RegistryValueType::String,
-                value_data: r"C:\Program Files (x86)\Steam\steam.exe".to_string(),
+                value_data: format!("{}\\steam.exe", STEAM_DIR),
             },
         ];
 
@@ -76,13 +121,8 @@ impl SteamLauncher {
         Ok(())
     }
 
-    pub async fn launch_steam(
-        bottle_path: &Path, 
-        wine_path: &Path, 
-        mode: SteamLaunchMode,
-        environment_type: &str
-    ) -> Result<u32, String> {
-        let steam_exe = "C:\\Program Files (x86)\\Steam\\steam.exe";
+    fn steam_command(bottle_path: &Path, wine_path: &Path, environment_type: &str) -> Command {
+        let steam_exe = format!("{}\\steam.exe", STEAM_DIR);
         
         let mut cmd = Command::new(wine_path);
         cmd.env("WINEPREFIX", bottle_path);
@@ -99,6 +139,16 @@ impl SteamLauncher {
 
         // Prevent CEF crashes and reduce overhead
         cmd.arg("-no-browser"); 
+        cmd
+    }
+
+    pub async fn launch_steam(
+        bottle_path: &Path, 
+        wine_path: &Path, 
+        mode: SteamLaunchMode,
+        environment_type: &str
+    ) -> Result<u32, String> {
+        let mut cmd = Self::steam_command(bottle_path, wine_path, environment_type);
         
         match mode {
             SteamLaunchMode::BigPicture => { cmd.arg("-tenfoot"); },
@@ -111,8 +161,199 @@ impl SteamLauncher {
         Ok(child.id().unwrap_or(0))
     }
 
+    /// Launches an installed game through the Steam client (`steam.exe -applaunch <appid>`)
+    pub async fn launch_game(
+        bottle_path: &Path,
+        wine_path: &Path,
+        app_id: u32,
+        environment_type: &str
+    ) -> Result<u32, String> {
+        let mut cmd = Self::steam_command(bottle_path, wine_path, environment_type);
+        cmd.arg("-applaunch").arg(app_id.to_string());
+
+        let child = cmd.spawn().map_err(|e| e.to_string())?;
+        Ok(child.id().unwrap_or(0))
+    }
+
+    pub fn steam_root(bottle_path: &Path) -> PathBuf {
+        windows_to_host_path(bottle_path, STEAM_DIR)
+            .unwrap_or_else(|| bottle_path.join("drive_c/Program Files (x86)/Steam"))
+    }
+
+    /// Every Steam library in the bottle, starting with the one inside the Steam install.
+    /// Extra libraries come from `libraryfolders.vdf` and are stored as Windows paths.
+    pub fn library_folders(bottle_path: &Path) -> Vec<PathBuf> {
+        let steam_root = Self::steam_root(bottle_path);
+        let mut folders = vec![steam_root.clone()];
+
+        let candidates = [
+            steam_root.join("steamapps/libraryfolders.vdf"),
+            steam_root.join("config/libraryfolders.vdf"),
+        ];
+
+        for vdf_path in candidates {
+            let Ok(content) = std::fs::read_to_string(&vdf_path) else { continue };
+            let Ok(doc) = vdf::parse_text(&content) else { continue };
+            let Some(root) = doc.get("libraryfolders") else { continue };
+
+            for (key, value) in root.entries() {
+                if !key.chars().all(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+
+                // New format: "0" { "path" "C:\\..." }. Old format: "1" "D:\\SteamLibrary"
+                let win_path = match value {
+                    vdf::VdfValue::Object(_) => value.get_str("path"),
+                    vdf::VdfValue::String(path) => Some(path.as_str()),
+                    _ => None,
+                };
+
+                if let Some(host_path) = win_path.and_then(|p| windows_to_host_path(bottle_path, p)) {
+                    if !folders.iter().any(|f| paths_match(f, &host_path)) {
+                        folders.push(host_path);
+                    }
+                }
+            }
+        }
+
+        folders
+    }
+
+    /// Reads every `appmanifest_*.acf` in the bottle's Steam libraries
+    pub fn list_installed_games(bottle_path: &Path) -> Vec<SteamGame> {
+        let mut games: Vec<SteamGame> = Vec::new();
+
+        for library in Self::library_folders(bottle_path) {
+            let steamapps = library.join("steamapps");
+            let Ok(entries) = std::fs::read_dir(&steamapps) else { continue };
+
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_lowercase();
+                if !file_name.starts_with("appmanifest_") || !file_name.ends_with(".acf") {
+                    continue;
+                }
+
+                if let Some(game) = Self::parse_app_manifest(&entry.path(), &library) {
+                    if game.app_id != REDIST_APP_ID && !games.iter().any(|g| g.app_id == game.app_id) {
+                        games.push(game);
+                    }
+                }
+            }
+        }
+
+        let launch_configs = Self::launch_executables(bottle_path, &games);
+        for game in games.iter_mut() {
+            game.artwork = Self::find_artwork(bottle_path, game.app_id);
+            game.executable = launch_configs.iter()
+                .find(|(app_id, _)| *app_id == game.app_id)
+                .map(|(_, exe)| resolve_relative(&game.install_dir, exe))
+                .filter(|path| path.is_file())
+                .or_else(|| largest_executable(&game.install_dir));
+        }
+
+        games.sort_by_key(|g| g.name.to_lowercase());
+        games
+    }
+
+    /// Finds cached artwork for an app without touching the network.
+    /// Older clients store `<appid>_header.jpg` files flat in librarycache,
+    /// newer ones use a `<appid>/` folder that may nest assets in hashed subfolders.
+    pub fn find_artwork(bottle_path: &Path, app_id: u32) -> SteamArtwork {
+        let cache_dir = Self::steam_root(bottle_path).join("appcache/librarycache");
+        let app_dir = cache_dir.join(app_id.to_string());
+
+        let mut search_dirs = vec![app_dir.clone()];
+        if let Ok(entries) = std::fs::read_dir(&app_dir) {
+            search_dirs.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()));
+        }
+
+        let find = |names: &[&str]| -> Option<PathBuf> {
+            for name in names {
+                let flat = cache_dir.join(format!("{}_{}", app_id, name));
+                if flat.is_file() {
+                    return Some(flat);
+                }
+                if let Some(nested) = search_dirs.iter().map(|d| d.join(name)).find(|p| p.is_file()) {
+                    return Some(nested);
+                }
+            }
+            None
+        };
+
+        SteamArtwork {
+            capsule: find(&["library_600x900_2x.jpg", "library_600x900.jpg", "library_capsule.jpg"]),
+            header: find(&["header.jpg", "library_header.jpg"]),
+            hero: find(&["library_hero.jpg", "library_hero_blur.jpg"]),
+            logo: find(&["logo.png"]),
+            icon: find(&["icon.jpg"]),
+        }
+    }
+
+    /// Default Windows launch option of each game, relative to its install dir, from the
+    /// client's `appcache/appinfo.vdf`. Games the client has no info for are left out.
+    fn launch_executables(bottle_path: &Path, games: &[SteamGame]) -> Vec<(u32, String)> {
+        let path = Self::steam_root(bottle_path).join("appcache/appinfo.vdf");
+        let Ok(data) = std::fs::read(path) else { return Vec::new() };
+        let Ok(apps) = vdf::parse_appinfo(&data, &|app_id| games.iter().any(|g| g.app_id == app_id)) else {
+            return Vec::new();
+        };
+
+        apps.iter()
+            .filter_map(|(app_id, info)| {
+                let launch = info.get("appinfo")?.get("config")?.get("launch")?;
+                let exe = launch.entries().iter()
+                    .map(|(_, option)| option)
+                    .filter(|option| {
+                        let oslist = option.get("config").and_then(|config| config.get_str("oslist"));
+                        oslist.is_none_or(|os| os.is_empty() || os.contains("windows"))
+                    })
+                    .find(|option| matches!(option.get_str("type"), None | Some("") | Some("default") | Some("none")))
+                    .and_then(|option| option.get_str("executable"))?;
+                Some((*app_id, exe.to_string()))
+            })
+            .collect()
+    }
+
+    fn parse_app_manifest(manifest_path: &Path, library: &Path) -> Option<SteamGame> {
+        let content = std::fs::read_to_string(manifest_path).ok()?;
+        let doc = vdf::parse_text(&content).ok()?;
+        let state = doc.get("AppState")?;
+
+        let app_id = state.get_str("appid")?.parse::<u32>().ok()?;
+        let install_dir = state.get_str("installdir")?;
+        let name = state.get_str("name")
+            .map(|n| n.to_string())
+            .unwrap_or_else(|| install_dir.to_string());
+        let size_on_disk = state.get_str("SizeOnDisk").and_then(|s| s.parse().ok()).unwrap_or(0);
+        let state_flags = state.get_str("StateFlags").and_then(|s| s.parse().ok()).unwrap_or(0);
+
+        Some(SteamGame {
+            app_id,
+            name,
+            install_dir: library.join("steamapps/common").join(install_dir),
+            library_path: library.to_path_buf(),
+            size_on_disk,
+            state_flags,
+            fully_installed: state_flags & STATE_FULLY_INSTALLED != 0,
+            artwork: SteamArtwork::default(),
+            executable: None,
+        })
+    }
+
+    /// True when `loginusers.vdf` holds an account Steam will sign in to without a password
+    pub fn has_logged_in_account(bottle_path: &Path) -> bool {
+        let path = Self::steam_root(bottle_path).join("config/loginusers.vdf");
+        let Ok(content) = std::fs::read_to_string(path) else { return false };
+        let Ok(doc) = vdf::parse_text(&content) else { return false };
+        let Some(users) = doc.get("users") else { return false };
+
+        users.entries().iter().any(|(_, user)| {
+            user.get_str("RememberPassword") == Some("1") || user.get_str("AllowAutoLogin") == Some("1")
+        })
+    }
+
     pub fn check_status(bottle_path: &Path) -> SteamStatus {
-        let steam_path = bottle_path.join("drive_c/Program Files (x86)/Steam/steam.exe");
+        let steam_path = Self::steam_root(bottle_path).join("steam.exe");
         SteamStatus {
             is_installed: steam_path.exists(),
             path: if steam_path.exists() { Some(steam_path) } else { None },
@@ -120,6 +361,26 @@ impl SteamLauncher {
     }
 }
 
+/// Fallback when appinfo has no launch option: the biggest .exe in the top two levels of the
+/// install dir, which is the game far more often than a launcher or crash reporter
+fn largest_executable(install_dir: &Path) -> Option<PathBuf> {
+    walkdir::WalkDir::new(install_dir)
+        .max_depth(2)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().to_lowercase();
+            name.ends_with(".exe") && !name.contains("unins") && !name.contains("crash") && !name.starts_with("vc_redist")
+        })
+        .max_by_key(|e| e.metadata().map(|m| m.len()).unwrap_or(0))
+        .map(|e| e.into_path())
+}
+
+fn paths_match(a: &Path, b: &Path) -> bool {
+    a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+}
+
 #[tauri::command]
 pub async fn install_steam(bottle_id: String, handle: tauri::AppHandle) -> Result<(), String> {
     let bottles = crate::core::bottle::list_bottles(&handle)?;
@@ -142,6 +403,27 @@ pub async fn launch_steam(
     SteamLauncher::launch_steam(&bottle.path, &wine_path, mode, &bottle.environment_type).await
 }
 
+#[tauri::command]
+pub async fn launch_steam_game(
+    bottle_id: String,
+    app_id: u32,
+    handle: tauri::AppHandle
+) -> Result<u32, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+    let wine_path = bottle.engine_path.clone().unwrap_or_else(|| PathBuf::from("wine"));
+
+    SteamLauncher::launch_game(&bottle.path, &wine_path, app_id, &bottle.environment_type).await
+}
+
+#[tauri::command]
+pub fn get_steam_games(bottle_id: String, handle: tauri::AppHandle) -> Result<Vec<SteamGame>, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+
+    Ok(SteamLauncher::list_installed_games(&bottle.path))
+}
+
 #[tauri::command]
 pub fn check_steam_status(bottle_id: String, handle: tauri::AppHandle) -> Result<SteamStatus, String> {
     let bottles = crate::core::bottle::list_bottles(&handle)?;
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::core::scanner::DetectedApp;
+use crate::wine::paths::DriveMap;
+use crate::wine::steam::SteamLauncher;
+use crate::wine::vdf::{self, BinaryVdf, VdfValue};
+
+/// A "non-Steam game" entry from `userdata/<id>/config/shortcuts.vdf`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SteamShortcut {
+    pub user_id: String,
+    pub app_id: u32,
+    pub app_name: String,
+    pub exe: String,
+    pub start_dir: String,
+    pub icon: String,
+    pub launch_options: String,
+    pub is_hidden: bool,
+    pub tags: Vec<String>,
+}
+
+pub struct SteamShortcutManager;
+
+impl SteamShortcutManager {
+    /// Steam account ids that have a userdata folder in this bottle
+    pub fn list_users(bottle_path: &Path) -> Vec<String> {
+        let userdata = SteamLauncher::steam_root(bottle_path).join("userdata");
+        let mut users = Vec::new();
+
+        if let Ok(entries) = fs::read_dir(userdata) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // "0" and "anonymous" folders exist before anyone logs in
+                if entry.path().is_dir() && name != "0" && name.chars().all(|c| c.is_ascii_digit()) {
+                    users.push(name);
+                }
+            }
+        }
+
+        users.sort();
+        users
+    }
+
+    fn shortcuts_path(bottle_path: &Path, user_id: &str) -> PathBuf {
+        SteamLauncher::steam_root(bottle_path)
+            .join("userdata")
+            .join(user_id)
+            .join("config/shortcuts.vdf")
+    }
+
+    fn load(bottle_path: &Path, user_id: &str) -> Result<BinaryVdf, String> {
+        let path = Self::shortcuts_path(bottle_path, user_id);
+        if !path.exists() {
+            return Ok(BinaryVdf::new(VdfValue::Object(vec![("shortcuts".to_string(), VdfValue::Object(Vec::new()))])));
+        }
+
+        let data = fs::read(&path).map_err(|e| e.to_string())?;
+        let mut doc = vdf::parse_binary(&data)?;
+        if doc.root.get("shortcuts").is_none() {
+            if let Some(entries) = doc.root.entries_mut() {
+                entries.push(("shortcuts".to_string(), VdfValue::Object(Vec::new())));
+            }
+        }
+        Ok(doc)
+    }
+
+    /// Writes through a temporary file so a failed write never leaves Steam with a truncated file
+    fn save(bottle_path: &Path, user_id: &str, doc: &BinaryVdf) -> Result<(), String> {
+        let path = Self::shortcuts_path(bottle_path, user_id);
+        let data = vdf::write_binary(doc)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let tmp_path = path.with_extension("vdf.pancho");
+        fs::write(&tmp_path, data).map_err(|e| e.to_string())?;
+        fs::rename(&tmp_path, &path).map_err(|e| e.to_string())
+    }
+
+    fn shortcuts_mut(doc: &mut BinaryVdf) -> Result<&mut Vec<(String, VdfValue)>, String> {
+        doc.root.entries_mut()
+            .and_then(|entries| entries.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case("shortcuts")))
+            .and_then(|(_, v)| v.entries_mut())
+            .ok_or("shortcuts.vdf has no shortcuts list".to_string())
+    }
+
+    /// Every user's shortcuts. A user whose shortcuts.vdf can't be read is skipped
+    /// so the others still show up.
+    pub fn list(bottle_path: &Path) -> Result<Vec<SteamShortcut>, String> {
+        let mut shortcuts = Vec::new();
+
+        for user_id in Self::list_users(bottle_path) {
+            let doc = match Self::load(bottle_path, &user_id) {
+                Ok(doc) => doc,
+                Err(e) => {
+                    eprintln!("Pancho-Steam: Skipping shortcuts of user {}: {}", user_id, e);
+                    continue;
+                }
+            };
+            let Some(list) = doc.root.get("shortcuts") else { continue };
+
+            for (_, entry) in list.entries() {
+                let text = |key: &str| entry.get_str(key).unwrap_or_default().to_string();
+                shortcuts.push(SteamShortcut {
+                    user_id: user_id.clone(),
+                    app_id: entry.get_int("appid").unwrap_or(0) as u32,
+                    app_name: text("AppName"),
+                    exe: text("Exe"),
+                    start_dir: text("StartDir"),
+                    icon: text("icon"),
+                    launch_options: text("LaunchOptions"),
+                    is_hidden: entry.get_int("IsHidden").unwrap_or(0) != 0,
+                    tags: entry.get("tags")
+                        .map(|tags| tags.entries().iter().filter_map(|(_, t)| t.as_str().map(String::from)).collect())
+                        .unwrap_or_default(),
+                });
+            }
+        }
+
+        Ok(shortcuts)
+    }
+
+    /// Adds detected apps as non-Steam games for every Steam user in the bottle.
+    /// Apps that are already Steam games, or already have a shortcut, are skipped.
+    /// Returns how many distinct apps got a new shortcut for at least one user.
+    /// Steam only picks up the change on its next start.
+    pub fn add_apps(bottle_path: &Path, apps: &[DetectedApp]) -> Result<u32, String> {
+        let users = Self::list_users(bottle_path);
+        if users.is_empty() {
+            return Err("No Steam user found in this bottle. Log in to Steam first.".to_string());
+        }
+
+        let drives = DriveMap::load(bottle_path);
+        let mut added = HashSet::new();
+        for user_id in users {
+            let mut doc = Self::load(bottle_path, &user_id)?;
+            let shortcuts = Self::shortcuts_mut(&mut doc)?;
+            // Keys are normally "0".."n", but a list edited by other tools can have gaps
+            let mut next_index = shortcuts.iter()
+                .filter_map(|(key, _)| key.parse::<u64>().ok())
+                .max()
+                .map_or(0, |max| max + 1);
+
+            for app in apps.iter().filter(|a| a.steam_app_id.is_none()) {
+                let Some(win_exe) = app.windows_path.clone().or_else(|| drives.to_windows(Path::new(&app.exe_path))) else {
+                    eprintln!("Pancho-Steam: No drive in the bottle reaches {}, not adding it", app.exe_path);
+                    continue;
+                };
+                let exe = format!("\"{}\"", win_exe);
+                let start_dir = match win_exe.rfind('\\') {
+                    Some(idx) => format!("\"{}\\\"", &win_exe[..idx]),
+                    None => String::new(),
+                };
+
+                let app_id = shortcut_app_id(&exe, &app.name);
+                let exists = shortcuts.iter().any(|(_, s)| {
+                    s.get_int("appid").map(|id| id as u32) == Some(app_id)
+                        || s.get_str("Exe").is_some_and(|e| e.eq_ignore_ascii_case(&exe))
+                });
+                if exists {
+                    continue;
+                }
+
+                let entry = VdfValue::Object(vec![
+                    ("appid".to_string(), VdfValue::Int32(app_id as i32)),
+                    ("AppName".to_string(), VdfValue::String(app.name.clone())),
+                    ("Exe".to_string(), VdfValue::String(exe)),
+                    ("StartDir".to_string(), VdfValue::String(start_dir)),
+                    ("icon".to_string(), VdfValue::String(String::new())),
+                    ("ShortcutPath".to_string(), VdfValue::String(String::new())),
+                    ("LaunchOptions".to_string(), VdfValue::String(String::new())),
+                    ("IsHidden".to_string(), VdfValue::Int32(0)),
+                    ("AllowDesktopConfig".to_string(), VdfValue::Int32(1)),
+                    ("AllowOverlay".to_string(), VdfValue::Int32(1)),
+                    ("OpenVR".to_string(), VdfValue::Int32(0)),
+                    ("Devkit".to_string(), VdfValue::Int32(0)),
+                    ("DevkitGameID".to_string(), VdfValue::String(String::new())),
+                    ("DevkitOverrideAppID".to_string(), VdfValue::Int32(0)),
+                    ("LastPlayTime".to_string(), VdfValue::Int32(0)),
+                    ("tags".to_string(), VdfValue::Object(vec![
+                        ("0".to_string(), VdfValue::String("Pancho".to_string())),
+                    ])),
+                ]);
+
+                shortcuts.push((next_index.to_string(), entry));
+                next_index += 1;
+                added.insert(app_id);
+            }
+
+            Self::save(bottle_path, &user_id, &doc)?;
+        }
+
+        Ok(added.len() as u32)
+    }
+
+    /// Removes a shortcut by app id. Remaining entries are renumbered
+    /// because Steam expects the list keys to be "0".."n" without gaps.
+    pub fn remove(bottle_path: &Path, app_id: u32) -> Result<bool, String> {
+        let mut removed = false;
+
+        for user_id in Self::list_users(bottle_path) {
+            if !Self::shortcuts_path(bottle_path, &user_id).exists() {
+                continue;
+            }
+
+            let mut doc = Self::load(bottle_path, &user_id)?;
+            let shortcuts = Self::shortcuts_mut(&mut doc)?;
+            let before = shortcuts.len();
+            shortcuts.retain(|(_, s)| s.get_int("appid").map(|id| id as u32) != Some(app_id));
+
+            if shortcuts.len() != before {
+                for (index, (key, _)) in shortcuts.iter_mut().enumerate() {
+                    *key = index.to_string();
+                }
+                Self::save(bottle_path, &user_id, &doc)?;
+                removed = true;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Same id Steam derives for non-Steam games: CRC32 of exe + name with the high bit set.
+/// It is also the id used for grid artwork under `userdata/<id>/config/grid`.
+pub fn shortcut_app_id(exe: &str, app_name: &str) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in exe.bytes().chain(app_name.bytes()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc | 0x8000_0000
+}
+
+#[tauri::command]
+pub fn get_steam_shortcuts(bottle_id: String, handle: tauri::AppHandle) -> Result<Vec<SteamShortcut>, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+
+    SteamShortcutManager::list(&bottle.path)
+}
+
+#[tauri::command]
+pub fn add_steam_shortcuts(bottle_id: String, apps: Vec<DetectedApp>, handle: tauri::AppHandle) -> Result<u32, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+
+    SteamShortcutManager::add_apps(&bottle.path, &apps)
+}
+
+#[tauri::command]
+pub fn remove_steam_shortcut(bottle_id: String, app_id: u32, handle: tauri::AppHandle) -> Result<bool, String> {
+    let bottles = crate::core::bottle::list_bottles(&handle)?;
+    let bottle = bottles.iter().find(|b| b.id == bottle_id).ok_or("Bottle not found")?;
+
+    SteamShortcutManager::remove(&bottle.path, app_id)
+}
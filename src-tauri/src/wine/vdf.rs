@@ -0,0 +1,444 @@
+/// Valve Data Format (KeyValues) parser
+/// Steam keeps its library state in this format: `libraryfolders.vdf`,
+/// every `appmanifest_<appid>.acf`, `config.vdf`, `loginusers.vdf`...
+/// The client caches (`shortcuts.vdf`, `appinfo.vdf`) use the binary flavour,
+/// which adds typed integers and floats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VdfValue {
+    String(String),
+    Object(Vec<(String, VdfValue)>),
+    Int32(i32),
+    Float32(f32),
+    Pointer(u32),
+    Color(u32),
+    UInt64(u64),
+    Int64(i64),
+}
+
+impl VdfValue {
+    /// Looks up a child key. Steam is not consistent with casing
+    /// ("AppState" vs "appstate", "LibraryFolders" vs "libraryfolders"),
+    /// so keys are matched case-insensitively like the Source engine does.
+    pub fn get(&self, key: &str) -> Option<&VdfValue> {
+        match self {
+            VdfValue::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(|v| v.as_str())
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        self.get(key).and_then(|v| v.as_int())
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            VdfValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Integer view of the value. Text VDF stores numbers as strings,
+    /// so those are parsed as well.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            VdfValue::Int32(v) => Some(*v as i64),
+            VdfValue::Pointer(v) | VdfValue::Color(v) => Some(*v as i64),
+            VdfValue::UInt64(v) => i64::try_from(*v).ok(),
+            VdfValue::Int64(v) => Some(*v),
+            VdfValue::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, VdfValue)] {
+        match self {
+            VdfValue::Object(entries) => entries,
+            _ => &[],
+        }
+    }
+
+    pub fn entries_mut(&mut self) -> Option<&mut Vec<(String, VdfValue)>> {
+        match self {
+            VdfValue::Object(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+/// Deepest object nesting either parser accepts. Steam's own files stay under ten levels;
+/// the limit keeps a corrupt or hostile file from overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Str(String),
+    Open,
+    Close,
+}
+
+struct Tokenizer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { chars: input.chars().peekable() }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, String> {
+        loop {
+            match self.chars.peek() {
+                None => return Ok(None),
+                Some(c) if c.is_whitespace() || *c == '\u{feff}' => {
+                    self.chars.next();
+                }
+                Some('/') => {
+                    // `// comment` until end of line
+                    self.chars.next();
+                    if self.chars.peek() == Some(&'/') {
+                        for c in self.chars.by_ref() {
+                            if c == '\n' { break; }
+                        }
+                    } else {
+                        return Err("VDF: Unexpected '/'".to_string());
+                    }
+                }
+                Some('[') => {
+                    // Platform conditionals like [$WIN32] are irrelevant inside a bottle
+                    for c in self.chars.by_ref() {
+                        if c == ']' { break; }
+                    }
+                }
+                Some('{') => {
+                    self.chars.next();
+                    return Ok(Some(Token::Open));
+                }
+                Some('}') => {
+                    self.chars.next();
+                    return Ok(Some(Token::Close));
+                }
+                Some('"') => {
+                    self.chars.next();
+                    return self.quoted().map(|s| Some(Token::Str(s)));
+                }
+                Some(_) => return Ok(Some(Token::Str(self.unquoted()))),
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        while let Some(c) = self.chars.next() {
+            match c {
+                '"' => return Ok(out),
+                '\\' => match self.chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(other) => out.push(other),
+                    None => break,
+                },
+                _ => out.push(c),
+            }
+        }
+        Err("VDF: Unterminated string".to_string())
+    }
+
+    fn unquoted(&mut self) -> String {
+        let mut out = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                break;
+            }
+            out.push(c);
+            self.chars.next();
+        }
+        out
+    }
+}
+
+/// Parses a text VDF document. The result is always an `Object` holding the
+/// top-level keys, e.g. `parse_text(acf)?.get("AppState")`.
+pub fn parse_text(input: &str) -> Result<VdfValue, String> {
+    let mut tokenizer = Tokenizer::new(input);
+    let entries = parse_entries(&mut tokenizer, 0)?;
+    Ok(VdfValue::Object(entries))
+}
+
+/// `depth` is 0 for the top level and grows by one per nested object.
+fn parse_entries(tokenizer: &mut Tokenizer, depth: usize) -> Result<Vec<(String, VdfValue)>, String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("VDF: Objects nested deeper than {} levels", MAX_DEPTH));
+    }
+    let nested = depth > 0;
+    let mut entries = Vec::new();
+
+    loop {
+        let key = match tokenizer.next_token()? {
+            Some(Token::Str(key)) => key,
+            Some(Token::Close) if nested => return Ok(entries),
+            None if !nested => return Ok(entries),
+            None => return Err("VDF: Unexpected end of file".to_string()),
+            Some(token) => return Err(format!("VDF: Expected key, found {:?}", token)),
+        };
+
+        let value = match tokenizer.next_token()? {
+            Some(Token::Str(value)) => VdfValue::String(value),
+            Some(Token::Open) => VdfValue::Object(parse_entries(tokenizer, depth + 1)?),
+            _ => return Err(format!("VDF: Missing value for key '{}'", key)),
+        };
+
+        entries.push((key, value));
+    }
+}
+
+// Binary KeyValues type tags
+const TYPE_OBJECT: u8 = 0x00;
+const TYPE_STRING: u8 = 0x01;
+const TYPE_INT32: u8 = 0x02;
+const TYPE_FLOAT32: u8 = 0x03;
+const TYPE_POINTER: u8 = 0x04;
+const TYPE_WIDE_STRING: u8 = 0x05;
+const TYPE_COLOR: u8 = 0x06;
+const TYPE_UINT64: u8 = 0x07;
+const TYPE_END: u8 = 0x08;
+const TYPE_INT64: u8 = 0x0A;
+const TYPE_END_ALT: u8 = 0x0B;
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// First end tag seen, so the writer can close objects the same way
+    end_tag: Option<u8>,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, end_tag: None }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len())
+            .ok_or("VDF: Unexpected end of binary data")?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn cstr(&mut self) -> Result<String, String> {
+        let rest = self.data.get(self.pos..).ok_or("VDF: Unexpected end of binary data")?;
+        let len = rest.iter().position(|b| *b == 0).ok_or("VDF: Unterminated string")?;
+        let s = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.pos += len + 1;
+        Ok(s)
+    }
+}
+
+/// A binary VDF document with the tag that closes its objects: 0x08 in `shortcuts.vdf`,
+/// 0x0B in files written by some client versions
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryVdf {
+    pub root: VdfValue,
+    pub end_tag: u8,
+}
+
+impl BinaryVdf {
+    /// A new document, closed with the usual 0x08 tag
+    pub fn new(root: VdfValue) -> Self {
+        BinaryVdf { root, end_tag: TYPE_END }
+    }
+}
+
+/// Parses a binary VDF document such as `shortcuts.vdf`.
+/// Like `parse_text`, the root is an `Object` holding the top-level keys.
+pub fn parse_binary(data: &[u8]) -> Result<BinaryVdf, String> {
+    let mut reader = BinaryReader::new(data);
+    let entries = parse_binary_entries(&mut reader, &mut |r| r.cstr(), 0)?;
+    Ok(BinaryVdf {
+        root: VdfValue::Object(entries),
+        end_tag: reader.end_tag.unwrap_or(TYPE_END),
+    })
+}
+
+/// Reads binary entries until an end tag. `read_key` abstracts over how key
+/// names are stored: inline C strings, or indices into a string table (appinfo v29).
+/// `depth` is 0 for the top level and grows by one per nested object.
+fn parse_binary_entries(
+    reader: &mut BinaryReader,
+    read_key: &mut dyn FnMut(&mut BinaryReader) -> Result<String, String>,
+    depth: usize,
+) -> Result<Vec<(String, VdfValue)>, String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("VDF: Objects nested deeper than {} levels", MAX_DEPTH));
+    }
+    let mut entries = Vec::new();
+
+    loop {
+        // A missing final end tag is tolerated at the top level only; inside an
+        // object it means the data was cut off
+        if reader.pos >= reader.data.len() {
+            if depth > 0 {
+                return Err("VDF: Unexpected end of binary data".to_string());
+            }
+            return Ok(entries);
+        }
+
+        let value_type = reader.u8()?;
+        if value_type == TYPE_END || value_type == TYPE_END_ALT {
+            reader.end_tag.get_or_insert(value_type);
+            return Ok(entries);
+        }
+
+        let key = read_key(reader)?;
+        let value = match value_type {
+            TYPE_OBJECT => VdfValue::Object(parse_binary_entries(reader, read_key, depth + 1)?),
+            TYPE_STRING => VdfValue::String(reader.cstr()?),
+            TYPE_INT32 => VdfValue::Int32(reader.u32()? as i32),
+            TYPE_FLOAT32 => VdfValue::Float32(f32::from_bits(reader.u32()?)),
+            TYPE_POINTER => VdfValue::Pointer(reader.u32()?),
+            TYPE_COLOR => VdfValue::Color(reader.u32()?),
+            TYPE_UINT64 => VdfValue::UInt64(reader.u64()?),
+            TYPE_INT64 => VdfValue::Int64(reader.u64()? as i64),
+            TYPE_WIDE_STRING => return Err(format!("VDF: Wide strings are not supported (key '{}')", key)),
+            other => return Err(format!("VDF: Unknown binary type 0x{:02x} for key '{}'", other, key)),
+        };
+
+        entries.push((key, value));
+    }
+}
+
+/// Serializes a document back to the binary format. Values keep their original types and
+/// every object is closed with `end_tag`, so an unmodified document round-trips byte for byte.
+pub fn write_binary(doc: &BinaryVdf) -> Result<Vec<u8>, String> {
+    if doc.end_tag != TYPE_END && doc.end_tag != TYPE_END_ALT {
+        return Err(format!("VDF: 0x{:02x} is not an end tag", doc.end_tag));
+    }
+    let mut out = Vec::new();
+    for (key, value) in doc.root.entries() {
+        write_binary_entry(&mut out, key, value, doc.end_tag)?;
+    }
+    out.push(doc.end_tag);
+    Ok(out)
+}
+
+fn write_binary_entry(out: &mut Vec<u8>, key: &str, value: &VdfValue, end_tag: u8) -> Result<(), String> {
+    let push_cstr = |out: &mut Vec<u8>, s: &str| -> Result<(), String> {
+        if s.as_bytes().contains(&0) {
+            return Err(format!("VDF: String for '{}' contains a NUL byte", key));
+        }
+        out.extend_from_slice(s.as_bytes());
+        out.push(0);
+        Ok(())
+    };
+
+    let type_tag = match value {
+        VdfValue::Object(_) => TYPE_OBJECT,
+        VdfValue::String(_) => TYPE_STRING,
+        VdfValue::Int32(_) => TYPE_INT32,
+        VdfValue::Float32(_) => TYPE_FLOAT32,
+        VdfValue::Pointer(_) => TYPE_POINTER,
+        VdfValue::Color(_) => TYPE_COLOR,
+        VdfValue::UInt64(_) => TYPE_UINT64,
+        VdfValue::Int64(_) => TYPE_INT64,
+    };
+    out.push(type_tag);
+    push_cstr(out, key)?;
+
+    match value {
+        VdfValue::Object(entries) => {
+            for (k, v) in entries {
+                write_binary_entry(out, k, v, end_tag)?;
+            }
+            out.push(end_tag);
+        }
+        VdfValue::String(s) => push_cstr(out, s)?,
+        VdfValue::Int32(v) => out.extend_from_slice(&v.to_le_bytes()),
+        VdfValue::Float32(v) => out.extend_from_slice(&v.to_bits().to_le_bytes()),
+        VdfValue::Pointer(v) | VdfValue::Color(v) => out.extend_from_slice(&v.to_le_bytes()),
+        VdfValue::UInt64(v) => out.extend_from_slice(&v.to_le_bytes()),
+        VdfValue::Int64(v) => out.extend_from_slice(&v.to_le_bytes()),
+    }
+
+    Ok(())
+}
+
+/// Parses Steam's `appcache/appinfo.vdf` (versions 27, 28 and 29).
+/// Returns each app `wanted` accepts with its binary KeyValues blob, usually rooted at `appinfo`;
+/// the others are skipped without being parsed.
+pub fn parse_appinfo(data: &[u8], wanted: &dyn Fn(u32) -> bool) -> Result<Vec<(u32, VdfValue)>, String> {
+    let mut reader = BinaryReader::new(data);
+    let magic = reader.u32()?;
+    let _universe = reader.u32()?;
+
+    let version = match magic {
+        0x07564427 => 27,
+        0x07564428 => 28,
+        0x07564429 => 29,
+        _ => return Err(format!("appinfo: Unsupported magic 0x{:08x}", magic)),
+    };
+
+    // v29 moved key names into a string table at the end of the file
+    let string_table = if version >= 29 {
+        let offset = reader.u64()? as usize;
+        let mut table_reader = BinaryReader::new(data);
+        table_reader.pos = offset;
+        let count = table_reader.u32()?;
+        let mut table = Vec::with_capacity(count.min(1 << 20) as usize);
+        for _ in 0..count {
+            table.push(table_reader.cstr()?);
+        }
+        Some(table)
+    } else {
+        None
+    };
+
+    let mut apps = Vec::new();
+    loop {
+        let app_id = reader.u32()?;
+        if app_id == 0 {
+            break;
+        }
+
+        let size = reader.u32()? as usize;
+        let blob_end = reader.pos.checked_add(size).filter(|end| *end <= data.len())
+            .ok_or_else(|| format!("appinfo: Entry for app {} runs past the end of the file", app_id))?;
+        if !wanted(app_id) {
+            reader.pos = blob_end;
+            continue;
+        }
+        // infoState(4) lastUpdated(4) picsToken(8) sha1(20) changeNumber(4) [binary sha1(20)]
+        let header_len = if version >= 28 { 60 } else { 40 };
+        reader.bytes(header_len)?;
+
+        let entries = match &string_table {
+            Some(table) => parse_binary_entries(&mut reader, &mut |r| {
+                let index = r.u32()? as usize;
+                table.get(index).cloned().ok_or_else(|| format!("appinfo: Bad string index {}", index))
+            }, 0)?,
+            None => parse_binary_entries(&mut reader, &mut |r| r.cstr(), 0)?,
+        };
+
+        apps.push((app_id, VdfValue::Object(entries)));
+        reader.pos = blob_end;
+    }
+
+    Ok(apps)
+}
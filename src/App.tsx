@@ -1,10 +1,12 @@
 import { useState, useEffect, useRef } from "react";
-import { invoke } from "@tauri-apps/api/core";
-import { open, ask } from "@tauri-apps/plugin-dialog";
+import { invoke, convertFileSrc } from "@tauri-apps/api/core";
+import { open, ask, save } from "@tauri-apps/plugin-dialog";
 import { listen } from "@tauri-apps/api/event";
 import * as Icons from "lucide-react";
 import { BottleWizard } from "@/components/BottleWizard";
 import { GraphicsConfig } from "@/components/GraphicsConfig";
+import { InstalledPrograms } from "@/components/InstalledPrograms";
+import { SteamShortcuts } from "@/components/SteamShortcuts";
 
 interface Bottle {
   id: string;
@@ -22,6 +24,11 @@ interface DetectedApp {
   exe_path: string;
   is_priority: boolean;
   pinned: boolean;
+  steam_app_id?: number | null;
+  cover?: string | null;
+  class?: string;
+  launch_args?: string[];
+  working_dir?: string | null;
 }
 
 interface BackgroundTask {
@@ -62,6 +69,14 @@ function App() {
   
   // Analysis State
   const [analysisInfo, setAnalysisInfo] = useState<any>(null);
+  const [apiTrace, setApiTrace] = useState<any[]>([]);
+  const [hleRun, setHleRun] = useState<{ running: boolean, runId?: string, report?: any }>({ running: false });
+  // The HLE helper only runs on Linux x86_64
+  const [hleSupported, setHleSupported] = useState(false);
+
+  // What the host can execute; Rosetta 2 before macOS 15 has no AVX
+  const [cpuProfiles, setCpuProfiles] = useState<string[]>([]);
+  const [cpuProfile, setCpuProfile] = useState(() => localStorage.getItem("cpuProfile") ?? "rosetta2");
 
   // Notification State
   const [notification, setNotification] = useState<{ message: string, type: 'info' | 'warning' } | null>(null);
@@ -77,6 +92,8 @@ function App() {
   useEffect(() => {
     loadBottles();
     checkEngine();
+    invoke<string[]>("list_cpu_profiles").then(setCpuProfiles).catch(() => {});
+    invoke<boolean>("is_hle_supported").then(setHleSupported).catch(() => {});
 
     const unlistenStatus = listen<string>("status-update", (event) => {
       addToLog(`[SYSTEM] ${event.payload}`);
@@ -90,6 +107,11 @@ function App() {
       }
     });
 
+    // HLE API calls, streamed in batches while an isolated run is traced
+    const unlistenTrace = listen<any>("api-trace", (event) => {
+      setApiTrace(prev => [...prev, ...event.payload.events].slice(-500));
+    });
+
     const unlistenEngine = listen<string>("engine-status", (event) => {
       addToLog(`[ENGINE] ${event.payload}`);
       updateTask('engine-setup', event.payload);
@@ -102,6 +124,7 @@ function App() {
       unlistenStatus.then(f => f()); 
       unlistenEngine.then(f => f());
       unlistenLib.then(f => f());
+      unlistenTrace.then(f => f());
     };
   }, []);
 
@@ -135,11 +158,43 @@ function App() {
   const handleAnalyzeApp = async (path: string) => {
     setActiveTab("analysis");
     try {
-      const info = await invoke("launch_installer", { path });
-      setAnalysisInfo(info);
+      const info = await invoke<any>("launch_installer", { path });
+      const graphics = await invoke("analyze_graphics", { path }).catch(() => null);
+      const coverage = await invoke("get_import_coverage", { path }).catch(() => null);
+      setAnalysisInfo({ ...info, path, graphics, coverage });
+      setHleRun({ running: false });
     } catch (e) { addToLog(`Analysis Error: ${e}`); }
   };
 
+  // Runs the executable in the isolated HLE helper; a crash there never touches the app
+  const handleRunIsolated = async (path: string) => {
+    // Cancelling goes by this id, so it can only ever stop this run
+    const runId = crypto.randomUUID();
+    setHleRun({ running: true, runId });
+    setApiTrace([]);
+    try {
+      const report = await invoke<any>("run_hle", { runId, path, bottleId: selectedBottle?.id ?? null, trace: true });
+      setHleRun({ running: false, report });
+    } catch (e) {
+      setHleRun({ running: false });
+      addToLog(`HLE run failed: ${e}`);
+    }
+  };
+
+  const handleCancelIsolated = () => {
+    if (!hleRun.runId) return;
+    invoke("cancel_hle", { runId: hleRun.runId }).catch(e => addToLog(`HLE cancel failed: ${e}`));
+  };
+
+  const handleExportTrace = async (format: "json" | "chrome") => {
+    const path = await save({ defaultPath: format === "chrome" ? "pancho-trace.chrome.json" : "pancho-trace.json", filters: [{ name: "JSON", extensions: ["json"] }] });
+    if (!path) return;
+    try {
+      const count = await invoke<number>("export_api_trace", { path, format });
+      addToLog(`Exported ${count} traced calls to ${path}`);
+    } catch (e) { addToLog(`Trace export failed: ${e}`); }
+  };
+
   const loadBottles = async () => {
     try {
       const list = await invoke<Bottle[]>("get_bottles");
@@ -214,9 +269,19 @@ function App() {
     } catch (e) { console.error(e); }
   };
 
-  const handleRun = async (path: string) => {
+  const handleRun = async (app: DetectedApp) => {
     if (!selectedBottle) return;
+    const path = app.exe_path;
     try {
+      if (!(await confirmCompatibility(app))) return;
+      if (!(await confirmSignature(app))) return;
+
+      if (app.steam_app_id) {
+          addToLog(`Launching ${app.name} through Steam...`);
+          await invoke("launch_steam_game", { bottleId: selectedBottle.id, appId: app.steam_app_id });
+          return;
+      }
+
       const fileName = path.split('/').pop()?.toLowerCase() || "";
       if (fileName.includes("steam.exe")) {
           setNotification({ 
@@ -234,14 +299,85 @@ function App() {
       }
 
       addToLog(`Launching ${path.split('/').pop()}...`);
-      await invoke("run_installer", { path, bottleId: selectedBottle.id });
+      await invoke("run_installer", { path, bottleId: selectedBottle.id, args: app.launch_args ?? [], workingDir: app.working_dir ?? null });
     } catch (err) { addToLog(`Error: ${err}`); }
   };
 
+  // Runs the installer with its framework's silent switches
+  const handleSilentInstall = async (app: DetectedApp) => {
+    if (!selectedBottle) return;
+    try {
+      if (!(await confirmSignature(app))) return;
+      addToLog(`Installing ${app.exe_path.split('/').pop()} unattended...`);
+      await invoke("run_installer", { path: app.exe_path, bottleId: selectedBottle.id, unattended: true });
+    } catch (err) { addToLog(`Error: ${err}`); }
+  };
+
+  // Anti-cheat, DRM, CPU extensions and DLLs that can't work under Wine are reported before launch
+  const confirmCompatibility = async (app: DetectedApp): Promise<boolean> => {
+    const findings: { name: string, message: string }[] = [];
+    const report = await invoke<any>("check_game_compatibility", { path: app.exe_path }).catch(() => null);
+    if (report) findings.push(...report.findings.filter((f: any) => f.severity !== "Info"));
+
+    // Extensions only reached after a CPUID check are skipped on CPUs without them
+    const isa = await invoke<any>("scan_instruction_sets", { path: app.exe_path, profile: cpuProfile }).catch(() => null);
+    if (isa && isa.unsupported.length > 0) {
+      findings.push({
+        name: "CPU instructions",
+        message: `Uses ${isa.unsupported.join(", ")}, which ${isa.profile.name} can't execute.`,
+      });
+    }
+
+    const deps = selectedBottle
+      ? await invoke<any>("check_dependencies", { bottleId: selectedBottle.id, exePath: app.exe_path }).catch(() => null)
+      : null;
+    for (const issue of deps?.issues ?? []) {
+      if (issue.MissingDll && !issue.MissingDll.delay_load) {
+        findings.push({ name: "Missing DLL", message: `${issue.MissingDll.dll_name}, needed by ${issue.MissingDll.required_by}, is not in this bottle.` });
+      } else if (issue.ArchitectureMismatch) {
+        const { dll_name, found, expected } = issue.ArchitectureMismatch;
+        findings.push({ name: "Architecture mismatch", message: `${dll_name} is ${found}, but the game needs ${expected}.` });
+      }
+    }
+    if (findings.some(f => f.name === "Missing DLL") && deps.winetricks.length > 0) {
+      findings.push({ name: "Fix", message: `Install ${deps.winetricks.join(", ")} with winetricks.` });
+    }
+
+    if (findings.length === 0) return true;
+    const details = findings.map(f => `${f.name}: ${f.message}`).join("\n\n");
+    return await ask(`${details}\n\nLaunch anyway?`, {
+      title: report?.severity === "Blocking" ? "This game will not run" : "Compatibility warning",
+      kind: "warning",
+    });
+  };
+
+  // Any installer could read the Steam login stored in the bottle; signers aren't verified
+  const confirmSignature = async (app: DetectedApp): Promise<boolean> => {
+    if (!selectedBottle || app.class !== "Installer") return true;
+    try {
+      const trust = await invoke<any>("check_installer_signature", { path: app.exe_path, bottleId: selectedBottle.id });
+      if (!trust.warn) return true;
+      const status = trust.signature.status;
+      const reason = status === "Tampered"
+        ? "was modified after it was signed"
+        : status === "DigestMatches" || status === "SignedUnverified"
+          ? `is signed${trust.signature.subject ? ` as "${trust.signature.subject}"` : ""}, but the signer is not verified`
+          : "has no intact signature";
+      return await ask(`${app.exe_path.split('/').pop()} ${reason}. This bottle holds a logged-in Steam account.\n\nRun it anyway?`, {
+        title: "Untrusted installer",
+        kind: "warning",
+      });
+    } catch (e) {
+      return true;
+    }
+  };
+
   const addToLog = (msg: string) => setLog(prev => [...prev, `[${new Date().toLocaleTimeString()}] ${msg}`]);
 
-  const getAsset = (name: string) => {
-    const n = name.toLowerCase();
+  const getAsset = (app: DetectedApp) => {
+    // Steam artwork lives inside the bottle; bundled covers are served from /covers
+    if (app.cover) return app.cover.startsWith("/covers/") ? app.cover : convertFileSrc(app.cover);
+    const n = app.name.toLowerCase();
     if (n.includes("steam")) return APP_ASSETS.steam;
     return APP_ASSETS.default;
   };
@@ -314,9 +450,9 @@ function App() {
                     <div className="grid grid-cols-2 md:grid-cols-3 lg:grid-cols-4 2xl:grid-cols-5 gap-8">
                         <div 
                           onClick={() => {
-                            open({ multiple: false, filters: [{ name: 'EXE', extensions: ['exe'] }] }).then(s => {
+                            open({ multiple: false, filters: [{ name: 'Programs', extensions: ['exe', 'msi'] }] }).then(s => {
                               if (s && typeof s === 'string') {
-                                handlePinApp({ name: s.split('/').pop()?.replace('.exe', '') || "App", exe_path: s, is_priority: false, pinned: true });
+                                handlePinApp({ name: s.split('/').pop()?.replace(/\.(exe|msi)$/i, '') || "App", exe_path: s, is_priority: false, pinned: true });
                               }
                             });
                           }}
@@ -329,13 +465,16 @@ function App() {
                         </div>
 
                         {priorityApps.map((app, i) => (
-                            <div key={i} style={glassyStyle} className="group relative aspect-[2/3] overflow-hidden cursor-pointer hover:border-white transition-all bg-zinc-900" onClick={() => handleRun(app.exe_path)}>
-                                <img src={getAsset(app.name)} className="absolute inset-0 w-full h-full object-cover opacity-60 group-hover:opacity-100 transition-opacity" />
+                            <div key={i} style={glassyStyle} className="group relative aspect-[2/3] overflow-hidden cursor-pointer hover:border-white transition-all bg-zinc-900" onClick={() => handleRun(app)}>
+                                <img src={getAsset(app)} className="absolute inset-0 w-full h-full object-cover opacity-60 group-hover:opacity-100 transition-opacity" />
                                 <div className="absolute inset-0 bg-gradient-to-t from-black via-transparent to-transparent opacity-80" />
                                 <div className="absolute inset-0 p-6 flex flex-col justify-end">
                                     <p className="text-xl font-black uppercase tracking-tight">{app.name}</p>
                                     <div className="flex gap-2 opacity-0 group-hover:opacity-100 transition-all">
                                         <button onClick={(e) => { e.stopPropagation(); handleAnalyzeApp(app.exe_path); }} className="mt-4 text-[9px] font-black text-zinc-500 hover:text-white uppercase tracking-widest border border-white/10 px-3 py-1 bg-black/50">Analyze</button>
+                                        {app.class === "Installer" && (
+                                            <button onClick={(e) => { e.stopPropagation(); handleSilentInstall(app); }} className="mt-4 text-[9px] font-black text-zinc-500 hover:text-white uppercase tracking-widest border border-white/10 px-3 py-1 bg-black/50">Silent</button>
+                                        )}
                                         <button onClick={(e) => { e.stopPropagation(); handleUnpinApp(app.exe_path); }} className="mt-4 text-[9px] font-black text-red-500 hover:text-red-400 uppercase tracking-widest border border-red-500/10 px-3 py-1 bg-black/50">Unpin</button>
                                     </div>
                                 </div>
@@ -359,7 +498,7 @@ function App() {
                         </header>
                         <div className="grid grid-cols-2 md:grid-cols-3 lg:grid-cols-4 2xl:grid-cols-5 gap-8">
                             {browseApps.map((app, i) => (
-                                <div key={i} className="group relative aspect-[2/3] border border-white/5 hover:border-white/20 bg-zinc-900/40 p-6 flex flex-col transition-all cursor-pointer" onClick={() => handleRun(app.exe_path)}>
+                                <div key={i} className="group relative aspect-[2/3] border border-white/5 hover:border-white/20 bg-zinc-900/40 p-6 flex flex-col transition-all cursor-pointer" onClick={() => handleRun(app)}>
                                     <div className="flex-1 flex items-center justify-center">
                                         <Icons.FileCode size={48} className="text-zinc-800 group-hover:text-zinc-600 transition-colors" />
                                     </div>
@@ -398,8 +537,96 @@ function App() {
                                     <p className="text-zinc-500 uppercase tracking-widest text-[10px]">Sections</p>
                                     <p className="text-xl text-white font-black">{analysisInfo.sections}</p>
                                 </div>
+                                <div className="space-y-4">
+                                    <p className="text-zinc-500 uppercase tracking-widest text-[10px]">Subsystem</p>
+                                    <p className="text-xl text-white font-black">{analysisInfo.subsystem}</p>
+                                </div>
+                                <div className="space-y-4">
+                                    <p className="text-zinc-500 uppercase tracking-widest text-[10px]">Linker / OS</p>
+                                    <p className="text-xl text-white font-black">{analysisInfo.linker_version} / {analysisInfo.os_version}</p>
+                                </div>
                             </div>
+                            {analysisInfo.msi && (
+                                <p className="text-[10px] font-black uppercase tracking-widest text-emerald-500">
+                                    {[analysisInfo.msi.product_name, analysisInfo.msi.manufacturer, analysisInfo.msi.product_version].filter(Boolean).join(" · ")}
+                                    {analysisInfo.msi.product_code ? ` · ${analysisInfo.msi.product_code}` : ""}
+                                    {analysisInfo.msi.all_users === "1" ? " · Per-machine" : ""}
+                                </p>
+                            )}
+                            {analysisInfo.dotnet && (
+                                <p className="text-[10px] font-black uppercase tracking-widest text-amber-500">
+                                    Requires .NET {analysisInfo.dotnet.target_framework ?? analysisInfo.dotnet.runtime_version}
+                                </p>
+                            )}
+                            {analysisInfo.graphics && (
+                                <p className="text-[10px] font-black uppercase tracking-widest text-emerald-500">
+                                    {analysisInfo.graphics.apis.join(" / ") || "No graphics API detected"}
+                                    {analysisInfo.graphics.engine ? ` · ${analysisInfo.graphics.engine}` : ""}
+                                    {` · Recommended: ${analysisInfo.graphics.recommended_backend}`}
+                                    {analysisInfo.graphics.components.length > 0 ? ` · Needs ${analysisInfo.graphics.components.join(", ")}` : ""}
+                                </p>
+                            )}
+                            {analysisInfo.signature && (
+                                <p className={`text-[10px] font-black uppercase tracking-widest ${["DigestMatches", "SignedUnverified"].includes(analysisInfo.signature.status) ? "text-zinc-500" : "text-amber-500"}`}>
+                                    {analysisInfo.signature.status === "Unsigned"
+                                        ? "Unsigned"
+                                        : analysisInfo.signature.status === "DigestMatches" || analysisInfo.signature.status === "SignedUnverified"
+                                            ? "Signed, signer not verified"
+                                            : `Signature ${analysisInfo.signature.status}`}
+                                    {analysisInfo.signature.subject ? ` · Claims ${analysisInfo.signature.subject} (unverified)` : ""}
+                                    {analysisInfo.signature.issuer ? ` · Issued by ${analysisInfo.signature.issuer} (unverified)` : ""}
+                                    {analysisInfo.signature.timestamp ? ` · Signed ${analysisInfo.signature.timestamp}` : ""}
+                                </p>
+                            )}
+                            {analysisInfo.coverage?.total > 0 && (
+                                <p className="text-[10px] font-black uppercase tracking-widest text-zinc-500">
+                                    {`HLE coverage ${analysisInfo.coverage.hle}/${analysisInfo.coverage.total} imports (${Math.round(analysisInfo.coverage.hle_ratio * 100)}%)`}
+                                    {analysisInfo.coverage.emulated_modules?.length > 0 ? ` · Emulated: ${analysisInfo.coverage.emulated_modules.join(", ")}` : ""}
+                                </p>
+                            )}
+                            {hleSupported && analysisInfo.coverage?.total > 0 && (
+                                <div className="flex items-center gap-4">
+                                    <button onClick={() => handleRunIsolated(analysisInfo.path)} disabled={hleRun.running} className="text-[8px] border border-white/10 px-2 py-1 hover:bg-white hover:text-black transition-all font-black disabled:opacity-50">{hleRun.running ? "RUNNING..." : "RUN ISOLATED"}</button>
+                                    {hleRun.running && (
+                                        <button onClick={handleCancelIsolated} className="text-[8px] border border-white/10 px-2 py-1 hover:bg-red-500 hover:text-black transition-all font-black">CANCEL</button>
+                                    )}
+                                    {hleRun.report && (
+                                        <p className={`text-[10px] font-black uppercase tracking-widest ${!hleRun.report.cancelled && (hleRun.report.signal || hleRun.report.error || hleRun.report.unimplemented) ? "text-red-500" : "text-zinc-500"}`}>
+                                            {hleRun.report.error
+                                                ? hleRun.report.error
+                                                : hleRun.report.cancelled
+                                                    ? "Cancelled"
+                                                    : hleRun.report.unimplemented
+                                                        ? `Called unimplemented ${hleRun.report.unimplemented}`
+                                                        : hleRun.report.signal
+                                                            ? `Crashed · signal ${hleRun.report.signal}${hleRun.report.fault_address != null ? ` at 0x${hleRun.report.fault_address.toString(16)}` : ""}${hleRun.report.instruction_pointer != null ? ` · rip 0x${hleRun.report.instruction_pointer.toString(16)}` : ""}`
+                                                            : `Exited with code ${hleRun.report.exit_code}`}
+                                        </p>
+                                    )}
+                                </div>
+                            )}
+                            {analysisInfo.manifest?.execution_level === "requireAdministrator" && (
+                                <p className="text-[10px] font-black uppercase tracking-widest text-amber-500">Requests administrator rights</p>
+                            )}
                         </div>
+                        {apiTrace.length > 0 && (
+                            <div className="space-y-3 pt-6 border-t border-white/5">
+                                <div className="flex justify-between items-center">
+                                    <p className="text-zinc-500 uppercase tracking-widest text-[10px]">API Trace · {apiTrace.length} calls</p>
+                                    <div className="flex gap-2">
+                                        <button onClick={() => handleExportTrace("json")} className="text-[8px] border border-white/10 px-2 py-1 hover:bg-white hover:text-black transition-all font-black">JSON</button>
+                                        <button onClick={() => handleExportTrace("chrome")} className="text-[8px] border border-white/10 px-2 py-1 hover:bg-white hover:text-black transition-all font-black">CHROME TRACE</button>
+                                    </div>
+                                </div>
+                                <div className="max-h-64 overflow-y-auto font-mono text-[10px] text-zinc-400 space-y-1">
+                                    {apiTrace.slice(-100).map(call => (
+                                        <p key={call.seq}>
+                                            {`${call.function}(${call.args.map((a: number) => "0x" + a.toString(16)).join(", ")}) = 0x${call.ret.toString(16)} · ${(call.duration_ns / 1000).toFixed(1)}µs`}
+                                        </p>
+                                    ))}
+                                </div>
+                            </div>
+                        )}
                     </div>
                 )}
             </main>
@@ -413,6 +640,16 @@ function App() {
                         <EnvStat label="TYPE" value={selectedBottle.environment_type} active />
                         <EnvStat label="ENGINE" value={selectedBottle.engine_path ? "CUSTOM" : "PANCHO-PRO"} active />
                         <EnvStat label="IPC BRIDGE" value="MACH-PORT" active />
+                        <div className="flex justify-between items-center p-4">
+                            <span className="text-[10px] font-black tracking-widest opacity-30 uppercase">CPU</span>
+                            <select
+                                value={cpuProfile}
+                                onChange={(e) => { setCpuProfile(e.target.value); localStorage.setItem("cpuProfile", e.target.value); }}
+                                className="bg-black text-[10px] font-black tracking-widest text-emerald-500 uppercase text-right outline-none"
+                            >
+                                {cpuProfiles.map(p => <option key={p} value={p}>{p}</option>)}
+                            </select>
+                        </div>
                     </div>
                 </div>
                 <div className="flex-1 bg-black p-6 flex flex-col overflow-hidden border border-white/5">
@@ -494,6 +731,10 @@ function App() {
                 <div className="pt-8 flex flex-col gap-4">
                     <GraphicsConfig bottleId={settingsTarget.id} />
                     <div className="h-px bg-white/10 my-4" />
+                    <InstalledPrograms bottleId={settingsTarget.id} />
+                    <div className="h-px bg-white/10 my-4" />
+                    <SteamShortcuts bottleId={settingsTarget.id} pinnedApps={(settingsTarget.app_registry ?? []).filter(a => a.pinned)} />
+                    <div className="h-px bg-white/10 my-4" />
                     <button onClick={handleSaveSettings} className="w-full bg-white text-black p-5 font-black text-xs uppercase tracking-widest hover:bg-zinc-200 transition-all">Save Changes</button>
                     <button onClick={() => handleDeleteBottle(settingsTarget.id)} className="w-full border border-red-500/20 text-red-500 p-5 font-black text-xs uppercase tracking-widest hover:bg-red-500 hover:text-white transition-all">Delete Bottle</button>
                 </div>
@@ -0,0 +1,76 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { ask } from "@tauri-apps/plugin-dialog";
+import { listen } from "@tauri-apps/api/event";
+import { Label } from "@/components/ui/label";
+import * as Icons from "lucide-react";
+
+interface InstalledProgramsProps {
+    bottleId: string;
+}
+
+interface InstalledProgram {
+    key: string;
+    display_name: string;
+    publisher?: string | null;
+    display_version?: string | null;
+    uninstall_string?: string | null;
+}
+
+export function InstalledPrograms({ bottleId }: InstalledProgramsProps) {
+    const [programs, setPrograms] = useState<InstalledProgram[]>([]);
+
+    useEffect(() => {
+        loadPrograms();
+
+        // Emitted once the uninstaller has exited
+        const unlisten = listen<string>("library-changed", (event) => {
+            if (event.payload === bottleId) loadPrograms();
+        });
+        return () => { unlisten.then(f => f()); };
+    }, [bottleId]);
+
+    const loadPrograms = async () => {
+        try {
+            setPrograms(await invoke<InstalledProgram[]>("get_installed_programs", { bottleId }));
+        } catch (e) {
+            console.error(e);
+        }
+    };
+
+    const handleUninstall = async (program: InstalledProgram) => {
+        const confirmed = await ask(`Uninstall ${program.display_name}?`, { title: "Pancho", kind: "warning" });
+        if (!confirmed) return;
+        try {
+            await invoke("uninstall_program", { bottleId, key: program.key });
+        } catch (e) {
+            console.error(e);
+        }
+    };
+
+    return (
+        <div className="space-y-4">
+            <Label className="text-[10px] font-black text-zinc-500 uppercase tracking-widest">Installed Programs</Label>
+            {programs.length === 0 && (
+                <p className="text-[9px] text-zinc-600 uppercase font-bold tracking-wide">No registered programs</p>
+            )}
+            <div className="max-h-64 overflow-y-auto divide-y divide-white/5 border border-white/10 bg-black">
+                {programs.map(program => (
+                    <div key={program.key} className="flex items-center justify-between gap-4 p-4">
+                        <div className="min-w-0">
+                            <p className="font-black uppercase tracking-tight text-xs truncate">{program.display_name}</p>
+                            <p className="text-[9px] text-zinc-500 uppercase font-bold tracking-wide truncate">
+                                {[program.publisher, program.display_version].filter(Boolean).join(" · ")}
+                            </p>
+                        </div>
+                        {program.uninstall_string && (
+                            <button onClick={() => handleUninstall(program)} className="p-2 text-zinc-500 hover:text-red-500 transition-colors">
+                                <Icons.Trash2 size={14} />
+                            </button>
+                        )}
+                    </div>
+                ))}
+            </div>
+        </div>
+    );
+}
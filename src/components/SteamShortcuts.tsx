@@ -0,0 +1,96 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { Label } from "@/components/ui/label";
+import * as Icons from "lucide-react";
+
+interface SteamShortcutsProps {
+    bottleId: string;
+    pinnedApps: any[];
+}
+
+interface SteamShortcut {
+    user_id: string;
+    app_id: number;
+    app_name: string;
+    exe: string;
+    tags: string[];
+}
+
+export function SteamShortcuts({ bottleId, pinnedApps }: SteamShortcutsProps) {
+    const [shortcuts, setShortcuts] = useState<SteamShortcut[]>([]);
+    const [status, setStatus] = useState("");
+    const [busy, setBusy] = useState(false);
+
+    useEffect(() => {
+        loadShortcuts();
+    }, [bottleId]);
+
+    const loadShortcuts = async () => {
+        try {
+            setShortcuts(await invoke<SteamShortcut[]>("get_steam_shortcuts", { bottleId }));
+        } catch (e) {
+            console.error(e);
+        }
+    };
+
+    // Adds the library (pinned apps and detected games) as non-Steam games
+    const handleAddLibrary = async () => {
+        setBusy(true);
+        try {
+            const detected = await invoke<any[]>("scan_for_apps", { bottleId });
+            const apps = [
+                ...pinnedApps,
+                ...detected.filter(a => a.is_priority && !pinnedApps.some(p => p.exe_path === a.exe_path)),
+            ];
+            const added = await invoke<number>("add_steam_shortcuts", { bottleId, apps });
+            setStatus(added > 0 ? `Added ${added} app(s). Restart Steam to see them.` : "Everything is already in Steam.");
+            await loadShortcuts();
+        } catch (e) {
+            setStatus(`${e}`);
+        } finally {
+            setBusy(false);
+        }
+    };
+
+    const handleRemove = async (shortcut: SteamShortcut) => {
+        try {
+            await invoke("remove_steam_shortcut", { bottleId, appId: shortcut.app_id });
+            await loadShortcuts();
+        } catch (e) {
+            console.error(e);
+        }
+    };
+
+    // A shortcut exists once per Steam user; list each app once
+    const unique = shortcuts.filter((s, i) => shortcuts.findIndex(o => o.app_id === s.app_id) === i);
+
+    return (
+        <div className="space-y-4">
+            <div className="flex items-center justify-between">
+                <Label className="text-[10px] font-black text-zinc-500 uppercase tracking-widest">Steam Shortcuts</Label>
+                <button onClick={handleAddLibrary} disabled={busy} className="text-[8px] border border-white/10 px-2 py-1 hover:bg-white hover:text-black transition-all font-black disabled:opacity-50">
+                    {busy ? "ADDING..." : "ADD LIBRARY TO STEAM"}
+                </button>
+            </div>
+            {status && (
+                <p className="text-[9px] text-zinc-400 uppercase font-bold tracking-wide">{status}</p>
+            )}
+            {unique.length === 0 && (
+                <p className="text-[9px] text-zinc-600 uppercase font-bold tracking-wide">No non-Steam games</p>
+            )}
+            <div className="max-h-64 overflow-y-auto divide-y divide-white/5 border border-white/10 bg-black">
+                {unique.map(shortcut => (
+                    <div key={shortcut.app_id} className="flex items-center justify-between gap-4 p-4">
+                        <div className="min-w-0">
+                            <p className="font-black uppercase tracking-tight text-xs truncate">{shortcut.app_name}</p>
+                            <p className="text-[9px] text-zinc-500 font-bold tracking-wide truncate">{shortcut.exe}</p>
+                        </div>
+                        <button onClick={() => handleRemove(shortcut)} className="p-2 text-zinc-500 hover:text-red-500 transition-colors">
+                            <Icons.Trash2 size={14} />
+                        </button>
+                    </div>
+                ))}
+            </div>
+        </div>
+    );
+}